@@ -0,0 +1,168 @@
+use std::fs;
+use std::path::Path;
+
+use crate::errors;
+use crate::topic;
+
+#[path = "acl_tests.rs"]
+#[cfg(test)]
+mod acl_tests;
+
+// Action is the operation of the Client which is authorized by the ACL.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Action {
+    Publish,   // The Client sends a PUBLISH Packet to the Topic Name.
+    Subscribe, // The Client sends a SUBSCRIBE Packet with the Topic Filter.
+}
+
+// Access is the permission of a rule. "read" permits Subscribe, and "write" permits Publish.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum Access {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl Access {
+    fn parse(access: &str) -> Option<Access> {
+        match access {
+            "read" => Some(Access::Read),
+            "write" => Some(Access::Write),
+            "readwrite" => Some(Access::ReadWrite),
+            _ => None,
+        }
+    }
+
+    fn permits(&self, action: Action) -> bool {
+        matches!(
+            (self, action),
+            (Access::ReadWrite, _) | (Access::Read, Action::Subscribe) | (Access::Write, Action::Publish)
+        )
+    }
+}
+
+// Principal is the set of the Clients which a rule applies to.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Principal {
+    Anonymous,    // The Clients which have not provided the User Name.
+    User(String), // The Clients which have provided the User Name.
+    Everyone,     // Every Client. The topic can contain %c and %u which are substituted.
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Rule {
+    pub principal: Principal,
+    pub access: Access,
+    pub topic: String,
+}
+
+// Acl decides whether a Client may publish to or subscribe to a topic. The actions which no rule permits are denied.
+//
+// The ACL file has the following lines, the empty lines and the lines starting with '#' are ignored.
+//   topic [read|write|readwrite] <topic filter>    The rule for the anonymous Clients, or the user of the last "user" line.
+//   user <user name>                               The following "topic" lines are the rules of the user.
+//   pattern [read|write|readwrite] <topic filter>  The rule for every Client, %c is substituted with the ClientID,
+//                                                  and %u is substituted with the User Name.
+// If the access is omitted, it is "readwrite".
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct Acl {
+    rules: Vec<Rule>,
+}
+
+impl Acl {
+    pub fn new(rules: Vec<Rule>) -> Acl {
+        Acl { rules }
+    }
+
+    pub fn load(path: &Path) -> Result<Acl, errors::Error> {
+        let content = fs::read_to_string(path)?;
+        Acl::parse(&content).map_err(|err| errors::Error::Common(format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn parse(content: &str) -> Result<Acl, errors::Error> {
+        let mut rules = Vec::new();
+        let mut principal = Principal::Anonymous;
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = |reason: &str| errors::Error::Common(format!("line {}: {}", i + 1, reason));
+            let (keyword, rest) = line.split_once(char::is_whitespace).ok_or_else(|| invalid("the value is missing"))?;
+            let rest = rest.trim();
+            match keyword {
+                "user" => principal = Principal::User(rest.to_string()),
+                "topic" | "pattern" => {
+                    let (access, topic) = rest
+                        .split_once(char::is_whitespace)
+                        .and_then(|(access, topic)| Some((Access::parse(access)?, topic.trim())))
+                        .unwrap_or((Access::ReadWrite, rest));
+                    topic::validate_topic_filter(topic).map_err(|err| invalid(&err.to_string()))?;
+                    let principal = if keyword == "pattern" {
+                        Principal::Everyone
+                    } else {
+                        principal.clone()
+                    };
+                    rules.push(Rule {
+                        principal,
+                        access,
+                        topic: topic.to_string(),
+                    });
+                }
+                _ => return Err(invalid(&format!("unknown keyword {}", keyword))),
+            }
+        }
+
+        Ok(Acl { rules })
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    // authorize results whether the Client may take the action to the topic.
+    // For Publish the topic is the Topic Name, and for Subscribe the topic is the Topic Filter
+    // which must be covered by the topic of a rule, e.g. the rule "a/#" permits subscribing "a/+/c".
+    pub fn authorize(&self, client_id: &str, user_name: Option<&str>, action: Action, topic: &str) -> bool {
+        self.rules.iter().any(|rule| {
+            if !rule.access.permits(action) {
+                return false;
+            }
+            let filter = match (&rule.principal, user_name) {
+                (Principal::Anonymous, None) => rule.topic.clone(),
+                (Principal::User(name), Some(user_name)) if name == user_name => rule.topic.clone(),
+                (Principal::Everyone, _) => match substitute(&rule.topic, client_id, user_name) {
+                    Some(filter) => filter,
+                    None => return false,
+                },
+                _ => return false,
+            };
+            match action {
+                Action::Publish => topic::matches(&filter, topic),
+                Action::Subscribe => topic::covers(&filter, topic),
+            }
+        })
+    }
+}
+
+// substitute replaces %c with the ClientID and %u with the User Name.
+// It results None when the rule cannot be applied, when the User Name is required but it is not provided,
+// or when the value contains the characters which change the meaning of the filter, e.g. the ClientID "+".
+fn substitute(filter: &str, client_id: &str, user_name: Option<&str>) -> Option<String> {
+    let is_safe = |value: &str| !value.is_empty() && !value.contains(['/', '+', '#']);
+
+    let mut filter = filter.to_string();
+    if filter.contains("%c") {
+        if !is_safe(client_id) {
+            return None;
+        }
+        filter = filter.replace("%c", client_id);
+    }
+    if filter.contains("%u") {
+        let user_name = user_name.filter(|name| is_safe(name))?;
+        filter = filter.replace("%u", user_name);
+    }
+
+    Some(filter)
+}
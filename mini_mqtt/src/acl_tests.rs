@@ -0,0 +1,102 @@
+use super::*;
+
+const ACL_FILE: &str = "
+# The anonymous Clients can read the public topics.
+topic read public/#
+
+user alice
+topic readwrite alice/#
+topic write shared/inbox
+
+# Every device can use its own topics.
+pattern readwrite devices/%u/%c/#
+pattern read clients/%c
+";
+
+#[test]
+fn parse_acl_file() {
+    let acl = Acl::parse(ACL_FILE).unwrap();
+    assert_eq!(acl.rules().len(), 5);
+    assert_eq!(
+        acl.rules()[1],
+        Rule {
+            principal: Principal::User("alice".to_string()),
+            access: Access::ReadWrite,
+            topic: "alice/#".to_string(),
+        }
+    );
+    assert_eq!(acl.rules()[3].principal, Principal::Everyone);
+}
+
+#[test]
+fn parse_default_access() {
+    let acl = Acl::parse("topic public/#").unwrap();
+    assert_eq!(acl.rules()[0].access, Access::ReadWrite);
+}
+
+#[test]
+fn parse_unknown_keyword() {
+    assert!(Acl::parse("group admins").is_err());
+}
+
+#[test]
+fn parse_invalid_topic_filter() {
+    assert!(Acl::parse("topic read public/#/a").is_err());
+}
+
+#[test]
+fn authorize_anonymous() {
+    let acl = Acl::parse(ACL_FILE).unwrap();
+    assert!(acl.authorize("client1", None, Action::Subscribe, "public/news"));
+    assert!(acl.authorize("client1", None, Action::Subscribe, "public/#"));
+    assert!(!acl.authorize("client1", None, Action::Publish, "public/news"));
+    assert!(!acl.authorize("client1", None, Action::Subscribe, "alice/inbox"));
+}
+
+#[test]
+fn authorize_user() {
+    let acl = Acl::parse(ACL_FILE).unwrap();
+    assert!(acl.authorize("client1", Some("alice"), Action::Publish, "alice/status"));
+    assert!(acl.authorize("client1", Some("alice"), Action::Subscribe, "alice/+/status"));
+    assert!(acl.authorize("client1", Some("alice"), Action::Publish, "shared/inbox"));
+    assert!(!acl.authorize("client1", Some("alice"), Action::Subscribe, "shared/inbox"));
+    // The rules of the anonymous Clients are not applied to the users.
+    assert!(!acl.authorize("client1", Some("alice"), Action::Subscribe, "public/news"));
+    assert!(!acl.authorize("client1", Some("bob"), Action::Publish, "alice/status"));
+}
+
+#[test]
+fn authorize_pattern() {
+    let acl = Acl::parse(ACL_FILE).unwrap();
+    assert!(acl.authorize("sensor1", Some("acme"), Action::Publish, "devices/acme/sensor1/temperature"));
+    assert!(acl.authorize("sensor1", Some("acme"), Action::Subscribe, "devices/acme/sensor1/#"));
+    assert!(!acl.authorize("sensor1", Some("acme"), Action::Subscribe, "devices/acme/#"));
+    assert!(!acl.authorize("sensor1", Some("acme"), Action::Subscribe, "devices/acme/sensor2/temperature"));
+    assert!(!acl.authorize("sensor1", Some("other"), Action::Publish, "devices/acme/sensor1/temperature"));
+    // %u is not substituted for the anonymous Clients.
+    assert!(!acl.authorize("sensor1", None, Action::Publish, "devices//sensor1/temperature"));
+    assert!(acl.authorize("sensor1", None, Action::Subscribe, "clients/sensor1"));
+}
+
+#[test]
+fn authorize_pattern_with_wildcard_client_id() {
+    let acl = Acl::parse("pattern read clients/%c").unwrap();
+    assert!(!acl.authorize("+", None, Action::Subscribe, "clients/+"));
+    assert!(!acl.authorize("#", None, Action::Subscribe, "clients/#"));
+}
+
+#[test]
+fn authorize_without_rules() {
+    let acl = Acl::default();
+    assert!(!acl.authorize("client1", None, Action::Publish, "a"));
+}
+
+#[test]
+fn load_acl_file() {
+    let path = std::env::temp_dir().join(format!("mini_mqtt_acl_file_{}", std::process::id()));
+    fs::write(&path, ACL_FILE).unwrap();
+    let acl = Acl::load(&path);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(acl.unwrap().rules().len(), 5);
+}
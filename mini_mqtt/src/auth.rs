@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::packets::connack;
+use crate::packets::connect::{Connect, ConnectError, ConnectFlags};
+use crate::packets::{Bits, ExtractValue, UTF8EncodedString};
+
+pub mod password_file;
+
+#[path = "auth_tests.rs"]
+#[cfg(test)]
+mod auth_tests;
+
+// Credentials are the values of the CONNECT Packet which are used to authenticate the Client.
+// Look the 3.1.3.5 User Name and the 3.1.3.6 Password subsections for more details.
+// The peer_credentials are the credentials of the process on the other end of a local transport, e.g. a Unix domain socket.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Credentials<'a> {
+    pub client_id: &'a str,
+    pub user_name: Option<&'a str>,
+    pub password: Option<&'a [u8]>,
+    pub peer_credentials: Option<&'a PeerCredentials>,
+}
+
+impl<'a> Credentials<'a> {
+    pub fn new(client_id: &'a str, user_name: Option<&'a str>, password: Option<&'a [u8]>) -> Credentials<'a> {
+        Credentials {
+            client_id,
+            user_name,
+            password,
+            peer_credentials: None,
+        }
+    }
+
+    pub fn with_peer_credentials(mut self, peer_credentials: Option<&'a PeerCredentials>) -> Credentials<'a> {
+        self.peer_credentials = peer_credentials;
+        self
+    }
+}
+
+// PeerCredentials are the user, the group and the process of the Client which the operating system reports.
+// The pid is None when the platform does not report it.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct PeerCredentials {
+    pub uid: u32,
+    pub gid: u32,
+    pub pid: Option<i32>,
+}
+
+// Peer is what the transport knows about the Client before the CONNECT Packet is handled.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct Peer {
+    pub identity: Option<PeerIdentity>,
+    pub credentials: Option<PeerCredentials>,
+}
+
+// PeerIdentity is the identity of the Client which the transport has verified,
+// e.g. the Common Name or the Subject Alternative Name of the TLS client certificate.
+// The identity replaces the ClientID and the User Name of the CONNECT Packet,
+// and the AuthProvider is not consulted because the transport has authenticated the Client.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct PeerIdentity {
+    pub client_id: Option<String>,
+    pub user_name: Option<String>,
+}
+
+impl PeerIdentity {
+    // apply results the CONNECT Packet whose ClientID and User Name are replaced with the identity.
+    pub fn apply(&self, connect: &Connect) -> Connect {
+        let mut connect = connect.clone();
+        if let Some(client_id) = &self.client_id {
+            connect.payload.client_id = UTF8EncodedString(client_id.clone());
+        }
+        if let Some(user_name) = &self.user_name {
+            connect.payload.user_name = Some(UTF8EncodedString(user_name.clone()));
+            // The User Name Flag must be set when the User Name is present [MQTT-3.1.2-17].
+            let flags = connect.variable_header.connect_flags.0.val() | 0b1000_0000;
+            connect.variable_header.connect_flags = ConnectFlags(Bits(flags));
+        }
+        connect
+    }
+}
+
+// AuthProvider authenticates the Client by the credentials of the CONNECT Packet.
+// The failure carries the Reason Code of the CONNACK Packet, usually
+// 0x86 (Bad User Name or Password) or 0x87 (Not authorized).
+pub trait AuthProvider: Send + Sync + fmt::Debug {
+    fn authenticate(&self, credentials: &Credentials) -> Result<(), ConnectError>;
+}
+
+pub fn bad_user_name_or_password(user_name: &str) -> ConnectError {
+    ConnectError::new(
+        connack::BAD_USER_NAME_OR_PASSWORD,
+        &format!("User Name or Password of {} is not accepted.", user_name),
+    )
+}
+
+pub fn not_authorized(reason: &str) -> ConnectError {
+    ConnectError::new(connack::NOT_AUTHORIZED, reason)
+}
+
+// AllowAll accepts every Client. It is the default when no authentication is configured.
+#[derive(Debug, Default)]
+pub struct AllowAll;
+
+impl AuthProvider for AllowAll {
+    fn authenticate(&self, _: &Credentials) -> Result<(), ConnectError> {
+        Ok(())
+    }
+}
+
+// InMemory holds the plain passwords for each User Name. It is intended for the tests.
+#[derive(Debug, Default)]
+pub struct InMemory {
+    passwords: HashMap<String, Vec<u8>>,
+}
+
+impl InMemory {
+    pub fn new() -> InMemory {
+        InMemory::default()
+    }
+
+    pub fn with_user(mut self, user_name: &str, password: &[u8]) -> InMemory {
+        self.passwords.insert(user_name.to_string(), password.to_vec());
+        self
+    }
+}
+
+impl AuthProvider for InMemory {
+    fn authenticate(&self, credentials: &Credentials) -> Result<(), ConnectError> {
+        let user_name = credentials
+            .user_name
+            .ok_or_else(|| not_authorized("User Name is required."))?;
+        match (self.passwords.get(user_name), credentials.password) {
+            (Some(expected), Some(password)) if constant_time_eq(expected, password) => Ok(()),
+            _ => Err(bad_user_name_or_password(user_name)),
+        }
+    }
+}
+
+// constant_time_eq compares the passwords in the time which depends only on their lengths,
+// so that the time of a failure does not tell how many bytes have matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+type HookFn = Box<dyn Fn(&Credentials) -> Result<(), ConnectError> + Send + Sync>;
+
+// Hook delegates the authentication to the provided function, for the custom logic of the broker.
+pub struct Hook {
+    f: HookFn,
+}
+
+impl Hook {
+    pub fn new<F>(f: F) -> Hook
+    where
+        F: Fn(&Credentials) -> Result<(), ConnectError> + Send + Sync + 'static,
+    {
+        Hook { f: Box::new(f) }
+    }
+}
+
+impl fmt::Debug for Hook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Hook")
+    }
+}
+
+impl AuthProvider for Hook {
+    fn authenticate(&self, credentials: &Credentials) -> Result<(), ConnectError> {
+        (self.f)(credentials)
+    }
+}
@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+
+use crate::auth::{bad_user_name_or_password, not_authorized, AuthProvider, Credentials};
+use crate::errors;
+use crate::packets::connect::ConnectError;
+
+#[path = "password_file_tests.rs"]
+#[cfg(test)]
+mod password_file_tests;
+
+// HashedPassword is a hashed password of the password file.
+// The kind of the hash is detected by the prefix of the hash, e.g. "$2b$" for bcrypt and "$argon2id$" for argon2.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum HashedPassword {
+    Bcrypt(String),
+    Argon2(String),
+}
+
+impl HashedPassword {
+    pub fn parse(hash: &str) -> Result<HashedPassword, errors::Error> {
+        if hash.starts_with("$2a$") || hash.starts_with("$2b$") || hash.starts_with("$2y$") {
+            Ok(HashedPassword::Bcrypt(hash.to_string()))
+        } else if hash.starts_with("$argon2") {
+            PasswordHash::new(hash)
+                .map_err(|err| errors::Error::Common(format!("Invalid argon2 hash: {}", err)))?;
+            Ok(HashedPassword::Argon2(hash.to_string()))
+        } else {
+            Err(errors::Error::Common("Unsupported password hash, it must be bcrypt or argon2".to_string()))
+        }
+    }
+
+    pub fn verify(&self, password: &[u8]) -> bool {
+        match self {
+            HashedPassword::Bcrypt(hash) => bcrypt::verify(password, hash).unwrap_or(false),
+            HashedPassword::Argon2(hash) => PasswordHash::new(hash)
+                .map(|hash| argon2::Argon2::default().verify_password(password, &hash).is_ok())
+                .unwrap_or(false),
+        }
+    }
+}
+
+// PasswordFile authenticates the Clients by the static file of the hashed passwords.
+// Each line of the file is "<user name>:<bcrypt or argon2 hash>",
+// the empty lines and the lines starting with '#' are ignored.
+#[derive(Debug, Default)]
+pub struct PasswordFile {
+    passwords: HashMap<String, HashedPassword>,
+}
+
+impl PasswordFile {
+    pub fn load(path: &Path) -> Result<PasswordFile, errors::Error> {
+        let content = fs::read_to_string(path)?;
+        PasswordFile::parse(&content)
+            .map_err(|err| errors::Error::Common(format!("{}: {}", path.display(), err)))
+    }
+
+    pub fn parse(content: &str) -> Result<PasswordFile, errors::Error> {
+        let mut passwords = HashMap::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (user_name, hash) = line.split_once(':').ok_or_else(|| {
+                errors::Error::Common(format!("line {}: it must be <user name>:<password hash>", i + 1))
+            })?;
+            let hash = HashedPassword::parse(hash)
+                .map_err(|err| errors::Error::Common(format!("line {}: {}", i + 1, err)))?;
+            passwords.insert(user_name.to_string(), hash);
+        }
+
+        Ok(PasswordFile { passwords })
+    }
+
+    pub fn len(&self) -> usize {
+        self.passwords.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passwords.is_empty()
+    }
+}
+
+impl AuthProvider for PasswordFile {
+    fn authenticate(&self, credentials: &Credentials) -> Result<(), ConnectError> {
+        let user_name = credentials
+            .user_name
+            .ok_or_else(|| not_authorized("User Name is required."))?;
+        let Some(password) = credentials.password else {
+            return Err(bad_user_name_or_password(user_name));
+        };
+        // The password of the unknown User Name is verified against the dummy hash,
+        // so that the time of the failure does not tell which User Names exist.
+        let verified = match self.passwords.get(user_name) {
+            Some(hash) => hash.verify(password),
+            None => {
+                let _ = dummy_hash().verify(password);
+                false
+            }
+        };
+        if verified {
+            Ok(())
+        } else {
+            Err(bad_user_name_or_password(user_name))
+        }
+    }
+}
+
+// dummy_hash results the argon2 hash of the default parameters, which only takes the time of a verification.
+fn dummy_hash() -> &'static HashedPassword {
+    static DUMMY_HASH: OnceLock<HashedPassword> = OnceLock::new();
+    DUMMY_HASH.get_or_init(|| {
+        let salt = SaltString::encode_b64(b"mini_mqtt_dummy").unwrap();
+        let hash = argon2::Argon2::default().hash_password(b"", &salt).unwrap();
+        HashedPassword::Argon2(hash.to_string())
+    })
+}
@@ -0,0 +1,88 @@
+use super::*;
+use argon2::password_hash::{PasswordHasher, SaltString};
+use crate::packets::connack;
+
+fn argon2_hash(password: &[u8]) -> String {
+    let salt = SaltString::encode_b64(b"mini_mqtt_salt").unwrap();
+    argon2::Argon2::default().hash_password(password, &salt).unwrap().to_string()
+}
+
+fn bcrypt_hash(password: &[u8]) -> String {
+    bcrypt::hash(password, 4).unwrap()
+}
+
+#[test]
+fn parse_password_file() {
+    let content = format!(
+        "# comment\n\nalice:{}\nbob:{}\n",
+        bcrypt_hash(b"alice-secret"),
+        argon2_hash(b"bob-secret")
+    );
+    let file = PasswordFile::parse(&content).unwrap();
+    assert_eq!(file.len(), 2);
+}
+
+#[test]
+fn parse_invalid_line() {
+    let result = PasswordFile::parse("alice");
+    assert!(result.is_err());
+}
+
+#[test]
+fn parse_unsupported_hash() {
+    let result = PasswordFile::parse("alice:plain");
+    assert!(result.is_err());
+}
+
+#[test]
+fn authenticate_bcrypt() {
+    let file = PasswordFile::parse(&format!("alice:{}", bcrypt_hash(b"alice-secret"))).unwrap();
+
+    let credentials = Credentials::new("client1", Some("alice"), Some(b"alice-secret"));
+    assert!(file.authenticate(&credentials).is_ok());
+
+    let credentials = Credentials::new("client1", Some("alice"), Some(b"wrong"));
+    let err = file.authenticate(&credentials).unwrap_err();
+    assert_eq!(err.reason_code, connack::BAD_USER_NAME_OR_PASSWORD);
+}
+
+#[test]
+fn authenticate_argon2() {
+    let file = PasswordFile::parse(&format!("bob:{}", argon2_hash(b"bob-secret"))).unwrap();
+
+    let credentials = Credentials::new("client1", Some("bob"), Some(b"bob-secret"));
+    assert!(file.authenticate(&credentials).is_ok());
+
+    let credentials = Credentials::new("client1", Some("bob"), None);
+    let err = file.authenticate(&credentials).unwrap_err();
+    assert_eq!(err.reason_code, connack::BAD_USER_NAME_OR_PASSWORD);
+}
+
+#[test]
+fn authenticate_unknown_user() {
+    let file = PasswordFile::parse(&format!("alice:{}", bcrypt_hash(b"alice-secret"))).unwrap();
+
+    // The empty password matches the dummy hash, which must not accept it.
+    for password in [b"alice-secret".as_slice(), b""] {
+        let credentials = Credentials::new("client1", Some("bob"), Some(password));
+        let err = file.authenticate(&credentials).unwrap_err();
+        assert_eq!(err.reason_code, connack::BAD_USER_NAME_OR_PASSWORD);
+    }
+}
+
+#[test]
+fn authenticate_anonymous() {
+    let file = PasswordFile::parse("").unwrap();
+    let err = file.authenticate(&Credentials::new("client1", None, None)).unwrap_err();
+    assert_eq!(err.reason_code, connack::NOT_AUTHORIZED);
+}
+
+#[test]
+fn load_password_file() {
+    let path = std::env::temp_dir().join(format!("mini_mqtt_password_file_{}", std::process::id()));
+    fs::write(&path, format!("alice:{}\n", bcrypt_hash(b"alice-secret"))).unwrap();
+    let file = PasswordFile::load(&path);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(file.unwrap().len(), 1);
+}
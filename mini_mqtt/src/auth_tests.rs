@@ -0,0 +1,54 @@
+use super::*;
+
+#[test]
+fn allow_all_accepts_anonymous() {
+    let credentials = Credentials::new("client1", None, None);
+    assert!(AllowAll.authenticate(&credentials).is_ok());
+}
+
+#[test]
+fn in_memory_accepts_valid_password() {
+    let provider = InMemory::new().with_user("alice", b"secret");
+    let credentials = Credentials::new("client1", Some("alice"), Some(b"secret"));
+    assert!(provider.authenticate(&credentials).is_ok());
+}
+
+#[test]
+fn in_memory_rejects_wrong_password() {
+    let provider = InMemory::new().with_user("alice", b"secret");
+    for password in [b"wrong".as_slice(), b"secre", b"secret!"] {
+        let credentials = Credentials::new("client1", Some("alice"), Some(password));
+        let err = provider.authenticate(&credentials).unwrap_err();
+        assert_eq!(err.reason_code, connack::BAD_USER_NAME_OR_PASSWORD);
+    }
+}
+
+#[test]
+fn in_memory_rejects_unknown_user() {
+    let provider = InMemory::new().with_user("alice", b"secret");
+    let credentials = Credentials::new("client1", Some("bob"), Some(b"secret"));
+    let err = provider.authenticate(&credentials).unwrap_err();
+    assert_eq!(err.reason_code, connack::BAD_USER_NAME_OR_PASSWORD);
+}
+
+#[test]
+fn in_memory_rejects_anonymous() {
+    let provider = InMemory::new().with_user("alice", b"secret");
+    let credentials = Credentials::new("client1", None, None);
+    let err = provider.authenticate(&credentials).unwrap_err();
+    assert_eq!(err.reason_code, connack::NOT_AUTHORIZED);
+}
+
+#[test]
+fn hook_delegates_to_function() {
+    let provider = Hook::new(|credentials| {
+        if credentials.client_id.starts_with("trusted") {
+            Ok(())
+        } else {
+            Err(not_authorized("untrusted client"))
+        }
+    });
+    assert!(provider.authenticate(&Credentials::new("trusted1", None, None)).is_ok());
+    let err = provider.authenticate(&Credentials::new("client1", None, None)).unwrap_err();
+    assert_eq!(err.reason_code, connack::NOT_AUTHORIZED);
+}
@@ -0,0 +1,359 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::TcpStream;
+use std::time::{Duration, Instant};
+
+use crate::codec::{decoder, encoder};
+use crate::errors;
+use crate::packets;
+use crate::packets::connack::ConnAck;
+use crate::packets::publish::Publish;
+use crate::packets::suback::SubAck;
+use crate::packets::{ExtractValue, QoS, ReasonCode};
+use crate::session::packet_identifier::PacketIdentifierAllocator;
+
+#[path = "client_tests.rs"]
+#[cfg(test)]
+mod client_tests;
+
+// KEEP_ALIVE is the Keep Alive which the Client requests, in seconds.
+pub const KEEP_ALIVE: u16 = 60;
+
+// Client is a minimal synchronous MQTT v5.0 Client on a TCP connection.
+// It publishes QoS 0 and QoS 1 messages and subscribes up to QoS 1, so it has no QoS 2 flows.
+// The Packet Identifiers of its PUBLISH and SUBSCRIBE Packets are issued by a PacketIdentifierAllocator,
+// and each one is released when its acknowledgement is received [MQTT-2.2.1-3].
+// The messages received while it waits for an acknowledgement are kept, and receive results them in order.
+// request sends a request message and waits for its response by the Response Topic and the Correlation Data
+// (4.10 Request / Response section), and respond answers a received request message.
+pub struct Client {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+    client_id: String,
+    keep_alive: Duration,
+    last_sent_at: Instant,
+    packet_identifiers: PacketIdentifierAllocator,
+    received: VecDeque<Publish>,
+    response_information: Option<String>,
+    response_subscribed: bool,
+    correlation_counter: u64,
+}
+
+impl Client {
+    // connect sends the CONNECT Packet with Clean Start and the properties, and waits for the CONNACK Packet.
+    // The zero-length ClientID lets the Server assign one (3.2.2.3.7 Assigned Client Identifier subsection),
+    // and the Server Keep Alive replaces the requested Keep Alive [MQTT-3.2.2-21].
+    pub fn connect(
+        stream: TcpStream,
+        client_id: &str,
+        properties: packets::Properties,
+        timeout: Duration,
+    ) -> Result<(Client, ConnAck), errors::Error> {
+        let mut client = Client {
+            stream,
+            buffer: Vec::new(),
+            client_id: client_id.to_string(),
+            keep_alive: Duration::from_secs(KEEP_ALIVE as u64),
+            last_sent_at: Instant::now(),
+            packet_identifiers: PacketIdentifierAllocator::new(),
+            received: VecDeque::new(),
+            response_information: None,
+            response_subscribed: false,
+            correlation_counter: 0,
+        };
+        let variable_header = packets::connect::VariableHeader::new(
+            packets::UTF8EncodedString("MQTT".to_string()),
+            packets::Bits(5),
+            packets::connect::ConnectFlags(packets::Bits(0b0000_0010)),
+            packets::TwoByteInteger(KEEP_ALIVE),
+            properties,
+        )?;
+        let payload = packets::connect::Payload::new(packets::UTF8EncodedString(client_id.to_string()), None, None, None, None, None)?;
+        let fixed_header = packets::FixedHeader::new(packets::Bits(packets::CONNECT), packets::Bits(0), packets::VariableByteInteger(0))?;
+        let connect = packets::connect::Connect::new(fixed_header, variable_header, payload)?;
+        client.send(&packets::Packet::Connect(connect))?;
+
+        let deadline = Instant::now() + timeout;
+        let connack = match client.read(deadline)? {
+            Some(packets::Packet::ConnAck(connack)) => connack,
+            Some(packet) => {
+                return Err(errors::Error::ProtocolError(format!("The Server has sent {:?} before the CONNACK Packet", packet)));
+            }
+            None => return Err(io::Error::from(io::ErrorKind::TimedOut).into()),
+        };
+        if !connack.is_success() {
+            return Err(errors::Error::ProtocolError(format!(
+                "The Server has refused the connection with the Reason Code 0x{:02X}",
+                connack.connect_reason_code.code()
+            )));
+        }
+        if let Some(assigned) = connack.properties.get_as::<packets::UTF8EncodedString>(packets::ASSIGNED_CLIENT_IDENTIFIER)? {
+            client.client_id = assigned.val().to_string();
+        }
+        if let Some(server_keep_alive) = connack.properties.get_as::<packets::TwoByteInteger>(packets::SERVER_KEEP_ALIVE)? {
+            client.keep_alive = Duration::from_secs(server_keep_alive.val() as u64);
+        }
+        client.response_information = connack
+            .properties
+            .get_as::<packets::UTF8EncodedString>(packets::RESPONSE_INFORMATION)?
+            .map(|response_information| response_information.val().to_string());
+        Ok((client, connack))
+    }
+
+    pub fn client_id(&self) -> &str {
+        &self.client_id
+    }
+
+    // publish sends the message, and waits for the PUBACK Packet of the QoS 1 message.
+    // The PUBACK Packet with a Reason Code of 0x80 or greater results an error.
+    pub fn publish(
+        &mut self,
+        topic_name: &str,
+        qos: QoS,
+        properties: packets::Properties,
+        payload: &[u8],
+        timeout: Duration,
+    ) -> Result<(), errors::Error> {
+        let packet_identifier = match qos {
+            QoS::AtMostOnce => None,
+            QoS::AtLeastOnce => Some(self.allocate()?),
+            _ => return Err(errors::Error::Common(format!("The Client does not publish the {} message", qos))),
+        };
+        let publish = Publish::new(
+            Publish::fixed_header(false, qos, false),
+            packets::UTF8EncodedString(topic_name.to_string()),
+            packet_identifier.clone(),
+            properties,
+            payload.to_vec(),
+        )?;
+        let result = self.send(&packets::Packet::Publish(publish));
+        let Some(packet_identifier) = packet_identifier else {
+            return result;
+        };
+        let puback = result.and_then(|_| {
+            self.wait(Instant::now() + timeout, |packet| match packet {
+                packets::Packet::PubAck(puback) if puback.packet_identifier == packet_identifier => Some(puback.clone()),
+                _ => None,
+            })
+        });
+        self.packet_identifiers.release(packet_identifier.val());
+        let puback = puback?;
+        if puback.reason_code.code() >= 0x80 {
+            return Err(errors::Error::ProtocolError(format!(
+                "The Server has not accepted the message with the Reason Code 0x{:02X}",
+                puback.reason_code.code()
+            )));
+        }
+        Ok(())
+    }
+
+    // subscribe sends the SUBSCRIBE Packet of the Topic Filter, and results the SUBACK Packet.
+    // The Reason Code in it tells the granted QoS or the failure.
+    pub fn subscribe(&mut self, topic_filter: &str, qos: QoS, timeout: Duration) -> Result<SubAck, errors::Error> {
+        if qos.exceeds(QoS::AtLeastOnce) {
+            return Err(errors::Error::Common(format!("The Client does not subscribe with {}", qos)));
+        }
+        let packet_identifier = self.allocate()?;
+        let options = packets::subscribe::SubscriptionOptions::new(qos.bits())?;
+        let subscribe = packets::subscribe::Subscribe::new(
+            packets::FixedHeader::new(packets::Bits(packets::SUBSCRIBE), packets::Bits(0b0010), packets::VariableByteInteger(0))?,
+            packet_identifier.clone(),
+            packets::Properties::new(),
+            vec![packets::subscribe::Subscription::new(packets::UTF8EncodedString(topic_filter.to_string()), options)],
+        )?;
+        let suback = self.send(&packets::Packet::Subscribe(subscribe)).and_then(|_| {
+            self.wait(Instant::now() + timeout, |packet| match packet {
+                packets::Packet::SubAck(suback) if suback.packet_identifier == packet_identifier => Some(suback.clone()),
+                _ => None,
+            })
+        });
+        self.packet_identifiers.release(packet_identifier.val());
+        suback
+    }
+
+    // response_topic results the Response Topic of the requests of the Client. It is the Response Information
+    // which the Server has returned for the Request Response Information 1 in the CONNECT Properties,
+    // otherwise the ClientID followed by /responses.
+    pub fn response_topic(&self) -> String {
+        self.response_information
+            .clone()
+            .unwrap_or_else(|| format!("{}/responses", self.client_id))
+    }
+
+    // request publishes the QoS 1 request message with the Response Topic and the Correlation Data,
+    // and results the response message which has the same Correlation Data, or the TimedOut error after the timeout.
+    // The Response Topic is subscribed by the first request. Each request has its own Correlation Data,
+    // and the late responses of the earlier requests are discarded.
+    pub fn request(&mut self, topic_name: &str, payload: &[u8], timeout: Duration) -> Result<Publish, errors::Error> {
+        let deadline = Instant::now() + timeout;
+        let response_topic = self.response_topic();
+        if !self.response_subscribed {
+            let suback = self.subscribe(&response_topic, QoS::AtLeastOnce, timeout)?;
+            if let Some(reason_code) = suback.reason_codes.iter().find(|reason_code| reason_code.code() >= 0x80) {
+                return Err(errors::Error::ProtocolError(format!(
+                    "The Server has not accepted the subscription of {} with the Reason Code 0x{:02X}",
+                    response_topic,
+                    reason_code.code()
+                )));
+            }
+            self.response_subscribed = true;
+        }
+        self.correlation_counter += 1;
+        let correlation_data = self.correlation_counter.to_be_bytes().to_vec();
+        let mut properties = packets::Properties::new();
+        properties.insert(packets::RESPONSE_TOPIC, packets::UTF8EncodedString(response_topic.clone()).into());
+        properties.insert(packets::CORRELATION_DATA, packets::BinaryData(correlation_data.clone()).into());
+        self.publish(topic_name, QoS::AtLeastOnce, properties, payload, deadline.saturating_duration_since(Instant::now()))?;
+
+        loop {
+            let mut response = None;
+            self.received.retain(|publish| {
+                if publish.topic_name.val() != response_topic {
+                    return true;
+                }
+                if response.is_none() && correlation_data_of(publish).as_deref() == Some(correlation_data.as_slice()) {
+                    response = Some(publish.clone());
+                }
+                false
+            });
+            if let Some(response) = response {
+                return Ok(response);
+            }
+            if self.read(deadline)?.is_none() {
+                return Err(io::Error::from(io::ErrorKind::TimedOut).into());
+            }
+        }
+    }
+
+    // respond publishes the response message to the Response Topic of the request message with its Correlation Data.
+    // The request message without the Response Topic expects no response, and it results an error.
+    pub fn respond(&mut self, request: &Publish, payload: &[u8], timeout: Duration) -> Result<(), errors::Error> {
+        let response_topic = request
+            .properties
+            .get_as::<packets::UTF8EncodedString>(packets::RESPONSE_TOPIC)?
+            .ok_or_else(|| errors::Error::Common(format!("The message of {} has no Response Topic", request.topic_name.val())))?;
+        let mut properties = packets::Properties::new();
+        if let Some(correlation_data) = correlation_data_of(request) {
+            properties.insert(packets::CORRELATION_DATA, packets::BinaryData(correlation_data).into());
+        }
+        self.publish(response_topic.val(), QoS::AtLeastOnce, properties, payload, timeout)
+    }
+
+    // receive results the next message from the Server, or None when no message has arrived within the timeout.
+    pub fn receive(&mut self, timeout: Duration) -> Result<Option<Publish>, errors::Error> {
+        let deadline = Instant::now() + timeout;
+        while self.received.is_empty() {
+            if self.read(deadline)?.is_none() {
+                return Ok(None);
+            }
+        }
+        Ok(self.received.pop_front())
+    }
+
+    // disconnect sends the DISCONNECT Packet with the Reason Code 0x00 (Normal disconnection) and closes the Network Connection.
+    pub fn disconnect(mut self) -> Result<(), errors::Error> {
+        let disconnect = packets::disconnect::Disconnect::new(packets::disconnect::NORMAL_DISCONNECTION, packets::Properties::new());
+        self.send(&packets::Packet::Disconnect(disconnect))?;
+        self.stream.shutdown(std::net::Shutdown::Both)?;
+        Ok(())
+    }
+
+    // allocate results a free Packet Identifier. As the Client waits for each acknowledgement,
+    // the Packet Identifiers run out only when the acknowledgements are lost.
+    fn allocate(&mut self) -> Result<packets::PacketIdentity, errors::Error> {
+        self.packet_identifiers
+            .allocate()
+            .ok_or_else(|| errors::Error::ProtocolError("No Packet Identifier is free".to_string()))
+    }
+
+    // wait reads the packets until the matching one arrives, or results the TimedOut error after the deadline.
+    fn wait<T>(&mut self, deadline: Instant, mut matches: impl FnMut(&packets::Packet) -> Option<T>) -> Result<T, errors::Error> {
+        loop {
+            let Some(packet) = self.read(deadline)? else {
+                return Err(io::Error::from(io::ErrorKind::TimedOut).into());
+            };
+            if let Some(matched) = matches(&packet) {
+                return Ok(matched);
+            }
+        }
+    }
+
+    // read results the next packet from the Server, or None when the deadline has passed.
+    // The PUBLISH Packet is kept for receive, the QoS 1 one is acknowledged by the PUBACK Packet,
+    // and the PINGREQ Packet is sent when the Client has sent nothing for the Keep Alive [MQTT-3.1.2-20].
+    // The DISCONNECT Packet from the Server results an error.
+    fn read(&mut self, deadline: Instant) -> Result<Option<packets::Packet>, errors::Error> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            if let Some(frame) = decoder::take_frame(&mut self.buffer)? {
+                let packet = decoder::decode(&mut frame.as_slice())?;
+                self.handle(&packet)?;
+                return Ok(Some(packet));
+            }
+
+            if !self.keep_alive.is_zero() && self.last_sent_at.elapsed() >= self.keep_alive {
+                self.send(&packets::Packet::PingReq)?;
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Ok(None);
+            }
+            let mut wait = deadline - now;
+            if !self.keep_alive.is_zero() {
+                wait = wait.min(self.keep_alive.saturating_sub(self.last_sent_at.elapsed()));
+            }
+            // The zero timeout is invalid, it is not the non-blocking read.
+            self.stream.set_read_timeout(Some(wait.max(Duration::from_millis(1))))?;
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    fn handle(&mut self, packet: &packets::Packet) -> Result<(), errors::Error> {
+        match packet {
+            packets::Packet::Publish(publish) => {
+                match (publish.qos(), &publish.packet_identifier) {
+                    (QoS::AtMostOnce, _) => {}
+                    (QoS::AtLeastOnce, Some(packet_identifier)) => {
+                        let puback = packets::puback::PubAck::new(packet_identifier.clone(), packets::puback::SUCCESS, packets::Properties::new());
+                        self.send(&packets::Packet::PubAck(puback))?;
+                    }
+                    (qos, _) => {
+                        return Err(errors::Error::ProtocolError(format!("The Server has sent the {} message beyond the subscriptions", qos)));
+                    }
+                }
+                self.received.push_back(publish.clone());
+            }
+            packets::Packet::Disconnect(disconnect) => {
+                return Err(errors::Error::ProtocolError(format!(
+                    "The Server has disconnected with the Reason Code 0x{:02X}",
+                    disconnect.reason_code.code()
+                )));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn send(&mut self, packet: &packets::Packet) -> Result<(), errors::Error> {
+        let mut buffer = Vec::new();
+        encoder::encode(&mut buffer, packet)?;
+        self.stream.write_all(&buffer)?;
+        self.stream.flush()?;
+        self.last_sent_at = Instant::now();
+        Ok(())
+    }
+}
+
+fn correlation_data_of(publish: &Publish) -> Option<Vec<u8>> {
+    publish
+        .properties
+        .get_as::<packets::BinaryData>(packets::CORRELATION_DATA)
+        .ok()
+        .flatten()
+        .map(|correlation_data| correlation_data.val().to_vec())
+}
@@ -0,0 +1,239 @@
+use std::net::TcpListener;
+use std::thread;
+
+use super::*;
+use crate::packets::{PacketIdentity, Properties, TwoByteInteger, UTF8EncodedString};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Server is the scripted Server side of the Network Connection of a Client.
+struct Server {
+    stream: TcpStream,
+    buffer: Vec<u8>,
+}
+
+impl Server {
+    fn read(&mut self) -> packets::Packet {
+        let mut chunk = [0u8; 4096];
+        loop {
+            if let Some(frame) = decoder::take_frame(&mut self.buffer).unwrap() {
+                return decoder::decode(&mut frame.as_slice()).unwrap();
+            }
+            let n = self.stream.read(&mut chunk).unwrap();
+            assert!(n > 0, "The Client has closed the Network Connection");
+            self.buffer.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    fn send(&mut self, packet: packets::Packet) {
+        encoder::encode(&mut self.stream, &packet).unwrap();
+    }
+}
+
+// start results the address of the Server which accepts a Client and runs the script on the connection.
+fn start(script: impl FnOnce(&mut Server) + Send + 'static) -> (std::net::SocketAddr, thread::JoinHandle<()>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let handle = thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut server = Server { stream, buffer: Vec::new() };
+        script(&mut server);
+    });
+    (address, handle)
+}
+
+fn accept(server: &mut Server, properties: Properties) {
+    assert!(matches!(server.read(), packets::Packet::Connect(_)));
+    let connack = ConnAck {
+        properties,
+        ..ConnAck::default()
+    };
+    server.send(packets::Packet::ConnAck(connack));
+}
+
+fn packet_identity(packet_identifier: u16) -> PacketIdentity {
+    PacketIdentity::new(TwoByteInteger(packet_identifier))
+}
+
+#[test]
+fn client_issues_and_releases_packet_identifiers() {
+    let (address, server) = start(|server| {
+        accept(server, Properties::new());
+        let packets::Packet::Subscribe(subscribe) = server.read() else {
+            panic!("The SUBSCRIBE Packet is expected");
+        };
+        assert_eq!(subscribe.packet_identifier, packet_identity(1));
+        assert_eq!(subscribe.subscriptions[0].topic_filter.val(), "a/+");
+        let suback = SubAck::new(subscribe.packet_identifier, Properties::new(), vec![packets::suback::GRANTED_QOS_1]);
+        server.send(packets::Packet::SubAck(suback));
+
+        for expected in [2, 3] {
+            let packets::Packet::Publish(publish) = server.read() else {
+                panic!("The PUBLISH Packet is expected");
+            };
+            assert_eq!(publish.packet_identifier, Some(packet_identity(expected)));
+            let puback = packets::puback::PubAck::new(packet_identity(expected), packets::puback::SUCCESS, Properties::new());
+            server.send(packets::Packet::PubAck(puback));
+        }
+        assert!(matches!(server.read(), packets::Packet::Disconnect(_)));
+    });
+
+    let stream = TcpStream::connect(address).unwrap();
+    let (mut client, connack) = Client::connect(stream, "c1", Properties::new(), TIMEOUT).unwrap();
+    assert!(connack.is_success());
+    let suback = client.subscribe("a/+", QoS::AtLeastOnce, TIMEOUT).unwrap();
+    assert_eq!(suback.reason_codes, vec![packets::suback::GRANTED_QOS_1]);
+    for payload in [b"1", b"2"] {
+        client.publish("a/1", QoS::AtLeastOnce, Properties::new(), payload, TIMEOUT).unwrap();
+    }
+    // Every Packet Identifier has been released by its acknowledgement.
+    assert!(client.packet_identifiers.is_empty());
+    client.disconnect().unwrap();
+    server.join().unwrap();
+}
+
+#[test]
+fn client_acknowledges_received_messages() {
+    let (address, server) = start(|server| {
+        accept(server, Properties::new());
+        let publish = Publish::new(
+            Publish::fixed_header(false, QoS::AtLeastOnce, false),
+            UTF8EncodedString("a/1".to_string()),
+            Some(packet_identity(9)),
+            Properties::new(),
+            b"hello".to_vec(),
+        )
+        .unwrap();
+        server.send(packets::Packet::Publish(publish));
+        let packets::Packet::PubAck(puback) = server.read() else {
+            panic!("The PUBACK Packet is expected");
+        };
+        assert_eq!(puback.packet_identifier, packet_identity(9));
+        assert!(matches!(server.read(), packets::Packet::Disconnect(_)));
+    });
+
+    let stream = TcpStream::connect(address).unwrap();
+    let (mut client, _) = Client::connect(stream, "c1", Properties::new(), TIMEOUT).unwrap();
+    let publish = client.receive(TIMEOUT).unwrap().unwrap();
+    assert_eq!(publish.payload, b"hello");
+    assert!(client.receive(Duration::from_millis(50)).unwrap().is_none());
+    client.disconnect().unwrap();
+    server.join().unwrap();
+}
+
+#[test]
+fn client_uses_assigned_client_id_and_server_keep_alive() {
+    let (address, server) = start(|server| {
+        let mut properties = Properties::new();
+        properties.insert(packets::ASSIGNED_CLIENT_IDENTIFIER, UTF8EncodedString("auto1".to_string()).into());
+        properties.insert(packets::SERVER_KEEP_ALIVE, TwoByteInteger(1).into());
+        accept(server, properties);
+        // The Client sends the PINGREQ Packet within the Server Keep Alive while it is idle.
+        assert!(matches!(server.read(), packets::Packet::PingReq));
+        server.send(packets::Packet::PingResp);
+        assert!(matches!(server.read(), packets::Packet::Disconnect(_)));
+    });
+
+    let stream = TcpStream::connect(address).unwrap();
+    let (mut client, _) = Client::connect(stream, "", Properties::new(), TIMEOUT).unwrap();
+    assert_eq!(client.client_id(), "auto1");
+    assert!(client.receive(Duration::from_millis(1500)).unwrap().is_none());
+    client.disconnect().unwrap();
+    server.join().unwrap();
+}
+
+#[test]
+fn client_reports_refused_connection() {
+    let (address, server) = start(|server| {
+        assert!(matches!(server.read(), packets::Packet::Connect(_)));
+        let connack = ConnAck {
+            connect_reason_code: packets::connack::NOT_AUTHORIZED,
+            ..ConnAck::default()
+        };
+        server.send(packets::Packet::ConnAck(connack));
+    });
+
+    let stream = TcpStream::connect(address).unwrap();
+    let err = Client::connect(stream, "c1", Properties::new(), TIMEOUT).err().unwrap();
+    assert!(err.to_string().contains("0x87"), "{}", err);
+    server.join().unwrap();
+}
+
+fn response(topic_name: &str, correlation_data: &[u8], payload: &[u8]) -> packets::Packet {
+    let mut properties = Properties::new();
+    properties.insert(packets::CORRELATION_DATA, packets::BinaryData(correlation_data.to_vec()).into());
+    let publish = Publish::new(
+        Publish::fixed_header(false, QoS::AtMostOnce, false),
+        UTF8EncodedString(topic_name.to_string()),
+        None,
+        properties,
+        payload.to_vec(),
+    )
+    .unwrap();
+    packets::Packet::Publish(publish)
+}
+
+#[test]
+fn client_request_waits_for_correlated_response() {
+    let (address, server) = start(|server| {
+        let mut properties = Properties::new();
+        properties.insert(packets::RESPONSE_INFORMATION, UTF8EncodedString("responses/c1".to_string()).into());
+        accept(server, properties);
+        let packets::Packet::Subscribe(subscribe) = server.read() else {
+            panic!("The SUBSCRIBE Packet is expected");
+        };
+        assert_eq!(subscribe.subscriptions[0].topic_filter.val(), "responses/c1");
+        let suback = SubAck::new(subscribe.packet_identifier, Properties::new(), vec![packets::suback::GRANTED_QOS_1]);
+        server.send(packets::Packet::SubAck(suback));
+
+        let mut correlations = Vec::new();
+        for _ in 0..2 {
+            let packets::Packet::Publish(request) = server.read() else {
+                panic!("The PUBLISH Packet is expected");
+            };
+            assert_eq!(request.topic_name.val(), "service/echo");
+            let response_topic = request.properties.get_as::<UTF8EncodedString>(packets::RESPONSE_TOPIC).unwrap().unwrap();
+            assert_eq!(response_topic.val(), "responses/c1");
+            correlations.push(correlation_data_of(&request).unwrap());
+            let puback = packets::puback::PubAck::new(request.packet_identifier.unwrap(), packets::puback::SUCCESS, Properties::new());
+            server.send(packets::Packet::PubAck(puback));
+            if correlations.len() == 1 {
+                // A late response of another request comes first, and it is discarded.
+                server.send(response("responses/c1", b"other", b"late"));
+                server.send(response("responses/c1", &correlations[0], b"pong"));
+            }
+        }
+        // Each request has its own Correlation Data.
+        assert_ne!(correlations[0], correlations[1]);
+        assert!(matches!(server.read(), packets::Packet::Disconnect(_)));
+    });
+
+    let stream = TcpStream::connect(address).unwrap();
+    let mut properties = Properties::new();
+    properties.insert(packets::REQUEST_RESPONSE_INFORMATION, packets::Bits(1).into());
+    let (mut client, _) = Client::connect(stream, "c1", properties, TIMEOUT).unwrap();
+    assert_eq!(client.response_topic(), "responses/c1");
+    let response = client.request("service/echo", b"ping", TIMEOUT).unwrap();
+    assert_eq!(response.payload, b"pong");
+    assert!(client.receive(Duration::from_millis(50)).unwrap().is_none());
+
+    // The second request is not answered.
+    let err = client.request("service/echo", b"ping", Duration::from_millis(200)).err().unwrap();
+    assert!(matches!(err, errors::Error::Io(err) if err.kind() == io::ErrorKind::TimedOut));
+    client.disconnect().unwrap();
+    server.join().unwrap();
+}
+
+#[test]
+fn client_response_topic_without_response_information() {
+    let (address, server) = start(|server| {
+        accept(server, Properties::new());
+        assert!(matches!(server.read(), packets::Packet::Disconnect(_)));
+    });
+
+    let stream = TcpStream::connect(address).unwrap();
+    let (client, _) = Client::connect(stream, "c1", Properties::new(), TIMEOUT).unwrap();
+    assert_eq!(client.response_topic(), "c1/responses");
+    client.disconnect().unwrap();
+    server.join().unwrap();
+}
@@ -6,16 +6,34 @@ use crate::errors;
 use crate::packets;
 use crate::packets::{BinaryData, Bits, ExtractValue, FourByteInteger, TwoByteInteger, UTF8EncodedString, UTF8StringPair, ValueTypes, VariableByteInteger};
 
+pub mod connack;
 pub mod connect;
+pub mod disconnect;
+pub mod publish;
+pub mod puback;
+pub mod pubrec;
+pub mod pubrel;
+pub mod pubcomp;
+pub mod suback;
+pub mod subscribe;
+pub mod unsubscribe;
+pub mod unsuback;
 
 #[path = "decoder_tests.rs"]
 #[cfg(test)]
 mod decoder_tests;
 
+// decode decodes a packet as the MQTT v5.0 format.
 pub fn decode(reader: &mut dyn Read) -> Result<packets::Packet, errors::Error> {
+    decode_as(reader, &packets::ProtocolVersion::V5)
+}
+
+// decode_as decodes a packet as the format of the provided protocol version.
+// As a note, the CONNECT Packet is decoded as the version which the packet itself declares.
+pub fn decode_as(reader: &mut dyn Read, protocol_version: &packets::ProtocolVersion) -> Result<packets::Packet, errors::Error> {
     let mut input = Vec::new();
     reader.read_to_end(&mut input)?;
-   let input = input.as_slice();
+    let input = input.as_slice();
 
     let fixed_header_result = parse_fixed_header(input);
     if fixed_header_result.is_err() {
@@ -23,15 +41,104 @@ pub fn decode(reader: &mut dyn Read) -> Result<packets::Packet, errors::Error> {
     }
 
     let (input, fixed_header) = fixed_header_result.unwrap();
+    let (_, input) = bytes::complete::take::<_, _, nom::error::Error<&[u8]>>(fixed_header.remaining_length.val() as usize)(input)
+        .finish()
+        .map_err(|_| errors::Error::MalformedPacket(
+            format!("The packet is shorter than the Remaining Length {}", fixed_header.remaining_length.val())
+        ))?;
     match fixed_header.control_packet_type {
         Bits(packets::CONNECT) => {
             let (_, connect) = connect::connect_parser(fixed_header)(input).finish()?;
             Ok(packets::Packet::Connect(connect))
         },
+        Bits(packets::CONNACK) => {
+            let (_, connack) = connack::connack_parser(fixed_header, *protocol_version)(input).finish()?;
+            Ok(packets::Packet::ConnAck(connack))
+        },
+        Bits(packets::PUBLISH) => {
+            let (_, publish) = publish::publish_parser(fixed_header, *protocol_version)(input).finish()?;
+            Ok(packets::Packet::Publish(publish))
+        },
+        Bits(packets::PUBACK) => {
+            let (_, puback) = puback::puback_parser(*protocol_version)(input).finish()?;
+            Ok(packets::Packet::PubAck(puback))
+        },
+        Bits(packets::PUBREC) => {
+            let (_, pubrec) = pubrec::pubrec_parser(*protocol_version)(input).finish()?;
+            Ok(packets::Packet::PubRec(pubrec))
+        },
+        Bits(packets::PUBREL) => {
+            // The PUBREL Packet with the other reserved flags is malformed [MQTT-3.6.1-1].
+            if fixed_header.flags != Bits(packets::pubrel::FLAGS) {
+                return Err(errors::Error::MalformedPacket(format!(
+                    "The flags of the PUBREL Packet must be 0x02. They are 0x{:02X}",
+                    fixed_header.flags.val()
+                )));
+            }
+            let (_, pubrel) = pubrel::pubrel_parser(*protocol_version)(input).finish()?;
+            Ok(packets::Packet::PubRel(pubrel))
+        },
+        Bits(packets::PUBCOMP) => {
+            let (_, pubcomp) = pubcomp::pubcomp_parser(*protocol_version)(input).finish()?;
+            Ok(packets::Packet::PubComp(pubcomp))
+        },
+        Bits(packets::SUBSCRIBE) => {
+            let (_, subscribe) = subscribe::subscribe_parser(fixed_header, *protocol_version)(input).finish()?;
+            Ok(packets::Packet::Subscribe(subscribe))
+        },
+        Bits(packets::SUBACK) => {
+            let (_, suback) = suback::suback_parser(*protocol_version)(input).finish()?;
+            Ok(packets::Packet::SubAck(suback))
+        },
+        Bits(packets::UNSUBSCRIBE) => {
+            let (_, unsubscribe) = unsubscribe::unsubscribe_parser(fixed_header, *protocol_version)(input).finish()?;
+            Ok(packets::Packet::Unsubscribe(unsubscribe))
+        },
+        Bits(packets::UNSUBACK) => {
+            let (_, unsuback) = unsuback::unsuback_parser(*protocol_version)(input).finish()?;
+            Ok(packets::Packet::UnsubAck(unsuback))
+        },
+        // The PINGREQ and PINGRESP Packets have only the Fixed Header (3.12 PINGREQ and 3.13 PINGRESP sections).
+        Bits(packets::PINGREQ) => Ok(packets::Packet::PingReq),
+        Bits(packets::PINGRESP) => Ok(packets::Packet::PingResp),
+        Bits(packets::DISCONNECT) => {
+            let (_, disconnect) = disconnect::disconnect_parser(*protocol_version)(input).finish()?;
+            Ok(packets::Packet::Disconnect(disconnect))
+        },
         _ => Err(errors::Error::Common("Not implemented yet".to_string())),
     }
 }
 
+// take_frame removes the bytes of a whole packet from the head of the buffer which a stream transport fills.
+// It results None while the buffer does not contain the whole packet yet.
+pub fn take_frame(buffer: &mut Vec<u8>) -> Result<Option<Vec<u8>>, errors::Error> {
+    match frame_length(buffer)? {
+        Some(length) if buffer.len() >= length => Ok(Some(buffer.drain(..length).collect())),
+        _ => Ok(None),
+    }
+}
+
+// frame_length results the size of the packet at the head of the buffer, the Fixed Header included,
+// as soon as the Fixed Header is received. So the size can be checked before the rest of the packet is buffered.
+// It results None while the Fixed Header is incomplete.
+// The Remaining Length is a Variable Byte Integer of up to 4 bytes (1.5.5 Variable Byte Integer subsection).
+pub fn frame_length(buffer: &[u8]) -> Result<Option<usize>, errors::Error> {
+    let mut remaining_length = 0usize;
+    let mut multiplier = 1usize;
+    for i in 1..=4 {
+        let Some(byte) = buffer.get(i) else {
+            return Ok(None);
+        };
+        remaining_length += (byte & 0x7F) as usize * multiplier;
+        if byte & 0x80 == 0 {
+            return Ok(Some(1 + i + remaining_length));
+        }
+        multiplier *= 128;
+    }
+
+    Err(errors::Error::MalformedPacket("The Remaining Length is longer than 4 bytes".to_string()))
+}
+
 fn parse_fixed_header(input: &[u8]) -> IResult<&[u8], packets::FixedHeader> {
     let (input, (control_packet_type, flags)) = parse_fixed_header_first_byte(input)?;
     let (input, remaining_length) = parse_variable_byte_integer(input)?;
@@ -140,10 +247,13 @@ fn parse_binary_data(input: &[u8]) -> IResult<&[u8], BinaryData> {
     Ok((input, BinaryData(data.to_vec())))
 }
 
+// BoxedParser is a boxed nom parser which borrows the input for the 'a lifetime.
+pub type BoxedParser<'a, T> = Box<dyn Fn(&'a [u8]) -> IResult<&'a [u8], T> + 'a>;
+
 // value_typed_parser is a helper function to convert the parsed value to ValueTypes.
 pub fn value_typed_parser<'a, F, T>(
     f: F,
-) -> Box<dyn Fn(&'a [u8]) -> IResult<&'a [u8], ValueTypes> + 'a>
+) -> BoxedParser<'a, ValueTypes>
 where
     F: Fn(&'a [u8]) -> IResult<&'a [u8], T> + 'a,
     T: Into<ValueTypes> + 'a,
@@ -155,7 +265,7 @@ where
 pub fn option_parser<'a, F, T>(
     f: F,
     b: bool,
-) -> Box<dyn Fn(&'a [u8]) -> IResult<&'a [u8], Option<T>> + 'a>
+) -> BoxedParser<'a, Option<T>>
 where
     F: Fn(&'a [u8]) -> IResult<&'a [u8], T> + 'a
 {
@@ -169,7 +279,8 @@ where
 }
 
 // parse_properties is a helper function to parse the properties from the input.
-// If there are duplicate properties, the last one will be used. But strictly speaking,
+// The User Property and the Subscription Identifier can appear multiple times, and every value is kept.
+// If there are the other duplicate properties, the last one will be used. But strictly speaking,
 // it should be an error as a Protocol Error of the reason code 0x97 of the MQTT 5.0 specs.
 fn parse_properties(input: &[u8]) -> IResult<&[u8], packets::Properties> {
     let (input, properties_length) = parse_variable_byte_integer(input)?;
@@ -181,7 +292,10 @@ fn parse_properties(input: &[u8]) -> IResult<&[u8], packets::Properties> {
     let mut properties = packets::Properties::new();
     while !properties_bytes.is_empty() {
         let (input, (identifier, value)) = parse_property(properties_bytes)?;
-        properties.insert(identifier, value);
+        match identifier {
+            packets::USER_PROPERTY | packets::SUBSCRIPTION_IDENTIFIER => properties.append(identifier, value),
+            _ => properties.insert(identifier, value),
+        }
         properties_bytes = input;
     }
 
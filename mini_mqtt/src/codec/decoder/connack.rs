@@ -0,0 +1,53 @@
+use super::*;
+use crate::packets;
+use nom::IResult;
+
+#[path = "connack_tests.rs"]
+#[cfg(test)]
+mod connack_tests;
+
+// connack_parser parses the CONNACK Packet which a Client receives.
+// MQTT v3.1.1 and v3.1 CONNACK Packets have no Properties, and have the Connect Return Code,
+// which is resulted as the corresponding Connect Reason Code.
+pub fn connack_parser<'a>(
+    fixed_header: packets::FixedHeader,
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::connack::ConnAck> {
+    move |input| {
+        // 3.2.2.1 Connect Acknowledge Flags, the Session Present flag is the bit 0 and the others are reserved.
+        let (input, flags) = parse_bits(input)?;
+        if flags.val() & 0b1111_1110 != 0 {
+            return Err(errors::Error::MalformedPacket(
+                format!("The reserved bits of the Connect Acknowledge Flags are set: {:#010b}", flags.val())
+            ).into());
+        }
+        let session_present = flags.val() & 0b0000_0001 != 0 && protocol_version.has_session_present();
+        let (input, code) = parse_bits(input)?;
+        let (input, connect_reason_code, properties) = if protocol_version.has_properties() {
+            let (input, properties) = parse_properties(input)?;
+            (input, packets::connack::ConnAckReasonCode(code.val()), properties)
+        } else {
+            let connect_reason_code = connect_reason_code_of(code.val()).ok_or_else(|| {
+                errors::Error::MalformedPacket(format!("The Connect Return Code 0x{:02X} is unknown", code.val()))
+            })?;
+            (input, connect_reason_code, packets::Properties::new())
+        };
+
+        let connack = packets::connack::ConnAck::new(fixed_header, session_present, connect_reason_code, properties);
+        Ok((input, connack))
+    }
+}
+
+// connect_reason_code_of results the Connect Reason Code of the Connect Return Code of MQTT v3.1.1
+// (3.2.2.3 Connect Return code of MQTT v3.1.1).
+fn connect_reason_code_of(return_code: u8) -> Option<packets::connack::ConnAckReasonCode> {
+    match return_code {
+        0x00 => Some(packets::connack::SUCCESS),
+        0x01 => Some(packets::connack::UNSUPPORTED_PROTOCOL_VERSION),
+        0x02 => Some(packets::connack::CLIENT_IDENTIFIER_NOT_VALID),
+        0x03 => Some(packets::connack::SERVER_UNAVAILABLE),
+        0x04 => Some(packets::connack::BAD_USER_NAME_OR_PASSWORD),
+        0x05 => Some(packets::connack::NOT_AUTHORIZED),
+        _ => None,
+    }
+}
@@ -0,0 +1,44 @@
+use super::connack::*;
+use crate::packets::connack;
+use crate::packets::{Bits, ExtractValue, FixedHeader, ProtocolVersion, UTF8EncodedString, VariableByteInteger};
+
+fn fixed_header(input: &[u8]) -> FixedHeader {
+    FixedHeader::new(Bits(0x02), Bits(0x00), VariableByteInteger(input.len() as u32)).unwrap()
+}
+
+#[test]
+fn connack_parser_v5() {
+    let input = vec![
+        0x01, // Session Present
+        0x00, // Success
+        0x09, // Properties Length
+        0x1A, 0x00, 0x06, b'r', b'e', b's', b'/', b'c', b'1', // Response Information
+    ];
+
+    let (_, connack) = connack_parser(fixed_header(&input), ProtocolVersion::V5)(&input).unwrap();
+    assert!(connack.session_present);
+    assert_eq!(connack.connect_reason_code, connack::SUCCESS);
+    assert_eq!(
+        connack.properties.get_as::<UTF8EncodedString>(crate::packets::RESPONSE_INFORMATION).unwrap().map(|value| value.val().to_string()),
+        Some("res/c1".to_string())
+    );
+}
+
+#[test]
+fn connack_parser_v3_1_1_maps_return_code() {
+    let input = vec![0x00, 0x05];
+
+    let (_, connack) = connack_parser(fixed_header(&input), ProtocolVersion::V3_1_1)(&input).unwrap();
+    assert!(!connack.session_present);
+    assert_eq!(connack.connect_reason_code, connack::NOT_AUTHORIZED);
+    assert!(connack.properties.is_empty());
+
+    let input = vec![0x00, 0x06];
+    assert!(connack_parser(fixed_header(&input), ProtocolVersion::V3_1_1)(&input).is_err());
+}
+
+#[test]
+fn connack_parser_rejects_reserved_flags() {
+    let input = vec![0x02, 0x00, 0x00];
+    assert!(connack_parser(fixed_header(&input), ProtocolVersion::V5)(&input).is_err());
+}
@@ -13,7 +13,8 @@ pub fn connect_parser<'a>(
         let (input, variable_header) = parse_variable_header(input)?;
         let (input, payload) = {
             let connect_flags = &variable_header.connect_flags;
-            payload_parser(connect_flags)(input)?
+            let protocol_version = protocol_version_of(&variable_header);
+            payload_parser(connect_flags, protocol_version)(input)?
         };
         let connect = packets::connect::Connect::new(fixed_header, variable_header, payload)?;
         Ok((input, connect))
@@ -22,9 +23,11 @@ pub fn connect_parser<'a>(
 
 pub fn parse_variable_header(input: &[u8]) -> IResult<&[u8], packets::connect::VariableHeader> {
     let (input, protocol_name) = parse_utf8_encoded_string(input)?; // should be "MQTT"
-    let (input, protocol_version) = parse_bits(input)?; // should be 5
+    let (input, protocol_version) = parse_bits(input)?; // should be 5 or 4
     let (input, connect_flags) = parse_connect_flags(input)?; //
     let (input, keep_alive) = parse_two_byte_integer(input)?;
+    // The Properties are parsed except for MQTT v3.1.1, even the version is unknown.
+    // Because the unknown version is rejected by the validation after the parsing.
     // CONNECT can have
     // - Session Expiry Interval
     // - Receive Maximum
@@ -35,7 +38,11 @@ pub fn parse_variable_header(input: &[u8]) -> IResult<&[u8], packets::connect::V
     // - User Property
     // - Authentication Method
     // - Authentication Data
-    let (input, properties) = parse_properties(input)?;
+    let (input, properties) = if protocol_version_has_properties(&protocol_version) {
+        parse_properties(input)?
+    } else {
+        (input, packets::Properties::new())
+    };
 
     let variable_header = packets::connect::VariableHeader::new(
         protocol_name,
@@ -47,6 +54,17 @@ pub fn parse_variable_header(input: &[u8]) -> IResult<&[u8], packets::connect::V
     Ok((input, variable_header))
 }
 
+fn protocol_version_has_properties(protocol_version: &Bits) -> bool {
+    packets::ProtocolVersion::from_level(protocol_version.val())
+        .is_none_or(|version| version.has_properties())
+}
+
+// protocol_version_of results the version to parse the payload, the unknown version is parsed as MQTT v5.0.
+fn protocol_version_of(variable_header: &packets::connect::VariableHeader) -> packets::ProtocolVersion {
+    packets::ProtocolVersion::from_level(variable_header.protocol_version.val())
+        .unwrap_or(packets::ProtocolVersion::V5)
+}
+
 fn parse_connect_flags(input: &[u8]) -> IResult<&[u8], packets::connect::ConnectFlags> {
     let (input, flags) = parse_bits(input)?;
 
@@ -54,14 +72,21 @@ fn parse_connect_flags(input: &[u8]) -> IResult<&[u8], packets::connect::Connect
     Ok((input, flags))
 }
 
-fn payload_parser<'a, 'b>(connect_flags: &'b packets::connect::ConnectFlags) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::connect::Payload> +'a {
+fn payload_parser<'a, 'b>(
+    connect_flags: &'b packets::connect::ConnectFlags,
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::connect::Payload> +'a {
     let connect_flags = connect_flags.clone();
     move |input|  {
-        parse_payload(input, connect_flags)
+        parse_payload(input, connect_flags, protocol_version)
     }
 }
 
-fn parse_payload(input: &[u8], connect_flags: packets::connect::ConnectFlags) -> IResult<&[u8], packets::connect::Payload> {
+fn parse_payload(
+    input: &[u8],
+    connect_flags: packets::connect::ConnectFlags,
+    protocol_version: packets::ProtocolVersion,
+) -> IResult<&[u8], packets::connect::Payload> {
     // 3.1.3 CONNECT Payload subsection
     // 3.1.3.1 Client Identifier (ClientID) subsection, TODO: validation
     let (input, client_id) = parse_utf8_encoded_string(input)?;
@@ -75,8 +100,14 @@ fn parse_payload(input: &[u8], connect_flags: packets::connect::ConnectFlags) ->
     // - Response Topic
     // - Correlation Data
     // - User Property
+    // MQTT v3.1.1 does not have the Will Properties, so it is treated as empty.
     let (input, will_properties) =
-        option_parser(parse_properties, connect_flags.will_flag())(input)?;
+        option_parser(parse_properties, connect_flags.will_flag() && protocol_version.has_properties())(input)?;
+    let will_properties = if connect_flags.will_flag() && !protocol_version.has_properties() {
+        Some(packets::Properties::new())
+    } else {
+        will_properties
+    };
     // 3.1.3.3 Will Topic subsection
     let (input, will_topic) =
         option_parser(parse_utf8_encoded_string, connect_flags.will_flag())(input)?;
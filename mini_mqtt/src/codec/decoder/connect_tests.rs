@@ -2,6 +2,7 @@
 use super::connect::*;
 use crate::packets::{FixedHeader, Bits, TwoByteInteger, UTF8EncodedString, VariableByteInteger, UTF8StringPair};
 use crate::packets::connect::ConnectFlags;
+use crate::packets::ProtocolVersion;
 
 #[test]
 fn connect_parser_valid_input() {
@@ -65,7 +66,7 @@ fn parse_payload_valid_input() {
     let input = vec![
         0x00, 0x04, b't', b'e', b's', b't', // Client ID
     ];
-    let result = parse_payload(&input, connect_flags);
+    let result = parse_payload(&input, connect_flags, ProtocolVersion::V5);
     let (_, payload) = result.unwrap();
     assert_eq!(payload.client_id, UTF8EncodedString("test".to_string()));
     assert!(payload.will_properties.is_none());
@@ -89,7 +90,7 @@ fn parse_payload_with_will_flag() {
         0x00, 0x08, b'u', b's', b'e', b'r', b'n', b'a', b'm', b'e', // User Name
         0x00, 0x03, b'p', b'a', b's', // Password
     ];
-    let result = parse_payload(&input, connect_flags);
+    let result = parse_payload(&input, connect_flags, ProtocolVersion::V5);
     assert!(result.is_ok());
     let (_, payload) = result.unwrap();
     assert_eq!(payload.client_id, UTF8EncodedString("test".to_string()));
@@ -101,4 +102,43 @@ fn parse_payload_with_will_flag() {
     assert_eq!(payload.will_payload.unwrap().val(), &vec![b'd', b'a', b't']);
     assert_eq!(payload.user_name.unwrap(), UTF8EncodedString("username".to_string()));
     assert_eq!(payload.password.unwrap().val(), &vec![b'p', b'a', b's']);
-}
\ No newline at end of file
+}
+#[test]
+fn connect_parser_v3_1_1_has_no_properties() {
+    let input = vec![
+        0x00, 0x04, b'M', b'Q', b'T', b'T', // Protocol Name
+        0x04, // Protocol Version
+        0x06, // Connect Flags, Will Flag and Clean Session set
+        0x00, 0x3C, // Keep Alive
+        0x00, 0x04, b't', b'e', b's', b't', // Client ID
+        0x00, 0x04, b'w', b'i', b'l', b'l', // Will Topic
+        0x00, 0x03, b'd', b'a', b't', // Will Payload
+    ];
+    let fixed_header = FixedHeader::new(Bits(0x10), Bits(0x00), VariableByteInteger(input.len() as u32)).unwrap();
+
+    let (remaining, connect) = connect_parser(fixed_header)(&input).unwrap();
+    assert!(remaining.is_empty());
+    assert_eq!(connect.protocol_version(), Some(ProtocolVersion::V3_1_1));
+    assert!(connect.variable_header.properties.is_empty());
+    assert_eq!(connect.payload.client_id, UTF8EncodedString("test".to_string()));
+    assert!(connect.payload.will_properties.unwrap().is_empty());
+    assert_eq!(connect.payload.will_topic.unwrap(), UTF8EncodedString("will".to_string()));
+    assert_eq!(connect.payload.will_payload.unwrap().val(), &vec![b'd', b'a', b't']);
+}
+
+#[test]
+fn connect_parser_mqisdp_has_no_properties() {
+    let input = vec![
+        0x00, 0x06, b'M', b'Q', b'I', b's', b'd', b'p', // Protocol Name
+        0x03, // Protocol Version
+        0x02, // Connect Flags
+        0x00, 0x3C, // Keep Alive
+        0x00, 0x04, b't', b'e', b's', b't', // Client ID
+    ];
+    let fixed_header = FixedHeader::new(Bits(0x10), Bits(0x00), VariableByteInteger(input.len() as u32)).unwrap();
+
+    let (remaining, connect) = connect_parser(fixed_header)(&input).unwrap();
+    assert!(remaining.is_empty());
+    assert_eq!(connect.protocol_version(), Some(ProtocolVersion::V3_1));
+    assert_eq!(connect.payload.client_id, UTF8EncodedString("test".to_string()));
+}
@@ -0,0 +1,34 @@
+use super::*;
+use crate::packets;
+use nom::IResult;
+
+#[path = "disconnect_tests.rs"]
+#[cfg(test)]
+mod disconnect_tests;
+
+// disconnect_parser parses the DISCONNECT Packet.
+// The Reason Code and the Property Length can be omitted if the Reason Code is 0x00 (Normal disconnection)
+// and there are no Properties. In this case the DISCONNECT has a Remaining Length of 0 (3.14.2.1 Disconnect Reason Code subsection).
+// The MQTT v3.1.1 and v3.1 DISCONNECT Packets have no Variable Header.
+pub fn disconnect_parser<'a>(
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::disconnect::Disconnect> {
+    move |input| {
+        let (input, reason_code) = if protocol_version.has_properties() && !input.is_empty() {
+            parse_bits(input)?
+        } else {
+            (input, Bits(packets::disconnect::NORMAL_DISCONNECTION.0))
+        };
+        let (input, properties) = if protocol_version.has_properties() && !input.is_empty() {
+            parse_properties(input)?
+        } else {
+            (input, packets::Properties::new())
+        };
+
+        let disconnect = packets::disconnect::Disconnect::new(
+            packets::disconnect::DisconnectReasonCode(reason_code.val()),
+            properties,
+        );
+        Ok((input, disconnect))
+    }
+}
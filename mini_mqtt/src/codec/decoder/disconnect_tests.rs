@@ -0,0 +1,45 @@
+use super::disconnect::*;
+use crate::packets::disconnect;
+use crate::packets::{ProtocolVersion, UTF8EncodedString};
+
+#[test]
+fn disconnect_parser_short_form() {
+    let input = vec![];
+
+    let (_, disconnect) = disconnect_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(disconnect.reason_code, disconnect::NORMAL_DISCONNECTION);
+    assert!(disconnect.properties.is_empty());
+}
+
+#[test]
+fn disconnect_parser_with_reason_code() {
+    let input = vec![0x04];
+
+    let (_, disconnect) = disconnect_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(disconnect.reason_code, disconnect::DISCONNECT_WITH_WILL_MESSAGE);
+    assert!(disconnect.properties.is_empty());
+}
+
+#[test]
+fn disconnect_parser_with_properties() {
+    let input = vec![
+        0x00, // Reason Code
+        0x05, // Properties Length
+        0x1F, 0x00, 0x02, b'b', b'y', // Reason String
+    ];
+
+    let (_, disconnect) = disconnect_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(disconnect.reason_code, disconnect::NORMAL_DISCONNECTION);
+    assert_eq!(
+        disconnect.properties.get_as::<UTF8EncodedString>(packets::REASON_STRING).unwrap(),
+        Some(&UTF8EncodedString("by".to_string()))
+    );
+}
+
+#[test]
+fn disconnect_parser_v3_1_1() {
+    let input = vec![];
+
+    let (_, disconnect) = disconnect_parser(ProtocolVersion::V3_1_1)(&input).unwrap();
+    assert_eq!(disconnect.reason_code, disconnect::NORMAL_DISCONNECTION);
+}
@@ -0,0 +1,47 @@
+use super::*;
+use crate::packets;
+use nom::IResult;
+
+#[path = "puback_tests.rs"]
+#[cfg(test)]
+mod puback_tests;
+
+// puback_parser parses the PUBACK Packet.
+// The Reason Code and the Property Length can be omitted if the Reason Code is 0x00 (Success)
+// and there are no Properties. In this case the PUBACK has a Remaining Length of 2 (3.4.2.1 PUBACK Reason Code subsection).
+pub fn puback_parser<'a>(
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::puback::PubAck> {
+    move |input| {
+        let (input, (packet_identifier, reason_code, properties)) = publish_response_parser(protocol_version)(input)?;
+
+        let puback = packets::puback::PubAck::new(
+            packet_identifier,
+            packets::puback::PubAckReasonCode(reason_code.val()),
+            properties,
+        );
+        Ok((input, puback))
+    }
+}
+
+// publish_response_parser parses the Variable Header of the PUBACK, PUBREC, PUBREL and PUBCOMP Packets,
+// which share the same layout. The omitted Reason Code is 0x00 (Success) for each of them.
+pub fn publish_response_parser<'a>(
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], (packets::PacketIdentity, Bits, packets::Properties)> {
+    move |input| {
+        let (input, packet_identifier) = parse_two_byte_integer(input)?;
+        let (input, reason_code) = if protocol_version.has_properties() && !input.is_empty() {
+            parse_bits(input)?
+        } else {
+            (input, Bits(0x00))
+        };
+        let (input, properties) = if protocol_version.has_properties() && !input.is_empty() {
+            parse_properties(input)?
+        } else {
+            (input, packets::Properties::new())
+        };
+
+        Ok((input, (packets::PacketIdentity::new(packet_identifier), reason_code, properties)))
+    }
+}
@@ -0,0 +1,47 @@
+use super::puback::*;
+use crate::packets::puback;
+use crate::packets::{ExtractValue, ProtocolVersion, UTF8EncodedString};
+
+#[test]
+fn puback_parser_short_form() {
+    let input = vec![0x00, 0x07];
+
+    let (_, puback) = puback_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(puback.packet_identifier.val(), 7);
+    assert_eq!(puback.reason_code, puback::SUCCESS);
+    assert!(puback.properties.is_empty());
+}
+
+#[test]
+fn puback_parser_with_reason_code() {
+    let input = vec![0x00, 0x07, 0x10];
+
+    let (_, puback) = puback_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(puback.reason_code, puback::NO_MATCHING_SUBSCRIBERS);
+}
+
+#[test]
+fn puback_parser_with_properties() {
+    let input = vec![
+        0x00, 0x07, // Packet Identifier
+        0x87, // Reason Code
+        0x05, // Properties Length
+        0x1F, 0x00, 0x02, b'n', b'o', // Reason String
+    ];
+
+    let (_, puback) = puback_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(puback.reason_code, puback::NOT_AUTHORIZED);
+    assert_eq!(
+        puback.properties.get_as::<UTF8EncodedString>(packets::REASON_STRING).unwrap(),
+        Some(&UTF8EncodedString("no".to_string()))
+    );
+}
+
+#[test]
+fn puback_parser_v3_1_1() {
+    let input = vec![0x00, 0x07];
+
+    let (_, puback) = puback_parser(ProtocolVersion::V3_1_1)(&input).unwrap();
+    assert_eq!(puback.packet_identifier.val(), 7);
+    assert_eq!(puback.reason_code, puback::SUCCESS);
+}
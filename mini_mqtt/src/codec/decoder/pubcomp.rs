@@ -0,0 +1,24 @@
+use super::puback::publish_response_parser;
+use super::*;
+use crate::packets;
+use nom::IResult;
+
+#[path = "pubcomp_tests.rs"]
+#[cfg(test)]
+mod pubcomp_tests;
+
+// pubcomp_parser parses the PUBCOMP Packet, whose Reason Code and Properties can be omitted as the PUBACK Packet.
+pub fn pubcomp_parser<'a>(
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::pubcomp::PubComp> {
+    move |input| {
+        let (input, (packet_identifier, reason_code, properties)) = publish_response_parser(protocol_version)(input)?;
+
+        let pubcomp = packets::pubcomp::PubComp::new(
+            packet_identifier,
+            packets::pubcomp::PubCompReasonCode(reason_code.val()),
+            properties,
+        );
+        Ok((input, pubcomp))
+    }
+}
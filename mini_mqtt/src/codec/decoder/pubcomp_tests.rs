@@ -0,0 +1,12 @@
+use super::pubcomp::*;
+use crate::packets::pubcomp;
+use crate::packets::{ExtractValue, ProtocolVersion};
+
+#[test]
+fn pubcomp_parser_packet_identifier_not_found() {
+    let input = vec![0x00, 0x07, 0x92];
+
+    let (_, pubcomp) = pubcomp_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(pubcomp.packet_identifier.val(), 7);
+    assert_eq!(pubcomp.reason_code, pubcomp::PACKET_IDENTIFIER_NOT_FOUND);
+}
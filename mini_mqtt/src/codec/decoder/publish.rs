@@ -0,0 +1,37 @@
+use super::*;
+use crate::packets;
+use nom::IResult;
+
+#[path = "publish_tests.rs"]
+#[cfg(test)]
+mod publish_tests;
+
+// publish_parser parses the PUBLISH Packet. The input must be the rest of the packet after the Fixed Header,
+// because the Payload is the remaining bytes of the packet (3.3.3 PUBLISH Payload subsection).
+pub fn publish_parser<'a>(
+    fixed_header: packets::FixedHeader,
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::publish::Publish> {
+    move |input| {
+        let qos_bits = (fixed_header.flags.val() & 0b0110) >> 1;
+        // 3.3.2.1 Topic Name subsection
+        let (input, topic_name) = parse_utf8_encoded_string(input)?;
+        // 3.3.2.2 Packet Identifier subsection, it is present only for QoS 1 and 2.
+        let (input, packet_identifier) = option_parser(parse_two_byte_integer, qos_bits > 0)(input)?;
+        // 3.3.2.3 PUBLISH Properties subsection
+        let (input, properties) = if protocol_version.has_properties() {
+            parse_properties(input)?
+        } else {
+            (input, packets::Properties::new())
+        };
+
+        let publish = packets::publish::Publish::new(
+            fixed_header,
+            topic_name,
+            packet_identifier.map(packets::PacketIdentity::new),
+            properties,
+            input.to_vec(),
+        )?;
+        Ok((&input[input.len()..], publish))
+    }
+}
@@ -0,0 +1,68 @@
+use super::publish::*;
+use crate::packets::{Bits, ExtractValue, FixedHeader, ProtocolVersion, QoS, UTF8EncodedString, VariableByteInteger};
+
+fn fixed_header(flags: u8, input: &[u8]) -> FixedHeader {
+    FixedHeader::new(Bits(0x03), Bits(flags), VariableByteInteger(input.len() as u32)).unwrap()
+}
+
+#[test]
+fn publish_parser_qos0() {
+    let input = vec![
+        0x00, 0x03, b'a', b'/', b'b', // Topic Name
+        0x00, // Properties Length
+        b'h', b'i', // Payload
+    ];
+
+    let (remaining, publish) = publish_parser(fixed_header(0b0001, &input), ProtocolVersion::V5)(&input).unwrap();
+    assert!(remaining.is_empty());
+    assert_eq!(publish.topic_name, UTF8EncodedString("a/b".to_string()));
+    assert_eq!(publish.qos(), QoS::AtMostOnce);
+    assert!(publish.retain());
+    assert!(publish.packet_identifier.is_none());
+    assert_eq!(publish.payload, b"hi".to_vec());
+}
+
+#[test]
+fn publish_parser_qos1_with_properties() {
+    let input = vec![
+        0x00, 0x03, b'a', b'/', b'b', // Topic Name
+        0x00, 0x07, // Packet Identifier
+        0x05, // Properties Length
+        0x02, 0x00, 0x00, 0x00, 0x3C, // Message Expiry Interval
+        b'h', b'i', // Payload
+    ];
+
+    let (_, publish) = publish_parser(fixed_header(0b0010, &input), ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(publish.qos(), QoS::AtLeastOnce);
+    assert_eq!(publish.packet_identifier.unwrap().val(), 7);
+    assert_eq!(
+        publish.properties.get_as::<packets::FourByteInteger>(packets::MESSAGE_EXPIRY_INTERVAL).unwrap(),
+        Some(&packets::FourByteInteger(60))
+    );
+    assert_eq!(publish.payload, b"hi".to_vec());
+}
+
+#[test]
+fn publish_parser_v3_1_1() {
+    let input = vec![
+        0x00, 0x03, b'a', b'/', b'b', // Topic Name
+        0x00, 0x07, // Packet Identifier
+        0x05, b'h', b'i', // Payload, it is not the Properties
+    ];
+
+    let (_, publish) = publish_parser(fixed_header(0b0010, &input), ProtocolVersion::V3_1_1)(&input).unwrap();
+    assert!(publish.properties.is_empty());
+    assert_eq!(publish.payload, vec![0x05, b'h', b'i']);
+}
+
+#[test]
+fn publish_parser_malformed_qos() {
+    let input = vec![
+        0x00, 0x03, b'a', b'/', b'b', // Topic Name
+        0x00, 0x07, // Packet Identifier
+        0x00, // Properties Length
+    ];
+
+    let result = publish_parser(fixed_header(0b0110, &input), ProtocolVersion::V5)(&input);
+    assert!(result.is_err());
+}
@@ -0,0 +1,24 @@
+use super::puback::publish_response_parser;
+use super::*;
+use crate::packets;
+use nom::IResult;
+
+#[path = "pubrec_tests.rs"]
+#[cfg(test)]
+mod pubrec_tests;
+
+// pubrec_parser parses the PUBREC Packet, whose Reason Code and Properties can be omitted as the PUBACK Packet.
+pub fn pubrec_parser<'a>(
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::pubrec::PubRec> {
+    move |input| {
+        let (input, (packet_identifier, reason_code, properties)) = publish_response_parser(protocol_version)(input)?;
+
+        let pubrec = packets::pubrec::PubRec::new(
+            packet_identifier,
+            packets::pubrec::PubRecReasonCode(reason_code.val()),
+            properties,
+        );
+        Ok((input, pubrec))
+    }
+}
@@ -0,0 +1,22 @@
+use super::pubrec::*;
+use crate::packets::pubrec;
+use crate::packets::{ExtractValue, ProtocolVersion};
+
+#[test]
+fn pubrec_parser_short_form() {
+    let input = vec![0x00, 0x07];
+
+    let (_, pubrec) = pubrec_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(pubrec.packet_identifier.val(), 7);
+    assert_eq!(pubrec.reason_code, pubrec::SUCCESS);
+    assert!(pubrec.properties.is_empty());
+}
+
+#[test]
+fn pubrec_parser_with_reason_code() {
+    let input = vec![0x00, 0x07, 0x87, 0x00];
+
+    let (_, pubrec) = pubrec_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(pubrec.reason_code, pubrec::NOT_AUTHORIZED);
+    assert!(pubrec.reason_code.is_failure());
+}
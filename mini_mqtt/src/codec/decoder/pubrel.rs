@@ -0,0 +1,24 @@
+use super::puback::publish_response_parser;
+use super::*;
+use crate::packets;
+use nom::IResult;
+
+#[path = "pubrel_tests.rs"]
+#[cfg(test)]
+mod pubrel_tests;
+
+// pubrel_parser parses the PUBREL Packet, whose Reason Code and Properties can be omitted as the PUBACK Packet.
+pub fn pubrel_parser<'a>(
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::pubrel::PubRel> {
+    move |input| {
+        let (input, (packet_identifier, reason_code, properties)) = publish_response_parser(protocol_version)(input)?;
+
+        let pubrel = packets::pubrel::PubRel::new(
+            packet_identifier,
+            packets::pubrel::PubRelReasonCode(reason_code.val()),
+            properties,
+        );
+        Ok((input, pubrel))
+    }
+}
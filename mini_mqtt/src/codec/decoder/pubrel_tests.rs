@@ -0,0 +1,21 @@
+use super::pubrel::*;
+use crate::packets::pubrel;
+use crate::packets::{ExtractValue, ProtocolVersion};
+
+#[test]
+fn pubrel_parser_short_form() {
+    let input = vec![0x00, 0x07];
+
+    let (_, pubrel) = pubrel_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(pubrel.packet_identifier.val(), 7);
+    assert_eq!(pubrel.reason_code, pubrel::SUCCESS);
+}
+
+#[test]
+fn pubrel_parser_v3_1_1() {
+    let input = vec![0x00, 0x07];
+
+    let (_, pubrel) = pubrel_parser(ProtocolVersion::V3_1_1)(&input).unwrap();
+    assert_eq!(pubrel.packet_identifier.val(), 7);
+    assert_eq!(pubrel.reason_code, pubrel::SUCCESS);
+}
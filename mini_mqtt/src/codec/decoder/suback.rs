@@ -0,0 +1,37 @@
+use super::*;
+use crate::packets;
+use nom::IResult;
+
+#[path = "suback_tests.rs"]
+#[cfg(test)]
+mod suback_tests;
+
+// suback_parser parses the SUBACK Packet which a Client receives.
+// MQTT v3.1.1 SUBACK Packet has no Properties, and its Return Codes are the same values as the Reason Codes.
+pub fn suback_parser<'a>(
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::suback::SubAck> {
+    move |input| {
+        // 3.9.2 SUBACK Variable Header subsection
+        let (input, packet_identifier) = parse_two_byte_integer(input)?;
+        let (mut input, properties) = if protocol_version.has_properties() {
+            parse_properties(input)?
+        } else {
+            (input, packets::Properties::new())
+        };
+        // 3.9.3 SUBACK Payload subsection, a Reason Code for each Topic Filter.
+        let mut reason_codes = Vec::new();
+        while !input.is_empty() {
+            let (remaining, reason_code) = parse_bits(input)?;
+            reason_codes.push(packets::suback::SubAckReasonCode(reason_code.val()));
+            input = remaining;
+        }
+
+        let suback = packets::suback::SubAck::new(
+            packets::PacketIdentity::new(packet_identifier),
+            properties,
+            reason_codes,
+        );
+        Ok((input, suback))
+    }
+}
@@ -0,0 +1,27 @@
+use super::suback::*;
+use crate::packets::suback;
+use crate::packets::{ExtractValue, ProtocolVersion};
+
+#[test]
+fn suback_parser_v5() {
+    let input = vec![
+        0x00, 0x07, // Packet Identifier
+        0x00, // Properties Length
+        0x01, // Granted QoS 1
+        0x87, // Not authorized
+    ];
+
+    let (_, suback) = suback_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(suback.packet_identifier.val(), 7);
+    assert_eq!(suback.reason_codes, vec![suback::GRANTED_QOS_1, suback::NOT_AUTHORIZED]);
+}
+
+#[test]
+fn suback_parser_v3_1_1() {
+    let input = vec![0x00, 0x07, 0x02, 0x80];
+
+    let (_, suback) = suback_parser(ProtocolVersion::V3_1_1)(&input).unwrap();
+    assert_eq!(suback.packet_identifier.val(), 7);
+    assert!(suback.properties.is_empty());
+    assert_eq!(suback.reason_codes, vec![suback::GRANTED_QOS_2, suback::UNSPECIFIED_ERROR]);
+}
@@ -0,0 +1,59 @@
+use super::*;
+use crate::packets;
+use nom::IResult;
+
+#[path = "subscribe_tests.rs"]
+#[cfg(test)]
+mod subscribe_tests;
+
+pub fn subscribe_parser<'a>(
+    fixed_header: packets::FixedHeader,
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::subscribe::Subscribe> {
+    move |input| {
+        // 3.8.2 SUBSCRIBE Variable Header subsection
+        let (input, packet_identifier) = parse_two_byte_integer(input)?;
+        // 3.8.2.1 SUBSCRIBE Properties subsection, it can have
+        // - Subscription Identifier
+        // - User Property
+        let (input, properties) = if protocol_version.has_properties() {
+            parse_properties(input)?
+        } else {
+            (input, packets::Properties::new())
+        };
+        let (input, subscriptions) = parse_subscriptions(input, protocol_version)?;
+
+        let subscribe = packets::subscribe::Subscribe::new(
+            fixed_header,
+            packets::PacketIdentity::new(packet_identifier),
+            properties,
+            subscriptions,
+        )?;
+        Ok((input, subscribe))
+    }
+}
+
+// parse_subscriptions parses the pairs of the Topic Filter and the Subscription Options until the end of the packet.
+// 3.8.3 SUBSCRIBE Payload subsection
+fn parse_subscriptions(
+    input: &[u8],
+    protocol_version: packets::ProtocolVersion,
+) -> IResult<&[u8], Vec<packets::subscribe::Subscription>> {
+    let mut subscriptions = Vec::new();
+    let mut input = input;
+    while !input.is_empty() {
+        let (remaining, topic_filter) = parse_utf8_encoded_string(input)?;
+        let (remaining, options) = parse_bits(remaining)?;
+        // The Requested QoS byte of MQTT v3.1.1 has only the QoS bits, the other bits are reserved.
+        if !protocol_version.has_properties() && options.val() & 0b1111_1100 != 0 {
+            return Err(errors::Error::MalformedPacket(
+                format!("The reserved bits of the Requested QoS are set: {:#010b}", options.val())
+            ).into());
+        }
+        let options = packets::subscribe::SubscriptionOptions::new(options)?;
+        subscriptions.push(packets::subscribe::Subscription::new(topic_filter, options));
+        input = remaining;
+    }
+
+    Ok((input, subscriptions))
+}
@@ -0,0 +1,69 @@
+use super::subscribe::*;
+use crate::packets::{Bits, FixedHeader, ProtocolVersion, QoS, UTF8EncodedString, VariableByteInteger};
+
+fn fixed_header(input: &[u8]) -> FixedHeader {
+    FixedHeader::new(Bits(0x08), Bits(0b0010), VariableByteInteger(input.len() as u32)).unwrap()
+}
+
+#[test]
+fn subscribe_parser_valid_input() {
+    let input = vec![
+        0x00, 0x0A, // Packet Identifier
+        0x02, // Properties Length
+        0x0B, 0x01, // Subscription Identifier
+        0x00, 0x03, b'a', b'/', b'b', // Topic Filter
+        0x01, // Subscription Options
+        0x00, 0x03, b'c', b'/', b'#', // Topic Filter
+        0x2E, // Subscription Options
+    ];
+
+    let (_, subscribe) = subscribe_parser(fixed_header(&input), ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(subscribe.packet_identifier.val(), 10);
+    assert_eq!(
+        subscribe.properties.get_as::<VariableByteInteger>(packets::SUBSCRIPTION_IDENTIFIER).unwrap(),
+        Some(&VariableByteInteger(1))
+    );
+    assert_eq!(subscribe.subscriptions.len(), 2);
+    assert_eq!(subscribe.subscriptions[0].topic_filter, UTF8EncodedString("a/b".to_string()));
+    assert_eq!(subscribe.subscriptions[0].options.maximum_qos(), QoS::AtLeastOnce);
+    assert_eq!(subscribe.subscriptions[1].topic_filter, UTF8EncodedString("c/#".to_string()));
+    assert!(subscribe.subscriptions[1].options.no_local());
+    assert_eq!(subscribe.subscriptions[1].options.retain_handling(), 2);
+}
+
+#[test]
+fn subscribe_parser_v3_1_1_has_no_properties() {
+    let input = vec![
+        0x00, 0x0A, // Packet Identifier
+        0x00, 0x03, b'a', b'/', b'b', // Topic Filter
+        0x02, // Requested QoS
+    ];
+
+    let (_, subscribe) = subscribe_parser(fixed_header(&input), ProtocolVersion::V3_1_1)(&input).unwrap();
+    assert!(subscribe.properties.is_empty());
+    assert_eq!(subscribe.subscriptions.len(), 1);
+    assert_eq!(subscribe.subscriptions[0].options.maximum_qos(), QoS::ExactlyOnce);
+}
+
+#[test]
+fn subscribe_parser_v3_1_1_reserved_bits() {
+    let input = vec![
+        0x00, 0x0A, // Packet Identifier
+        0x00, 0x03, b'a', b'/', b'b', // Topic Filter
+        0x06, // Requested QoS with the reserved bit
+    ];
+
+    let result = subscribe_parser(fixed_header(&input), ProtocolVersion::V3_1_1)(&input);
+    assert!(result.is_err());
+}
+
+#[test]
+fn subscribe_parser_without_topic_filter() {
+    let input = vec![
+        0x00, 0x0A, // Packet Identifier
+        0x00, // Properties Length
+    ];
+
+    let result = subscribe_parser(fixed_header(&input), ProtocolVersion::V5)(&input);
+    assert!(result.is_err());
+}
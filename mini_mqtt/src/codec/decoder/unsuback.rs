@@ -0,0 +1,41 @@
+use super::*;
+use crate::packets;
+use nom::IResult;
+
+#[path = "unsuback_tests.rs"]
+#[cfg(test)]
+mod unsuback_tests;
+
+// unsuback_parser parses the UNSUBACK Packet which a Client receives.
+// MQTT v3.1.1 UNSUBACK Packet has only the Packet Identifier, so it has no Reason Codes then.
+pub fn unsuback_parser<'a>(
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::unsuback::UnsubAck> {
+    move |input| {
+        // 3.11.2 UNSUBACK Variable Header subsection
+        let (input, packet_identifier) = parse_two_byte_integer(input)?;
+        if !protocol_version.has_properties() {
+            let unsuback = packets::unsuback::UnsubAck::new(
+                packets::PacketIdentity::new(packet_identifier),
+                packets::Properties::new(),
+                Vec::new(),
+            );
+            return Ok((input, unsuback));
+        }
+        let (mut input, properties) = parse_properties(input)?;
+        // 3.11.3 UNSUBACK Payload subsection, a Reason Code for each Topic Filter.
+        let mut reason_codes = Vec::new();
+        while !input.is_empty() {
+            let (remaining, reason_code) = parse_bits(input)?;
+            reason_codes.push(packets::unsuback::UnsubAckReasonCode(reason_code.val()));
+            input = remaining;
+        }
+
+        let unsuback = packets::unsuback::UnsubAck::new(
+            packets::PacketIdentity::new(packet_identifier),
+            properties,
+            reason_codes,
+        );
+        Ok((input, unsuback))
+    }
+}
@@ -0,0 +1,26 @@
+use super::unsuback::*;
+use crate::packets::unsuback;
+use crate::packets::{ExtractValue, ProtocolVersion};
+
+#[test]
+fn unsuback_parser_v5() {
+    let input = vec![
+        0x00, 0x07, // Packet Identifier
+        0x00, // Properties Length
+        0x00, // Success
+        0x11, // No subscription existed
+    ];
+
+    let (_, unsuback) = unsuback_parser(ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(unsuback.packet_identifier.val(), 7);
+    assert_eq!(unsuback.reason_codes, vec![unsuback::SUCCESS, unsuback::NO_SUBSCRIPTION_EXISTED]);
+}
+
+#[test]
+fn unsuback_parser_v3_1_1() {
+    let input = vec![0x00, 0x07];
+
+    let (_, unsuback) = unsuback_parser(ProtocolVersion::V3_1_1)(&input).unwrap();
+    assert_eq!(unsuback.packet_identifier.val(), 7);
+    assert!(unsuback.reason_codes.is_empty());
+}
@@ -0,0 +1,39 @@
+use super::*;
+use crate::packets;
+use nom::IResult;
+
+#[path = "unsubscribe_tests.rs"]
+#[cfg(test)]
+mod unsubscribe_tests;
+
+pub fn unsubscribe_parser<'a>(
+    fixed_header: packets::FixedHeader,
+    protocol_version: packets::ProtocolVersion,
+) -> impl FnOnce(&'a [u8]) -> IResult<&'a [u8], packets::unsubscribe::Unsubscribe> {
+    move |input| {
+        // 3.10.2 UNSUBSCRIBE Variable Header subsection
+        let (input, packet_identifier) = parse_two_byte_integer(input)?;
+        // 3.10.2.1 UNSUBSCRIBE Properties subsection, it can have
+        // - User Property
+        let (mut input, properties) = if protocol_version.has_properties() {
+            parse_properties(input)?
+        } else {
+            (input, packets::Properties::new())
+        };
+        // 3.10.3 UNSUBSCRIBE Payload subsection, the Topic Filters until the end of the packet.
+        let mut topic_filters = Vec::new();
+        while !input.is_empty() {
+            let (remaining, topic_filter) = parse_utf8_encoded_string(input)?;
+            topic_filters.push(topic_filter);
+            input = remaining;
+        }
+
+        let unsubscribe = packets::unsubscribe::Unsubscribe::new(
+            fixed_header,
+            packets::PacketIdentity::new(packet_identifier),
+            properties,
+            topic_filters,
+        )?;
+        Ok((input, unsubscribe))
+    }
+}
@@ -0,0 +1,46 @@
+use super::unsubscribe::*;
+use crate::packets::{Bits, ExtractValue, FixedHeader, ProtocolVersion, UTF8EncodedString, VariableByteInteger};
+
+fn fixed_header(input: &[u8]) -> FixedHeader {
+    FixedHeader::new(Bits(0x0A), Bits(0b0010), VariableByteInteger(input.len() as u32)).unwrap()
+}
+
+#[test]
+fn unsubscribe_parser_valid_input() {
+    let input = vec![
+        0x00, 0x0A, // Packet Identifier
+        0x00, // Properties Length
+        0x00, 0x03, b'a', b'/', b'b', // Topic Filter
+        0x00, 0x03, b'c', b'/', b'#', // Topic Filter
+    ];
+
+    let (_, unsubscribe) = unsubscribe_parser(fixed_header(&input), ProtocolVersion::V5)(&input).unwrap();
+    assert_eq!(unsubscribe.packet_identifier.val(), 10);
+    assert_eq!(
+        unsubscribe.topic_filters,
+        vec![UTF8EncodedString("a/b".to_string()), UTF8EncodedString("c/#".to_string())]
+    );
+}
+
+#[test]
+fn unsubscribe_parser_v3_1_1_has_no_properties() {
+    let input = vec![
+        0x00, 0x0A, // Packet Identifier
+        0x00, 0x03, b'a', b'/', b'b', // Topic Filter
+    ];
+
+    let (_, unsubscribe) = unsubscribe_parser(fixed_header(&input), ProtocolVersion::V3_1_1)(&input).unwrap();
+    assert!(unsubscribe.properties.is_empty());
+    assert_eq!(unsubscribe.topic_filters, vec![UTF8EncodedString("a/b".to_string())]);
+}
+
+#[test]
+fn unsubscribe_parser_without_topic_filter() {
+    let input = vec![
+        0x00, 0x0A, // Packet Identifier
+        0x00, // Properties Length
+    ];
+
+    let result = unsubscribe_parser(fixed_header(&input), ProtocolVersion::V5)(&input);
+    assert!(result.is_err());
+}
@@ -38,6 +38,14 @@ fn decode_fixed_header_as_connect() {
     assert_eq!(packets::Packet::Connect(expected), packet);
 }
 
+#[test]
+fn decode_pingreq() {
+    let mut cursor = io::Cursor::new(vec![0b1100_0000, 0x00]);
+    let packet = decode(&mut cursor).unwrap();
+
+    assert_eq!(packets::Packet::PingReq, packet);
+}
+
 #[test]
 fn decode_invalid_packet_data() {
     let data = vec![0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
@@ -574,9 +582,9 @@ fn parse_properties_multiple_properties() {
     let result = parse_properties(&data);
     let (_, properties) = result.unwrap();
     assert_eq!(properties.0.len(), 3);
-    assert_eq!(properties.0[&VariableByteInteger(0x01)], ValueTypes::Bits(Bits(0x12)));
-    assert_eq!(properties.0[&VariableByteInteger(0x02)], ValueTypes::FourByteInteger(FourByteInteger(0x00000001)));
-    assert_eq!(properties.0[&VariableByteInteger(0x03)], ValueTypes::UTF8EncodedString(UTF8EncodedString("test".to_string())));
+    assert_eq!(properties.0[&VariableByteInteger(0x01)], vec![ValueTypes::Bits(Bits(0x12))]);
+    assert_eq!(properties.0[&VariableByteInteger(0x02)], vec![ValueTypes::FourByteInteger(FourByteInteger(0x00000001))]);
+    assert_eq!(properties.0[&VariableByteInteger(0x03)], vec![ValueTypes::UTF8EncodedString(UTF8EncodedString("test".to_string()))]);
 }
 
 #[test]
@@ -588,5 +596,101 @@ fn parse_properties_with_user_property() {
     let result = parse_properties(&data);
     let (_, properties) = result.unwrap();
     assert_eq!(properties.0.len(), 1);
-    assert_eq!(properties.0[&VariableByteInteger(0x26)], ValueTypes::UTF8StringPair(UTF8StringPair("key".to_string(), "value".to_string())));
+    assert_eq!(properties.0[&VariableByteInteger(0x26)], vec![ValueTypes::UTF8StringPair(UTF8StringPair("key".to_string(), "value".to_string()))]);
+}
+
+#[test]
+fn decode_subscribe_as_v3_1_1() {
+    let data = vec![
+        0x82, 0x08, // Fixed header
+        0x00, 0x01, // Packet Identifier
+        0x00, 0x03, b'a', b'/', b'b', // Topic Filter
+        0x01, // Requested QoS
+    ];
+    let mut cursor = io::Cursor::new(data);
+    let packet = decode_as(&mut cursor, &packets::ProtocolVersion::V3_1_1).unwrap();
+
+    match packet {
+        packets::Packet::Subscribe(subscribe) => {
+            assert_eq!(subscribe.subscriptions.len(), 1);
+            assert!(subscribe.properties.is_empty());
+        }
+        _ => panic!("Unexpected packet: {:?}", packet),
+    }
+}
+
+#[test]
+fn decode_pubrel() {
+    let data = vec![0x62, 0x02, 0x00, 0x07];
+    let mut cursor = io::Cursor::new(data);
+    let packet = decode(&mut cursor).unwrap();
+
+    match packet {
+        packets::Packet::PubRel(pubrel) => assert_eq!(pubrel.packet_identifier.val(), 7),
+        _ => panic!("Unexpected packet: {:?}", packet),
+    }
+}
+
+#[test]
+fn decode_pubrel_with_invalid_flags() {
+    let data = vec![0x60, 0x02, 0x00, 0x07];
+    let mut cursor = io::Cursor::new(data);
+    let result = decode(&mut cursor);
+
+    assert!(matches!(result, Err(errors::Error::MalformedPacket(_))));
+}
+
+#[test]
+fn decode_shorter_than_remaining_length() {
+    let data = vec![0x82, 0x08, 0x00, 0x01];
+    let mut cursor = io::Cursor::new(data);
+    let result = decode(&mut cursor);
+
+    assert!(result.is_err());
+}
+
+#[test]
+fn take_frame_waits_for_whole_packet() {
+    let mut buffer = vec![0x30, 0x80];
+    assert!(take_frame(&mut buffer).unwrap().is_none());
+    buffer.extend([0x01]);
+    buffer.extend(vec![0x00; 127]);
+    assert!(take_frame(&mut buffer).unwrap().is_none());
+    buffer.extend([0x00, 0xC0, 0x00]);
+    assert_eq!(take_frame(&mut buffer).unwrap().unwrap().len(), 131);
+    assert_eq!(buffer, vec![0xC0, 0x00]);
+    assert_eq!(take_frame(&mut buffer).unwrap().unwrap(), vec![0xC0, 0x00]);
+    assert!(buffer.is_empty());
+}
+
+#[test]
+fn take_frame_rejects_too_long_remaining_length() {
+    let mut buffer = vec![0x30, 0xFF, 0xFF, 0xFF, 0xFF, 0x01];
+    assert!(take_frame(&mut buffer).is_err());
+}
+
+#[test]
+fn frame_length_is_known_from_fixed_header() {
+    assert_eq!(frame_length(&[0x30]).unwrap(), None);
+    assert_eq!(frame_length(&[0x30, 0x80]).unwrap(), None);
+    // The Remaining Length 268,435,455 is known before the rest of the packet is received.
+    assert_eq!(frame_length(&[0x30, 0xFF, 0xFF, 0xFF, 0x7F]).unwrap(), Some(268_435_460));
+    assert_eq!(frame_length(&[0xE0, 0x00]).unwrap(), Some(2));
+}
+
+#[test]
+fn parse_properties_keeps_repeated_properties() {
+    let data = vec![
+        0x0C, // Total length of properties
+        0x0B, 0x01, // SUBSCRIPTION_IDENTIFIER
+        0x0B, 0x80, 0x01, // SUBSCRIPTION_IDENTIFIER
+        0x26, 0x00, 0x01, b'k', 0x00, 0x01, b'v', // USER_PROPERTY
+    ];
+    let (_, properties) = parse_properties(&data).unwrap();
+    assert_eq!(properties.len(), 3);
+    assert_eq!(
+        properties.get_all_as::<VariableByteInteger>(packets::SUBSCRIPTION_IDENTIFIER).unwrap(),
+        vec![&VariableByteInteger(1), &VariableByteInteger(128)]
+    );
+    assert_eq!(properties.get_all_as::<UTF8StringPair>(packets::USER_PROPERTY).unwrap().len(), 1);
 }
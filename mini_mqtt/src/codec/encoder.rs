@@ -4,15 +4,74 @@ use crate::packets::{ExtractValue, ValueTypes};
 use std::io::Write;
 
 pub mod connack;
+pub mod connect;
+pub mod disconnect;
+pub mod publish;
+pub mod puback;
+pub mod pubrec;
+pub mod pubrel;
+pub mod pubcomp;
+pub mod suback;
+pub mod subscribe;
+pub mod unsuback;
 
 #[path = "encoder_tests.rs"]
 #[cfg(test)]
 mod encoder_tests;
 
+// encode encodes the packet as the MQTT v5.0 format.
 pub fn encode(writer: &mut dyn Write, packet: &packets::Packet) -> Result<(), errors::Error> {
+    encode_as(writer, packet, &packets::ProtocolVersion::V5)
+}
+
+// encode_as encodes the packet as the format of the provided protocol version,
+// which should be the version negotiated by the CONNECT Packet of the session.
+pub fn encode_as(
+    writer: &mut dyn Write,
+    packet: &packets::Packet,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
     match packet {
+        // The CONNECT Packet is encoded as the version which the packet itself declares.
+        packets::Packet::Connect(packet) => {
+            connect::encode_connect(writer, packet)?;
+        }
         packets::Packet::ConnAck(packet) => {
-            connack::encode_connack(writer, packet)?;
+            connack::encode_connack(writer, packet, protocol_version)?;
+        }
+        packets::Packet::Publish(packet) => {
+            publish::encode_publish(writer, packet, protocol_version)?;
+        }
+        packets::Packet::PubAck(packet) => {
+            puback::encode_puback(writer, packet, protocol_version)?;
+        }
+        packets::Packet::PubRec(packet) => {
+            pubrec::encode_pubrec(writer, packet, protocol_version)?;
+        }
+        packets::Packet::PubRel(packet) => {
+            pubrel::encode_pubrel(writer, packet, protocol_version)?;
+        }
+        packets::Packet::PubComp(packet) => {
+            pubcomp::encode_pubcomp(writer, packet, protocol_version)?;
+        }
+        packets::Packet::Subscribe(packet) => {
+            subscribe::encode_subscribe(writer, packet, protocol_version)?;
+        }
+        packets::Packet::SubAck(packet) => {
+            suback::encode_suback(writer, packet, protocol_version)?;
+        }
+        packets::Packet::UnsubAck(packet) => {
+            unsuback::encode_unsuback(writer, packet, protocol_version)?;
+        }
+        // The PINGREQ and PINGRESP Packets have only the Fixed Header (3.12 PINGREQ and 3.13 PINGRESP sections).
+        packets::Packet::PingReq => {
+            encode_empty(writer, packets::PINGREQ)?;
+        }
+        packets::Packet::PingResp => {
+            encode_empty(writer, packets::PINGRESP)?;
+        }
+        packets::Packet::Disconnect(packet) => {
+            disconnect::encode_disconnect(writer, packet, protocol_version)?;
         }
         _ => {
             return Err(errors::Error::Common("Not implemented yet".to_string()));
@@ -22,6 +81,60 @@ pub fn encode(writer: &mut dyn Write, packet: &packets::Packet) -> Result<(), er
     Ok(())
 }
 
+// encode_within encodes the packet as encode_as, within the Maximum Packet Size which the receiver has sent.
+// The sender MUST NOT send the Reason String and the User Properties if they would increase the size of the packet
+// beyond the Maximum Packet Size (e.g. [MQTT-3.4.2-2] [MQTT-3.4.2-3]), so they are dropped from the acknowledgements first.
+// It results None when the packet is still too large, e.g. the PUBLISH Packet, then the packet MUST be discarded
+// without sending it [MQTT-3.1.2-25]. The Properties of the PUBLISH Packet are the Application Message, they are not dropped.
+pub fn encode_within(
+    packet: &packets::Packet,
+    protocol_version: &packets::ProtocolVersion,
+    maximum_packet_size: Option<u32>,
+) -> Result<Option<Vec<u8>>, errors::Error> {
+    let mut buffer = Vec::new();
+    encode_as(&mut buffer, packet, protocol_version)?;
+    let Some(maximum_packet_size) = maximum_packet_size else {
+        return Ok(Some(buffer));
+    };
+    if buffer.len() <= maximum_packet_size as usize {
+        return Ok(Some(buffer));
+    }
+
+    let mut packet = packet.clone();
+    let properties = match &mut packet {
+        packets::Packet::ConnAck(packet) => &mut packet.properties,
+        packets::Packet::PubAck(packet) => &mut packet.properties,
+        packets::Packet::PubRec(packet) => &mut packet.properties,
+        packets::Packet::PubRel(packet) => &mut packet.properties,
+        packets::Packet::PubComp(packet) => &mut packet.properties,
+        packets::Packet::SubAck(packet) => &mut packet.properties,
+        packets::Packet::UnsubAck(packet) => &mut packet.properties,
+        packets::Packet::Disconnect(packet) => &mut packet.properties,
+        _ => return Ok(None),
+    };
+    let reason_string = properties.remove(&packets::REASON_STRING);
+    let user_property = properties.remove(&packets::USER_PROPERTY);
+    if reason_string.is_none() && user_property.is_none() {
+        return Ok(None);
+    }
+    buffer.clear();
+    encode_as(&mut buffer, &packet, protocol_version)?;
+    if buffer.len() <= maximum_packet_size as usize {
+        Ok(Some(buffer))
+    } else {
+        Ok(None)
+    }
+}
+
+fn encode_empty(writer: &mut dyn Write, control_packet_type: u8) -> Result<(), errors::Error> {
+    let fixed_header = packets::FixedHeader::new(
+        packets::Bits(control_packet_type),
+        packets::Bits(0),
+        packets::VariableByteInteger(0),
+    )?;
+    encode_fixed_header(writer, &fixed_header)
+}
+
 pub fn encode_reason_code(
     writer: &mut dyn Write,
     reason_code: &dyn packets::ReasonCode,
@@ -33,13 +146,13 @@ pub fn encode_reason_code(
 
 pub fn encode_value(writer: &mut dyn Write, value: &ValueTypes) -> Result<(), errors::Error> {
     match value {
-        ValueTypes::Bits(val) => encode_bits(writer, &val),
-        ValueTypes::TwoByteInteger(val) => encode_two_byte_integer(writer, &val),
-        ValueTypes::FourByteInteger(val) => encode_four_byte_integer(writer, &val),
-        ValueTypes::VariableByteInteger(val) => encode_variable_byte_integer(writer, &val),
-        ValueTypes::UTF8EncodedString(val) => encode_utf8_encoded_string(writer, &val),
-        ValueTypes::UTF8StringPair(val) => encode_utf8_string_pair(writer, &val),
-        ValueTypes::BinaryData(val) => encode_binary_data(writer, &val),
+        ValueTypes::Bits(val) => encode_bits(writer, val),
+        ValueTypes::TwoByteInteger(val) => encode_two_byte_integer(writer, val),
+        ValueTypes::FourByteInteger(val) => encode_four_byte_integer(writer, val),
+        ValueTypes::VariableByteInteger(val) => encode_variable_byte_integer(writer, val),
+        ValueTypes::UTF8EncodedString(val) => encode_utf8_encoded_string(writer, val),
+        ValueTypes::UTF8StringPair(val) => encode_utf8_string_pair(writer, val),
+        ValueTypes::BinaryData(val) => encode_binary_data(writer, val),
     }
 }
 
@@ -1,6 +1,6 @@
 use std::io::Write;
 
-use crate::codec::encoder::{encode_fixed_header, encode_properties, encode_reason_code};
+use crate::codec::encoder::{encode_bits, encode_fixed_header, encode_properties, encode_reason_code};
 use crate::packets;
 use crate::errors;
 use crate::packets::VariableByteInteger;
@@ -9,12 +9,34 @@ use crate::packets::VariableByteInteger;
 #[cfg(test)]
 mod connack_tests;
 
-pub fn encode_connack(writer : &mut dyn Write, packet : &packets::connack::ConnAck) -> Result<(), errors::Error> {
+// encode_connack encodes the CONNACK Packet as the provided protocol version's format.
+// MQTT v3.1.1 and v3.1 CONNACK Packets have no Properties, and have the Connect Return Code instead of the Connect Reason Code.
+pub fn encode_connack(
+    writer: &mut dyn Write,
+    packet: &packets::connack::ConnAck,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
     let mut buffer = Vec::new();
     let mut vector_writer = std::io::Cursor::new(&mut buffer);
 
-    encode_reason_code(&mut vector_writer, &packet.connect_reason_code)?;
-    encode_properties(&mut vector_writer, &packet.properties)?;
+    // 3.2.2.1 Connect Acknowledge Flags, the Session Present flag is the bit 0.
+    // If a Server sends a CONNACK packet containing a non-zero Reason Code it MUST set Session Present to 0 [MQTT-3.2.2-6].
+    // The Connect Acknowledge Flags of MQTT v3.1 are reserved, so it is always 0.
+    let session_present = packet.session_present && packet.is_success() && protocol_version.has_session_present();
+    encode_bits(&mut vector_writer, &packets::Bits(session_present as u8))?;
+
+    if protocol_version.has_properties() {
+        encode_reason_code(&mut vector_writer, &packet.connect_reason_code)?;
+        encode_properties(&mut vector_writer, &packet.properties)?;
+    } else {
+        let return_code = packet.connect_reason_code.v3_return_code().ok_or_else(|| {
+            errors::Error::ProtocolError(format!(
+                "The reason code 0x{:02X} has no corresponding Connect Return Code of {}",
+                packet.connect_reason_code.0, protocol_version
+            ))
+        })?;
+        encode_bits(&mut vector_writer, &packets::Bits(return_code))?;
+    }
 
    let fixed_header = packets::FixedHeader::new(
        packets::Bits(packets::CONNACK),
@@ -2,7 +2,7 @@
 use crate::codec::encoder::connack::*; // The test targets
 
 use crate::packets;
-use crate::packets::{Bits, VariableByteInteger, UTF8EncodedString};
+use crate::packets::{Bits, ProtocolVersion, VariableByteInteger, UTF8EncodedString};
 
 #[test]
 fn encode_connack_success() {
@@ -15,12 +15,14 @@ fn encode_connack_success() {
 
     let packet = packets::connack::ConnAck {
         fixed_header: packets::FixedHeader::new(Bits(0b00100000), Bits(0), VariableByteInteger(0)).unwrap(),
+        session_present: false,
         connect_reason_code: packets::connack::SUCCESS,
         properties,
     };
 
     let expected_data = vec![
-        0x00u8, // Reason code
+        0x00u8, // Connect Acknowledge Flags
+        0x00, // Reason code
         0x07, // Properties length (Variable Byte Integer)
         0x03,  // Content Type
         0x00, 0x04, // UTF-8 Encoded String length
@@ -32,7 +34,7 @@ fn encode_connack_success() {
     ];
     let expected_data = [ fixed_header_data, expected_data ].concat();
 
-    let result = encode_connack(&mut buffer, &packet);
+    let result = encode_connack(&mut buffer, &packet, &ProtocolVersion::V5);
     assert!(result.is_ok());
     assert_eq!(buffer, expected_data);
 }
@@ -42,18 +44,92 @@ fn encode_connack_with_no_properties() {
     let mut buffer = Vec::new();
     let packet = packets::connack::ConnAck {
         fixed_header: packets::FixedHeader::new(Bits(0b00100000), Bits(0), VariableByteInteger(0)).unwrap(),
+        session_present: false,
         connect_reason_code: packets::connack::SUCCESS,
         properties : packets::Properties::new(),
     };
 
     let expected = vec![
         0b0010_0000u8, // Fixed header
-        0x02u8, // Remaining length (Variable Byte Integer)
+        0x03u8, // Remaining length (Variable Byte Integer)
+        0x00u8, // Connect Acknowledge Flags
         0x00u8, // Reason code
         0x00, // Properties length (Variable Byte Integer)
     ];
 
-    let result = encode_connack(&mut buffer, &packet);
+    let result = encode_connack(&mut buffer, &packet, &ProtocolVersion::V5);
     assert!(result.is_ok());
     assert_eq!(buffer, expected);
 }
+
+#[test]
+fn encode_connack_session_present() {
+    let mut buffer = Vec::new();
+    let packet = packets::connack::ConnAck {
+        session_present: true,
+        ..packets::connack::ConnAck::default()
+    };
+
+    let result = encode_connack(&mut buffer, &packet, &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x20, 0x03, 0x01, 0x00, 0x00]);
+}
+
+#[test]
+fn encode_connack_v3_1_1() {
+    let mut buffer = Vec::new();
+    let mut packet = packets::connack::ConnAck::default();
+    packet.properties.insert(
+        packets::ASSIGNED_CLIENT_IDENTIFIER,
+        packets::ValueTypes::UTF8EncodedString(UTF8EncodedString("auto1".to_string())));
+
+    let expected = vec![
+        0b0010_0000u8, // Fixed header
+        0x02u8, // Remaining length (Variable Byte Integer)
+        0x00u8, // Connect Acknowledge Flags
+        0x00u8, // Connect Return code, the properties are not encoded
+    ];
+
+    let result = encode_connack(&mut buffer, &packet, &ProtocolVersion::V3_1_1);
+    assert!(result.is_ok());
+    assert_eq!(buffer, expected);
+}
+
+#[test]
+fn encode_connack_v3_1_1_return_code() {
+    let mut buffer = Vec::new();
+    let packet = packets::connack::ConnAck {
+        session_present: true, // ignored for the rejected CONNACK
+        connect_reason_code: packets::connack::BAD_USER_NAME_OR_PASSWORD,
+        ..packets::connack::ConnAck::default()
+    };
+
+    let result = encode_connack(&mut buffer, &packet, &ProtocolVersion::V3_1_1);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x20, 0x02, 0x00, 0x04]);
+}
+
+#[test]
+fn encode_connack_v3_1_1_without_return_code() {
+    let mut buffer = Vec::new();
+    let packet = packets::connack::ConnAck {
+        connect_reason_code: packets::connack::MALFORMED_PACKET,
+        ..packets::connack::ConnAck::default()
+    };
+
+    let result = encode_connack(&mut buffer, &packet, &ProtocolVersion::V3_1_1);
+    assert!(result.is_err());
+}
+
+#[test]
+fn encode_connack_v3_1() {
+    let mut buffer = Vec::new();
+    let packet = packets::connack::ConnAck {
+        session_present: true, // MQTT v3.1 has no Session Present flag
+        ..packets::connack::ConnAck::default()
+    };
+
+    let result = encode_connack(&mut buffer, &packet, &ProtocolVersion::V3_1);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x20, 0x02, 0x00, 0x00]);
+}
@@ -0,0 +1,60 @@
+use std::io::Write;
+
+use crate::codec::encoder::{
+    encode_binary_data, encode_bits, encode_fixed_header, encode_properties, encode_two_byte_integer,
+    encode_utf8_encoded_string,
+};
+use crate::errors;
+use crate::packets;
+use crate::packets::VariableByteInteger;
+
+#[path = "connect_tests.rs"]
+#[cfg(test)]
+mod connect_tests;
+
+// encode_connect encodes the CONNECT Packet which a Client sends, as the format of the version which the packet itself declares.
+// MQTT v3.1.1 and v3.1 CONNECT Packets have no Properties nor Will Properties.
+// The Payload fields are encoded in the order of 3.1.3 CONNECT Payload subsection, each one only when it is present.
+pub fn encode_connect(writer: &mut dyn Write, packet: &packets::connect::Connect) -> Result<(), errors::Error> {
+    let mut buffer = Vec::new();
+    let mut vector_writer = std::io::Cursor::new(&mut buffer);
+    let has_properties = packet.protocol_version().is_none_or(|version| version.has_properties());
+
+    let variable_header = &packet.variable_header;
+    encode_utf8_encoded_string(&mut vector_writer, &variable_header.protocol_name)?;
+    encode_bits(&mut vector_writer, &variable_header.protocol_version)?;
+    encode_bits(&mut vector_writer, &variable_header.connect_flags.0)?;
+    encode_two_byte_integer(&mut vector_writer, &variable_header.keep_alive)?;
+    if has_properties {
+        encode_properties(&mut vector_writer, &variable_header.properties)?;
+    }
+
+    let payload = &packet.payload;
+    encode_utf8_encoded_string(&mut vector_writer, &payload.client_id)?;
+    if let (Some(will_properties), true) = (&payload.will_properties, has_properties) {
+        encode_properties(&mut vector_writer, will_properties)?;
+    }
+    if let Some(will_topic) = &payload.will_topic {
+        encode_utf8_encoded_string(&mut vector_writer, will_topic)?;
+    }
+    if let Some(will_payload) = &payload.will_payload {
+        encode_binary_data(&mut vector_writer, will_payload)?;
+    }
+    if let Some(user_name) = &payload.user_name {
+        encode_utf8_encoded_string(&mut vector_writer, user_name)?;
+    }
+    if let Some(password) = &payload.password {
+        encode_binary_data(&mut vector_writer, password)?;
+    }
+
+    let fixed_header = packets::FixedHeader::new(
+        packets::Bits(packets::CONNECT),
+        packets::Bits(0),
+        VariableByteInteger(buffer.len() as u32),
+    )?;
+
+    encode_fixed_header(writer, &fixed_header)?;
+    writer.write_all(&buffer)?;
+
+    Ok(())
+}
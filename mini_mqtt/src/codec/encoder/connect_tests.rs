@@ -0,0 +1,72 @@
+use crate::codec::encoder::connect::*; // The test targets
+
+use crate::codec::decoder;
+use crate::packets;
+use crate::packets::connect::{Connect, ConnectFlags, Payload, VariableHeader};
+use crate::packets::{BinaryData, Bits, FixedHeader, TwoByteInteger, UTF8EncodedString, VariableByteInteger};
+
+fn connect_packet(protocol_name: &str, protocol_version: u8, flags: u8, payload: Payload) -> Connect {
+    let mut properties = packets::Properties::new();
+    properties.insert(packets::REQUEST_RESPONSE_INFORMATION, Bits(1).into());
+    let variable_header = VariableHeader::new(
+        UTF8EncodedString(protocol_name.to_string()),
+        Bits(protocol_version),
+        ConnectFlags(Bits(flags)),
+        TwoByteInteger(60),
+        if protocol_version == 5 { properties } else { packets::Properties::new() },
+    )
+    .unwrap();
+    let fixed_header = FixedHeader::new(Bits(packets::CONNECT), Bits(0), VariableByteInteger(0)).unwrap();
+    Connect::new(fixed_header, variable_header, payload).unwrap()
+}
+
+#[test]
+fn encode_connect_v5() {
+    let payload = Payload::new(UTF8EncodedString("c1".to_string()), None, None, None, None, None).unwrap();
+    let mut buffer = Vec::new();
+    encode_connect(&mut buffer, &connect_packet("MQTT", 5, 0x02, payload)).unwrap();
+
+    let expected = vec![
+        0x10, // Fixed header
+        0x11, // Remaining length
+        0x00, 0x04, b'M', b'Q', b'T', b'T', // Protocol Name
+        0x05, // Protocol Version
+        0x02, // Connect Flags
+        0x00, 0x3C, // Keep Alive
+        0x02, // Properties Length
+        0x19, 0x01, // Request Response Information
+        0x00, 0x02, b'c', b'1', // Client ID
+    ];
+    assert_eq!(buffer, expected);
+}
+
+#[test]
+fn encode_connect_round_trips_payload() {
+    for (protocol_name, protocol_version) in [("MQTT", 5), ("MQTT", 4), ("MQIsdp", 3)] {
+        let will_properties = (protocol_version == 5).then(|| {
+            let mut properties = packets::Properties::new();
+            properties.insert(packets::MESSAGE_EXPIRY_INTERVAL, packets::FourByteInteger(10).into());
+            properties
+        });
+        let payload = Payload::new(
+            UTF8EncodedString("c1".to_string()),
+            will_properties.or_else(|| Some(packets::Properties::new())),
+            Some(UTF8EncodedString("will".to_string())),
+            Some(BinaryData(b"gone".to_vec())),
+            Some(UTF8EncodedString("user".to_string())),
+            Some(BinaryData(b"secret".to_vec())),
+        )
+        .unwrap();
+        // User Name, Password, Will QoS 1, Will Flag and Clean Start.
+        let connect = connect_packet(protocol_name, protocol_version, 0b1100_1110, payload);
+        let mut buffer = Vec::new();
+        encode_connect(&mut buffer, &connect).unwrap();
+
+        let decoded = decoder::decode(&mut buffer.as_slice()).unwrap();
+        let packets::Packet::Connect(decoded) = decoded else {
+            panic!("The CONNECT Packet is decoded as {:?}", decoded);
+        };
+        assert_eq!(decoded.variable_header, connect.variable_header);
+        assert_eq!(decoded.payload, connect.payload);
+    }
+}
@@ -0,0 +1,40 @@
+use std::io::Write;
+
+use crate::codec::encoder::{encode_fixed_header, encode_properties, encode_reason_code};
+use crate::errors;
+use crate::packets;
+use crate::packets::VariableByteInteger;
+
+#[path = "disconnect_tests.rs"]
+#[cfg(test)]
+mod disconnect_tests;
+
+// encode_disconnect encodes the DISCONNECT Packet as the provided protocol version's format.
+// The Reason Code and the Properties are omitted when the Reason Code is 0x00 (Normal disconnection)
+// and there are no Properties, then the Remaining Length is 0 (3.14.2.1 Disconnect Reason Code subsection).
+// The MQTT v3.1.1 and v3.1 DISCONNECT Packets have no Variable Header.
+pub fn encode_disconnect(
+    writer: &mut dyn Write,
+    packet: &packets::disconnect::Disconnect,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
+    let mut buffer = Vec::new();
+    let mut vector_writer = std::io::Cursor::new(&mut buffer);
+
+    let omittable = packet.reason_code == packets::disconnect::NORMAL_DISCONNECTION && packet.properties.is_empty();
+    if protocol_version.has_properties() && !omittable {
+        encode_reason_code(&mut vector_writer, &packet.reason_code)?;
+        encode_properties(&mut vector_writer, &packet.properties)?;
+    }
+
+    let fixed_header = packets::FixedHeader::new(
+        packets::Bits(packets::DISCONNECT),
+        packets::Bits(0),
+        VariableByteInteger(buffer.len() as u32),
+    )?;
+
+    encode_fixed_header(writer, &fixed_header)?;
+    writer.write_all(&buffer)?;
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use crate::codec::encoder::disconnect::*; // The test targets
+
+use crate::packets;
+use crate::packets::disconnect::{self, Disconnect};
+use crate::packets::ProtocolVersion;
+
+#[test]
+fn encode_disconnect_normal_short_form() {
+    let mut buffer = Vec::new();
+    let packet = Disconnect::new(disconnect::NORMAL_DISCONNECTION, packets::Properties::new());
+
+    let result = encode_disconnect(&mut buffer, &packet, &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0xE0, 0x00]);
+}
+
+#[test]
+fn encode_disconnect_with_reason_string() {
+    let mut buffer = Vec::new();
+    let packet = Disconnect::with_reason(disconnect::QOS_NOT_SUPPORTED, "no");
+
+    let result = encode_disconnect(&mut buffer, &packet, &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0xE0, 0x07, 0x9B, 0x05, 0x1F, 0x00, 0x02, b'n', b'o']);
+}
+
+#[test]
+fn encode_disconnect_v3_1_1() {
+    let mut buffer = Vec::new();
+    let packet = Disconnect::with_reason(disconnect::QOS_NOT_SUPPORTED, "no");
+
+    let result = encode_disconnect(&mut buffer, &packet, &ProtocolVersion::V3_1_1);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0xE0, 0x00]);
+}
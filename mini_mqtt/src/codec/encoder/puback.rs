@@ -0,0 +1,61 @@
+use std::io::Write;
+
+use crate::codec::encoder::{encode_fixed_header, encode_properties, encode_reason_code, encode_two_byte_integer};
+use crate::errors;
+use crate::packets;
+use crate::packets::{ExtractValue, TwoByteInteger, VariableByteInteger};
+
+#[path = "puback_tests.rs"]
+#[cfg(test)]
+mod puback_tests;
+
+// encode_puback encodes the PUBACK Packet as the provided protocol version's format.
+// The Reason Code and the Properties are omitted when the Reason Code is 0x00 (Success) and there are no Properties,
+// and they are always omitted for MQTT v3.1.1 and v3.1.
+pub fn encode_puback(
+    writer: &mut dyn Write,
+    packet: &packets::puback::PubAck,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
+    encode_publish_response(
+        writer,
+        packet.fixed_header.clone(),
+        &packet.packet_identifier,
+        &packet.reason_code,
+        &packet.properties,
+        protocol_version,
+    )
+}
+
+// encode_publish_response encodes the Variable Header of the PUBACK, PUBREC, PUBREL and PUBCOMP Packets,
+// which share the same layout: the Packet Identifier, the Reason Code and the Properties.
+// The Remaining Length of the provided Fixed Header is replaced by the encoded length.
+pub fn encode_publish_response(
+    writer: &mut dyn Write,
+    fixed_header: packets::FixedHeader,
+    packet_identifier: &packets::PacketIdentity,
+    reason_code: &dyn packets::ReasonCode,
+    properties: &packets::Properties,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
+    let mut buffer = Vec::new();
+    let mut vector_writer = std::io::Cursor::new(&mut buffer);
+
+    encode_two_byte_integer(&mut vector_writer, &TwoByteInteger(packet_identifier.val()))?;
+    let omittable = reason_code.code() == 0x00 && properties.is_empty();
+    if protocol_version.has_properties() && !omittable {
+        encode_reason_code(&mut vector_writer, reason_code)?;
+        encode_properties(&mut vector_writer, properties)?;
+    }
+
+    let fixed_header = packets::FixedHeader::new(
+        fixed_header.control_packet_type,
+        fixed_header.flags,
+        VariableByteInteger(buffer.len() as u32),
+    )?;
+
+    encode_fixed_header(writer, &fixed_header)?;
+    writer.write_all(&buffer)?;
+
+    Ok(())
+}
@@ -0,0 +1,35 @@
+use crate::codec::encoder::puback::*; // The test targets
+
+use crate::packets;
+use crate::packets::puback::{self, PubAck};
+use crate::packets::{PacketIdentity, ProtocolVersion, TwoByteInteger};
+
+#[test]
+fn encode_puback_success_short_form() {
+    let mut buffer = Vec::new();
+    let packet = PubAck::new(PacketIdentity::new(TwoByteInteger(7)), puback::SUCCESS, packets::Properties::new());
+
+    let result = encode_puback(&mut buffer, &packet, &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x40, 0x02, 0x00, 0x07]);
+}
+
+#[test]
+fn encode_puback_not_authorized() {
+    let mut buffer = Vec::new();
+    let packet = PubAck::new(PacketIdentity::new(TwoByteInteger(7)), puback::NOT_AUTHORIZED, packets::Properties::new());
+
+    let result = encode_puback(&mut buffer, &packet, &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x40, 0x04, 0x00, 0x07, 0x87, 0x00]);
+}
+
+#[test]
+fn encode_puback_v3_1_1() {
+    let mut buffer = Vec::new();
+    let packet = PubAck::new(PacketIdentity::new(TwoByteInteger(7)), puback::NOT_AUTHORIZED, packets::Properties::new());
+
+    let result = encode_puback(&mut buffer, &packet, &ProtocolVersion::V3_1_1);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x40, 0x02, 0x00, 0x07]);
+}
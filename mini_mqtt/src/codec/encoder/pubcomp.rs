@@ -0,0 +1,26 @@
+use std::io::Write;
+
+use crate::codec::encoder::puback::encode_publish_response;
+use crate::errors;
+use crate::packets;
+
+#[path = "pubcomp_tests.rs"]
+#[cfg(test)]
+mod pubcomp_tests;
+
+// encode_pubcomp encodes the PUBCOMP Packet as the provided protocol version's format.
+// The Reason Code and the Properties are omitted as the PUBACK Packet.
+pub fn encode_pubcomp(
+    writer: &mut dyn Write,
+    packet: &packets::pubcomp::PubComp,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
+    encode_publish_response(
+        writer,
+        packet.fixed_header.clone(),
+        &packet.packet_identifier,
+        &packet.reason_code,
+        &packet.properties,
+        protocol_version,
+    )
+}
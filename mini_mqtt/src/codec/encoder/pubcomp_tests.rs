@@ -0,0 +1,29 @@
+use crate::codec::encoder::pubcomp::*; // The test targets
+
+use crate::packets;
+use crate::packets::pubcomp::{self, PubComp};
+use crate::packets::{PacketIdentity, ProtocolVersion, TwoByteInteger};
+
+#[test]
+fn encode_pubcomp_success_short_form() {
+    let mut buffer = Vec::new();
+    let packet = PubComp::new(PacketIdentity::new(TwoByteInteger(7)), pubcomp::SUCCESS, packets::Properties::new());
+
+    let result = encode_pubcomp(&mut buffer, &packet, &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x70, 0x02, 0x00, 0x07]);
+}
+
+#[test]
+fn encode_pubcomp_packet_identifier_not_found() {
+    let mut buffer = Vec::new();
+    let packet = PubComp::new(
+        PacketIdentity::new(TwoByteInteger(7)),
+        pubcomp::PACKET_IDENTIFIER_NOT_FOUND,
+        packets::Properties::new(),
+    );
+
+    let result = encode_pubcomp(&mut buffer, &packet, &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x70, 0x04, 0x00, 0x07, 0x92, 0x00]);
+}
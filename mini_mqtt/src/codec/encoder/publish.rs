@@ -0,0 +1,41 @@
+use std::io::Write;
+
+use crate::codec::encoder::{encode_fixed_header, encode_properties, encode_two_byte_integer, encode_utf8_encoded_string};
+use crate::errors;
+use crate::packets;
+use crate::packets::{ExtractValue, TwoByteInteger, VariableByteInteger};
+
+#[path = "publish_tests.rs"]
+#[cfg(test)]
+mod publish_tests;
+
+// encode_publish encodes the PUBLISH Packet as the provided protocol version's format.
+// The Properties are encoded only for MQTT v5.0.
+pub fn encode_publish(
+    writer: &mut dyn Write,
+    packet: &packets::publish::Publish,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
+    let mut buffer = Vec::new();
+    let mut vector_writer = std::io::Cursor::new(&mut buffer);
+
+    encode_utf8_encoded_string(&mut vector_writer, &packet.topic_name)?;
+    if let Some(packet_identifier) = &packet.packet_identifier {
+        encode_two_byte_integer(&mut vector_writer, &TwoByteInteger(packet_identifier.val()))?;
+    }
+    if protocol_version.has_properties() {
+        encode_properties(&mut vector_writer, &packet.properties)?;
+    }
+    vector_writer.write_all(&packet.payload)?;
+
+    let fixed_header = packets::FixedHeader::new(
+        packets::Bits(packets::PUBLISH),
+        packet.fixed_header.flags.clone(),
+        VariableByteInteger(buffer.len() as u32),
+    )?;
+
+    encode_fixed_header(writer, &fixed_header)?;
+    writer.write_all(&buffer)?;
+
+    Ok(())
+}
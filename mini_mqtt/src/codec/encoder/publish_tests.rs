@@ -0,0 +1,74 @@
+use crate::codec::encoder::publish::*; // The test targets
+
+use crate::packets;
+use crate::packets::publish::Publish;
+use crate::packets::{PacketIdentity, ProtocolVersion, QoS, TwoByteInteger, UTF8EncodedString};
+
+fn publish_packet() -> Publish {
+    let mut properties = packets::Properties::new();
+    properties.insert(
+        packets::CONTENT_TYPE,
+        packets::ValueTypes::UTF8EncodedString(UTF8EncodedString("t".to_string())));
+
+    Publish::new(
+        Publish::fixed_header(false, QoS::AtLeastOnce, true),
+        UTF8EncodedString("a/b".to_string()),
+        Some(PacketIdentity::new(TwoByteInteger(0x0102))),
+        properties,
+        b"hi".to_vec(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn encode_publish_v5() {
+    let mut buffer = Vec::new();
+
+    let expected = vec![
+        0b0011_0011u8, // Fixed header, QoS 1 and RETAIN
+        0x0E, // Remaining length
+        0x00, 0x03, b'a', b'/', b'b', // Topic Name
+        0x01, 0x02, // Packet Identifier
+        0x04, // Properties length
+        0x03, 0x00, 0x01, b't', // Content Type
+        b'h', b'i', // Payload
+    ];
+
+    let result = encode_publish(&mut buffer, &publish_packet(), &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, expected);
+}
+
+#[test]
+fn encode_publish_v3_1_1() {
+    let mut buffer = Vec::new();
+
+    let expected = vec![
+        0b0011_0011u8, // Fixed header, QoS 1 and RETAIN
+        0x09, // Remaining length
+        0x00, 0x03, b'a', b'/', b'b', // Topic Name
+        0x01, 0x02, // Packet Identifier
+        b'h', b'i', // Payload
+    ];
+
+    let result = encode_publish(&mut buffer, &publish_packet(), &ProtocolVersion::V3_1_1);
+    assert!(result.is_ok());
+    assert_eq!(buffer, expected);
+}
+
+#[test]
+fn encode_publish_qos0() {
+    let mut buffer = Vec::new();
+    let publish = Publish::new(
+        Publish::fixed_header(false, QoS::AtMostOnce, false),
+        UTF8EncodedString("a".to_string()),
+        None,
+        packets::Properties::new(),
+        vec![],
+    )
+    .unwrap();
+
+    let result = encode_publish(&mut buffer, &publish, &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x30, 0x04, 0x00, 0x01, b'a', 0x00]);
+}
@@ -0,0 +1,26 @@
+use std::io::Write;
+
+use crate::codec::encoder::puback::encode_publish_response;
+use crate::errors;
+use crate::packets;
+
+#[path = "pubrec_tests.rs"]
+#[cfg(test)]
+mod pubrec_tests;
+
+// encode_pubrec encodes the PUBREC Packet as the provided protocol version's format.
+// The Reason Code and the Properties are omitted as the PUBACK Packet.
+pub fn encode_pubrec(
+    writer: &mut dyn Write,
+    packet: &packets::pubrec::PubRec,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
+    encode_publish_response(
+        writer,
+        packet.fixed_header.clone(),
+        &packet.packet_identifier,
+        &packet.reason_code,
+        &packet.properties,
+        protocol_version,
+    )
+}
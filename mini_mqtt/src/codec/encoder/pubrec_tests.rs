@@ -0,0 +1,25 @@
+use crate::codec::encoder::pubrec::*; // The test targets
+
+use crate::packets;
+use crate::packets::pubrec::{self, PubRec};
+use crate::packets::{PacketIdentity, ProtocolVersion, TwoByteInteger};
+
+#[test]
+fn encode_pubrec_success_short_form() {
+    let mut buffer = Vec::new();
+    let packet = PubRec::new(PacketIdentity::new(TwoByteInteger(7)), pubrec::SUCCESS, packets::Properties::new());
+
+    let result = encode_pubrec(&mut buffer, &packet, &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x50, 0x02, 0x00, 0x07]);
+}
+
+#[test]
+fn encode_pubrec_not_authorized() {
+    let mut buffer = Vec::new();
+    let packet = PubRec::new(PacketIdentity::new(TwoByteInteger(7)), pubrec::NOT_AUTHORIZED, packets::Properties::new());
+
+    let result = encode_pubrec(&mut buffer, &packet, &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x50, 0x04, 0x00, 0x07, 0x87, 0x00]);
+}
@@ -0,0 +1,26 @@
+use std::io::Write;
+
+use crate::codec::encoder::puback::encode_publish_response;
+use crate::errors;
+use crate::packets;
+
+#[path = "pubrel_tests.rs"]
+#[cfg(test)]
+mod pubrel_tests;
+
+// encode_pubrel encodes the PUBREL Packet as the provided protocol version's format.
+// The Reason Code and the Properties are omitted as the PUBACK Packet.
+pub fn encode_pubrel(
+    writer: &mut dyn Write,
+    packet: &packets::pubrel::PubRel,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
+    encode_publish_response(
+        writer,
+        packet.fixed_header.clone(),
+        &packet.packet_identifier,
+        &packet.reason_code,
+        &packet.properties,
+        protocol_version,
+    )
+}
@@ -0,0 +1,29 @@
+use crate::codec::encoder::pubrel::*; // The test targets
+
+use crate::packets;
+use crate::packets::pubrel::{self, PubRel};
+use crate::packets::{PacketIdentity, ProtocolVersion, TwoByteInteger};
+
+#[test]
+fn encode_pubrel_sets_reserved_flags() {
+    let mut buffer = Vec::new();
+    let packet = PubRel::new(PacketIdentity::new(TwoByteInteger(7)), pubrel::SUCCESS, packets::Properties::new());
+
+    let result = encode_pubrel(&mut buffer, &packet, &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x62, 0x02, 0x00, 0x07]);
+}
+
+#[test]
+fn encode_pubrel_v3_1_1() {
+    let mut buffer = Vec::new();
+    let packet = PubRel::new(
+        PacketIdentity::new(TwoByteInteger(7)),
+        pubrel::PACKET_IDENTIFIER_NOT_FOUND,
+        packets::Properties::new(),
+    );
+
+    let result = encode_pubrel(&mut buffer, &packet, &ProtocolVersion::V3_1_1);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0x62, 0x02, 0x00, 0x07]);
+}
@@ -0,0 +1,45 @@
+use std::io::Write;
+
+use crate::codec::encoder::{encode_bits, encode_fixed_header, encode_properties, encode_reason_code, encode_two_byte_integer};
+use crate::errors;
+use crate::packets;
+use crate::packets::{ExtractValue, TwoByteInteger, VariableByteInteger};
+
+#[path = "suback_tests.rs"]
+#[cfg(test)]
+mod suback_tests;
+
+// encode_suback encodes the SUBACK Packet as the provided protocol version's format.
+// MQTT v3.1.1 SUBACK Packet has no Properties, so the Reason String and the User Properties are not sent,
+// and the failure Reason Codes are sent as the Return Code 0x80.
+pub fn encode_suback(
+    writer: &mut dyn Write,
+    packet: &packets::suback::SubAck,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
+    let mut buffer = Vec::new();
+    let mut vector_writer = std::io::Cursor::new(&mut buffer);
+
+    encode_two_byte_integer(&mut vector_writer, &TwoByteInteger(packet.packet_identifier.val()))?;
+    if protocol_version.has_properties() {
+        encode_properties(&mut vector_writer, &packet.properties)?;
+        for reason_code in packet.reason_codes.iter() {
+            encode_reason_code(&mut vector_writer, reason_code)?;
+        }
+    } else {
+        for reason_code in packet.reason_codes.iter() {
+            encode_bits(&mut vector_writer, &packets::Bits(reason_code.v3_return_code()))?;
+        }
+    }
+
+    let fixed_header = packets::FixedHeader::new(
+        packets::Bits(packets::SUBACK),
+        packets::Bits(0),
+        VariableByteInteger(buffer.len() as u32),
+    )?;
+
+    encode_fixed_header(writer, &fixed_header)?;
+    writer.write_all(&buffer)?;
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use crate::codec::encoder::suback::*; // The test targets
+
+use crate::packets;
+use crate::packets::suback::{self, SubAck};
+use crate::packets::{PacketIdentity, ProtocolVersion, TwoByteInteger, UTF8EncodedString};
+
+fn suback_packet() -> SubAck {
+    let mut properties = packets::Properties::new();
+    properties.insert(
+        packets::REASON_STRING,
+        packets::ValueTypes::UTF8EncodedString(UTF8EncodedString("no".to_string())));
+
+    SubAck::new(
+        PacketIdentity::new(TwoByteInteger(0x0102)),
+        properties,
+        vec![suback::GRANTED_QOS_1, suback::NOT_AUTHORIZED],
+    )
+}
+
+#[test]
+fn encode_suback_v5() {
+    let mut buffer = Vec::new();
+
+    let expected = vec![
+        0b1001_0000u8, // Fixed header
+        0x0A, // Remaining length
+        0x01, 0x02, // Packet Identifier
+        0x05, // Properties length
+        0x1F, 0x00, 0x02, b'n', b'o', // Reason String
+        0x01, // Granted QoS 1
+        0x87, // Not authorized
+    ];
+
+    let result = encode_suback(&mut buffer, &suback_packet(), &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, expected);
+}
+
+#[test]
+fn encode_suback_v3_1_1() {
+    let mut buffer = Vec::new();
+
+    let expected = vec![
+        0b1001_0000u8, // Fixed header
+        0x04, // Remaining length
+        0x01, 0x02, // Packet Identifier
+        0x01, // Granted QoS 1
+        0x80, // Failure
+    ];
+
+    let result = encode_suback(&mut buffer, &suback_packet(), &ProtocolVersion::V3_1_1);
+    assert!(result.is_ok());
+    assert_eq!(buffer, expected);
+}
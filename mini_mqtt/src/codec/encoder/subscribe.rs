@@ -0,0 +1,47 @@
+use std::io::Write;
+
+use crate::codec::encoder::{encode_bits, encode_fixed_header, encode_properties, encode_two_byte_integer, encode_utf8_encoded_string};
+use crate::errors;
+use crate::packets;
+use crate::packets::{ExtractValue, TwoByteInteger, VariableByteInteger};
+
+#[path = "subscribe_tests.rs"]
+#[cfg(test)]
+mod subscribe_tests;
+
+// encode_subscribe encodes the SUBSCRIBE Packet which a Client sends, as the provided protocol version's format.
+// MQTT v3.1.1 SUBSCRIBE Packet has no Properties, and its Requested QoS byte has only the QoS bits.
+pub fn encode_subscribe(
+    writer: &mut dyn Write,
+    packet: &packets::subscribe::Subscribe,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
+    let mut buffer = Vec::new();
+    let mut vector_writer = std::io::Cursor::new(&mut buffer);
+
+    encode_two_byte_integer(&mut vector_writer, &TwoByteInteger(packet.packet_identifier.val()))?;
+    if protocol_version.has_properties() {
+        encode_properties(&mut vector_writer, &packet.properties)?;
+    }
+    for subscription in packet.subscriptions.iter() {
+        encode_utf8_encoded_string(&mut vector_writer, &subscription.topic_filter)?;
+        let options = if protocol_version.has_properties() {
+            subscription.options.0.clone()
+        } else {
+            packets::Bits(subscription.options.0.val() & 0b0000_0011)
+        };
+        encode_bits(&mut vector_writer, &options)?;
+    }
+
+    // Bits 3,2,1 and 0 of the Fixed Header of the SUBSCRIBE packet are reserved and MUST be set to 0,0,1 and 0 [MQTT-3.8.1-1].
+    let fixed_header = packets::FixedHeader::new(
+        packets::Bits(packets::SUBSCRIBE),
+        packets::Bits(0b0010),
+        VariableByteInteger(buffer.len() as u32),
+    )?;
+
+    encode_fixed_header(writer, &fixed_header)?;
+    writer.write_all(&buffer)?;
+
+    Ok(())
+}
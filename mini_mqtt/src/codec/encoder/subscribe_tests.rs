@@ -0,0 +1,52 @@
+use crate::codec::encoder::subscribe::*; // The test targets
+
+use crate::packets;
+use crate::packets::subscribe::{Subscribe, Subscription, SubscriptionOptions};
+use crate::packets::{Bits, FixedHeader, PacketIdentity, ProtocolVersion, TwoByteInteger, UTF8EncodedString, VariableByteInteger};
+
+fn subscribe_packet() -> Subscribe {
+    let fixed_header = FixedHeader::new(Bits(packets::SUBSCRIBE), Bits(0b0010), VariableByteInteger(0)).unwrap();
+    let mut properties = packets::Properties::new();
+    properties.insert(packets::SUBSCRIPTION_IDENTIFIER, VariableByteInteger(3).into());
+    // QoS 1 and No Local.
+    let options = SubscriptionOptions::new(Bits(0b0000_0101)).unwrap();
+    Subscribe::new(
+        fixed_header,
+        PacketIdentity::new(TwoByteInteger(0x0102)),
+        properties,
+        vec![Subscription::new(UTF8EncodedString("a/+".to_string()), options)],
+    )
+    .unwrap()
+}
+
+#[test]
+fn encode_subscribe_v5() {
+    let mut buffer = Vec::new();
+    encode_subscribe(&mut buffer, &subscribe_packet(), &ProtocolVersion::V5).unwrap();
+
+    let expected = vec![
+        0b1000_0010u8, // Fixed header
+        0x0B, // Remaining length
+        0x01, 0x02, // Packet Identifier
+        0x02, // Properties length
+        0x0B, 0x03, // Subscription Identifier
+        0x00, 0x03, b'a', b'/', b'+', // Topic Filter
+        0x05, // Subscription Options
+    ];
+    assert_eq!(buffer, expected);
+}
+
+#[test]
+fn encode_subscribe_v3_1_1() {
+    let mut buffer = Vec::new();
+    encode_subscribe(&mut buffer, &subscribe_packet(), &ProtocolVersion::V3_1_1).unwrap();
+
+    let expected = vec![
+        0b1000_0010u8, // Fixed header
+        0x08, // Remaining length
+        0x01, 0x02, // Packet Identifier
+        0x00, 0x03, b'a', b'/', b'+', // Topic Filter
+        0x01, // Requested QoS
+    ];
+    assert_eq!(buffer, expected);
+}
@@ -0,0 +1,40 @@
+use std::io::Write;
+
+use crate::codec::encoder::{encode_fixed_header, encode_properties, encode_reason_code, encode_two_byte_integer};
+use crate::errors;
+use crate::packets;
+use crate::packets::{ExtractValue, TwoByteInteger, VariableByteInteger};
+
+#[path = "unsuback_tests.rs"]
+#[cfg(test)]
+mod unsuback_tests;
+
+// encode_unsuback encodes the UNSUBACK Packet as the provided protocol version's format.
+// MQTT v3.1.1 UNSUBACK Packet has only the Packet Identifier, so the Properties and the Reason Codes are not sent.
+pub fn encode_unsuback(
+    writer: &mut dyn Write,
+    packet: &packets::unsuback::UnsubAck,
+    protocol_version: &packets::ProtocolVersion,
+) -> Result<(), errors::Error> {
+    let mut buffer = Vec::new();
+    let mut vector_writer = std::io::Cursor::new(&mut buffer);
+
+    encode_two_byte_integer(&mut vector_writer, &TwoByteInteger(packet.packet_identifier.val()))?;
+    if protocol_version.has_properties() {
+        encode_properties(&mut vector_writer, &packet.properties)?;
+        for reason_code in packet.reason_codes.iter() {
+            encode_reason_code(&mut vector_writer, reason_code)?;
+        }
+    }
+
+    let fixed_header = packets::FixedHeader::new(
+        packets::Bits(packets::UNSUBACK),
+        packets::Bits(0),
+        VariableByteInteger(buffer.len() as u32),
+    )?;
+
+    encode_fixed_header(writer, &fixed_header)?;
+    writer.write_all(&buffer)?;
+
+    Ok(())
+}
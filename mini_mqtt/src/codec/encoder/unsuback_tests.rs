@@ -0,0 +1,52 @@
+use crate::codec::encoder::unsuback::*; // The test targets
+
+use crate::packets;
+use crate::packets::unsuback::{self, UnsubAck};
+use crate::packets::{PacketIdentity, ProtocolVersion, TwoByteInteger, UTF8EncodedString};
+
+fn unsuback_packet() -> UnsubAck {
+    let mut properties = packets::Properties::new();
+    properties.insert(
+        packets::REASON_STRING,
+        packets::ValueTypes::UTF8EncodedString(UTF8EncodedString("no".to_string())));
+
+    UnsubAck::new(
+        PacketIdentity::new(TwoByteInteger(0x0102)),
+        properties,
+        vec![unsuback::SUCCESS, unsuback::NO_SUBSCRIPTION_EXISTED],
+    )
+}
+
+#[test]
+fn encode_unsuback_v5() {
+    let mut buffer = Vec::new();
+
+    let expected = vec![
+        0b1011_0000u8, // Fixed header
+        0x0A, // Remaining length
+        0x01, 0x02, // Packet Identifier
+        0x05, // Properties length
+        0x1F, 0x00, 0x02, b'n', b'o', // Reason String
+        0x00, // Success
+        0x11, // No subscription existed
+    ];
+
+    let result = encode_unsuback(&mut buffer, &unsuback_packet(), &ProtocolVersion::V5);
+    assert!(result.is_ok());
+    assert_eq!(buffer, expected);
+}
+
+#[test]
+fn encode_unsuback_v3_1_1() {
+    let mut buffer = Vec::new();
+
+    let expected = vec![
+        0b1011_0000u8, // Fixed header
+        0x02, // Remaining length
+        0x01, 0x02, // Packet Identifier
+    ];
+
+    let result = encode_unsuback(&mut buffer, &unsuback_packet(), &ProtocolVersion::V3_1_1);
+    assert!(result.is_ok());
+    assert_eq!(buffer, expected);
+}
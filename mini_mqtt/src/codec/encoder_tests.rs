@@ -22,6 +22,14 @@ fn encode_unsupported_packet() {
     assert!(result.is_err());
 }
 
+#[test]
+fn encode_pingresp_packet() {
+    let mut buffer = Vec::new();
+    let result = encode(&mut buffer, &Packet::PingResp);
+    assert!(result.is_ok());
+    assert_eq!(buffer, vec![0b1101_0000, 0x00]);
+}
+
 #[test]
 fn encode_reason_code_valid() {
     let mut buffer = Vec::new();
@@ -223,3 +231,43 @@ fn encode_properties_valid() {
     assert_eq!(buffer, vec![0x02, 0x01, 0x01]);
 }
 
+#[test]
+fn encode_properties_repeated_property() {
+    let mut buffer = Vec::new();
+    let mut properties = Properties::new();
+    properties.append(packets::SUBSCRIPTION_IDENTIFIER, VariableByteInteger(1).into());
+    properties.append(packets::SUBSCRIPTION_IDENTIFIER, VariableByteInteger(128).into());
+    encode_properties(&mut buffer, &properties).unwrap();
+    assert_eq!(buffer, vec![0x05, 0x0B, 0x01, 0x0B, 0x80, 0x01]);
+}
+
+
+#[test]
+fn encode_within_without_maximum_packet_size() {
+    let packet = Packet::Disconnect(packets::disconnect::Disconnect::with_reason(packets::disconnect::QOS_NOT_SUPPORTED, "no"));
+    let buffer = encode_within(&packet, &packets::ProtocolVersion::V5, None).unwrap().unwrap();
+    assert_eq!(buffer.len(), 9);
+}
+
+#[test]
+fn encode_within_drops_reason_string() {
+    let packet = Packet::Disconnect(packets::disconnect::Disconnect::with_reason(packets::disconnect::QOS_NOT_SUPPORTED, "no"));
+    let buffer = encode_within(&packet, &packets::ProtocolVersion::V5, Some(8)).unwrap().unwrap();
+    assert_eq!(buffer, vec![0xE0, 0x02, 0x9B, 0x00]);
+}
+
+#[test]
+fn encode_within_discards_too_large_publish() {
+    let publish = packets::publish::Publish::new(
+        packets::publish::Publish::fixed_header(false, packets::QoS::AtMostOnce, false),
+        UTF8EncodedString("a/b".to_string()),
+        None,
+        Properties::new(),
+        b"hello".to_vec(),
+    )
+    .unwrap();
+    let packet = Packet::Publish(publish);
+    // The PUBLISH Packet is 13 bytes.
+    assert!(encode_within(&packet, &packets::ProtocolVersion::V5, Some(13)).unwrap().is_some());
+    assert!(encode_within(&packet, &packets::ProtocolVersion::V5, Some(12)).unwrap().is_none());
+}
@@ -0,0 +1,78 @@
+use std::sync::Arc;
+
+use crate::acl;
+use crate::auth;
+use crate::packets::QoS;
+use crate::session::client_id_policy::ClientIdPolicy;
+use crate::session::queue::QueueLimits;
+use crate::session::shared_subscription::SharedSubscriptionStrategy;
+use crate::store;
+
+// Config is the set of the Server behaviours which the broker configures.
+// The default values follow the minimum requirements of the MQTT v5.0 specification.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub client_id_policy: ClientIdPolicy,
+    // legacy_protocol_enabled accepts MQTT v3.1, the protocol name "MQIsdp" and the protocol level 3.
+    pub legacy_protocol_enabled: bool,
+    // auth_provider authenticates the Clients by the User Name and the Password of the CONNECT Packets.
+    // The default accepts every Client.
+    pub auth_provider: Arc<dyn auth::AuthProvider>,
+    // acl authorizes the Clients to publish and subscribe. When it is None, every action is permitted.
+    pub acl: Option<acl::Acl>,
+    // maximum_qos is the highest QoS which the Server grants to the subscriptions and accepts from the Clients.
+    pub maximum_qos: QoS,
+    // retain_available is whether the Server accepts the messages with the RETAIN flag.
+    pub retain_available: bool,
+    // maximum_packet_size is the largest packet in bytes which the Server accepts. None means the protocol limit.
+    pub maximum_packet_size: Option<u32>,
+    // session_expiry_interval_maximum caps the Session Expiry Interval which the Clients request in seconds.
+    // None means the requested interval is used as it is.
+    pub session_expiry_interval_maximum: Option<u32>,
+    // receive_maximum is the number of QoS 1 and QoS 2 publications which the Server processes concurrently for a Client.
+    pub receive_maximum: u16,
+    // topic_alias_maximum is the highest Topic Alias which the Server accepts. 0 means no Topic Alias is accepted.
+    pub topic_alias_maximum: u16,
+    // wildcard_subscription_available is whether the Server accepts the Topic Filters containing the wildcards.
+    pub wildcard_subscription_available: bool,
+    // subscription_identifier_available is whether the Server accepts the Subscription Identifiers.
+    pub subscription_identifier_available: bool,
+    // shared_subscription_available is whether the Server accepts the Shared Subscriptions.
+    pub shared_subscription_available: bool,
+    // shared_subscription_strategy chooses the session which receives each message of a Shared Subscription.
+    pub shared_subscription_strategy: SharedSubscriptionStrategy,
+    // queue_limits limit the messages which wait in each session, e.g. while the persistent session is disconnected.
+    pub queue_limits: QueueLimits,
+    // server_keep_alive replaces the Keep Alive which the Clients request in seconds. None means the requested one is used.
+    pub server_keep_alive: Option<u16>,
+    // response_information_prefix is the prefix of the Response Information which the Server returns to the Client requesting it,
+    // followed by the ClientID, so each Client has its own topics to receive the responses. None means none is returned.
+    pub response_information_prefix: Option<String>,
+    // store keeps the persistent sessions and the retained messages. The default keeps them in the memory of the process.
+    pub store: Arc<dyn store::Store>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            client_id_policy: ClientIdPolicy::default(),
+            legacy_protocol_enabled: false,
+            auth_provider: Arc::new(auth::AllowAll),
+            acl: None,
+            maximum_qos: QoS::ExactlyOnce,
+            retain_available: true,
+            maximum_packet_size: None,
+            session_expiry_interval_maximum: None,
+            receive_maximum: u16::MAX,
+            topic_alias_maximum: 0,
+            wildcard_subscription_available: true,
+            subscription_identifier_available: true,
+            shared_subscription_available: true,
+            shared_subscription_strategy: SharedSubscriptionStrategy::default(),
+            queue_limits: QueueLimits::default(),
+            server_keep_alive: None,
+            response_information_prefix: None,
+            store: Arc::new(store::InMemory::new()),
+        }
+    }
+}
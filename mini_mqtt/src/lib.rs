@@ -1,4 +1,10 @@
+pub mod acl;
+pub mod auth;
+pub mod client;
 pub mod codec;
+pub mod config;
 pub mod packets;
 pub mod session;
+pub mod store;
 pub mod errors;
+pub mod topic;
@@ -5,29 +5,39 @@ use crate::errors;
 
 pub mod connect;
 pub mod connack;
+pub mod disconnect;
+pub mod publish;
+pub mod puback;
+pub mod pubrec;
+pub mod pubrel;
+pub mod pubcomp;
+pub mod subscribe;
+pub mod suback;
+pub mod unsubscribe;
+pub mod unsuback;
 
 #[path = "packets_tests.rs"]
 #[cfg(test)]
 mod packets_tests;
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone)]
 pub enum Packet {
     Unknown,
     //Reserved,
     Connect(connect::Connect),
     ConnAck(connack::ConnAck),
-    //Publish,
-    //PubAck,
-    //PubRec,
-    //PubRel,
-    //PubComp,
-    //Subscribe,
-    //SubAck,
-    //Unsubscribe,
-    //UnSuback,
-    //PingReq,
-    //PingResp,
-    //Disconnect,
+    Publish(publish::Publish),
+    PubAck(puback::PubAck),
+    PubRec(pubrec::PubRec),
+    PubRel(pubrel::PubRel),
+    PubComp(pubcomp::PubComp),
+    Subscribe(subscribe::Subscribe),
+    SubAck(suback::SubAck),
+    Unsubscribe(unsubscribe::Unsubscribe),
+    UnsubAck(unsuback::UnsubAck),
+    PingReq,
+    PingResp,
+    Disconnect(disconnect::Disconnect),
     //Auth,
 }
 
@@ -35,7 +45,6 @@ pub enum Packet {
 //pub const RESERVED: u8 = 0;
 pub const CONNECT: u8 = 1;
 pub const CONNACK: u8 = 2;
-/*
 pub const PUBLISH: u8 = 3;
 pub const PUBACK: u8 = 4;
 pub const PUBREC: u8 = 5;
@@ -45,15 +54,14 @@ pub const SUBSCRIBE: u8 = 8;
 pub const SUBACK: u8 = 9;
 pub const UNSUBSCRIBE: u8 = 10;
 pub const UNSUBACK: u8 = 11;
-const PINGREQ: u8 = 12;
-const PINGRESP: u8 = 13;
-const DISCONNECT: u8 = 14;
-const AUTH: u8 = 15;
- */
+pub const PINGREQ: u8 = 12;
+pub const PINGRESP: u8 = 13;
+pub const DISCONNECT: u8 = 14;
+//const AUTH: u8 = 15;
 
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub struct Bits(pub u8); // 1.5.1 Bits subsection
-#[derive(Debug, Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Clone, Hash)]
 pub struct TwoByteInteger(pub u16); // 1.5.2 Two Byte Integer subsection
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct FourByteInteger(pub u32); // 1.5.3 Four Byte Integer subsection
@@ -338,56 +346,166 @@ impl fmt::Display for FixedHeader {
 
 // PacketIdentity is a 16-bit unsigned integer that identifies a packet.
 // It is used in the MQTT 5.0 protocol to identify packets(2.2.1).
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct PacketIdentity(TwoByteInteger);
 
+impl PacketIdentity {
+    pub fn new(id: TwoByteInteger) -> PacketIdentity {
+        PacketIdentity(id)
+    }
+}
+
+impl ExtractValue<'_, u16> for PacketIdentity {
+    fn val(&self) -> u16 {
+        self.0.val()
+    }
+}
+
+// ProtocolVersion is the MQTT protocol version which the Client has requested by the CONNECT Packet.
+// After the connection is established, every packet is encoded and decoded as the version's format.
+// Look the 3.1.2.2 Protocol Version subsection for more details.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum ProtocolVersion {
+    V3_1,   // Protocol level 3, the legacy protocol name "MQIsdp"
+    V3_1_1, // Protocol level 4
+    V5,     // Protocol level 5
+}
+
+impl ProtocolVersion {
+    pub fn from_level(level: u8) -> Option<ProtocolVersion> {
+        match level {
+            3 => Some(ProtocolVersion::V3_1),
+            4 => Some(ProtocolVersion::V3_1_1),
+            5 => Some(ProtocolVersion::V5),
+            _ => None,
+        }
+    }
+
+    // from_name_and_level results the version only when the Protocol Name matches the level.
+    pub fn from_name_and_level(name: &str, level: u8) -> Option<ProtocolVersion> {
+        ProtocolVersion::from_level(level).filter(|version| version.protocol_name() == name)
+    }
+
+    pub fn level(&self) -> u8 {
+        match self {
+            ProtocolVersion::V3_1 => 3,
+            ProtocolVersion::V3_1_1 => 4,
+            ProtocolVersion::V5 => 5,
+        }
+    }
+
+    pub fn protocol_name(&self) -> &'static str {
+        match self {
+            ProtocolVersion::V3_1 => "MQIsdp",
+            ProtocolVersion::V3_1_1 | ProtocolVersion::V5 => "MQTT",
+        }
+    }
+
+    // has_properties results whether the packets of the version have the Properties.
+    // The Properties are introduced by MQTT v5.0.
+    pub fn has_properties(&self) -> bool {
+        *self == ProtocolVersion::V5
+    }
+
+    // has_session_present results whether the CONNACK Packet of the version has the Session Present flag.
+    // The first byte of the MQTT v3.1 CONNACK Variable Header is reserved.
+    pub fn has_session_present(&self) -> bool {
+        *self != ProtocolVersion::V3_1
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProtocolVersion::V3_1 => write!(f, "MQTT v3.1"),
+            ProtocolVersion::V3_1_1 => write!(f, "MQTT v3.1.1"),
+            ProtocolVersion::V5 => write!(f, "MQTT v5.0"),
+        }
+    }
+}
+
 pub trait PacketIdentifier {
     fn packet_identity(&self) -> &PacketIdentity;
 }
 
+// Properties keeps the values of each property in the received order.
+// Most of the properties appear at most once, but the User Property and the Subscription Identifier of the PUBLISH Packet
+// can appear multiple times (2.2.2.2 Property subsection), so they are added by append instead of insert.
 #[derive(Debug, Eq, PartialEq, Clone)]
-pub struct Properties(pub HashMap<VariableByteInteger, ValueTypes>); // 2.2.2 Property Length subsection
+pub struct Properties(pub HashMap<VariableByteInteger, Vec<ValueTypes>>); // 2.2.2 Property Length subsection
+
+impl Default for Properties {
+    fn default() -> Self {
+        Self::new()
+    }
+}
 
 impl Properties {
     pub fn new() -> Self {
         Properties(HashMap::new())
     }
 
+    // insert sets the value of the property, replacing the values which it has had.
     pub fn insert(&mut self, key: VariableByteInteger, value: ValueTypes) {
-        self.0.insert(key, value);
+        self.0.insert(key, vec![value]);
+    }
+
+    // append adds the value of the property which can appear multiple times, keeping the values which it has had.
+    pub fn append(&mut self, key: VariableByteInteger, value: ValueTypes) {
+        self.0.entry(key).or_default().push(value);
     }
 
-    pub fn iter(&self) -> std::collections::hash_map::Iter<VariableByteInteger, ValueTypes> {
-        self.0.iter()
+    // iter results every value of the properties with its identifier.
+    pub fn iter(&self) -> impl Iterator<Item = (&VariableByteInteger, &ValueTypes)> {
+        self.0.iter().flat_map(|(key, values)| values.iter().map(move |value| (key, value)))
     }
 
     pub fn get_as<'a, T>(&'a self, key: VariableByteInteger) -> Result<Option<&'a T>, errors::Error>
     where
         T: FromValueTypesRef<'a>,
     {
-        let val = self.0.get(&key);
+        let val = self.0.get(&key).and_then(|values| values.first());
         match val {
-            Some(value) => {
-                if let Some(typed) = T::from_value_types_ref(value) {
-                    Ok(Some(typed))
-                } else {
-                    Err(errors::Error::Common(format!(
-                        "The provided identifier is not a {:?} type. The stored value's type: {:?}",
-                        T::type_name(),
-                        value
-                    )))
-                }
-            }
+            Some(value) => Properties::typed(value).map(Some),
             None => Ok(None),
         }
     }
 
+    // get_all_as results every value of the property which can appear multiple times, in the received order.
+    pub fn get_all_as<'a, T>(&'a self, key: VariableByteInteger) -> Result<Vec<&'a T>, errors::Error>
+    where
+        T: FromValueTypesRef<'a>,
+    {
+        self.0
+            .get(&key)
+            .map_or(Ok(Vec::new()), |values| values.iter().map(Properties::typed).collect())
+    }
+
+    fn typed<'a, T>(value: &'a ValueTypes) -> Result<&'a T, errors::Error>
+    where
+        T: FromValueTypesRef<'a>,
+    {
+        T::from_value_types_ref(value).ok_or_else(|| {
+            errors::Error::Common(format!(
+                "The provided identifier is not a {:?} type. The stored value's type: {:?}",
+                T::type_name(),
+                value
+            ))
+        })
+    }
+
+    // remove removes every value of the property, and results the first one.
+    pub fn remove(&mut self, key: &VariableByteInteger) -> Option<ValueTypes> {
+        self.0.remove(key).and_then(|values| values.into_iter().next())
+    }
+
     pub fn is_empty(&self) -> bool {
         self.0.is_empty()
     }
 
+    // len results the number of the values of the properties.
     pub fn len(&self) -> usize {
-        self.0.len()
+        self.0.values().map(|values| values.len()).sum()
     }
 }
 
@@ -419,7 +537,7 @@ pub const WILDCARD_SUBSCRIPTION_AVAILABLE: VariableByteInteger = VariableByteInt
 pub const SUBSCRIPTION_IDENTIFIER_AVAILABLE: VariableByteInteger = VariableByteInteger(0x29);
 pub const SHARED_SUBSCRIPTION_AVAILABLE: VariableByteInteger = VariableByteInteger(0x2A);
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub enum QoS {
     AtMostOnce,
     AtLeastOnce,
@@ -438,6 +556,31 @@ impl fmt::Display for QoS {
     }
 }
 
+impl QoS {
+    pub fn bits(&self) -> Bits {
+        match self {
+            QoS::AtMostOnce => Bits(0),
+            QoS::AtLeastOnce => Bits(1),
+            QoS::ExactlyOnce => Bits(2),
+            QoS::Malformed => Bits(3),
+        }
+    }
+
+    // min results the lower QoS, it is used to downgrade the QoS of the delivered message to the granted QoS.
+    pub fn min(self, other: QoS) -> QoS {
+        if self.bits().val() <= other.bits().val() {
+            self
+        } else {
+            other
+        }
+    }
+
+    // exceeds results whether the QoS is higher than the maximum, e.g. the Maximum QoS of the Server.
+    pub fn exceeds(self, maximum: QoS) -> bool {
+        self.bits().val() > maximum.bits().val()
+    }
+}
+
 pub fn qos_from_bits(qos: Bits) -> Result<QoS, errors::Error> {
     let val = qos.val();
     match val {
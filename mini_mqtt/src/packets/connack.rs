@@ -1,10 +1,16 @@
 use crate::packets;
 use crate::packets::Bits;
-use crate::packets::{FixedHeader, Properties, ReasonCode, VariableByteInteger};
+use crate::packets::connect::ConnectError;
+use crate::packets::{FixedHeader, Properties, ReasonCode, UTF8EncodedString, VariableByteInteger};
+
+#[path = "connack_tests.rs"]
+#[cfg(test)]
+mod connack_tests;
 
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct ConnAck {
     pub fixed_header: FixedHeader,
+    pub session_present: bool,                  // 3.2.2.1.1 Session Present subsection
     pub connect_reason_code: ConnAckReasonCode, // 3.2.2.2 Connect Reason Code subsection
     pub properties: Properties,                 // 3.2.2.3 CONNACK Properties subsection
                                                 // There is no payload in CONNACK packet
@@ -13,20 +19,45 @@ pub struct ConnAck {
 impl ConnAck {
     pub fn new(
         fixed_header: FixedHeader,
+        session_present: bool,
         connect_reason_code: ConnAckReasonCode,
         properties: Properties,
     ) -> ConnAck {
         ConnAck {
             fixed_header,
+            session_present,
             connect_reason_code,
             properties,
         }
     }
 
-    pub fn default() -> ConnAck {
+    // rejected results the CONNACK Packet answering the CONNECT Packet which has failed the validation.
+    // The reason of the failure is set as the Reason String property (3.2.2.3.9 Reason String subsection).
+    pub fn rejected(error: &ConnectError) -> ConnAck {
+        let mut properties = Properties::new();
+        properties.insert(
+            packets::REASON_STRING,
+            UTF8EncodedString(error.reason.clone()).into(),
+        );
+
+        ConnAck {
+            connect_reason_code: error.reason_code.clone(),
+            properties,
+            ..ConnAck::default()
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.connect_reason_code.code() < 0x80
+    }
+}
+
+impl Default for ConnAck {
+    fn default() -> ConnAck {
         ConnAck {
-            fixed_header: FixedHeader::new(Bits(packets::CONNACK), Bits(0), VariableByteInteger(2))
+            fixed_header: FixedHeader::new(Bits(packets::CONNACK), Bits(0), VariableByteInteger(3))
                 .unwrap(),
+            session_present: false, // 1 byte as the Connect Acknowledge Flags
             connect_reason_code: SUCCESS, // 1 byte
             properties: Properties::new(), // no properties are 1 byte
         }
@@ -42,6 +73,23 @@ impl ReasonCode for ConnAckReasonCode {
     }
 }
 
+impl ConnAckReasonCode {
+    // v3_return_code results the Connect Return Code of MQTT v3.1.1 (3.2.2.3 Connect Return code of MQTT v3.1.1).
+    // When there is no corresponding Return Code, it results None, then the Server MUST close
+    // the Network Connection without sending a CONNACK [MQTT-3.2.2-6] of MQTT v3.1.1.
+    pub fn v3_return_code(&self) -> Option<u8> {
+        match *self {
+            SUCCESS => Some(0x00),
+            UNSUPPORTED_PROTOCOL_VERSION => Some(0x01),
+            CLIENT_IDENTIFIER_NOT_VALID => Some(0x02),
+            SERVER_UNAVAILABLE | SERVER_BUSY | USE_ANOTHER_SERVER | SERVER_MOVED => Some(0x03),
+            BAD_USER_NAME_OR_PASSWORD => Some(0x04),
+            NOT_AUTHORIZED | BANNED => Some(0x05),
+            _ => None,
+        }
+    }
+}
+
 // 3.2.2.2 Connect Reason Code
 // The Connection is accepted.
 pub const SUCCESS: ConnAckReasonCode = ConnAckReasonCode(0x00);
@@ -0,0 +1,36 @@
+use super::*;
+use crate::packets::connect::ConnectError;
+use crate::packets::UTF8EncodedString;
+
+#[test]
+fn default_is_success() {
+    let connack = ConnAck::default();
+    assert_eq!(connack.connect_reason_code, SUCCESS);
+    assert!(connack.properties.is_empty());
+    assert!(connack.is_success());
+}
+
+#[test]
+fn rejected_carries_reason_code_and_reason_string() {
+    let error = ConnectError::client_identifier_not_valid("Client ID is too long");
+    let connack = ConnAck::rejected(&error);
+
+    assert_eq!(connack.connect_reason_code, CLIENT_IDENTIFIER_NOT_VALID);
+    assert!(!connack.is_success());
+    let reason = connack
+        .properties
+        .get_as::<UTF8EncodedString>(packets::REASON_STRING)
+        .unwrap();
+    assert_eq!(reason, Some(&UTF8EncodedString("Client ID is too long".to_string())));
+}
+
+#[test]
+fn v3_return_codes() {
+    assert_eq!(SUCCESS.v3_return_code(), Some(0x00));
+    assert_eq!(UNSUPPORTED_PROTOCOL_VERSION.v3_return_code(), Some(0x01));
+    assert_eq!(CLIENT_IDENTIFIER_NOT_VALID.v3_return_code(), Some(0x02));
+    assert_eq!(SERVER_UNAVAILABLE.v3_return_code(), Some(0x03));
+    assert_eq!(BAD_USER_NAME_OR_PASSWORD.v3_return_code(), Some(0x04));
+    assert_eq!(NOT_AUTHORIZED.v3_return_code(), Some(0x05));
+    assert_eq!(MALFORMED_PACKET.v3_return_code(), None);
+}
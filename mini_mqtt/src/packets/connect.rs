@@ -2,8 +2,12 @@ use std::fmt;
 use super::{
     BinaryData, Bits, ExtractValue, QoS, TwoByteInteger, UTF8EncodedString,
 };
+use crate::config;
 use crate::errors;
 use crate::packets;
+use crate::packets::connack::{self, ConnAckReasonCode};
+use crate::packets::ReasonCode;
+use crate::topic;
 
 #[path = "connect_tests.rs"]
 #[cfg(test)]
@@ -28,6 +32,37 @@ impl Connect {
             payload,
         })
     }
+
+    // protocol_version results the requested version, or None if the pair of the Protocol Name and the Protocol Version is unknown.
+    pub fn protocol_version(&self) -> Option<packets::ProtocolVersion> {
+        packets::ProtocolVersion::from_name_and_level(
+            self.variable_header.protocol_name.val(),
+            self.variable_header.protocol_version.val(),
+        )
+    }
+
+    // will_message results the Will Message as the PUBLISH Packet to publish, or None without the Will Flag.
+    // The Will Properties are sent as the Properties of the PUBLISH Packet (3.1.3.2 Will Properties subsection),
+    // except the Will Delay Interval, which is not supported yet, so the Will Message is published without the delay.
+    // The Packet Identifier of the QoS 1 and QoS 2 Will Message is a placeholder, each delivery allocates its own one.
+    pub fn will_message(&self) -> Option<packets::publish::Publish> {
+        let connect_flags = &self.variable_header.connect_flags;
+        if !connect_flags.will_flag() {
+            return None;
+        }
+        let mut properties = self.payload.will_properties.clone().unwrap_or_default();
+        properties.remove(&packets::WILL_DELAY_INTERVAL);
+        let qos = connect_flags.will_qos();
+        let packet_identifier = (qos != QoS::AtMostOnce).then(|| packets::PacketIdentity::new(TwoByteInteger(1)));
+        packets::publish::Publish::new(
+            packets::publish::Publish::fixed_header(false, qos, connect_flags.will_retain()),
+            self.payload.will_topic.clone()?,
+            packet_identifier,
+            properties,
+            self.payload.will_payload.as_ref().map(|payload| payload.val().to_vec()).unwrap_or_default(),
+        )
+        .ok()
+    }
 }
 
 // VariableHeader struct is a part of CONNECT Packet.
@@ -96,7 +131,7 @@ impl ConnectFlags {
 
     pub fn will_qos(&self) -> QoS {
         super::qos_from_bits(Bits((self.0.val() & 0b0001_1000) >> 3))
-            .unwrap_or_else(|_| QoS::Malformed)
+            .unwrap_or(QoS::Malformed)
     }
 
     pub fn will_flag(&self) -> bool {
@@ -138,124 +173,204 @@ impl Payload {
     }
 }
 
-// validate the provided CONNECT Packet as the version 5.0.
-pub fn validate(connect: &Connect) -> Result<(), Vec<errors::Error>> {
+// ConnectError is a validation failure of the CONNECT Packet.
+// It carries the CONNACK Reason Code the Server answers with (3.2.2.2 Connect Reason Code subsection),
+// and the reason which can be sent as the Reason String property of the CONNACK Packet.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct ConnectError {
+    pub reason_code: ConnAckReasonCode,
+    pub reason: String,
+}
+
+impl ConnectError {
+    pub fn new(reason_code: ConnAckReasonCode, reason: &str) -> ConnectError {
+        ConnectError {
+            reason_code,
+            reason: reason.to_string(),
+        }
+    }
+
+    pub fn malformed_packet(reason: &str) -> ConnectError {
+        ConnectError::new(connack::MALFORMED_PACKET, reason)
+    }
+
+    pub fn protocol_error(reason: &str) -> ConnectError {
+        ConnectError::new(connack::PROTOCOL_ERROR, reason)
+    }
+
+    pub fn unsupported_protocol_version(reason: &str) -> ConnectError {
+        ConnectError::new(connack::UNSUPPORTED_PROTOCOL_VERSION, reason)
+    }
+
+    pub fn client_identifier_not_valid(reason: &str) -> ConnectError {
+        ConnectError::new(connack::CLIENT_IDENTIFIER_NOT_VALID, reason)
+    }
+}
+
+impl fmt::Display for ConnectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (CONNACK reason code: 0x{:02X})", self.reason, self.reason_code.code())
+    }
+}
+
+impl From<ConnectError> for errors::Error {
+    fn from(err: ConnectError) -> Self {
+        match err.reason_code {
+            connack::MALFORMED_PACKET => errors::Error::MalformedPacket(err.reason),
+            connack::PROTOCOL_ERROR => errors::Error::ProtocolError(err.reason),
+            _ => errors::Error::Common(err.to_string()),
+        }
+    }
+}
+
+// validate the provided CONNECT Packet as the version 5.0, 3.1.1 or 3.1.
+// The Client ID is validated by the ClientIdPolicy which the Server is configured with.
+// Each failure carries the Reason Code of the CONNACK Packet which should be returned to the Client.
+pub fn validate(connect: &Connect, config: &config::Config) -> Result<(), Vec<ConnectError>> {
     let mut errors = Vec::new();
 
     let fixed_header = &connect.fixed_header;
     if fixed_header.control_packet_type != Bits(packets::CONNECT) {
-        errors.push(errors::Error::MalformedPacket(
-            format!("Control Packet Type is {:?}. It is not CONNECT{}",
-                fixed_header.control_packet_type, packets::CONNECT).to_string()
+        errors.push(ConnectError::malformed_packet(
+            &format!("Control Packet Type is {:?}. It is not CONNECT{}",
+                fixed_header.control_packet_type, packets::CONNECT)
         ));
     }
     // Ignore the flags of the Fixed Header for now...
 
+    // If the Protocol Name is incorrect the Server MAY continue processing the CONNECT packet
+    // and respond with a CONNACK containing the Reason Code 0x84 (Unsupported Protocol Version).
+    // Look the 3.1.2.1 Protocol Name subsection for more details.
+    // MQTT v3.1.1 (the protocol level 4) is also supported, and MQTT v3.1 ("MQIsdp" and the protocol level 3)
+    // is supported only when the legacy protocol is enabled by the configuration.
     let variable_header = &connect.variable_header;
-    if variable_header.protocol_name.val() != "MQTT" {
-        errors.push(errors::Error::MalformedPacket(
-            format!("Protocol Name is not MQTT. It is {}", variable_header.protocol_name.val()).to_string()
-        ));
+    match connect.protocol_version() {
+        Some(packets::ProtocolVersion::V3_1) if !config.legacy_protocol_enabled => {
+            errors.push(ConnectError::unsupported_protocol_version(
+                "MQTT v3.1 (MQIsdp) is not enabled by the Server."
+            ));
+        }
+        Some(_) => {}
+        None => {
+            errors.push(ConnectError::unsupported_protocol_version(
+                &format!(
+                    "Protocol Name {} and Protocol Version {} are not supported.",
+                    variable_header.protocol_name.val(),
+                    variable_header.protocol_version.val()
+                )
+            ));
+        }
     }
 
-    if variable_header.protocol_version.val() != 5 {
-        errors.push(errors::Error::MalformedPacket(
-            format!("Protocol Version is not 5. It is {}", variable_header.protocol_version.val()).to_string()
-        ));
+    // The Will Topic is a Topic Name, so it cannot contain the wildcard characters [MQTT-3.3.2-2].
+    if let Some(will_topic) = &connect.payload.will_topic {
+        if let Err(err) = topic::validate_topic_name(will_topic.val()) {
+            errors.push(ConnectError::new(connack::TOPIC_NAME_INVALID, &format!("Will Topic is invalid: {}", err)));
+        }
     }
 
-    // Ignore around the Will Flags for now...
+    // The Will Message is published as the capabilities of the Server allow.
+    // The Will QoS which exceeds the Maximum QoS is rejected with the Reason Code 0x9B (QoS not supported) [MQTT-3.2.2-12],
+    // and the Will Retain without the retained messages support is rejected with 0x9A (Retain not supported) [MQTT-3.2.2-13].
+    let connect_flags = &variable_header.connect_flags;
+    if connect_flags.will_flag() && connect_flags.will_qos().exceeds(config.maximum_qos) {
+        errors.push(ConnectError::new(
+            connack::QOS_NOT_SUPPORTED,
+            &format!("Will QoS {} exceeds the Maximum QoS {}", connect_flags.will_qos(), config.maximum_qos),
+        ));
+    }
+    if connect_flags.will_flag() && connect_flags.will_retain() && !config.retain_available {
+        errors.push(ConnectError::new(
+            connack::RETAIN_NOT_SUPPORTED,
+            "Will Retain is 1 even the Server does not support the retained messages.",
+        ));
+    }
 
+    // The mismatches between the flags and the payload are Malformed Packets [MQTT-3.1.2-16] [MQTT-3.1.2-18].
     if variable_header.connect_flags.username() {
         if connect.payload.user_name.is_none() {
-            errors.push(errors::Error::MalformedPacket(
-                "User Name is not provided even the user name flag is 1.".to_string()
-            ));
-        }
-    } else {
-        if connect.payload.user_name.is_some() {
-            errors.push(errors::Error::MalformedPacket(
-                "User Name is provided even the user name flag is 0.".to_string()
+            errors.push(ConnectError::malformed_packet(
+                "User Name is not provided even the user name flag is 1."
             ));
         }
+    } else if connect.payload.user_name.is_some() {
+        errors.push(ConnectError::malformed_packet(
+            "User Name is provided even the user name flag is 0."
+        ));
     }
 
     if variable_header.connect_flags.password() {
         if connect.payload.password.is_none() {
-            errors.push(errors::Error::MalformedPacket(
-                "Password is not provided even the password flag is 1.".to_string()
-            ));
-        }
-    } else {
-        if connect.payload.password.is_some() {
-            errors.push(errors::Error::MalformedPacket(
-                "Password is provided even the password flag is 0.".to_string()
+            errors.push(ConnectError::malformed_packet(
+                "Password is not provided even the password flag is 1."
             ));
         }
+    } else if connect.payload.password.is_some() {
+        errors.push(ConnectError::malformed_packet(
+            "Password is provided even the password flag is 0."
+        ));
     }
 
     // Keep Alive is not necessary to validate.
 
+    // It is a Protocol Error to include the Maximum Packet Size value 0 (3.1.2.11.4 Maximum Packet Size subsection).
+    let maximum_packet_size = variable_header.properties.get_as::<packets::FourByteInteger>(packets::MAXIMUM_PACKET_SIZE);
+    if matches!(maximum_packet_size, Ok(Some(packets::FourByteInteger(0)))) {
+        errors.push(ConnectError::protocol_error("Maximum Packet Size is 0."));
+    }
+
+    // It is a Protocol Error to include the Receive Maximum value 0 (3.1.2.11.3 Receive Maximum subsection).
+    let receive_maximum = variable_header.properties.get_as::<packets::TwoByteInteger>(packets::RECEIVE_MAXIMUM);
+    if matches!(receive_maximum, Ok(Some(packets::TwoByteInteger(0)))) {
+        errors.push(ConnectError::protocol_error("Receive Maximum is 0."));
+    }
+
+    // It is a Protocol Error to include the Request Response Information other than 0 or 1
+    // (3.1.2.11.6 Request Response Information subsection).
+    let request_response_information = variable_header.properties.get_as::<Bits>(packets::REQUEST_RESPONSE_INFORMATION);
+    if let Ok(Some(request_response_information)) = request_response_information {
+        if request_response_information.val() > 1 {
+            errors.push(ConnectError::protocol_error(&format!(
+                "Request Response Information is {}. It is not 0 or 1.",
+                request_response_information.val()
+            )));
+        }
+    }
+
     // Ignore the following properties for now...
-    // - Receive Maximum
-    // - Maximum Packet Size
     // - Topic Alias Maximum
-    // - Request Response Information
     // - Request Problem Information
     // - User Property
     // - Authentication Method
     // - Authentication Data
 
-    if let Err(err) = validate_client_id(&connect.payload.client_id.val()) {
-        errors.push(err);
-    }
-
-    // Ignore the Will Properties for now...
-
-    if variable_header.connect_flags.username() {
-        if connect.payload.user_name.is_none() {
-            errors.push(errors::Error::MalformedPacket(
-                "User Name is not provided even the user name flag is 1.".to_string()
+    // A zero-length Client ID is allowed only with Clean Start, the Server assigns a unique ClientID then.
+    // Otherwise, the Server MUST respond with the Reason Code 0x85 (Client Identifier not valid) [MQTT-3.1.3-8].
+    let client_id = connect.payload.client_id.val();
+    if connect.protocol_version() == Some(packets::ProtocolVersion::V3_1) {
+        // The Client Identifier of MQTT v3.1 MUST be between 1 and 23 characters (3.1 CONNECT of MQTT v3.1).
+        let len = client_id.chars().count();
+        if !(1..=23).contains(&len) {
+            errors.push(ConnectError::client_identifier_not_valid(
+                &format!("Client ID length of MQTT v3.1 is not between 1 and 23. It is {}", len)
             ));
         }
     }
-
-    if variable_header.connect_flags.password() {
-        if connect.payload.password.is_none() {
-            errors.push(errors::Error::MalformedPacket(
-                "Password is not provided even the password flag is 1.".to_string()
+    if client_id.is_empty() {
+        if !variable_header.connect_flags.clean_start() {
+            errors.push(ConnectError::client_identifier_not_valid(
+                "Client ID is zero-length even the clean start flag is 0."
             ));
         }
+    } else if let Err(err) = config.client_id_policy.validate(client_id, connect.payload.user_name.as_ref().map(|n| n.val())) {
+        errors.push(err);
     }
 
+    // Ignore the Will Properties for now...
+
     if errors.is_empty() {
         Ok(())
     } else {
         Err(errors)
     }
 }
-
-// Validate the Client ID of the CONNECT Packet.
-// There is the following specification for the Client ID:
-//   A Server MAY allow a Client to supply a ClientID that has a length of zero bytes, however if it does so the
-//   Server MUST treat this as a special case and assign a unique ClientID to that Client [MQTT-3.1.3-6]
-// But, for now, we don't support the zero-length Client ID.
-// Look the 3.1.3.1 Client Identifier (ClientID) subsection for more details.
-pub fn validate_client_id(client_id: &str) -> Result<(), errors::Error> {
-    let len = client_id.len();
-    if len < 1 || len > 23 {
-        return Err(errors::Error::MalformedPacket(
-            format!("Client ID length is not between 1 and 23. It is {}", client_id.len()).to_string()
-        ));
-    }
-    // Allowed characters are: "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ"
-    for c in client_id.chars() {
-        if !c.is_ascii_alphanumeric() {
-            return Err(errors::Error::MalformedPacket(
-                format!("Client ID contains non-alphanumeric character: {}", c).to_string()
-            ));
-        }
-    }
-
-    Ok(())
-}
@@ -1,5 +1,7 @@
 use super::*;
 use crate::packets::{Bits, QoS, VariableByteInteger};
+use crate::packets::connack;
+use crate::session::client_id_policy::{AllowedCharacters, ClientIdPolicy};
 
 #[test]
 fn connect_flags_username() {
@@ -115,7 +117,7 @@ fn validate_valid_connect() {
     )
     .unwrap();
     let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
-    let result = validate(&connect);
+    let result = validate(&connect, &config::Config::default());
     assert!(result.is_ok());
 }
 
@@ -140,8 +142,8 @@ fn validate_invalid_protocol_name() {
     )
     .unwrap();
     let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
-    let result = validate(&connect);
-    assert!(result.is_err());
+    let result = validate(&connect, &config::Config::default());
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::UNSUPPORTED_PROTOCOL_VERSION);
 }
 
 #[test]
@@ -149,7 +151,7 @@ fn validate_invalid_protocol_version() {
     let fixed_header = valid_fixed_header();
     let variable_header = VariableHeader::new(
         UTF8EncodedString("MQTT".to_string()),
-        Bits(4), // unsupported version
+        Bits(6), // unsupported version
         ConnectFlags::new(Bits(0b0000_0000)).unwrap(),
         TwoByteInteger(60),
         packets::Properties::new(),
@@ -165,8 +167,8 @@ fn validate_invalid_protocol_version() {
     )
     .unwrap();
     let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
-    let result = validate(&connect);
-    assert!(result.is_err());
+    let result = validate(&connect, &config::Config::default());
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::UNSUPPORTED_PROTOCOL_VERSION);
 }
 
 #[test]
@@ -190,8 +192,8 @@ fn validate_missing_username() {
     )
     .unwrap();
     let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
-    let result = validate(&connect);
-    assert!(result.is_err());
+    let result = validate(&connect, &config::Config::default());
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::MALFORMED_PACKET);
 }
 
 #[test]
@@ -208,7 +210,7 @@ fn validate_provided_username_flag_not_set() {
     )
     .unwrap();
     let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
-    let result = validate(&connect);
+    let result = validate(&connect, &config::Config::default());
     assert!(result.is_err());
 }
 
@@ -233,8 +235,8 @@ fn validate_missing_password() {
     )
     .unwrap();
     let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
-    let result = validate(&connect);
-    assert!(result.is_err());
+    let result = validate(&connect, &config::Config::default());
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::MALFORMED_PACKET);
 }
 
 #[test]
@@ -251,7 +253,7 @@ fn validate_provided_password_flag_not_set() {
     )
     .unwrap();
     let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
-    let result = validate(&connect);
+    let result = validate(&connect, &config::Config::default());
     assert!(result.is_err());
 }
 
@@ -269,24 +271,224 @@ fn validate_invalid_client_id() {
     )
     .unwrap();
     let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
-    let result = validate(&connect);
-    assert!(result.is_err());
+    let result = validate(&connect, &config::Config::default());
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::CLIENT_IDENTIFIER_NOT_VALID);
 }
 
 #[test]
-fn validate_client_id_too_short() {
-    let result = validate_client_id("");
-    assert!(result.is_err());
+fn validate_reports_each_failure() {
+    let fixed_header = valid_fixed_header();
+    let variable_header = VariableHeader::new(
+        UTF8EncodedString("MQTT".to_string()),
+        Bits(3), // unsupported version
+        ConnectFlags::new(Bits(0b0000_0000)).unwrap(),
+        TwoByteInteger(60),
+        packets::Properties::new(),
+    )
+    .unwrap();
+    let payload = Payload::new(
+        UTF8EncodedString("invalid_client_id!".to_string()),
+        None,
+        None,
+        None,
+        None,
+        None,
+    )
+    .unwrap();
+    let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
+    let errors = validate(&connect, &config::Config::default()).unwrap_err();
+    let codes: Vec<_> = errors.iter().map(|e| e.reason_code.clone()).collect();
+    assert_eq!(
+        codes,
+        vec![connack::UNSUPPORTED_PROTOCOL_VERSION, connack::CLIENT_IDENTIFIER_NOT_VALID]
+    );
 }
 
 #[test]
-fn validate_client_id_too_long() {
-    let result = validate_client_id("a".repeat(24).as_str());
-    assert!(result.is_err());
+fn connect_error_into_error() {
+    let err: errors::Error = ConnectError::malformed_packet("broken").into();
+    assert!(matches!(err, errors::Error::MalformedPacket(msg) if msg == "broken"));
+
+    let err: errors::Error = ConnectError::protocol_error("violation").into();
+    assert!(matches!(err, errors::Error::ProtocolError(msg) if msg == "violation"));
 }
 
 #[test]
-fn validate_client_id_contains_non_alphanumeric() {
-    let result = validate_client_id("client_id!");
-    assert!(result.is_err());
+fn validate_zero_length_client_id_with_clean_start() {
+    let fixed_header = valid_fixed_header();
+    let variable_header = VariableHeader::new(
+        UTF8EncodedString("MQTT".to_string()),
+        Bits(5),
+        ConnectFlags::new(Bits(0b0000_0010)).unwrap(), // clean start
+        TwoByteInteger(60),
+        packets::Properties::new(),
+    )
+    .unwrap();
+    let payload = Payload::new(UTF8EncodedString("".to_string()), None, None, None, None, None).unwrap();
+    let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
+    assert!(validate(&connect, &config::Config::default()).is_ok());
+}
+
+#[test]
+fn validate_zero_length_client_id_without_clean_start() {
+    let fixed_header = valid_fixed_header();
+    let variable_header = valid_variable_header(); // clean start flag not set
+    let payload = Payload::new(UTF8EncodedString("".to_string()), None, None, None, None, None).unwrap();
+    let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
+    let result = validate(&connect, &config::Config::default());
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::CLIENT_IDENTIFIER_NOT_VALID);
+}
+
+#[test]
+fn validate_client_id_with_tenant_prefix() {
+    let policy = ClientIdPolicy::new(64, AllowedCharacters::AlphanumericAnd("-".to_string()))
+        .with_tenant_prefix("tenant1", "t1-");
+    let fixed_header = valid_fixed_header();
+    let variable_header = VariableHeader::new(
+        UTF8EncodedString("MQTT".to_string()),
+        Bits(5),
+        ConnectFlags::new(Bits(0b1000_0000)).unwrap(), // username flag set
+        TwoByteInteger(60),
+        packets::Properties::new(),
+    )
+    .unwrap();
+    let connect = |client_id: &str| {
+        let payload = Payload::new(
+            UTF8EncodedString(client_id.to_string()),
+            None,
+            None,
+            None,
+            Some(UTF8EncodedString("tenant1".to_string())),
+            None,
+        )
+        .unwrap();
+        Connect::new(fixed_header.clone(), variable_header.clone(), payload).unwrap()
+    };
+
+    let config = config::Config {
+        client_id_policy: policy,
+        ..config::Config::default()
+    };
+    assert!(validate(&connect("t1-sensor-01"), &config).is_ok());
+    let result = validate(&connect("t2-sensor-01"), &config);
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::CLIENT_IDENTIFIER_NOT_VALID);
+}
+
+#[test]
+fn validate_protocol_version_3_1_1() {
+    let fixed_header = valid_fixed_header();
+    let variable_header = VariableHeader::new(
+        UTF8EncodedString("MQTT".to_string()),
+        Bits(4),
+        ConnectFlags::new(Bits(0b0000_0000)).unwrap(),
+        TwoByteInteger(60),
+        packets::Properties::new(),
+    )
+    .unwrap();
+    let payload = Payload::new(UTF8EncodedString("testclient".to_string()), None, None, None, None, None).unwrap();
+    let connect = Connect::new(fixed_header, variable_header, payload).unwrap();
+    assert!(validate(&connect, &config::Config::default()).is_ok());
+    assert_eq!(connect.protocol_version(), Some(packets::ProtocolVersion::V3_1_1));
+}
+
+fn mqisdp_connect(client_id: &str) -> Connect {
+    let variable_header = VariableHeader::new(
+        UTF8EncodedString("MQIsdp".to_string()),
+        Bits(3),
+        ConnectFlags::new(Bits(0b0000_0010)).unwrap(),
+        TwoByteInteger(60),
+        packets::Properties::new(),
+    )
+    .unwrap();
+    let payload = Payload::new(UTF8EncodedString(client_id.to_string()), None, None, None, None, None).unwrap();
+    Connect::new(valid_fixed_header(), variable_header, payload).unwrap()
+}
+
+fn legacy_config() -> config::Config {
+    config::Config {
+        legacy_protocol_enabled: true,
+        ..config::Config::default()
+    }
+}
+
+#[test]
+fn validate_mqisdp_is_disabled_by_default() {
+    let result = validate(&mqisdp_connect("testclient"), &config::Config::default());
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::UNSUPPORTED_PROTOCOL_VERSION);
+}
+
+#[test]
+fn validate_mqisdp_with_legacy_protocol() {
+    let connect = mqisdp_connect("testclient");
+    assert!(validate(&connect, &legacy_config()).is_ok());
+    assert_eq!(connect.protocol_version(), Some(packets::ProtocolVersion::V3_1));
+}
+
+#[test]
+fn validate_mqisdp_zero_length_client_id() {
+    // MQTT v3.1 does not allow the zero-length Client ID even the clean session flag is set.
+    let result = validate(&mqisdp_connect(""), &legacy_config());
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::CLIENT_IDENTIFIER_NOT_VALID);
+}
+
+#[test]
+fn validate_mqisdp_too_long_client_id() {
+    let mut config = legacy_config();
+    config.client_id_policy = ClientIdPolicy::new(64, AllowedCharacters::Alphanumeric);
+    let result = validate(&mqisdp_connect("a".repeat(24).as_str()), &config);
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::CLIENT_IDENTIFIER_NOT_VALID);
+}
+
+#[test]
+fn validate_mqtt_name_with_level_3() {
+    let variable_header = VariableHeader::new(
+        UTF8EncodedString("MQTT".to_string()),
+        Bits(3),
+        ConnectFlags::new(Bits(0b0000_0000)).unwrap(),
+        TwoByteInteger(60),
+        packets::Properties::new(),
+    )
+    .unwrap();
+    let payload = Payload::new(UTF8EncodedString("testclient".to_string()), None, None, None, None, None).unwrap();
+    let connect = Connect::new(valid_fixed_header(), variable_header, payload).unwrap();
+    let result = validate(&connect, &legacy_config());
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::UNSUPPORTED_PROTOCOL_VERSION);
+}
+
+fn will_connect(flags: u8, will_topic: &str) -> Connect {
+    let mut variable_header = valid_variable_header();
+    variable_header.connect_flags = ConnectFlags::new(Bits(flags)).unwrap();
+    let mut will_properties = packets::Properties::new();
+    will_properties.insert(packets::WILL_DELAY_INTERVAL, packets::FourByteInteger(10).into());
+    will_properties.insert(packets::MESSAGE_EXPIRY_INTERVAL, packets::FourByteInteger(60).into());
+    let payload = Payload::new(
+        UTF8EncodedString("testclient".to_string()),
+        Some(will_properties),
+        Some(UTF8EncodedString(will_topic.to_string())),
+        Some(BinaryData(b"gone".to_vec())),
+        None,
+        None,
+    )
+    .unwrap();
+    Connect::new(valid_fixed_header(), variable_header, payload).unwrap()
+}
+
+#[test]
+fn will_message_of_will_flag() {
+    // Will Retain, Will QoS 1 and Will Flag.
+    let will = will_connect(0b0010_1100, "clients/testclient/status").will_message().unwrap();
+    assert_eq!(will.topic_name.val(), "clients/testclient/status");
+    assert_eq!(will.qos(), QoS::AtLeastOnce);
+    assert!(will.retain());
+    assert_eq!(will.payload, b"gone".to_vec());
+    assert_eq!(will.message_expiry_interval(), Some(60));
+    assert!(will.properties.get_as::<packets::FourByteInteger>(packets::WILL_DELAY_INTERVAL).unwrap().is_none());
+
+    assert!(will_connect(0b0000_0000, "clients/testclient/status").will_message().is_none());
+}
+
+#[test]
+fn validate_will_topic_with_wildcard() {
+    let result = validate(&will_connect(0b0000_0100, "clients/+/status"), &config::Config::default());
+    assert_eq!(result.unwrap_err()[0].reason_code, connack::TOPIC_NAME_INVALID);
 }
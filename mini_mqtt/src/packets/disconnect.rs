@@ -0,0 +1,130 @@
+use crate::packets;
+use crate::packets::{Bits, FixedHeader, Properties, ReasonCode, UTF8EncodedString, VariableByteInteger};
+
+#[path = "disconnect_tests.rs"]
+#[cfg(test)]
+mod disconnect_tests;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Disconnect {
+    pub fixed_header: FixedHeader,
+    pub reason_code: DisconnectReasonCode, // 3.14.2.1 Disconnect Reason Code subsection
+    pub properties: Properties,            // 3.14.2.2 DISCONNECT Properties subsection
+                                           // There is no payload in DISCONNECT packet
+}
+
+impl Disconnect {
+    pub fn new(reason_code: DisconnectReasonCode, properties: Properties) -> Disconnect {
+        Disconnect {
+            fixed_header: FixedHeader::new(Bits(packets::DISCONNECT), Bits(0), VariableByteInteger(0)).unwrap(),
+            reason_code,
+            properties,
+        }
+    }
+
+    // with_reason results the DISCONNECT Packet which the Server sends on a protocol violation of the Client.
+    // The reason is set as the Reason String property (3.14.2.2.3 Reason String subsection).
+    pub fn with_reason(reason_code: DisconnectReasonCode, reason: &str) -> Disconnect {
+        let mut properties = Properties::new();
+        properties.insert(packets::REASON_STRING, UTF8EncodedString(reason.to_string()).into());
+        Disconnect::new(reason_code, properties)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct DisconnectReasonCode(pub u8);
+
+impl ReasonCode for DisconnectReasonCode {
+    fn code(&self) -> u8 {
+        self.0
+    }
+}
+
+// 3.14.2.1 Disconnect Reason Code
+// Close the connection normally. Do not send the Will Message.
+pub const NORMAL_DISCONNECTION: DisconnectReasonCode = DisconnectReasonCode(0x00);
+
+// The Client wishes to disconnect but requires that the Server also publishes its Will Message.
+pub const DISCONNECT_WITH_WILL_MESSAGE: DisconnectReasonCode = DisconnectReasonCode(0x04);
+
+// The Connection is closed but the sender either does not wish to reveal the reason, or none of the other Reason Codes apply.
+pub const UNSPECIFIED_ERROR: DisconnectReasonCode = DisconnectReasonCode(0x80);
+
+// The received packet does not conform to this specification.
+pub const MALFORMED_PACKET: DisconnectReasonCode = DisconnectReasonCode(0x81);
+
+// An unexpected or out of order packet was received.
+pub const PROTOCOL_ERROR: DisconnectReasonCode = DisconnectReasonCode(0x82);
+
+// The packet received is valid but cannot be processed by this implementation.
+pub const IMPLEMENTATION_SPECIFIC_ERROR: DisconnectReasonCode = DisconnectReasonCode(0x83);
+
+// The request is not authorized.
+pub const NOT_AUTHORIZED: DisconnectReasonCode = DisconnectReasonCode(0x87);
+
+// The Server is busy and cannot continue processing requests from this Client.
+pub const SERVER_BUSY: DisconnectReasonCode = DisconnectReasonCode(0x89);
+
+// The Server is shutting down.
+pub const SERVER_SHUTTING_DOWN: DisconnectReasonCode = DisconnectReasonCode(0x8B);
+
+// The Connection is closed because no packet has been received for 1.5 times the Keepalive time.
+pub const KEEP_ALIVE_TIMEOUT: DisconnectReasonCode = DisconnectReasonCode(0x8D);
+
+// Another Connection using the same ClientID has connected causing this Connection to be closed.
+pub const SESSION_TAKEN_OVER: DisconnectReasonCode = DisconnectReasonCode(0x8E);
+
+// The Topic Filter is correctly formed, but is not accepted by this Server.
+pub const TOPIC_FILTER_INVALID: DisconnectReasonCode = DisconnectReasonCode(0x8F);
+
+// The Topic Name is correctly formed, but is not accepted by this Client or Server.
+pub const TOPIC_NAME_INVALID: DisconnectReasonCode = DisconnectReasonCode(0x90);
+
+// The Client or Server has received more than Receive Maximum publication for which it has not sent PUBACK or PUBCOMP.
+pub const RECEIVE_MAXIMUM_EXCEEDED: DisconnectReasonCode = DisconnectReasonCode(0x93);
+
+// The Client or Server has received a PUBLISH packet containing a Topic Alias which is greater than
+// the Maximum Topic Alias it sent in the CONNECT or CONNACK packet.
+pub const TOPIC_ALIAS_INVALID: DisconnectReasonCode = DisconnectReasonCode(0x94);
+
+// The packet size is greater than Maximum Packet Size for this Client or Server.
+pub const PACKET_TOO_LARGE: DisconnectReasonCode = DisconnectReasonCode(0x95);
+
+// The received data rate is too high.
+pub const MESSAGE_RATE_TOO_HIGH: DisconnectReasonCode = DisconnectReasonCode(0x96);
+
+// An implementation or administrative imposed limit has been exceeded.
+pub const QUOTA_EXCEEDED: DisconnectReasonCode = DisconnectReasonCode(0x97);
+
+// The Connection is closed due to an administrative action.
+pub const ADMINISTRATIVE_ACTION: DisconnectReasonCode = DisconnectReasonCode(0x98);
+
+// The payload format does not match the one specified by the Payload Format Indicator.
+pub const PAYLOAD_FORMAT_INVALID: DisconnectReasonCode = DisconnectReasonCode(0x99);
+
+// The Server does not support retained messages.
+pub const RETAIN_NOT_SUPPORTED: DisconnectReasonCode = DisconnectReasonCode(0x9A);
+
+// The Client specified a QoS greater than the QoS specified in a Maximum QoS in the CONNACK.
+pub const QOS_NOT_SUPPORTED: DisconnectReasonCode = DisconnectReasonCode(0x9B);
+
+// The Client should temporarily change its Server.
+pub const USE_ANOTHER_SERVER: DisconnectReasonCode = DisconnectReasonCode(0x9C);
+
+// The Server is moved and the Client should permanently change its server location.
+pub const SERVER_MOVED: DisconnectReasonCode = DisconnectReasonCode(0x9D);
+
+// The Server does not support Shared Subscriptions.
+pub const SHARED_SUBSCRIPTIONS_NOT_SUPPORTED: DisconnectReasonCode = DisconnectReasonCode(0x9E);
+
+// This connection is closed because the connection rate is too high.
+pub const CONNECTION_RATE_EXCEEDED: DisconnectReasonCode = DisconnectReasonCode(0x9F);
+
+// The maximum connection time authorized for this connection has been exceeded.
+pub const MAXIMUM_CONNECT_TIME: DisconnectReasonCode = DisconnectReasonCode(0xA0);
+
+// The Server does not support Subscription Identifiers; the subscription is not accepted.
+pub const SUBSCRIPTION_IDENTIFIERS_NOT_SUPPORTED: DisconnectReasonCode = DisconnectReasonCode(0xA1);
+
+// The Server does not support Wildcard Subscriptions; the subscription is not accepted.
+pub const WILDCARD_SUBSCRIPTIONS_NOT_SUPPORTED: DisconnectReasonCode = DisconnectReasonCode(0xA2);
@@ -0,0 +1,13 @@
+use super::*;
+
+#[test]
+fn with_reason_carries_reason_string() {
+    let disconnect = Disconnect::with_reason(QOS_NOT_SUPPORTED, "QoS 2 is not supported");
+
+    assert_eq!(disconnect.fixed_header.control_packet_type, Bits(packets::DISCONNECT));
+    assert_eq!(disconnect.reason_code, QOS_NOT_SUPPORTED);
+    assert_eq!(
+        disconnect.properties.get_as::<UTF8EncodedString>(packets::REASON_STRING).unwrap(),
+        Some(&UTF8EncodedString("QoS 2 is not supported".to_string()))
+    );
+}
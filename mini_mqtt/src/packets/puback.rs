@@ -0,0 +1,59 @@
+use crate::packets;
+use crate::packets::{Bits, FixedHeader, PacketIdentity, Properties, ReasonCode, VariableByteInteger};
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PubAck {
+    pub fixed_header: FixedHeader,
+    pub packet_identifier: PacketIdentity, // 3.4.2 PUBACK Variable Header subsection
+    pub reason_code: PubAckReasonCode,     // 3.4.2.1 PUBACK Reason Code subsection
+    pub properties: Properties,            // 3.4.2.2 PUBACK Properties subsection
+                                           // There is no payload in PUBACK packet
+}
+
+impl PubAck {
+    pub fn new(packet_identifier: PacketIdentity, reason_code: PubAckReasonCode, properties: Properties) -> PubAck {
+        PubAck {
+            fixed_header: FixedHeader::new(Bits(packets::PUBACK), Bits(0), VariableByteInteger(0)).unwrap(),
+            packet_identifier,
+            reason_code,
+            properties,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PubAckReasonCode(pub u8);
+
+impl ReasonCode for PubAckReasonCode {
+    fn code(&self) -> u8 {
+        self.0
+    }
+}
+
+// 3.4.2.1 PUBACK Reason Code
+// The message is accepted. Publication of the QoS 1 message proceeds.
+pub const SUCCESS: PubAckReasonCode = PubAckReasonCode(0x00);
+
+// The message is accepted but there are no subscribers.
+pub const NO_MATCHING_SUBSCRIBERS: PubAckReasonCode = PubAckReasonCode(0x10);
+
+// The receiver does not accept the publish but either does not want to reveal the reason, or it does not match one of the other values.
+pub const UNSPECIFIED_ERROR: PubAckReasonCode = PubAckReasonCode(0x80);
+
+// The PUBLISH is valid but the receiver is not willing to accept it.
+pub const IMPLEMENTATION_SPECIFIC_ERROR: PubAckReasonCode = PubAckReasonCode(0x83);
+
+// The PUBLISH is not authorized.
+pub const NOT_AUTHORIZED: PubAckReasonCode = PubAckReasonCode(0x87);
+
+// The Topic Name is not malformed, but is not accepted by this Client or Server.
+pub const TOPIC_NAME_INVALID: PubAckReasonCode = PubAckReasonCode(0x90);
+
+// The Packet Identifier is already in use.
+pub const PACKET_IDENTIFIER_IN_USE: PubAckReasonCode = PubAckReasonCode(0x91);
+
+// An implementation or administrative imposed limit has been exceeded.
+pub const QUOTA_EXCEEDED: PubAckReasonCode = PubAckReasonCode(0x97);
+
+// The payload format does not match the specified Payload Format Indicator.
+pub const PAYLOAD_FORMAT_INVALID: PubAckReasonCode = PubAckReasonCode(0x99);
@@ -0,0 +1,40 @@
+use crate::packets;
+use crate::packets::{Bits, FixedHeader, PacketIdentity, Properties, ReasonCode, VariableByteInteger};
+
+// PUBCOMP is the response to a PUBREL Packet, the fourth and final packet of the QoS 2 protocol exchange.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PubComp {
+    pub fixed_header: FixedHeader,
+    pub packet_identifier: PacketIdentity, // 3.7.2 PUBCOMP Variable Header subsection
+    pub reason_code: PubCompReasonCode,    // 3.7.2.1 PUBCOMP Reason Code subsection
+    pub properties: Properties,            // 3.7.2.2 PUBCOMP Properties subsection
+                                           // There is no payload in PUBCOMP packet
+}
+
+impl PubComp {
+    pub fn new(packet_identifier: PacketIdentity, reason_code: PubCompReasonCode, properties: Properties) -> PubComp {
+        PubComp {
+            fixed_header: FixedHeader::new(Bits(packets::PUBCOMP), Bits(0), VariableByteInteger(0)).unwrap(),
+            packet_identifier,
+            reason_code,
+            properties,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PubCompReasonCode(pub u8);
+
+impl ReasonCode for PubCompReasonCode {
+    fn code(&self) -> u8 {
+        self.0
+    }
+}
+
+// 3.7.2.1 PUBCOMP Reason Code
+// Packet Identifier released. Publication of QoS 2 message is complete.
+pub const SUCCESS: PubCompReasonCode = PubCompReasonCode(0x00);
+
+// The Packet Identifier is not known. This is not an error during recovery, but at other times indicates a mismatch
+// between the Session State on the Client and Server.
+pub const PACKET_IDENTIFIER_NOT_FOUND: PubCompReasonCode = PubCompReasonCode(0x92);
@@ -0,0 +1,79 @@
+use super::{Bits, ExtractValue, QoS, UTF8EncodedString, VariableByteInteger};
+use crate::errors;
+use crate::packets;
+
+#[path = "publish_tests.rs"]
+#[cfg(test)]
+mod publish_tests;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Publish {
+    pub fixed_header: packets::FixedHeader,                // 3.3.1 PUBLISH Fixed Header subsection
+    pub topic_name: UTF8EncodedString,                     // 3.3.2.1 Topic Name subsection
+    pub packet_identifier: Option<packets::PacketIdentity>, // 3.3.2.2 Packet Identifier subsection
+    pub properties: packets::Properties,                   // 3.3.2.3 PUBLISH Properties subsection
+    pub payload: Vec<u8>,                                  // 3.3.3 PUBLISH Payload subsection
+}
+
+impl Publish {
+    pub fn new(
+        fixed_header: packets::FixedHeader,
+        topic_name: UTF8EncodedString,
+        packet_identifier: Option<packets::PacketIdentity>,
+        properties: packets::Properties,
+        payload: Vec<u8>,
+    ) -> Result<Publish, errors::Error> {
+        // A PUBLISH Packet MUST NOT have both QoS bits set to 1 [MQTT-3.3.1-4].
+        let qos = packets::qos_from_bits(Bits((fixed_header.flags.val() & 0b0110) >> 1))?;
+        // The Packet Identifier field is only present in PUBLISH packets where the QoS level is 1 or 2.
+        if (qos == QoS::AtMostOnce) != packet_identifier.is_none() {
+            return Err(errors::Error::ProtocolError(
+                format!("The Packet Identifier presence does not match the QoS {}", qos)
+            ));
+        }
+        // The DUP flag MUST be set to 0 for all QoS 0 messages [MQTT-3.3.1-2].
+        if qos == QoS::AtMostOnce && fixed_header.take_flag(3) == 1 {
+            return Err(errors::Error::MalformedPacket(
+                "The DUP flag is set to the QoS 0 message".to_string()
+            ));
+        }
+
+        Ok(Publish {
+            fixed_header,
+            topic_name,
+            packet_identifier,
+            properties,
+            payload,
+        })
+    }
+
+    // fixed_header results the PUBLISH Fixed Header of the provided flags.
+    // As a note, the Remaining Length is calculated by the encoder.
+    pub fn fixed_header(dup: bool, qos: QoS, retain: bool) -> packets::FixedHeader {
+        let flags = (dup as u8) << 3 | qos.bits().val() << 1 | retain as u8;
+        packets::FixedHeader::new(Bits(packets::PUBLISH), Bits(flags), VariableByteInteger(0)).unwrap()
+    }
+
+    pub fn dup(&self) -> bool {
+        self.fixed_header.take_flag(3) == 1
+    }
+
+    pub fn qos(&self) -> QoS {
+        packets::qos_from_bits(Bits((self.fixed_header.flags.val() & 0b0110) >> 1))
+            .unwrap_or(QoS::Malformed)
+    }
+
+    pub fn retain(&self) -> bool {
+        self.fixed_header.take_flag(0) == 1
+    }
+
+    // message_expiry_interval results the lifetime of the Application Message in seconds (3.3.2.3.3 Message Expiry Interval subsection).
+    // If absent, the Application Message does not expire.
+    pub fn message_expiry_interval(&self) -> Option<u32> {
+        self.properties
+            .get_as::<packets::FourByteInteger>(packets::MESSAGE_EXPIRY_INTERVAL)
+            .ok()
+            .flatten()
+            .map(|interval| interval.val())
+    }
+}
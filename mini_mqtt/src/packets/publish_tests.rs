@@ -0,0 +1,85 @@
+use super::*;
+use crate::packets::{PacketIdentity, Properties, TwoByteInteger};
+
+#[test]
+fn fixed_header_flags() {
+    let fixed_header = Publish::fixed_header(true, QoS::ExactlyOnce, true);
+    assert_eq!(fixed_header.control_packet_type, Bits(packets::PUBLISH));
+    assert_eq!(fixed_header.flags, Bits(0b1101));
+}
+
+#[test]
+fn publish_flags() {
+    let publish = Publish::new(
+        Publish::fixed_header(true, QoS::AtLeastOnce, true),
+        UTF8EncodedString("a/b".to_string()),
+        Some(PacketIdentity::new(TwoByteInteger(1))),
+        Properties::new(),
+        b"payload".to_vec(),
+    )
+    .unwrap();
+    assert!(publish.dup());
+    assert_eq!(publish.qos(), QoS::AtLeastOnce);
+    assert!(publish.retain());
+}
+
+#[test]
+fn publish_qos0_without_packet_identifier() {
+    let result = Publish::new(
+        Publish::fixed_header(false, QoS::AtMostOnce, false),
+        UTF8EncodedString("a/b".to_string()),
+        None,
+        Properties::new(),
+        vec![],
+    );
+    assert!(result.is_ok());
+}
+
+#[test]
+fn publish_qos0_with_packet_identifier() {
+    let result = Publish::new(
+        Publish::fixed_header(false, QoS::AtMostOnce, false),
+        UTF8EncodedString("a/b".to_string()),
+        Some(PacketIdentity::new(TwoByteInteger(1))),
+        Properties::new(),
+        vec![],
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn publish_qos1_without_packet_identifier() {
+    let result = Publish::new(
+        Publish::fixed_header(false, QoS::AtLeastOnce, false),
+        UTF8EncodedString("a/b".to_string()),
+        None,
+        Properties::new(),
+        vec![],
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn publish_qos0_with_dup() {
+    let result = Publish::new(
+        Publish::fixed_header(true, QoS::AtMostOnce, false),
+        UTF8EncodedString("a/b".to_string()),
+        None,
+        Properties::new(),
+        vec![],
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn publish_malformed_qos() {
+    let fixed_header = packets::FixedHeader::new(Bits(packets::PUBLISH), Bits(0b0110), VariableByteInteger(0)).unwrap();
+    let result = Publish::new(
+        fixed_header,
+        UTF8EncodedString("a/b".to_string()),
+        Some(PacketIdentity::new(TwoByteInteger(1))),
+        Properties::new(),
+        vec![],
+    );
+    assert!(result.is_err());
+}
@@ -0,0 +1,68 @@
+use crate::packets;
+use crate::packets::{Bits, FixedHeader, PacketIdentity, Properties, ReasonCode, VariableByteInteger};
+
+// PUBREC is the response to a PUBLISH Packet with QoS 2, the second packet of the QoS 2 protocol exchange.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PubRec {
+    pub fixed_header: FixedHeader,
+    pub packet_identifier: PacketIdentity, // 3.5.2 PUBREC Variable Header subsection
+    pub reason_code: PubRecReasonCode,     // 3.5.2.1 PUBREC Reason Code subsection
+    pub properties: Properties,            // 3.5.2.2 PUBREC Properties subsection
+                                           // There is no payload in PUBREC packet
+}
+
+impl PubRec {
+    pub fn new(packet_identifier: PacketIdentity, reason_code: PubRecReasonCode, properties: Properties) -> PubRec {
+        PubRec {
+            fixed_header: FixedHeader::new(Bits(packets::PUBREC), Bits(0), VariableByteInteger(0)).unwrap(),
+            packet_identifier,
+            reason_code,
+            properties,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PubRecReasonCode(pub u8);
+
+impl ReasonCode for PubRecReasonCode {
+    fn code(&self) -> u8 {
+        self.0
+    }
+}
+
+impl PubRecReasonCode {
+    // is_failure results whether the Reason Code ends the QoS 2 protocol exchange without the PUBREL Packet.
+    // A Reason Code of 0x80 or greater indicates failure (2.4 Reason Code section).
+    pub fn is_failure(&self) -> bool {
+        self.0 >= 0x80
+    }
+}
+
+// 3.5.2.1 PUBREC Reason Code
+// The message is accepted. Publication of the QoS 2 message proceeds.
+pub const SUCCESS: PubRecReasonCode = PubRecReasonCode(0x00);
+
+// The message is accepted but there are no subscribers.
+pub const NO_MATCHING_SUBSCRIBERS: PubRecReasonCode = PubRecReasonCode(0x10);
+
+// The receiver does not accept the publish but either does not want to reveal the reason, or it does not match one of the other values.
+pub const UNSPECIFIED_ERROR: PubRecReasonCode = PubRecReasonCode(0x80);
+
+// The PUBLISH is valid but the receiver is not willing to accept it.
+pub const IMPLEMENTATION_SPECIFIC_ERROR: PubRecReasonCode = PubRecReasonCode(0x83);
+
+// The PUBLISH is not authorized.
+pub const NOT_AUTHORIZED: PubRecReasonCode = PubRecReasonCode(0x87);
+
+// The Topic Name is not malformed, but is not accepted by this Client or Server.
+pub const TOPIC_NAME_INVALID: PubRecReasonCode = PubRecReasonCode(0x90);
+
+// The Packet Identifier is already in use.
+pub const PACKET_IDENTIFIER_IN_USE: PubRecReasonCode = PubRecReasonCode(0x91);
+
+// An implementation or administrative imposed limit has been exceeded.
+pub const QUOTA_EXCEEDED: PubRecReasonCode = PubRecReasonCode(0x97);
+
+// The payload format does not match the specified Payload Format Indicator.
+pub const PAYLOAD_FORMAT_INVALID: PubRecReasonCode = PubRecReasonCode(0x99);
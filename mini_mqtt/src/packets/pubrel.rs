@@ -0,0 +1,44 @@
+use crate::packets;
+use crate::packets::{Bits, FixedHeader, PacketIdentity, Properties, ReasonCode, VariableByteInteger};
+
+// PUBREL is the response to a PUBREC Packet, the third packet of the QoS 2 protocol exchange.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PubRel {
+    pub fixed_header: FixedHeader,
+    pub packet_identifier: PacketIdentity, // 3.6.2 PUBREL Variable Header subsection
+    pub reason_code: PubRelReasonCode,     // 3.6.2.1 PUBREL Reason Code subsection
+    pub properties: Properties,            // 3.6.2.2 PUBREL Properties subsection
+                                           // There is no payload in PUBREL packet
+}
+
+impl PubRel {
+    pub fn new(packet_identifier: PacketIdentity, reason_code: PubRelReasonCode, properties: Properties) -> PubRel {
+        PubRel {
+            fixed_header: FixedHeader::new(Bits(packets::PUBREL), Bits(FLAGS), VariableByteInteger(0)).unwrap(),
+            packet_identifier,
+            reason_code,
+            properties,
+        }
+    }
+}
+
+// Bits 3,2,1 and 0 of the Fixed Header in the PUBREL packet are reserved and MUST be set to 0,0,1 and 0 respectively.
+// The Server MUST treat any other value as malformed and close the Network Connection [MQTT-3.6.1-1].
+pub const FLAGS: u8 = 0b0010;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct PubRelReasonCode(pub u8);
+
+impl ReasonCode for PubRelReasonCode {
+    fn code(&self) -> u8 {
+        self.0
+    }
+}
+
+// 3.6.2.1 PUBREL Reason Code
+// Message released.
+pub const SUCCESS: PubRelReasonCode = PubRelReasonCode(0x00);
+
+// The Packet Identifier is not known. This is not an error during recovery, but at other times indicates a mismatch
+// between the Session State on the Client and Server.
+pub const PACKET_IDENTIFIER_NOT_FOUND: PubRelReasonCode = PubRelReasonCode(0x92);
@@ -0,0 +1,93 @@
+use crate::packets;
+use crate::packets::{Bits, FixedHeader, PacketIdentity, Properties, QoS, ReasonCode, VariableByteInteger};
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct SubAck {
+    pub fixed_header: FixedHeader,
+    pub packet_identifier: PacketIdentity, // 3.9.2 SUBACK Variable Header subsection
+    pub properties: Properties,            // 3.9.2.1 SUBACK Properties subsection
+    pub reason_codes: Vec<SubAckReasonCode>, // 3.9.3 SUBACK Payload subsection
+}
+
+impl SubAck {
+    pub fn new(
+        packet_identifier: PacketIdentity,
+        properties: Properties,
+        reason_codes: Vec<SubAckReasonCode>,
+    ) -> SubAck {
+        SubAck {
+            fixed_header: FixedHeader::new(Bits(packets::SUBACK), Bits(0), VariableByteInteger(0))
+                .unwrap(),
+            packet_identifier,
+            properties,
+            reason_codes,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct SubAckReasonCode(pub u8);
+
+impl ReasonCode for SubAckReasonCode {
+    fn code(&self) -> u8 {
+        self.0
+    }
+}
+
+impl SubAckReasonCode {
+    pub fn granted(qos: QoS) -> SubAckReasonCode {
+        match qos {
+            QoS::AtMostOnce => GRANTED_QOS_0,
+            QoS::AtLeastOnce => GRANTED_QOS_1,
+            QoS::ExactlyOnce => GRANTED_QOS_2,
+            QoS::Malformed => UNSPECIFIED_ERROR,
+        }
+    }
+
+    // v3_return_code results the Return Code of MQTT v3.1.1 SUBACK Packet.
+    // The version has only a single failure code 0x80 (3.9.3 Payload of MQTT v3.1.1).
+    pub fn v3_return_code(&self) -> u8 {
+        if self.0 < 0x80 {
+            self.0
+        } else {
+            UNSPECIFIED_ERROR.0
+        }
+    }
+}
+
+// 3.9.3 SUBACK Payload
+// The subscription is accepted and the maximum QoS sent will be QoS 0.
+pub const GRANTED_QOS_0: SubAckReasonCode = SubAckReasonCode(0x00);
+
+// The subscription is accepted and the maximum QoS sent will be QoS 1.
+pub const GRANTED_QOS_1: SubAckReasonCode = SubAckReasonCode(0x01);
+
+// The subscription is accepted and any received QoS will be sent to this subscription.
+pub const GRANTED_QOS_2: SubAckReasonCode = SubAckReasonCode(0x02);
+
+// The subscription is not accepted and the Server either does not wish to reveal the reason or none of the other Reason Codes apply.
+pub const UNSPECIFIED_ERROR: SubAckReasonCode = SubAckReasonCode(0x80);
+
+// The SUBSCRIBE is valid but the Server does not accept it.
+pub const IMPLEMENTATION_SPECIFIC_ERROR: SubAckReasonCode = SubAckReasonCode(0x83);
+
+// The Client is not authorized to make this subscription.
+pub const NOT_AUTHORIZED: SubAckReasonCode = SubAckReasonCode(0x87);
+
+// The Topic Filter is correctly formed but is not allowed for this Client.
+pub const TOPIC_FILTER_INVALID: SubAckReasonCode = SubAckReasonCode(0x8F);
+
+// The specified Packet Identifier is already in use.
+pub const PACKET_IDENTIFIER_IN_USE: SubAckReasonCode = SubAckReasonCode(0x91);
+
+// An implementation or administrative imposed limit has been exceeded.
+pub const QUOTA_EXCEEDED: SubAckReasonCode = SubAckReasonCode(0x97);
+
+// The Server does not support Shared Subscriptions for this Client.
+pub const SHARED_SUBSCRIPTIONS_NOT_SUPPORTED: SubAckReasonCode = SubAckReasonCode(0x9E);
+
+// The Server does not support Subscription Identifiers; the subscription is not accepted.
+pub const SUBSCRIPTION_IDENTIFIERS_NOT_SUPPORTED: SubAckReasonCode = SubAckReasonCode(0xA1);
+
+// The Server does not support Wildcard Subscriptions; the subscription is not accepted.
+pub const WILDCARD_SUBSCRIPTIONS_NOT_SUPPORTED: SubAckReasonCode = SubAckReasonCode(0xA2);
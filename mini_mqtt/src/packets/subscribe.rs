@@ -0,0 +1,100 @@
+use super::{Bits, ExtractValue, QoS, UTF8EncodedString};
+use crate::errors;
+use crate::packets;
+
+#[path = "subscribe_tests.rs"]
+#[cfg(test)]
+mod subscribe_tests;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Subscribe {
+    pub fixed_header: packets::FixedHeader,        // 3.8.1 SUBSCRIBE Fixed Header subsection
+    pub packet_identifier: packets::PacketIdentity, // 3.8.2 SUBSCRIBE Variable Header subsection
+    pub properties: packets::Properties,           // 3.8.2.1 SUBSCRIBE Properties subsection
+    pub subscriptions: Vec<Subscription>,          // 3.8.3 SUBSCRIBE Payload subsection
+}
+
+impl Subscribe {
+    pub fn new(
+        fixed_header: packets::FixedHeader,
+        packet_identifier: packets::PacketIdentity,
+        properties: packets::Properties,
+        subscriptions: Vec<Subscription>,
+    ) -> Result<Subscribe, errors::Error> {
+        // Bits 3,2,1 and 0 of the Fixed Header of the SUBSCRIBE packet are reserved and MUST be set to 0,0,1 and 0 [MQTT-3.8.1-1].
+        if fixed_header.flags != Bits(0b0010) {
+            return Err(errors::Error::MalformedPacket(
+                format!("The flags of the SUBSCRIBE Fixed Header are {:?}. It must be 0b0010", fixed_header.flags)
+            ));
+        }
+        // The Payload MUST contain at least one Topic Filter and Subscription Options pair [MQTT-3.8.3-2].
+        if subscriptions.is_empty() {
+            return Err(errors::Error::ProtocolError(
+                "SUBSCRIBE Packet has no Topic Filter".to_string()
+            ));
+        }
+
+        Ok(Subscribe {
+            fixed_header,
+            packet_identifier,
+            properties,
+            subscriptions,
+        })
+    }
+}
+
+// Subscription is a pair of the Topic Filter and the Subscription Options in the SUBSCRIBE Payload.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Subscription {
+    pub topic_filter: UTF8EncodedString,
+    pub options: SubscriptionOptions,
+}
+
+impl Subscription {
+    pub fn new(topic_filter: UTF8EncodedString, options: SubscriptionOptions) -> Subscription {
+        Subscription {
+            topic_filter,
+            options,
+        }
+    }
+}
+
+// SubscriptionOptions is the options byte of a Topic Filter.
+// Look the 3.8.3.1 Subscription Options subsection for more details.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct SubscriptionOptions(pub Bits);
+
+impl SubscriptionOptions {
+    pub fn new(options: Bits) -> Result<SubscriptionOptions, errors::Error> {
+        // Bits 6 and 7 of the Subscription Options byte are reserved and MUST be set to 0 [MQTT-3.8.3-5].
+        if options.val() & 0b1100_0000 != 0 {
+            return Err(errors::Error::MalformedPacket(
+                format!("The reserved bits of the Subscription Options are set: {:#010b}", options.val())
+            ));
+        }
+
+        // It is a Protocol Error to send a Retain Handling value of 3 (3.8.3.1 Subscription Options subsection).
+        if (options.val() & 0b0011_0000) >> 4 == 3 {
+            return Err(errors::Error::ProtocolError("The Retain Handling is 3".to_string()));
+        }
+
+        Ok(SubscriptionOptions(options))
+    }
+
+    pub fn maximum_qos(&self) -> QoS {
+        super::qos_from_bits(Bits(self.0.val() & 0b0000_0011))
+            .unwrap_or(QoS::Malformed)
+    }
+
+    pub fn no_local(&self) -> bool {
+        self.0.val() & 0b0000_0100 != 0
+    }
+
+    pub fn retain_as_published(&self) -> bool {
+        self.0.val() & 0b0000_1000 != 0
+    }
+
+    pub fn retain_handling(&self) -> u8 {
+        (self.0.val() & 0b0011_0000) >> 4
+    }
+}
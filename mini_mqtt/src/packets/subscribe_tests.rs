@@ -0,0 +1,69 @@
+use super::*;
+use crate::packets::{FixedHeader, PacketIdentity, Properties, TwoByteInteger, VariableByteInteger};
+
+fn subscription() -> Subscription {
+    Subscription::new(
+        UTF8EncodedString("a/b".to_string()),
+        SubscriptionOptions::new(Bits(0x01)).unwrap(),
+    )
+}
+
+#[test]
+fn subscribe_valid() {
+    let fixed_header = FixedHeader::new(Bits(packets::SUBSCRIBE), Bits(0b0010), VariableByteInteger(0)).unwrap();
+    let result = Subscribe::new(
+        fixed_header,
+        PacketIdentity::new(TwoByteInteger(1)),
+        Properties::new(),
+        vec![subscription()],
+    );
+    assert!(result.is_ok());
+}
+
+#[test]
+fn subscribe_invalid_flags() {
+    let fixed_header = FixedHeader::new(Bits(packets::SUBSCRIBE), Bits(0b0000), VariableByteInteger(0)).unwrap();
+    let result = Subscribe::new(
+        fixed_header,
+        PacketIdentity::new(TwoByteInteger(1)),
+        Properties::new(),
+        vec![subscription()],
+    );
+    assert!(result.is_err());
+}
+
+#[test]
+fn subscribe_without_subscriptions() {
+    let fixed_header = FixedHeader::new(Bits(packets::SUBSCRIBE), Bits(0b0010), VariableByteInteger(0)).unwrap();
+    let result = Subscribe::new(fixed_header, PacketIdentity::new(TwoByteInteger(1)), Properties::new(), vec![]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn subscription_options() {
+    let options = SubscriptionOptions::new(Bits(0b0010_1110)).unwrap();
+    assert_eq!(options.maximum_qos(), QoS::ExactlyOnce);
+    assert!(options.no_local());
+    assert!(options.retain_as_published());
+    assert_eq!(options.retain_handling(), 2);
+
+    let options = SubscriptionOptions::new(Bits(0b0000_0000)).unwrap();
+    assert_eq!(options.maximum_qos(), QoS::AtMostOnce);
+    assert!(!options.no_local());
+    assert!(!options.retain_as_published());
+    assert_eq!(options.retain_handling(), 0);
+}
+
+#[test]
+fn subscription_options_reserved_bits() {
+    assert!(SubscriptionOptions::new(Bits(0b0100_0000)).is_err());
+    assert!(SubscriptionOptions::new(Bits(0b1000_0000)).is_err());
+}
+
+#[test]
+fn subscription_options_retain_handling_3() {
+    assert!(matches!(
+        SubscriptionOptions::new(Bits(0b0011_0000)),
+        Err(errors::Error::ProtocolError(_))
+    ));
+}
@@ -0,0 +1,57 @@
+use crate::packets;
+use crate::packets::{Bits, FixedHeader, PacketIdentity, Properties, ReasonCode, VariableByteInteger};
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct UnsubAck {
+    pub fixed_header: FixedHeader,
+    pub packet_identifier: PacketIdentity,     // 3.11.2 UNSUBACK Variable Header subsection
+    pub properties: Properties,                // 3.11.2.1 UNSUBACK Properties subsection
+    pub reason_codes: Vec<UnsubAckReasonCode>, // 3.11.3 UNSUBACK Payload subsection
+}
+
+impl UnsubAck {
+    pub fn new(
+        packet_identifier: PacketIdentity,
+        properties: Properties,
+        reason_codes: Vec<UnsubAckReasonCode>,
+    ) -> UnsubAck {
+        UnsubAck {
+            fixed_header: FixedHeader::new(Bits(packets::UNSUBACK), Bits(0), VariableByteInteger(0))
+                .unwrap(),
+            packet_identifier,
+            properties,
+            reason_codes,
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct UnsubAckReasonCode(pub u8);
+
+impl ReasonCode for UnsubAckReasonCode {
+    fn code(&self) -> u8 {
+        self.0
+    }
+}
+
+// 3.11.3 UNSUBACK Payload
+// The subscription is deleted.
+pub const SUCCESS: UnsubAckReasonCode = UnsubAckReasonCode(0x00);
+
+// No matching Topic Filter is being used by the Client.
+pub const NO_SUBSCRIPTION_EXISTED: UnsubAckReasonCode = UnsubAckReasonCode(0x11);
+
+// The unsubscribe could not be completed and the Server either does not wish to reveal the reason or none of the other Reason Codes apply.
+pub const UNSPECIFIED_ERROR: UnsubAckReasonCode = UnsubAckReasonCode(0x80);
+
+// The UNSUBSCRIBE is valid but the Server does not accept it.
+pub const IMPLEMENTATION_SPECIFIC_ERROR: UnsubAckReasonCode = UnsubAckReasonCode(0x83);
+
+// The Client is not authorized to unsubscribe.
+pub const NOT_AUTHORIZED: UnsubAckReasonCode = UnsubAckReasonCode(0x87);
+
+// The Topic Filter is correctly formed but is not allowed for this Client.
+pub const TOPIC_FILTER_INVALID: UnsubAckReasonCode = UnsubAckReasonCode(0x8F);
+
+// The specified Packet Identifier is already in use.
+pub const PACKET_IDENTIFIER_IN_USE: UnsubAckReasonCode = UnsubAckReasonCode(0x91);
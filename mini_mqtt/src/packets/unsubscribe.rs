@@ -0,0 +1,44 @@
+use super::{Bits, UTF8EncodedString};
+use crate::errors;
+use crate::packets;
+
+#[path = "unsubscribe_tests.rs"]
+#[cfg(test)]
+mod unsubscribe_tests;
+
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Unsubscribe {
+    pub fixed_header: packets::FixedHeader,        // 3.10.1 UNSUBSCRIBE Fixed Header subsection
+    pub packet_identifier: packets::PacketIdentity, // 3.10.2 UNSUBSCRIBE Variable Header subsection
+    pub properties: packets::Properties,           // 3.10.2.1 UNSUBSCRIBE Properties subsection
+    pub topic_filters: Vec<UTF8EncodedString>,     // 3.10.3 UNSUBSCRIBE Payload subsection
+}
+
+impl Unsubscribe {
+    pub fn new(
+        fixed_header: packets::FixedHeader,
+        packet_identifier: packets::PacketIdentity,
+        properties: packets::Properties,
+        topic_filters: Vec<UTF8EncodedString>,
+    ) -> Result<Unsubscribe, errors::Error> {
+        // Bits 3,2,1 and 0 of the Fixed Header of the UNSUBSCRIBE packet are reserved and MUST be set to 0,0,1 and 0 [MQTT-3.10.1-1].
+        if fixed_header.flags != Bits(0b0010) {
+            return Err(errors::Error::MalformedPacket(
+                format!("The flags of the UNSUBSCRIBE Fixed Header are {:?}. It must be 0b0010", fixed_header.flags)
+            ));
+        }
+        // The Payload of an UNSUBSCRIBE packet MUST contain at least one Topic Filter [MQTT-3.10.3-2].
+        if topic_filters.is_empty() {
+            return Err(errors::Error::ProtocolError(
+                "UNSUBSCRIBE Packet has no Topic Filter".to_string()
+            ));
+        }
+
+        Ok(Unsubscribe {
+            fixed_header,
+            packet_identifier,
+            properties,
+            topic_filters,
+        })
+    }
+}
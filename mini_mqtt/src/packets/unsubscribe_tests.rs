@@ -0,0 +1,27 @@
+use super::*;
+use crate::packets::{FixedHeader, PacketIdentity, Properties, TwoByteInteger, VariableByteInteger};
+
+fn unsubscribe(flags: u8, topic_filters: &[&str]) -> Result<Unsubscribe, errors::Error> {
+    let fixed_header = FixedHeader::new(Bits(packets::UNSUBSCRIBE), Bits(flags), VariableByteInteger(0)).unwrap();
+    Unsubscribe::new(
+        fixed_header,
+        PacketIdentity::new(TwoByteInteger(1)),
+        Properties::new(),
+        topic_filters.iter().map(|topic_filter| UTF8EncodedString(topic_filter.to_string())).collect(),
+    )
+}
+
+#[test]
+fn unsubscribe_valid() {
+    assert!(unsubscribe(0b0010, &["a/b"]).is_ok());
+}
+
+#[test]
+fn unsubscribe_invalid_flags() {
+    assert!(matches!(unsubscribe(0b0000, &["a/b"]), Err(errors::Error::MalformedPacket(_))));
+}
+
+#[test]
+fn unsubscribe_without_topic_filter() {
+    assert!(matches!(unsubscribe(0b0010, &[]), Err(errors::Error::ProtocolError(_))));
+}
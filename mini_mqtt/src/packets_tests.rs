@@ -63,3 +63,24 @@ fn test_get_wrong_type() {
     let result = properties.get_as::<TwoByteInteger>(VariableByteInteger(0x01));
     assert!(result.is_err());
 }
+
+#[test]
+fn test_append_repeated_property() {
+    let mut properties = Properties::new();
+    properties.append(SUBSCRIPTION_IDENTIFIER, VariableByteInteger(1).into());
+    properties.append(SUBSCRIPTION_IDENTIFIER, VariableByteInteger(2).into());
+    assert_eq!(properties.len(), 2);
+    assert_eq!(properties.get_as::<VariableByteInteger>(SUBSCRIPTION_IDENTIFIER).unwrap(), Some(&VariableByteInteger(1)));
+    assert_eq!(
+        properties.get_all_as::<VariableByteInteger>(SUBSCRIPTION_IDENTIFIER).unwrap(),
+        vec![&VariableByteInteger(1), &VariableByteInteger(2)]
+    );
+    assert!(properties.get_all_as::<Bits>(SUBSCRIPTION_IDENTIFIER).is_err());
+    assert!(properties.get_all_as::<VariableByteInteger>(USER_PROPERTY).unwrap().is_empty());
+
+    // insert replaces every value.
+    properties.insert(SUBSCRIPTION_IDENTIFIER, VariableByteInteger(3).into());
+    assert_eq!(properties.len(), 1);
+    assert_eq!(properties.remove(&SUBSCRIPTION_IDENTIFIER), Some(VariableByteInteger(3).into()));
+    assert!(properties.is_empty());
+}
@@ -1,17 +1,30 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
 use chrono;
 
-use crate::{errors, packets};
+use crate::errors;
+use crate::packets;
+use crate::packets::ExtractValue;
 
+pub mod client_id_policy;
 pub mod handler;
+pub mod packet_identifier;
+pub mod queue;
+pub mod shared_subscription;
 
 // ClientId is the identifier of the client. This is defined by the MQTT v5.0 protocol.
 // You can confirm them at the 3.1.3.1 Client Identifier (ClientID) subsection.
 #[derive(Debug, Eq, PartialEq, Clone)]
-pub struct ClientId(String);
+pub struct ClientId(pub(crate) String);
 
 impl ClientId {
-    pub fn new(id: &str) -> Result<ClientId, errors::Error> {
-        packets::connect::validate_client_id(id)?;
+    // new validates the Client ID by the policy, with the User Name of the CONNECT Packet if it has been provided.
+    pub fn new(
+        id: &str,
+        user_name: Option<&str>,
+        policy: &client_id_policy::ClientIdPolicy,
+    ) -> Result<ClientId, errors::Error> {
+        policy.validate(id, user_name)?;
 
         Ok(ClientId(id.to_string()))
     }
@@ -45,8 +58,95 @@ pub enum SessionState {
     Disconnected,  // DISCONNECT Packet is received, or the TCP connection is closed.
 }
 
+// Subscription is a subscription of the session, the granted Subscription Options
+// and the Subscription Identifier which the SUBSCRIBE Packet has carried (3.8.2.1.2 Subscription Identifier subsection).
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Subscription {
+    pub options: packets::subscribe::SubscriptionOptions,
+    pub identifier: Option<u32>,
+}
+
+impl Subscription {
+    pub fn new(options: packets::subscribe::SubscriptionOptions, identifier: Option<u32>) -> Subscription {
+        Subscription { options, identifier }
+    }
+}
+
+// Delivery is a message which the Server sends to the Client.
+// shared_subscription is the Shared Subscription "$share/{ShareName}/{filter}" which the message is delivered by,
+// the message can be delivered to another session of the Shared Subscription instead.
+// expires_at is when the Message Expiry Interval of the message passes, None means the message does not expire.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Delivery {
+    pub publish: packets::publish::Publish,
+    pub shared_subscription: Option<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Delivery {
+    pub fn new(publish: packets::publish::Publish, shared_subscription: Option<String>) -> Delivery {
+        Delivery {
+            publish,
+            shared_subscription,
+            expires_at: None,
+        }
+    }
+
+    pub fn with_expires_at(self, expires_at: Option<chrono::DateTime<chrono::Utc>>) -> Delivery {
+        Delivery { expires_at, ..self }
+    }
+
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+
+    // publish_at results the PUBLISH Packet to send at the time. The Message Expiry Interval is the received value
+    // minus the time that the message has been waiting in the Server [MQTT-3.3.2-6], rounded up to keep it non-zero.
+    fn publish_at(&self, now: chrono::DateTime<chrono::Utc>) -> packets::publish::Publish {
+        let mut publish = self.publish.clone();
+        if let Some(expires_at) = self.expires_at {
+            let remaining = (expires_at - now).num_milliseconds().max(0) as u64;
+            let remaining = remaining.div_ceil(1000).min(u32::MAX as u64) as u32;
+            publish
+                .properties
+                .insert(packets::MESSAGE_EXPIRY_INTERVAL, packets::FourByteInteger(remaining).into());
+        }
+        publish
+    }
+}
+
+// queued_size results the size of the message which counts for the max_bytes of the queue limits.
+fn queued_size(publish: &packets::publish::Publish) -> usize {
+    publish.topic_name.val().len() + publish.payload.len()
+}
+
+// expires_at results when the Message Expiry Interval of the message received at the time passes.
+pub fn expires_at(
+    publish: &packets::publish::Publish,
+    received_at: chrono::DateTime<chrono::Utc>,
+) -> Option<chrono::DateTime<chrono::Utc>> {
+    publish
+        .message_expiry_interval()
+        .map(|interval| received_at + chrono::Duration::seconds(interval as i64))
+}
+
 // Session represents the session of the client.
 // It contains the client_id, the time when the TCP connection is established, the keep_alive time, and the state of the session.
+// protocol_version is the version negotiated by the CONNECT Packet, every later packet of the session is encoded as the version.
+// subscriptions are the Topic Filters which the Client has subscribed to, with the granted Subscription Options and the identifiers.
+// session_expiry_interval is the seconds which the Server keeps the session after the Network Connection is closed,
+// and disconnected_at is when it has been closed.
+// maximum_packet_size is the largest packet which the Client accepts, None means the protocol limit.
+// topic_alias_maximum is the highest Topic Alias which the Client accepts, 0 means no Topic Alias is sent to the Client.
+// receive_maximum is the number of QoS 1 and QoS 2 publications which the Client processes concurrently.
+// outbound_inflight are the QoS 1 and QoS 2 PUBLISH Packets sent to the Client and not acknowledged yet by the Packet Identifiers,
+// and outbound_pending are the queued ones waiting for the quota of the Receive Maximum or for the Client to resume the session,
+// within queue_limits.
+// outbound_released are the Packet Identifiers of the QoS 2 PUBLISH Packets in outbound_inflight which the Client has received,
+// the PUBREL Packets are sent for them and the PUBCOMP Packets are waited.
+// packet_identifiers issue the Packet Identifiers of the PUBLISH Packets sent to the Client, the ones in outbound_inflight are in use.
+// inbound_inflight are the Packet Identifiers of the QoS 2 PUBLISH Packets received from the Client and not completed yet.
+// will is the Will Message of the Network Connection, which is published unless the Client disconnects normally.
 // When the session state changes, you can get a new session instance by the change methods.
 #[derive(Debug, Eq, PartialEq, Clone)]
 pub struct Session {
@@ -54,17 +154,200 @@ pub struct Session {
     pub client_id: ClientId,
     pub tcp_connection_established_at: Option<chrono::DateTime<chrono::Utc>>,
     pub keep_alive: chrono::Duration,
+    pub protocol_version: packets::ProtocolVersion,
+    pub user_name: Option<String>,
+    pub subscriptions: HashMap<String, Subscription>,
+    pub session_expiry_interval: u32,
+    pub disconnected_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub maximum_packet_size: Option<u32>,
+    pub topic_alias_maximum: u16,
+    pub receive_maximum: u16,
+    pub outbound_inflight: BTreeMap<u16, Delivery>,
+    pub outbound_pending: VecDeque<Delivery>,
+    pub queue_limits: queue::QueueLimits,
+    pub outbound_released: BTreeSet<u16>,
+    pub inbound_inflight: BTreeSet<u16>,
+    pub packet_identifiers: packet_identifier::PacketIdentifierAllocator,
+    pub will: Option<Box<packets::publish::Publish>>,
     pub state: SessionState,
 }
 
 impl Session {
-    pub fn new(session_id: SessionId, client_id: ClientId, keep_alive: chrono::Duration) -> Session {
+    pub fn new(
+        session_id: SessionId,
+        client_id: ClientId,
+        keep_alive: chrono::Duration,
+        protocol_version: packets::ProtocolVersion,
+    ) -> Session {
         Session {
             session_id,
             client_id,
             tcp_connection_established_at: None,
             keep_alive,
+            protocol_version,
+            user_name: None,
+            subscriptions: HashMap::new(),
+            session_expiry_interval: 0,
+            disconnected_at: None,
+            maximum_packet_size: None,
+            topic_alias_maximum: 0,
+            receive_maximum: u16::MAX,
+            outbound_inflight: BTreeMap::new(),
+            outbound_pending: VecDeque::new(),
+            queue_limits: queue::QueueLimits::default(),
+            outbound_released: BTreeSet::new(),
+            inbound_inflight: BTreeSet::new(),
+            packet_identifiers: packet_identifier::PacketIdentifierAllocator::new(),
+            will: None,
+            state: SessionState::BeforeTcpConnectionEstablished,
+        }
+    }
+
+    // send_publish results the PUBLISH Packet to send to the Client now, with the Packet Identifier for QoS 1 and QoS 2.
+    // The Server MUST NOT send more QoS 1 and QoS 2 PUBLISH Packets than the Receive Maximum of the Client
+    // for which it has not received the acknowledgements [MQTT-3.3.4-9], so the message exceeding the quota is kept pending,
+    // as well as the message for which no Packet Identifier is free.
+    // The message whose Message Expiry Interval has passed before the onward delivery starts is discarded [MQTT-3.3.2-5].
+    // Once the PUBLISH Packet is sent, the message does not expire any more, it is sent again as it was.
+    // While the session is disconnected, the QoS 1 and QoS 2 messages are queued and the QoS 0 messages are discarded.
+    pub fn send_publish(&mut self, delivery: Delivery) -> Option<packets::publish::Publish> {
+        let now = chrono::Utc::now();
+        if delivery.is_expired(now) {
+            return None;
+        }
+        if delivery.publish.qos() == packets::QoS::AtMostOnce {
+            if self.state == SessionState::Disconnected {
+                return None;
+            }
+            return Some(delivery.publish_at(now));
+        }
+        if self.queues() {
+            self.enqueue(delivery, now);
+            return None;
+        }
+        let Some(packet_identifier) = self.packet_identifiers.allocate() else {
+            self.enqueue(delivery, now);
+            return None;
+        };
+
+        let mut publish = delivery.publish_at(now);
+        let delivery = Delivery {
+            publish: publish.clone(),
+            ..delivery
+        };
+        publish.packet_identifier = Some(packet_identifier.clone());
+        self.outbound_inflight.insert(packet_identifier.val(), delivery);
+        Some(publish)
+    }
+
+    // queues results whether the QoS 1 and QoS 2 messages wait in the queue instead of being sent now.
+    fn queues(&self) -> bool {
+        self.state == SessionState::Disconnected
+            || self.outbound_inflight.len() >= self.receive_maximum as usize
+            || self.packet_identifiers.is_exhausted()
+    }
+
+    // queue_overflows results whether the message delivered with the QoS would exceed the queue limits.
+    pub fn queue_overflows(&self, qos: packets::QoS, publish: &packets::publish::Publish) -> bool {
+        if qos == packets::QoS::AtMostOnce || !self.queues() {
+            return false;
+        }
+        let bytes: usize = self.outbound_pending.iter().map(|pending| queued_size(&pending.publish)).sum();
+        self.outbound_pending.len() >= self.queue_limits.max_messages
+            || bytes + queued_size(publish) > self.queue_limits.max_bytes
+    }
+
+    // enqueue keeps the message pending, and the expired messages are dropped from the pending ones at the time.
+    // When the queue is full, the overflow policy drops the oldest messages or the new one.
+    // As a note, reject_publisher has rejected the message before, unless it is delivered again from another session.
+    fn enqueue(&mut self, delivery: Delivery, now: chrono::DateTime<chrono::Utc>) {
+        self.outbound_pending.retain(|pending| !pending.is_expired(now));
+        let limits = self.queue_limits;
+        let size = queued_size(&delivery.publish);
+        if limits.max_messages == 0 || size > limits.max_bytes {
+            return;
+        }
+        let mut bytes: usize = self.outbound_pending.iter().map(|pending| queued_size(&pending.publish)).sum();
+        let overflows = |length: usize, bytes: usize| length >= limits.max_messages || bytes + size > limits.max_bytes;
+        if overflows(self.outbound_pending.len(), bytes) {
+            if limits.overflow_policy != queue::OverflowPolicy::DropOldest {
+                return;
+            }
+            while overflows(self.outbound_pending.len(), bytes) {
+                let Some(oldest) = self.outbound_pending.pop_front() else {
+                    break;
+                };
+                bytes -= queued_size(&oldest.publish);
+            }
+        }
+        self.outbound_pending.push_back(delivery);
+    }
+
+    // acknowledge completes the delivery of the PUBLISH Packet, which frees the quota of the Receive Maximum.
+    // It results the pending PUBLISH Packets which can be sent now.
+    pub fn acknowledge(&mut self, packet_identifier: u16) -> Vec<packets::publish::Publish> {
+        if self.outbound_inflight.remove(&packet_identifier).is_some() {
+            self.packet_identifiers.release(packet_identifier);
+        }
+        self.outbound_released.remove(&packet_identifier);
+        self.release_pending()
+    }
+
+    // release_pending results the queued PUBLISH Packets which can be sent now, e.g. when the Client has resumed the session.
+    pub fn release_pending(&mut self) -> Vec<packets::publish::Publish> {
+        let mut publishes = Vec::new();
+        while self.state != SessionState::Disconnected
+            && self.outbound_inflight.len() < self.receive_maximum as usize && !self.packet_identifiers.is_exhausted() {
+            let Some(delivery) = self.outbound_pending.pop_front() else {
+                break;
+            };
+            publishes.extend(self.send_publish(delivery));
+        }
+        publishes
+    }
+
+    // release records that the Client has received the QoS 2 PUBLISH Packet by the PUBREC Packet.
+    // The PUBLISH Packet is not sent again after that, the PUBREL Packet is sent instead until the PUBCOMP Packet is received
+    // [MQTT-4.3.3-4] [MQTT-4.3.3-6]. It results false for the Packet Identifier which is not in flight.
+    pub fn release(&mut self, packet_identifier: u16) -> bool {
+        if !self.outbound_inflight.contains_key(&packet_identifier) {
+            return false;
+        }
+        self.outbound_released.insert(packet_identifier);
+        true
+    }
+
+    // retransmissions results the packets to send again when the Client resumes the session.
+    // The unacknowledged PUBLISH Packets are sent with the DUP flag and the PUBREL Packets for the released ones,
+    // with their original Packet Identifiers [MQTT-4.4.0-1] [MQTT-3.3.1-1].
+    pub fn retransmissions(&self) -> Vec<packets::Packet> {
+        self.outbound_inflight
+            .iter()
+            .map(|(packet_identifier, delivery)| {
+                let packet_identifier = packets::PacketIdentity::new(packets::TwoByteInteger(*packet_identifier));
+                if self.outbound_released.contains(&packet_identifier.val()) {
+                    let pubrel = packets::pubrel::PubRel::new(packet_identifier, packets::pubrel::SUCCESS, packets::Properties::new());
+                    return packets::Packet::PubRel(pubrel);
+                }
+                let mut publish = delivery.publish.clone();
+                publish.fixed_header = packets::publish::Publish::fixed_header(true, publish.qos(), publish.retain());
+                publish.packet_identifier = Some(packet_identifier);
+                packets::Packet::Publish(publish)
+            })
+            .collect()
+    }
+
+    // resumed results the session which a new Network Connection of the Client continues with Clean Start 0.
+    // The subscriptions and the messages in flight are kept, the Network Connection specific ones are renewed.
+    pub fn resumed(&self, keep_alive: chrono::Duration, protocol_version: packets::ProtocolVersion) -> Session {
+        Session {
+            tcp_connection_established_at: None,
+            keep_alive,
+            protocol_version,
+            disconnected_at: None,
+            will: None,
             state: SessionState::BeforeTcpConnectionEstablished,
+            ..self.clone()
         }
     }
 
@@ -106,7 +389,19 @@ impl Session {
     pub fn disconnected(&self) -> Session {
         Session {
             state: SessionState::Disconnected,
+            disconnected_at: Some(chrono::Utc::now()),
             ..self.clone()
         }
     }
+
+    // is_expired results whether the Session Expiry Interval has passed since the Network Connection was closed.
+    // The interval 0xFFFFFFFF means that the session does not expire (3.1.2.11.2 Session Expiry Interval subsection).
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        match self.disconnected_at {
+            Some(disconnected_at) if self.session_expiry_interval != u32::MAX => {
+                disconnected_at + chrono::Duration::seconds(self.session_expiry_interval as i64) <= now
+            }
+            _ => false,
+        }
+    }
 }
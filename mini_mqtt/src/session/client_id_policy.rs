@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use crate::errors;
+use crate::packets::connect::ConnectError;
+
+#[path = "client_id_policy_tests.rs"]
+#[cfg(test)]
+mod client_id_policy_tests;
+
+// The Server MUST allow ClientIDs which are between 1 and 23 UTF-8 encoded bytes in length, and that contain
+// only the alphanumeric characters [MQTT-3.1.3-5]. The Server MAY allow longer ClientIDs and other characters.
+// Look the 3.1.3.1 Client Identifier (ClientID) subsection for more details.
+pub const DEFAULT_MAX_LENGTH: usize = 23;
+
+// AllowedCharacters is the set of the characters which a ClientID can contain.
+#[derive(Debug, Clone)]
+pub enum AllowedCharacters {
+    // "0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ"
+    Alphanumeric,
+    // The alphanumeric characters and the provided characters. e.g. "-_:" allows "sensor-01".
+    AlphanumericAnd(String),
+    // The whole ClientID must match the pattern.
+    Pattern(regex::Regex),
+}
+
+impl AllowedCharacters {
+    // pattern compiles the provided regular expression anchored at the both ends of the ClientID.
+    pub fn pattern(pattern: &str) -> Result<AllowedCharacters, errors::Error> {
+        regex::Regex::new(&format!("^(?:{})$", pattern))
+            .map(AllowedCharacters::Pattern)
+            .map_err(|err| errors::Error::Common(format!("Invalid ClientID pattern {}: {}", pattern, err)))
+    }
+
+    fn validate(&self, client_id: &str) -> Result<(), ConnectError> {
+        match self {
+            AllowedCharacters::Alphanumeric => validate_characters(client_id, ""),
+            AllowedCharacters::AlphanumericAnd(extra) => validate_characters(client_id, extra),
+            AllowedCharacters::Pattern(pattern) => {
+                if pattern.is_match(client_id) {
+                    Ok(())
+                } else {
+                    Err(ConnectError::client_identifier_not_valid(
+                        &format!("Client ID {} does not match the pattern {}", client_id, pattern.as_str())
+                    ))
+                }
+            }
+        }
+    }
+}
+
+fn validate_characters(client_id: &str, extra: &str) -> Result<(), ConnectError> {
+    for c in client_id.chars() {
+        if !c.is_ascii_alphanumeric() && !extra.contains(c) {
+            return Err(ConnectError::client_identifier_not_valid(
+                &format!("Client ID contains not allowed character: {}", c)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+// ClientIdPolicy is the rule of the ClientIDs which the Server accepts.
+// The default policy accepts only the ClientIDs which the specification requires the Server to allow.
+// tenant_prefixes maps a User Name to the prefix which the ClientIDs of the tenant are required to start with.
+#[derive(Debug, Clone)]
+pub struct ClientIdPolicy {
+    pub max_length: usize,
+    pub allowed_characters: AllowedCharacters,
+    pub tenant_prefixes: HashMap<String, String>,
+}
+
+impl Default for ClientIdPolicy {
+    fn default() -> ClientIdPolicy {
+        ClientIdPolicy {
+            max_length: DEFAULT_MAX_LENGTH,
+            allowed_characters: AllowedCharacters::Alphanumeric,
+            tenant_prefixes: HashMap::new(),
+        }
+    }
+}
+
+impl ClientIdPolicy {
+    pub fn new(max_length: usize, allowed_characters: AllowedCharacters) -> ClientIdPolicy {
+        ClientIdPolicy {
+            max_length,
+            allowed_characters,
+            tenant_prefixes: HashMap::new(),
+        }
+    }
+
+    pub fn with_tenant_prefix(mut self, user_name: &str, prefix: &str) -> ClientIdPolicy {
+        self.tenant_prefixes.insert(user_name.to_string(), prefix.to_string());
+        self
+    }
+
+    // validate the Client ID. The user_name is the User Name of the CONNECT Packet if it has been provided,
+    // the required prefix is checked only when the User Name is a tenant of the policy.
+    // The zero-length Client ID is handled by the connect::validate function, so this function rejects it.
+    pub fn validate(&self, client_id: &str, user_name: Option<&str>) -> Result<(), ConnectError> {
+        let len = client_id.len();
+        if !(1..=self.max_length).contains(&len) {
+            return Err(ConnectError::client_identifier_not_valid(
+                &format!("Client ID length is not between 1 and {}. It is {}", self.max_length, len)
+            ));
+        }
+
+        self.allowed_characters.validate(client_id)?;
+
+        if let Some(prefix) = user_name.and_then(|name| self.tenant_prefixes.get(name)) {
+            if !client_id.starts_with(prefix.as_str()) {
+                return Err(ConnectError::client_identifier_not_valid(
+                    &format!("Client ID {} does not start with the prefix {} of the tenant", client_id, prefix)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
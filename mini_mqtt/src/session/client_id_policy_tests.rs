@@ -0,0 +1,74 @@
+use super::*;
+use crate::packets::connack;
+
+#[test]
+fn default_policy_accepts_alphanumeric_client_id() {
+    let policy = ClientIdPolicy::default();
+    assert!(policy.validate("client1", None).is_ok());
+    assert!(policy.validate("a".repeat(23).as_str(), None).is_ok());
+}
+
+#[test]
+fn default_policy_rejects_zero_length_client_id() {
+    let result = ClientIdPolicy::default().validate("", None);
+    assert_eq!(result.unwrap_err().reason_code, connack::CLIENT_IDENTIFIER_NOT_VALID);
+}
+
+#[test]
+fn default_policy_rejects_too_long_client_id() {
+    let result = ClientIdPolicy::default().validate("a".repeat(24).as_str(), None);
+    assert_eq!(result.unwrap_err().reason_code, connack::CLIENT_IDENTIFIER_NOT_VALID);
+}
+
+#[test]
+fn default_policy_rejects_non_alphanumeric_client_id() {
+    let result = ClientIdPolicy::default().validate("client_id!", None);
+    assert!(result.is_err());
+}
+
+#[test]
+fn max_length_is_configurable() {
+    let policy = ClientIdPolicy::new(64, AllowedCharacters::Alphanumeric);
+    assert!(policy.validate("a".repeat(64).as_str(), None).is_ok());
+    assert!(policy.validate("a".repeat(65).as_str(), None).is_err());
+}
+
+#[test]
+fn alphanumeric_and_allows_extra_characters() {
+    let policy = ClientIdPolicy::new(23, AllowedCharacters::AlphanumericAnd("-_".to_string()));
+    assert!(policy.validate("sensor-01", None).is_ok());
+    assert!(policy.validate("sensor_01", None).is_ok());
+    assert!(policy.validate("sensor:01", None).is_err());
+}
+
+#[test]
+fn pattern_must_match_whole_client_id() {
+    let policy = ClientIdPolicy::new(64, AllowedCharacters::pattern("sensor-[0-9]+").unwrap());
+    assert!(policy.validate("sensor-01", None).is_ok());
+    assert!(policy.validate("my-sensor-01", None).is_err());
+    assert!(policy.validate("sensor-01a", None).is_err());
+}
+
+#[test]
+fn invalid_pattern() {
+    assert!(AllowedCharacters::pattern("sensor-[0-9").is_err());
+}
+
+#[test]
+fn tenant_prefix_is_required_for_tenant() {
+    let policy = ClientIdPolicy::new(23, AllowedCharacters::AlphanumericAnd("-".to_string()))
+        .with_tenant_prefix("acme", "acme-");
+    assert!(policy.validate("acme-sensor01", Some("acme")).is_ok());
+    assert!(policy.validate("sensor01", Some("acme")).is_err());
+    // The prefix is not required for the other users, and when the User Name is not provided.
+    assert!(policy.validate("sensor01", Some("other")).is_ok());
+    assert!(policy.validate("sensor01", None).is_ok());
+}
+
+#[test]
+fn client_id_requires_tenant_prefix_of_user_name() {
+    let policy = ClientIdPolicy::new(23, AllowedCharacters::AlphanumericAnd("-".to_string()))
+        .with_tenant_prefix("acme", "acme-");
+    assert!(crate::session::ClientId::new("acme-sensor01", Some("acme"), &policy).is_ok());
+    assert!(crate::session::ClientId::new("sensor01", Some("acme"), &policy).is_err());
+}
@@ -1,33 +1,1228 @@
+use std::collections::BTreeMap;
 use std::sync::{Arc};
+use crate::acl;
+use crate::auth;
+use crate::config;
+use crate::errors;
+use crate::packets::connack::{self, ConnAck};
+use crate::packets::connect::{self, Connect};
+use crate::packets::disconnect::{self, Disconnect};
+use crate::packets::puback::{self, PubAck};
+use crate::packets::pubcomp::{self, PubComp};
+use crate::packets::pubrec::{self, PubRec};
+use crate::packets::pubrel::{self, PubRel};
+use crate::packets::publish::Publish;
+use crate::packets::suback::{self, SubAck, SubAckReasonCode};
+use crate::packets::subscribe::{Subscribe, SubscriptionOptions};
+use crate::packets::unsuback::{self, UnsubAck};
+use crate::packets::unsubscribe::Unsubscribe;
+use crate::packets;
+use crate::packets::{Bits, ExtractValue, QoS, UTF8EncodedString};
 use crate::session;
+use crate::session::queue::OverflowPolicy;
+use crate::session::shared_subscription::Balancer;
+use crate::store;
+use crate::topic;
+
+#[path = "handler_tests.rs"]
+#[cfg(test)]
+mod handler_tests;
+
+// Target is a session which receives a message, with the QoS, the RETAIN flag and the Subscription Identifiers
+// which its matching subscriptions deliver the message with.
+#[derive(Debug)]
+struct Target {
+    session_id: session::SessionId,
+    qos: QoS,
+    retain: bool,
+    subscription_identifiers: Vec<u32>,
+    shared_subscription: Option<String>,
+}
+
+// Outgoing is a packet which the Server sends to the Client of the session.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Outgoing {
+    pub session_id: session::SessionId,
+    pub packet: packets::Packet,
+}
+
+impl Outgoing {
+    pub fn new(session_id: session::SessionId, packet: packets::Packet) -> Outgoing {
+        Outgoing { session_id, packet }
+    }
+}
 
 pub struct Handler {
+    config: config::Config,
     session_id_counter: u32,
     sessions : std::collections::HashMap<session::SessionId, session::Session>,
+    balancer: Balancer,
+    retained: BTreeMap<String, store::Retained>, // The retained messages by the Topic Names.
 }
 
 impl Handler {
     pub fn new() -> Arc<std::sync::RwLock<Handler>> {
+        Handler::with_config(config::Config::default())
+    }
+
+    pub fn with_config(config: config::Config) -> Arc<std::sync::RwLock<Handler>> {
         Arc::new(std::sync::RwLock::new(Handler {
+            balancer: Balancer::new(config.shared_subscription_strategy),
+            config,
             session_id_counter: 0,
             sessions: std::collections::HashMap::new(),
+            retained: BTreeMap::new(),
         }))
     }
 
+    pub fn config(&self) -> &config::Config {
+        &self.config
+    }
+
+    // restore loads the persistent sessions and the retained messages from the configured Store when the Server starts.
+    // The sessions are restored as disconnected with new session ids, and their Clients resume them by the ClientIDs.
+    // The ones whose Session Expiry Interval or Message Expiry Interval has passed meanwhile are discarded.
+    pub fn restore(&mut self) -> Result<(), errors::Error> {
+        let state = self.config.store.load()?;
+        let now = chrono::Utc::now();
+        for (_, mut session) in state.sessions {
+            // The session whose Network Connection was open has been disconnected by the stop of the Server.
+            session.disconnected_at.get_or_insert(now);
+            session.state = session::SessionState::Disconnected;
+            self.increment_session_id_counter();
+            session.session_id = session::SessionId(self.session_id_counter);
+            session.queue_limits = self.config.queue_limits;
+            self.sessions.insert(session.session_id.clone(), session);
+        }
+        self.expire_sessions(now)?;
+        for (topic_name, retained) in state.retained {
+            if retained.is_expired(now) {
+                self.config.store.remove_retained(&topic_name)?;
+                continue;
+            }
+            self.retained.insert(topic_name, retained);
+        }
+
+        Ok(())
+    }
+
+    // expire_sessions ends the disconnected sessions whose Session Expiry Interval has passed (3.1.2.11.2 Session Expiry Interval subsection),
+    // and removes the persistent ones from the configured Store.
+    pub fn expire_sessions(&mut self, now: chrono::DateTime<chrono::Utc>) -> Result<(), errors::Error> {
+        let expired: Vec<session::SessionId> = self
+            .sessions
+            .values()
+            .filter(|session| session.state == session::SessionState::Disconnected && session.is_expired(now))
+            .map(|session| session.session_id.clone())
+            .collect();
+        for session_id in expired {
+            let Some(session) = self.sessions.remove(&session_id) else {
+                continue;
+            };
+            if session.session_expiry_interval > 0 {
+                self.config.store.remove_session(session.client_id.as_str())?;
+            }
+        }
+        Ok(())
+    }
+
+
     fn increment_session_id_counter(&mut self) {
         self.session_id_counter += 1;
     }
 
-    pub fn create_session(&mut self, client_id: &session::ClientId, keep_alive: chrono::Duration) -> session::Session {
+    pub fn create_session(
+        &mut self,
+        client_id: &session::ClientId,
+        keep_alive: chrono::Duration,
+        protocol_version: packets::ProtocolVersion,
+    ) -> session::Session {
         self.increment_session_id_counter();
 
         let session_id = session::SessionId(self.session_id_counter);
-        let session = session::Session::new(session_id.clone(), client_id.clone(), keep_alive);
+        let session = session::Session::new(session_id.clone(), client_id.clone(), keep_alive, protocol_version);
         self.sessions.insert(session_id, session.clone());
 
         session
     }
 
+    // handle_connect validates the received CONNECT Packet, authenticates the Client by the configured AuthProvider,
+    // and creates the session for the Client.
+    // The session keeps the negotiated protocol version to encode the later packets.
+    // It results the CONNACK Packet to answer with. When the CONNECT Packet is rejected,
+    // the CONNACK Packet carries the Reason Code and the Reason String of the first failure.
+    pub fn handle_connect(&mut self, connect: &Connect) -> Result<(session::Session, ConnAck), ConnAck> {
+        self.handle_connect_from(connect, &auth::Peer::default())
+            .map(|(session, connack, _)| (session, connack))
+    }
+
+    // handle_connect_from handles the CONNECT Packet with what the transport knows about the Client.
+    // When the transport has verified the identity, e.g. by the TLS client certificate, the identity replaces
+    // the ClientID and the User Name of the CONNECT Packet, so the ACL applies to the verified identity,
+    // and the configured AuthProvider is skipped.
+    // Otherwise the AuthProvider authenticates the Client, with the peer credentials of a local transport.
+    // It also results the packets to the other sessions, i.e. the DISCONNECT Packet to the session which is taken over
+    // and its Will Message.
+    pub fn handle_connect_from(
+        &mut self,
+        connect: &Connect,
+        peer: &auth::Peer,
+    ) -> Result<(session::Session, ConnAck, Vec<Outgoing>), ConnAck> {
+        let identified;
+        let connect = match &peer.identity {
+            Some(identity) => {
+                identified = identity.apply(connect);
+                &identified
+            }
+            None => connect,
+        };
+        if let Err(errors) = connect::validate(connect, &self.config) {
+            return Err(ConnAck::rejected(&errors[0]));
+        }
+
+        if peer.identity.is_none() {
+            let credentials = auth::Credentials::new(
+                connect.payload.client_id.val(),
+                connect.payload.user_name.as_ref().map(|user_name| user_name.val()),
+                connect.payload.password.as_ref().map(|password| password.val().as_slice()),
+            )
+            .with_peer_credentials(peer.credentials.as_ref());
+            if let Err(err) = self.config.auth_provider.authenticate(&credentials) {
+                return Err(ConnAck::rejected(&err));
+            }
+        }
+
+        let mut connack = ConnAck::default();
+        let client_id = if connect.payload.client_id.val().is_empty() {
+            let client_id = self.assign_client_id().map_err(|err| {
+                ConnAck::rejected(&connect::ConnectError::new(connack::UNSPECIFIED_ERROR, &err.to_string()))
+            })?;
+            connack.properties.insert(
+                packets::ASSIGNED_CLIENT_IDENTIFIER,
+                UTF8EncodedString(client_id.as_str().to_string()).into(),
+            );
+            client_id
+        } else {
+            // The ClientID has been validated by connect::validate with the User Name.
+            session::ClientId(connect.payload.client_id.val().to_string())
+        };
+        // The version has been validated, so the fallback is never used.
+        let protocol_version = connect.protocol_version().unwrap_or(packets::ProtocolVersion::V5);
+        if protocol_version.has_properties() {
+            self.capabilities(&mut connack);
+            self.response_information(connect, &client_id, &mut connack);
+        }
+        // The Client MUST use the Server Keep Alive instead of the Keep Alive it has sent [MQTT-3.2.2-21].
+        // The older versions have no Server Keep Alive, so the requested one is used.
+        let keep_alive = match self.config.server_keep_alive {
+            Some(server_keep_alive) if protocol_version.has_properties() => server_keep_alive,
+            _ => connect.variable_header.keep_alive.val(),
+        };
+        let keep_alive = chrono::Duration::seconds(keep_alive as i64);
+        let clean_start = connect.variable_header.connect_flags.clean_start();
+        if let Err(err) = self.expire_sessions(chrono::Utc::now()) {
+            return Err(ConnAck::rejected(&connect::ConnectError::new(
+                packets::connack::UNSPECIFIED_ERROR,
+                &format!("Failed to store the session: {}", err),
+            )));
+        }
+        let outgoings = self.take_over_sessions(&client_id);
+        let (resumed, discarded) = self.resume_session(&client_id, clean_start);
+        let mut session = match resumed {
+            Some(previous) => {
+                connack.session_present = true;
+                previous.resumed(keep_alive, protocol_version)
+            }
+            None => self.create_session(&client_id, keep_alive, protocol_version),
+        };
+        session.user_name = connect.payload.user_name.as_ref().map(|user_name| user_name.val().to_string());
+        session.will = connect.will_message().map(Box::new);
+        session.session_expiry_interval = self.session_expiry_interval(connect, &mut connack);
+        // The Server MUST NOT send packets exceeding the Maximum Packet Size to the Client [MQTT-3.1.2-24].
+        session.maximum_packet_size = connect
+            .variable_header
+            .properties
+            .get_as::<packets::FourByteInteger>(packets::MAXIMUM_PACKET_SIZE)
+            .ok()
+            .flatten()
+            .map(|maximum_packet_size| maximum_packet_size.val());
+        // The absent Topic Alias Maximum means 0 (3.1.2.11.5 Topic Alias Maximum subsection).
+        session.topic_alias_maximum = connect
+            .variable_header
+            .properties
+            .get_as::<packets::TwoByteInteger>(packets::TOPIC_ALIAS_MAXIMUM)
+            .ok()
+            .flatten()
+            .map(|topic_alias_maximum| topic_alias_maximum.val())
+            .unwrap_or(0);
+        // The absent Receive Maximum means 65,535 (3.1.2.11.3 Receive Maximum subsection).
+        session.receive_maximum = connect
+            .variable_header
+            .properties
+            .get_as::<packets::TwoByteInteger>(packets::RECEIVE_MAXIMUM)
+            .ok()
+            .flatten()
+            .map(|receive_maximum| receive_maximum.val())
+            .unwrap_or(u16::MAX);
+        session.queue_limits = self.config.queue_limits;
+        // The previous session which has been discarded, or which ends with this Network Connection, is removed from the Store.
+        let stored = if session.session_expiry_interval > 0 {
+            self.config.store.save_session(&session)
+        } else if discarded {
+            self.config.store.remove_session(client_id.as_str())
+        } else {
+            Ok(())
+        };
+        if let Err(err) = stored {
+            self.sessions.remove(&session.session_id);
+            return Err(ConnAck::rejected(&connect::ConnectError::new(
+                packets::connack::UNSPECIFIED_ERROR,
+                &format!("Failed to store the session: {}", err),
+            )));
+        }
+        self.update_session(session.clone());
+
+        Ok((session, connack, outgoings))
+    }
+
+    // take_over_sessions disconnects the session of the ClientID whose Network Connection is still open.
+    // The Server sends the DISCONNECT Packet with the Reason Code 0x8E (Session taken over) to the existing Client,
+    // and MUST close its Network Connection [MQTT-3.1.4-3]. The Will Message of the existing Client is published then.
+    // The session is kept as disconnected under a new session id, so that the closing connection no longer reaches it,
+    // and the new Network Connection resumes or discards it by the Clean Start.
+    fn take_over_sessions(&mut self, client_id: &session::ClientId) -> Vec<Outgoing> {
+        let connected: Vec<session::SessionId> = self
+            .sessions
+            .values()
+            .filter(|session| session.client_id == *client_id && session.state != session::SessionState::Disconnected)
+            .map(|session| session.session_id.clone())
+            .collect();
+
+        let mut outgoings = Vec::new();
+        for session_id in connected {
+            let disconnect = Disconnect::with_reason(disconnect::SESSION_TAKEN_OVER, "Another Client has connected with the ClientID");
+            outgoings.push(Outgoing::new(session_id.clone(), packets::Packet::Disconnect(disconnect)));
+            let will = self.sessions.get_mut(&session_id).and_then(|session| session.will.take());
+            if let Some(will) = will {
+                match self.publish_will(&session_id, &will) {
+                    Ok(published) => outgoings.extend(published),
+                    Err(err) => log::error!("Failed to publish the Will Message of the session {:?}: {}", session_id, err),
+                }
+            }
+            let Some(session) = self.sessions.remove(&session_id) else {
+                continue;
+            };
+            let mut session = session.disconnected();
+            self.increment_session_id_counter();
+            session.session_id = session::SessionId(self.session_id_counter);
+            self.sessions.insert(session.session_id.clone(), session);
+        }
+        outgoings
+    }
+
+    // resume_session results the existing session of the ClientID which the Client continues (3.1.2.4 Clean Start subsection).
+    // With Clean Start 1 the existing session is discarded, and with Clean Start 0 it is resumed
+    // unless it has ended, i.e. its Session Expiry Interval was 0 when the Network Connection was closed [MQTT-3.1.2-4] [MQTT-3.1.2-5].
+    // It also results whether a previous session has been in the Store, i.e. its Session Expiry Interval was not 0.
+    fn resume_session(&mut self, client_id: &session::ClientId, clean_start: bool) -> (Option<session::Session>, bool) {
+        let previous: Vec<session::SessionId> = self
+            .sessions
+            .values()
+            .filter(|session| session.client_id == *client_id && session.state == session::SessionState::Disconnected)
+            .map(|session| session.session_id.clone())
+            .collect();
+
+        let mut resumed = None;
+        let mut stored = false;
+        for session_id in previous {
+            let Some(session) = self.sessions.remove(&session_id) else {
+                continue;
+            };
+            stored |= session.session_expiry_interval > 0;
+            if !clean_start && session.session_expiry_interval > 0 && resumed.is_none() {
+                resumed = Some(session);
+            }
+        }
+        (resumed, stored)
+    }
+
+    // capabilities sets the configured capabilities of the Server to the CONNACK Properties (3.2.2.3 CONNACK Properties subsection).
+    // Each property is sent only when the configuration differs from the value which its absence means,
+    // e.g. the absent Maximum QoS means QoS 2 is supported, and the absent Receive Maximum means 65,535.
+    // As a note, the Maximum QoS 2 must not be sent, the Client would treat it as a Protocol Error.
+    fn capabilities(&self, connack: &mut ConnAck) {
+        let config = &self.config;
+        let properties = &mut connack.properties;
+        if !config.maximum_qos.exceeds(QoS::AtLeastOnce) {
+            properties.insert(packets::MAXIMUM_QOS, config.maximum_qos.bits().into());
+        }
+        if !config.retain_available {
+            properties.insert(packets::RETAIN_AVAILABLE, Bits(0).into());
+        }
+        if let Some(maximum_packet_size) = config.maximum_packet_size {
+            properties.insert(packets::MAXIMUM_PACKET_SIZE, packets::FourByteInteger(maximum_packet_size).into());
+        }
+        if config.topic_alias_maximum > 0 {
+            properties.insert(packets::TOPIC_ALIAS_MAXIMUM, packets::TwoByteInteger(config.topic_alias_maximum).into());
+        }
+        if !config.wildcard_subscription_available {
+            properties.insert(packets::WILDCARD_SUBSCRIPTION_AVAILABLE, Bits(0).into());
+        }
+        if !config.subscription_identifier_available {
+            properties.insert(packets::SUBSCRIPTION_IDENTIFIER_AVAILABLE, Bits(0).into());
+        }
+        if !config.shared_subscription_available {
+            properties.insert(packets::SHARED_SUBSCRIPTION_AVAILABLE, Bits(0).into());
+        }
+        if config.receive_maximum < u16::MAX {
+            properties.insert(packets::RECEIVE_MAXIMUM, packets::TwoByteInteger(config.receive_maximum).into());
+        }
+        if let Some(server_keep_alive) = config.server_keep_alive {
+            properties.insert(packets::SERVER_KEEP_ALIVE, packets::TwoByteInteger(server_keep_alive).into());
+        }
+    }
+
+    // session_expiry_interval results the Session Expiry Interval of the session in seconds.
+    // If the Session Expiry Interval is absent the value 0 is used (3.1.2.11.2 Session Expiry Interval subsection).
+    // When the configured maximum shortens the requested interval, the CONNACK Packet carries the interval
+    // which the Server uses (3.2.2.3.2 Session Expiry Interval subsection).
+    fn session_expiry_interval(&self, connect: &Connect, connack: &mut ConnAck) -> u32 {
+        let requested = connect
+            .variable_header
+            .properties
+            .get_as::<packets::FourByteInteger>(packets::SESSION_EXPIRY_INTERVAL)
+            .ok()
+            .flatten()
+            .map(|interval| interval.val())
+            .unwrap_or(0);
+        // MQTT v3.1.1 and v3.1 have no Session Expiry Interval, the session of Clean Session 0 never expires,
+        // which is the interval 0xFFFFFFFF of MQTT v5.0.
+        let requested = match connect.protocol_version() {
+            Some(protocol_version) if !protocol_version.has_properties() && !connect.variable_header.connect_flags.clean_start() => u32::MAX,
+            _ => requested,
+        };
+        match self.config.session_expiry_interval_maximum {
+            Some(maximum) if requested > maximum => {
+                connack
+                    .properties
+                    .insert(packets::SESSION_EXPIRY_INTERVAL, packets::FourByteInteger(maximum).into());
+                maximum
+            }
+            _ => requested,
+        }
+    }
+
+    // response_information sets the Response Information to the CONNACK Properties when the Client has requested it
+    // by the Request Response Information 1 (3.2.2.3.15 Response Information subsection).
+    // It is the configured prefix followed by the ClientID, which the Client uses as the basis of its Response Topics.
+    // The Server may send none even the Client requests it, so it is not sent unless the prefix is configured,
+    // nor when it would not be a valid Topic Name, e.g. the ClientID contains the wildcard characters.
+    fn response_information(&self, connect: &Connect, client_id: &session::ClientId, connack: &mut ConnAck) {
+        let requested = connect
+            .variable_header
+            .properties
+            .get_as::<Bits>(packets::REQUEST_RESPONSE_INFORMATION)
+            .ok()
+            .flatten()
+            .is_some_and(|request_response_information| request_response_information.val() == 1);
+        let Some(prefix) = self.config.response_information_prefix.as_ref().filter(|_| requested) else {
+            return;
+        };
+        let response_information = format!("{}{}", prefix, client_id.as_str());
+        if topic::validate_topic_name(&response_information).is_ok() {
+            connack
+                .properties
+                .insert(packets::RESPONSE_INFORMATION, UTF8EncodedString(response_information).into());
+        }
+    }
+
+    // handle_subscribe stores the subscriptions of the SUBSCRIBE Packet to the session.
+    // Each Topic Filter is authorized by the configured ACL, the denied one results the Reason Code 0x87 (Not authorized).
+    // The granted QoS is downgraded to the configured Maximum QoS.
+    // The Topic Filters which need the unsupported capabilities result the Reason Codes 0x9E (Shared Subscriptions not supported)
+    // and 0xA2 (Wildcard Subscriptions not supported), and the Subscription Identifier without the support
+    // results 0xA1 (Subscription Identifiers not supported) for every Topic Filter.
+    // The Shared Subscription is authorized and checked by its Topic Filter after the ShareName.
+    // The Subscription Identifier is kept with each subscription, to be sent with the matching messages.
+    // It results the SUBACK Packet, and the retained messages which the Retain Handling option requests to send:
+    // 0 sends them at every subscribe, 1 only when the subscription does not exist yet, and 2 never (3.8.3.1 Subscription Options subsection).
+    // The retained messages are not sent to the Shared Subscriptions (4.8.2 Shared Subscriptions subsection).
+    pub fn handle_subscribe(
+        &mut self,
+        session_id: &session::SessionId,
+        subscribe: &Subscribe,
+    ) -> Result<(SubAck, Vec<Outgoing>), errors::Error> {
+        let session = self.sessions.get(session_id)
+            .ok_or_else(|| errors::Error::Common(format!("Session {:?} is not found", session_id)))?;
+
+        let mut granted = Vec::new();
+        let mut reason_codes = Vec::new();
+        let subscription_identifier = subscribe
+            .properties
+            .get_as::<packets::VariableByteInteger>(packets::SUBSCRIPTION_IDENTIFIER)
+            .ok()
+            .flatten()
+            .map(|identifier| identifier.val());
+        // It is a Protocol Error if the Subscription Identifier has a value of 0 (3.8.2.1.2 Subscription Identifier subsection).
+        if subscription_identifier == Some(0) {
+            return Err(errors::Error::ProtocolError("Subscription Identifier is 0".to_string()));
+        }
+        for subscription in subscribe.subscriptions.iter() {
+            let filter = subscription.topic_filter.val();
+            if subscription_identifier.is_some() && !self.config.subscription_identifier_available {
+                reason_codes.push(suback::SUBSCRIPTION_IDENTIFIERS_NOT_SUPPORTED);
+                continue;
+            }
+            if filter.starts_with(topic::SHARED_SUBSCRIPTION_PREFIX) && !self.config.shared_subscription_available {
+                reason_codes.push(suback::SHARED_SUBSCRIPTIONS_NOT_SUPPORTED);
+                continue;
+            }
+            let topic_filter = match topic::parse_shared_subscription(filter) {
+                // It is a Protocol Error to set the No Local bit to 1 on a Shared Subscription [MQTT-3.8.3-4].
+                Ok(Some(_)) if subscription.options.no_local() => {
+                    return Err(errors::Error::ProtocolError(format!("No Local is set to the Shared Subscription {}", filter)));
+                }
+                Ok(Some((_, topic_filter))) => topic_filter,
+                Ok(None) if topic::validate_topic_filter(filter).is_ok() => filter,
+                _ => {
+                    reason_codes.push(suback::TOPIC_FILTER_INVALID);
+                    continue;
+                }
+            };
+            if topic::has_wildcard(topic_filter) && !self.config.wildcard_subscription_available {
+                reason_codes.push(suback::WILDCARD_SUBSCRIPTIONS_NOT_SUPPORTED);
+                continue;
+            }
+            if !self.authorize(session, acl::Action::Subscribe, topic_filter) {
+                reason_codes.push(suback::NOT_AUTHORIZED);
+                continue;
+            }
+
+            let qos = subscription.options.maximum_qos().min(self.config.maximum_qos);
+            let options = SubscriptionOptions::new(Bits(subscription.options.0.val() & !0b0000_0011 | qos.bits().val()))?;
+            granted.push((filter.to_string(), session::Subscription::new(options, subscription_identifier)));
+            reason_codes.push(SubAckReasonCode::granted(qos));
+        }
+
+        let mut outgoings = Vec::new();
+        let now = chrono::Utc::now();
+        let expired: Vec<String> = self
+            .retained
+            .iter()
+            .filter(|(_, retained)| retained.is_expired(now))
+            .map(|(topic_name, _)| topic_name.clone())
+            .collect();
+        for topic_name in expired {
+            self.retained.remove(&topic_name);
+            self.config.store.remove_retained(&topic_name)?;
+        }
+        if let Some(session) = self.sessions.get_mut(session_id) {
+            let deliveries = (session.outbound_inflight.len(), session.outbound_pending.len());
+            let subscribed = granted.clone();
+            for (filter, subscription) in granted {
+                let send_retained = match subscription.options.retain_handling() {
+                    0 => true,
+                    1 => !session.subscriptions.contains_key(&filter),
+                    _ => false,
+                };
+                if send_retained && !filter.starts_with(topic::SHARED_SUBSCRIPTION_PREFIX) {
+                    let subscription_identifiers = Vec::from_iter(subscription.identifier);
+                    let matching = self
+                        .retained
+                        .values()
+                        .filter(|retained| topic::matches(&filter, retained.publish.topic_name.val()));
+                    for retained in matching {
+                        // The retained message sent for the new subscription has the RETAIN flag 1 [MQTT-3.3.1-9].
+                        outgoings.extend(Handler::deliver(
+                            session,
+                            &retained.publish,
+                            subscription.options.maximum_qos(),
+                            true,
+                            &subscription_identifiers,
+                            None,
+                            retained.expires_at,
+                        ));
+                    }
+                }
+                session.subscriptions.insert(filter, subscription);
+            }
+            // The whole session is saved only when the retained messages have been put in flight or queued.
+            if session.session_expiry_interval > 0 {
+                if deliveries != (session.outbound_inflight.len(), session.outbound_pending.len()) {
+                    self.config.store.save_session(session)?;
+                } else {
+                    for (filter, subscription) in &subscribed {
+                        self.config.store.save_subscription(session.client_id.as_str(), filter, subscription)?;
+                    }
+                }
+            }
+        }
+
+        let suback = SubAck::new(subscribe.packet_identifier.clone(), packets::Properties::new(), reason_codes);
+        Ok((suback, outgoings))
+    }
+
+    // handle_unsubscribe removes the subscriptions of the UNSUBSCRIBE Packet from the session.
+    // Each Topic Filter, including the Shared Subscription, is compared character-by-character with the existing ones [MQTT-3.10.4-1].
+    // It results the UNSUBACK Packet with the Reason Code 0x00 (Success) for the removed subscription,
+    // and 0x11 (No subscription existed) for the Topic Filter which has no subscription.
+    pub fn handle_unsubscribe(
+        &mut self,
+        session_id: &session::SessionId,
+        unsubscribe: &Unsubscribe,
+    ) -> Result<UnsubAck, errors::Error> {
+        let session = self.sessions.get_mut(session_id)
+            .ok_or_else(|| errors::Error::Common(format!("Session {:?} is not found", session_id)))?;
+
+        let mut reason_codes = Vec::new();
+        for topic_filter in unsubscribe.topic_filters.iter() {
+            if session.subscriptions.contains_key(topic_filter.val()) {
+                if session.session_expiry_interval > 0 {
+                    self.config.store.remove_subscription(session.client_id.as_str(), topic_filter.val())?;
+                }
+                session.subscriptions.remove(topic_filter.val());
+                reason_codes.push(unsuback::SUCCESS);
+            } else {
+                reason_codes.push(unsuback::NO_SUBSCRIPTION_EXISTED);
+            }
+        }
+
+        let unsuback = UnsubAck::new(unsubscribe.packet_identifier.clone(), packets::Properties::new(), reason_codes);
+        Ok(unsuback)
+    }
+
+    // handle_publish authorizes the PUBLISH Packet by the configured ACL and delivers it to the matching subscriptions.
+    // It results the packets to send, the PUBACK or PUBREC Packet to the publisher first and the PUBLISH Packets to the subscribers.
+    // The denied QoS 1 and QoS 2 messages are answered with the Reason Code 0x87 (Not authorized), and the denied QoS 0 message is discarded.
+    // The QoS 2 message is delivered as soon as it is received, and its Packet Identifier is kept until the PUBREL Packet
+    // to detect the duplicates (4.3.3 QoS 2: Exactly once delivery section).
+    // The Topic Alias must be resolved to the Topic Name before, e.g. by topic::alias::InboundTopicAliases of the connection.
+    // The message which exceeds the capabilities of the Server results the DISCONNECT Packet to the publisher,
+    // as well as the QoS 1 or QoS 2 message beyond the Receive Maximum of the Server, with the Reason Code 0x93 (Receive Maximum exceeded).
+    // A PUBLISH Packet sent from a Client to a Server MUST NOT contain a Subscription Identifier [MQTT-3.3.4-6].
+    // The invalid Topic Name results the DISCONNECT Packet with the Reason Code 0x90 (Topic Name invalid).
+    pub fn handle_publish(&mut self, session_id: &session::SessionId, publish: &Publish) -> Result<Vec<Outgoing>, errors::Error> {
+        let session = self.sessions.get(session_id)
+            .ok_or_else(|| errors::Error::Common(format!("Session {:?} is not found", session_id)))?;
+        if !publish.properties.get_all_as::<packets::VariableByteInteger>(packets::SUBSCRIPTION_IDENTIFIER)?.is_empty() {
+            let disconnect = Disconnect::with_reason(
+                disconnect::PROTOCOL_ERROR,
+                "The PUBLISH Packet from the Client contains the Subscription Identifier",
+            );
+            return Ok(vec![Outgoing::new(session_id.clone(), packets::Packet::Disconnect(disconnect))]);
+        }
+        if let Some(disconnect) = self.check_capabilities(publish) {
+            return Ok(vec![Outgoing::new(session_id.clone(), packets::Packet::Disconnect(disconnect))]);
+        }
+        // Until the PUBREL Packet is received, the PUBLISH Packet with the Packet Identifier of the QoS 2 message
+        // is answered by the PUBREC Packet again, and the message is not delivered twice [MQTT-4.3.3-10].
+        if let (QoS::ExactlyOnce, Some(packet_identifier)) = (publish.qos(), &publish.packet_identifier) {
+            if session.inbound_inflight.contains(&packet_identifier.val()) {
+                let pubrec = PubRec::new(packet_identifier.clone(), pubrec::SUCCESS, packets::Properties::new());
+                return Ok(vec![Outgoing::new(session_id.clone(), packets::Packet::PubRec(pubrec))]);
+            }
+        }
+        // The QoS 1 message is acknowledged at once, so only the uncompleted QoS 2 messages occupy the quota.
+        if publish.qos() != QoS::AtMostOnce && session.inbound_inflight.len() >= self.config.receive_maximum as usize {
+            let disconnect = Disconnect::with_reason(
+                disconnect::RECEIVE_MAXIMUM_EXCEEDED,
+                &format!("The Receive Maximum {} is exceeded", self.config.receive_maximum),
+            );
+            return Ok(vec![Outgoing::new(session_id.clone(), packets::Packet::Disconnect(disconnect))]);
+        }
+        let topic_name = publish.topic_name.val();
+        if topic::validate_topic_name(topic_name).is_err() {
+            let disconnect = Disconnect::with_reason(
+                disconnect::TOPIC_NAME_INVALID,
+                &format!("The Topic Name {} is invalid", topic_name),
+            );
+            return Ok(vec![Outgoing::new(session_id.clone(), packets::Packet::Disconnect(disconnect))]);
+        }
+
+        // The Message Expiry Interval counts from the time the Server has received the message.
+        let received_at = chrono::Utc::now();
+        let authorized = self.authorize(session, acl::Action::Publish, topic_name);
+        let targets = if authorized { self.targets(session_id, publish) } else { Vec::new() };
+        let matched = targets.len();
+        // With the overflow policy reject_publisher, the message which a full queue cannot keep is delivered to nobody,
+        // and the publisher is answered with the Reason Code 0x97 (Quota exceeded).
+        let quota_exceeded = self.config.queue_limits.overflow_policy == OverflowPolicy::RejectPublisher
+            && targets.iter().any(|target| {
+                self.sessions
+                    .get(&target.session_id)
+                    .is_some_and(|session| session.queue_overflows(target.qos, publish))
+            });
+        let accepted = authorized && !quota_exceeded;
+        if accepted && publish.retain() {
+            self.retain(publish, received_at)?;
+        }
+        // The Packet Identifier of the accepted QoS 2 message is kept until the PUBREL Packet, and it is stored
+        // before the message is routed, so that the message is not delivered twice when the Server restarts.
+        // The rejected one is free at once, the next PUBLISH Packet with it is a new message [MQTT-4.3.3-9].
+        if let (true, QoS::ExactlyOnce, Some(packet_identifier)) = (accepted, publish.qos(), &publish.packet_identifier) {
+            if let Some(session) = self.sessions.get_mut(session_id) {
+                if session.session_expiry_interval > 0 {
+                    self.config.store.save_inbound(session.client_id.as_str(), packet_identifier.val())?;
+                }
+                session.inbound_inflight.insert(packet_identifier.val());
+            }
+        }
+        let mut outgoings = Vec::new();
+        let deliveries = if accepted { self.route(publish, targets, received_at) } else { Vec::new() };
+        match (publish.qos(), &publish.packet_identifier) {
+            (QoS::AtLeastOnce, Some(packet_identifier)) => {
+                let reason_code = match (authorized, quota_exceeded, matched) {
+                    (false, _, _) => puback::NOT_AUTHORIZED,
+                    (true, true, _) => puback::QUOTA_EXCEEDED,
+                    (true, false, 0) => puback::NO_MATCHING_SUBSCRIBERS,
+                    (true, false, _) => puback::SUCCESS,
+                };
+                let puback = PubAck::new(packet_identifier.clone(), reason_code, packets::Properties::new());
+                outgoings.push(Outgoing::new(session_id.clone(), packets::Packet::PubAck(puback)));
+            }
+            (QoS::ExactlyOnce, Some(packet_identifier)) => {
+                let reason_code = match (authorized, quota_exceeded, matched) {
+                    (false, _, _) => pubrec::NOT_AUTHORIZED,
+                    (true, true, _) => pubrec::QUOTA_EXCEEDED,
+                    (true, false, 0) => pubrec::NO_MATCHING_SUBSCRIBERS,
+                    (true, false, _) => pubrec::SUCCESS,
+                };
+                let pubrec = PubRec::new(packet_identifier.clone(), reason_code, packets::Properties::new());
+                outgoings.push(Outgoing::new(session_id.clone(), packets::Packet::PubRec(pubrec)));
+            }
+            _ => {}
+        }
+        outgoings.extend(deliveries);
+
+        Ok(outgoings)
+    }
+
+    // handle_puback completes the delivery of the QoS 1 message which the PUBACK Packet acknowledges.
+    // It results the pending PUBLISH Packets to the Client, which the freed quota of the Receive Maximum allows to send.
+    // The PUBACK Packet of an unknown Packet Identifier is ignored.
+    pub fn handle_puback(&mut self, session_id: &session::SessionId, puback: &PubAck) -> Result<Vec<Outgoing>, errors::Error> {
+        self.complete_delivery(session_id, puback.packet_identifier.val())
+    }
+
+    // handle_pubrec answers the PUBREC Packet of the QoS 2 message sent to the Client by the PUBREL Packet [MQTT-4.3.3-4].
+    // The PUBREC Packet with a Reason Code of 0x80 or greater completes the delivery without the PUBREL Packet,
+    // and the pending PUBLISH Packets which the freed quota allows are resulted instead.
+    // The unknown Packet Identifier is answered with the Reason Code 0x92 (Packet Identifier not found).
+    pub fn handle_pubrec(&mut self, session_id: &session::SessionId, pubrec: &PubRec) -> Result<Vec<Outgoing>, errors::Error> {
+        if pubrec.reason_code.is_failure() {
+            return self.complete_delivery(session_id, pubrec.packet_identifier.val());
+        }
+        let session = self.sessions.get_mut(session_id)
+            .ok_or_else(|| errors::Error::Common(format!("Session {:?} is not found", session_id)))?;
+
+        let packet_identifier = pubrec.packet_identifier.val();
+        if session.session_expiry_interval > 0 && session.outbound_inflight.contains_key(&packet_identifier) {
+            self.config.store.release(session.client_id.as_str(), packet_identifier)?;
+        }
+        let reason_code = if session.release(packet_identifier) {
+            pubrel::SUCCESS
+        } else {
+            pubrel::PACKET_IDENTIFIER_NOT_FOUND
+        };
+        let pubrel = PubRel::new(pubrec.packet_identifier.clone(), reason_code, packets::Properties::new());
+        Ok(vec![Outgoing::new(session_id.clone(), packets::Packet::PubRel(pubrel))])
+    }
+
+    // handle_pubrel answers the PUBREL Packet of the QoS 2 message received from the Client by the PUBCOMP Packet [MQTT-4.3.3-11].
+    // The Packet Identifier is released, the next PUBLISH Packet with it is a new message [MQTT-4.3.3-12].
+    // The unknown Packet Identifier is answered with the Reason Code 0x92 (Packet Identifier not found),
+    // e.g. when the Client sends the PUBREL Packet again because the PUBCOMP Packet has been lost.
+    pub fn handle_pubrel(&mut self, session_id: &session::SessionId, pubrel: &PubRel) -> Result<Vec<Outgoing>, errors::Error> {
+        let session = self.sessions.get_mut(session_id)
+            .ok_or_else(|| errors::Error::Common(format!("Session {:?} is not found", session_id)))?;
+
+        let packet_identifier = pubrel.packet_identifier.val();
+        if session.session_expiry_interval > 0 && session.inbound_inflight.contains(&packet_identifier) {
+            self.config.store.remove_inbound(session.client_id.as_str(), packet_identifier)?;
+        }
+        let reason_code = if session.inbound_inflight.remove(&packet_identifier) {
+            pubcomp::SUCCESS
+        } else {
+            pubcomp::PACKET_IDENTIFIER_NOT_FOUND
+        };
+        let pubcomp = PubComp::new(pubrel.packet_identifier.clone(), reason_code, packets::Properties::new());
+        Ok(vec![Outgoing::new(session_id.clone(), packets::Packet::PubComp(pubcomp))])
+    }
+
+    // handle_pubcomp completes the delivery of the QoS 2 message which the PUBCOMP Packet acknowledges, as handle_puback.
+    pub fn handle_pubcomp(&mut self, session_id: &session::SessionId, pubcomp: &PubComp) -> Result<Vec<Outgoing>, errors::Error> {
+        self.complete_delivery(session_id, pubcomp.packet_identifier.val())
+    }
+
+    // resume_deliveries results the packets which the session sends, when the Client has resumed it by a new Network Connection.
+    // They are sent after the CONNACK Packet: the packets in flight are sent again first,
+    // and then the messages queued while the session was disconnected within the Receive Maximum of the Client.
+    pub fn resume_deliveries(&mut self, session_id: &session::SessionId) -> Result<Vec<Outgoing>, errors::Error> {
+        let Some(session) = self.sessions.get_mut(session_id) else {
+            return Ok(Vec::new());
+        };
+
+        let retransmissions = session.retransmissions();
+        let pending = session.outbound_pending.len();
+        let publishes = session.release_pending();
+        // The packets are sent even when the Store fails, as the session in memory has them in flight already.
+        if session.session_expiry_interval > 0 {
+            if let Err(err) = Handler::store_released(self.config.store.as_ref(), session, pending, &publishes) {
+                log::error!("Failed to store the released messages of the session {:?}: {}", session_id, err);
+            }
+        }
+        let outgoings = retransmissions
+            .into_iter()
+            .chain(publishes.into_iter().map(packets::Packet::Publish))
+            .map(|packet| Outgoing::new(session_id.clone(), packet))
+            .collect();
+        Ok(outgoings)
+    }
+
+    // complete_delivery completes the delivery of the PUBLISH Packet of the Packet Identifier without its acknowledgement.
+    // The Server treats the PUBLISH Packet discarded for the Maximum Packet Size of the Client
+    // as if it had completed sending it (3.1.2.11.4 Maximum Packet Size subsection).
+    pub fn complete_delivery(&mut self, session_id: &session::SessionId, packet_identifier: u16) -> Result<Vec<Outgoing>, errors::Error> {
+        let session = self.sessions.get_mut(session_id)
+            .ok_or_else(|| errors::Error::Common(format!("Session {:?} is not found", session_id)))?;
+
+        if session.session_expiry_interval > 0 && session.outbound_inflight.contains_key(&packet_identifier) {
+            self.config.store.acknowledge(session.client_id.as_str(), packet_identifier)?;
+        }
+        let pending = session.outbound_pending.len();
+        let publishes = session.acknowledge(packet_identifier);
+        if session.session_expiry_interval > 0 {
+            if let Err(err) = Handler::store_released(self.config.store.as_ref(), session, pending, &publishes) {
+                log::error!("Failed to store the released messages of the session {:?}: {}", session_id, err);
+            }
+        }
+        let outgoings = publishes
+            .into_iter()
+            .map(|publish| Outgoing::new(session_id.clone(), packets::Packet::Publish(publish)))
+            .collect();
+        Ok(outgoings)
+    }
+
+    // check_capabilities results the DISCONNECT Packet when the PUBLISH Packet uses what the Server has not advertised.
+    // - The QoS greater than the Maximum QoS is the Reason Code 0x9B (QoS not supported) [MQTT-3.2.2-11].
+    // - The RETAIN flag without the retained messages support is 0x9A (Retain not supported) [MQTT-3.2.2-14].
+    // - The Topic Alias of 0 or greater than the Topic Alias Maximum is 0x94 (Topic Alias invalid) [MQTT-3.3.2-8] [MQTT-3.3.2-9].
+    fn check_capabilities(&self, publish: &Publish) -> Option<Disconnect> {
+        if publish.qos().exceeds(self.config.maximum_qos) {
+            return Some(Disconnect::with_reason(
+                disconnect::QOS_NOT_SUPPORTED,
+                &format!("QoS {} exceeds the Maximum QoS {}", publish.qos(), self.config.maximum_qos),
+            ));
+        }
+        if publish.retain() && !self.config.retain_available {
+            return Some(Disconnect::with_reason(
+                disconnect::RETAIN_NOT_SUPPORTED,
+                "The retained messages are not supported",
+            ));
+        }
+        let topic_alias = publish.properties.get_as::<packets::TwoByteInteger>(packets::TOPIC_ALIAS).ok().flatten();
+        if let Some(topic_alias) = topic_alias {
+            if topic_alias.val() == 0 || topic_alias.val() > self.config.topic_alias_maximum {
+                return Some(Disconnect::with_reason(
+                    disconnect::TOPIC_ALIAS_INVALID,
+                    &format!("Topic Alias {} exceeds the Topic Alias Maximum {}", topic_alias.val(), self.config.topic_alias_maximum),
+                ));
+            }
+        }
+
+        None
+    }
+
+    fn authorize(&self, session: &session::Session, action: acl::Action, topic: &str) -> bool {
+        match &self.config.acl {
+            Some(acl) => acl.authorize(session.client_id.as_str(), session.user_name.as_deref(), action, topic),
+            None => true,
+        }
+    }
+
+    // retain stores the message with the RETAIN flag as the retained message of the Topic Name, replacing the previous one
+    // [MQTT-3.3.1-5]. The message with the zero-length payload removes the retained message, and it is not stored [MQTT-3.3.1-6] [MQTT-3.3.1-7].
+    fn retain(&mut self, publish: &Publish, received_at: chrono::DateTime<chrono::Utc>) -> Result<(), errors::Error> {
+        let topic_name = publish.topic_name.val().to_string();
+        if publish.payload.is_empty() {
+            self.retained.remove(&topic_name);
+            return self.config.store.remove_retained(&topic_name);
+        }
+        let mut retained = publish.clone();
+        retained.packet_identifier = None;
+        retained.properties.remove(&packets::TOPIC_ALIAS);
+        let retained = store::Retained::new(retained, session::expires_at(publish, received_at));
+        self.config.store.save_retained(&retained)?;
+        self.retained.insert(topic_name, retained);
+        Ok(())
+    }
+
+    // targets results the sessions which receive the message of the Topic Name, and how their subscriptions deliver it.
+    // When the several non-shared subscriptions of a session match, the message is delivered once with the maximum granted QoS,
+    // and it carries the Subscription Identifiers of all the matching subscriptions [MQTT-3.3.4-4].
+    // Each matching Shared Subscription delivers the message to one of its sessions, chosen by the configured strategy,
+    // in addition to the non-shared subscriptions (4.8.2 Shared Subscriptions subsection).
+    // The subscription with No Local does not receive the messages of its own session [MQTT-3.8.3-3],
+    // and the one with Retain As Published keeps the RETAIN flag of the message, which is 0 otherwise [MQTT-3.3.1-12] [MQTT-3.3.1-13].
+    // The disconnected session receives the messages while it persists, i.e. its Session Expiry Interval is not 0,
+    // and the messages wait in its queue. The Shared Subscriptions deliver only to the connected sessions.
+    fn targets(&mut self, publisher: &session::SessionId, publish: &Publish) -> Vec<Target> {
+        let topic_name = publish.topic_name.val();
+        let mut targets = Vec::new();
+        let mut shared_subscriptions: BTreeMap<String, Vec<(session::SessionId, session::Subscription)>> = BTreeMap::new();
+        for session in self.sessions.values() {
+            let disconnected = session.state == session::SessionState::Disconnected;
+            if disconnected && session.session_expiry_interval == 0 {
+                continue;
+            }
+            let mut granted: Option<QoS> = None;
+            let mut retain_as_published = false;
+            let mut subscription_identifiers = Vec::new();
+            let own = session.session_id == *publisher;
+            for (filter, subscription) in session.subscriptions.iter() {
+                if own && subscription.options.no_local() {
+                    continue;
+                }
+                match topic::parse_shared_subscription(filter) {
+                    Ok(Some((_, topic_filter))) => {
+                        if !disconnected && topic::matches(topic_filter, topic_name) {
+                            shared_subscriptions
+                                .entry(filter.clone())
+                                .or_default()
+                                .push((session.session_id.clone(), subscription.clone()));
+                        }
+                    }
+                    _ => {
+                        if topic::matches(filter, topic_name) {
+                            let qos = subscription.options.maximum_qos();
+                            granted = Some(granted.map_or(qos, |granted| if qos.exceeds(granted) { qos } else { granted }));
+                            retain_as_published |= subscription.options.retain_as_published();
+                            subscription_identifiers.extend(subscription.identifier);
+                        }
+                    }
+                }
+            }
+            if let Some(granted) = granted {
+                subscription_identifiers.sort_unstable();
+                targets.push(Target {
+                    session_id: session.session_id.clone(),
+                    qos: publish.qos().min(granted),
+                    retain: publish.retain() && retain_as_published,
+                    subscription_identifiers,
+                    shared_subscription: None,
+                });
+            }
+        }
+
+        for (shared_subscription, mut members) in shared_subscriptions {
+            members.sort_by_key(|(session_id, _)| session_id.0);
+            let (session_id, subscription) = self.select_member(&shared_subscription, topic_name, &members);
+            targets.push(Target {
+                session_id,
+                qos: publish.qos().min(subscription.options.maximum_qos()),
+                retain: publish.retain() && subscription.options.retain_as_published(),
+                subscription_identifiers: Vec::from_iter(subscription.identifier),
+                shared_subscription: Some(shared_subscription),
+            });
+        }
+
+        targets
+    }
+
+    // route delivers the message to the targets, and results the PUBLISH Packets to send now.
+    fn route(&mut self, publish: &Publish, targets: Vec<Target>, received_at: chrono::DateTime<chrono::Utc>) -> Vec<Outgoing> {
+        let expires_at = session::expires_at(publish, received_at);
+        let mut outgoings = Vec::new();
+        for target in targets {
+            let Some(session) = self.sessions.get_mut(&target.session_id) else {
+                continue;
+            };
+            let pending = (session.session_expiry_interval > 0)
+                .then(|| (session.outbound_pending.len(), session.outbound_pending.back().cloned()));
+            let outgoing = Handler::deliver(
+                session,
+                publish,
+                target.qos,
+                target.retain,
+                &target.subscription_identifiers,
+                target.shared_subscription,
+                expires_at,
+            );
+            // The message has been routed already, so the Store failure does not stop the other deliveries.
+            if let Some(pending) = pending {
+                if let Err(err) = Handler::store_delivery(self.config.store.as_ref(), session, pending, outgoing.as_ref()) {
+                    log::error!("Failed to store the delivery to the session {:?}: {}", session.session_id, err);
+                }
+            }
+            outgoings.extend(outgoing);
+        }
+        outgoings
+    }
+
+    // store_delivery saves what the delivery has changed in the persistent session by the record of the change,
+    // the message sent in flight or appended to the queue. The pending are the length and the last message of the queue
+    // before the delivery. The whole session is saved only when the queue has dropped messages to keep the new one,
+    // i.e. the expired messages or the oldest ones by the overflow policy.
+    fn store_delivery(
+        store: &dyn store::Store,
+        session: &session::Session,
+        pending: (usize, Option<session::Delivery>),
+        outgoing: Option<&Outgoing>,
+    ) -> Result<(), errors::Error> {
+        let client_id = session.client_id.as_str();
+        let in_flight = outgoing.and_then(|outgoing| match &outgoing.packet {
+            packets::Packet::Publish(publish) => publish.packet_identifier.as_ref().map(|packet_identifier| packet_identifier.val()),
+            _ => None,
+        });
+        if let Some((packet_identifier, delivery)) = in_flight.and_then(|packet_identifier| session.outbound_inflight.get_key_value(&packet_identifier)) {
+            return store.save_in_flight(client_id, *packet_identifier, delivery);
+        }
+        let (length, last) = pending;
+        match session.outbound_pending.back() {
+            Some(delivery) if session.outbound_pending.len() == length + 1 => store.save_queued(client_id, delivery),
+            back if session.outbound_pending.len() != length || back != last.as_ref() => store.save_session(session),
+            _ => Ok(()),
+        }
+    }
+
+    // store_released saves the queued messages which the persistent session has taken to send by the records of the change,
+    // the count taken from the queue and the messages sent in flight. The pending is the length of the queue before,
+    // and the taken messages which are not sent have expired.
+    fn store_released(
+        store: &dyn store::Store,
+        session: &session::Session,
+        pending: usize,
+        publishes: &[Publish],
+    ) -> Result<(), errors::Error> {
+        let client_id = session.client_id.as_str();
+        let count = pending.saturating_sub(session.outbound_pending.len());
+        if count > 0 {
+            store.remove_queued(client_id, count)?;
+        }
+        for publish in publishes {
+            let in_flight = publish
+                .packet_identifier
+                .as_ref()
+                .and_then(|packet_identifier| session.outbound_inflight.get_key_value(&packet_identifier.val()));
+            if let Some((packet_identifier, delivery)) = in_flight {
+                store.save_in_flight(client_id, *packet_identifier, delivery)?;
+            }
+        }
+        Ok(())
+    }
+
+    // select_member results the session of the Shared Subscription which receives the message, by the configured strategy.
+    fn select_member(
+        &mut self,
+        shared_subscription: &str,
+        topic_name: &str,
+        members: &[(session::SessionId, session::Subscription)],
+    ) -> (session::SessionId, session::Subscription) {
+        let inflight: Vec<usize> = members
+            .iter()
+            .map(|(session_id, _)| {
+                self.sessions
+                    .get(session_id)
+                    .map_or(0, |session| session.outbound_inflight.len() + session.outbound_pending.len())
+            })
+            .collect();
+        let index = self.balancer.select(shared_subscription, topic_name, &inflight);
+        members[index].clone()
+    }
+
+    // deliver results the PUBLISH Packet to send to the session now, if the Receive Maximum of the Client allows.
+    // The delivered QoS is the minimum of the QoS of the message and the granted QoS (4.3 Quality of Service levels and protocol flows).
+    // The QoS 1 and QoS 2 messages beyond the Receive Maximum of the Client wait in the session until the quota is freed,
+    // or until the Message Expiry Interval passes at expires_at.
+    fn deliver(
+        session: &mut session::Session,
+        publish: &Publish,
+        granted: QoS,
+        retain: bool,
+        subscription_identifiers: &[u32],
+        shared_subscription: Option<String>,
+        expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    ) -> Option<Outgoing> {
+        let qos = publish.qos().min(granted);
+        // The Topic Alias mappings are not forwarded, they belong to the Network Connection of the publisher.
+        let mut properties = publish.properties.clone();
+        properties.remove(&packets::TOPIC_ALIAS);
+        properties.remove(&packets::SUBSCRIPTION_IDENTIFIER);
+        for subscription_identifier in subscription_identifiers {
+            properties.append(
+                packets::SUBSCRIPTION_IDENTIFIER,
+                packets::VariableByteInteger(*subscription_identifier).into(),
+            );
+        }
+        let delivery = Publish {
+            fixed_header: Publish::fixed_header(false, qos, retain),
+            topic_name: publish.topic_name.clone(),
+            packet_identifier: None,
+            properties,
+            payload: publish.payload.clone(),
+        };
+        session
+            .send_publish(session::Delivery::new(delivery, shared_subscription).with_expires_at(expires_at))
+            .map(|delivery| Outgoing::new(session.session_id.clone(), packets::Packet::Publish(delivery)))
+    }
+
+    // disconnect_session marks the session disconnected when the Network Connection of the Client is closed.
+    // The messages of the Shared Subscriptions which the Client has not acknowledged are delivered to another session
+    // of the Shared Subscription (4.8.2 Shared Subscriptions subsection), and the resulted packets are sent to them.
+    // When no other session is connected, the messages stay in the session.
+    // The Will Message which the DISCONNECT Packet has not discarded is published then [MQTT-3.1.2-8].
+    pub fn disconnect_session(&mut self, session_id: &session::SessionId) -> Result<Vec<Outgoing>, errors::Error> {
+        let replaced = self.is_replaced(session_id);
+        let Some(session) = self.sessions.get_mut(session_id) else {
+            return Ok(Vec::new());
+        };
+        *session = session.disconnected();
+        let will = session.will.take();
+        let mut unacknowledged: Vec<session::Delivery> = Vec::new();
+        let inflight = std::mem::take(&mut session.outbound_inflight);
+        for (packet_identifier, delivery) in inflight {
+            // The QoS 2 message which the Client has received by the PUBREC Packet is not delivered again.
+            if delivery.shared_subscription.is_some() && !session.outbound_released.contains(&packet_identifier) {
+                session.packet_identifiers.release(packet_identifier);
+                unacknowledged.push(delivery);
+            } else {
+                session.outbound_inflight.insert(packet_identifier, delivery);
+            }
+        }
+        let (shared, pending): (Vec<_>, Vec<_>) = std::mem::take(&mut session.outbound_pending)
+            .into_iter()
+            .partition(|delivery| delivery.shared_subscription.is_some());
+        session.outbound_pending = pending.into();
+        unacknowledged.extend(shared);
+
+        let mut outgoings = Vec::new();
+        let mut undelivered = Vec::new();
+        for delivery in unacknowledged {
+            let Some(shared_subscription) = delivery.shared_subscription.clone() else {
+                continue;
+            };
+            let mut members: Vec<(session::SessionId, session::Subscription)> = self
+                .sessions
+                .values()
+                .filter(|member| member.state != session::SessionState::Disconnected)
+                .filter_map(|member| {
+                    member
+                        .subscriptions
+                        .get(&shared_subscription)
+                        .map(|subscription| (member.session_id.clone(), subscription.clone()))
+                })
+                .collect();
+            if members.is_empty() {
+                undelivered.push(delivery);
+                continue;
+            }
+            members.sort_by_key(|(session_id, _)| session_id.0);
+            let topic_name = delivery.publish.topic_name.val();
+            // The Subscription Identifier of the new session replaces the one of the disconnected session.
+            let (member, subscription) = self.select_member(&shared_subscription, topic_name, &members);
+            if let Some(session) = self.sessions.get_mut(&member) {
+                let pending = (session.session_expiry_interval > 0)
+                    .then(|| (session.outbound_pending.len(), session.outbound_pending.back().cloned()));
+                let outgoing = Handler::deliver(
+                    session,
+                    &delivery.publish,
+                    subscription.options.maximum_qos(),
+                    delivery.publish.retain(),
+                    &Vec::from_iter(subscription.identifier),
+                    Some(shared_subscription),
+                    delivery.expires_at,
+                );
+                if let Some(pending) = pending {
+                    if let Err(err) = Handler::store_delivery(self.config.store.as_ref(), session, pending, outgoing.as_ref()) {
+                        log::error!("Failed to store the delivery to the session {:?}: {}", member, err);
+                    }
+                }
+                outgoings.extend(outgoing);
+            }
+        }
+        // The disconnected session is saved as a whole, its messages of the Shared Subscriptions have moved
+        // and it is restored as disconnected. The messages have been delivered already, so the Store failure is only logged.
+        // The replaced session is not saved, the record of its ClientID belongs to the newer session.
+        if let Some(session) = self.sessions.get_mut(session_id) {
+            session.outbound_pending.extend(undelivered);
+            if session.session_expiry_interval > 0 && !replaced {
+                if let Err(err) = self.config.store.save_session(session) {
+                    log::error!("Failed to store the session {:?}: {}", session_id, err);
+                }
+            }
+        }
+        if let Some(will) = will {
+            outgoings.extend(self.publish_will(session_id, &will)?);
+        }
+
+        Ok(outgoings)
+    }
+
+    // is_replaced results whether a newer session of the same ClientID is held, i.e. the session has been taken over.
+    fn is_replaced(&self, session_id: &session::SessionId) -> bool {
+        let Some(session) = self.sessions.get(session_id) else {
+            return false;
+        };
+        self.sessions
+            .values()
+            .any(|other| other.client_id == session.client_id && other.session_id.0 > session.session_id.0)
+    }
+
+    // handle_disconnect handles the DISCONNECT Packet from the Client before the Network Connection is closed.
+    // The Will Message is discarded without publishing it on the normal disconnection [MQTT-3.1.2-10],
+    // unless the Reason Code is 0x04 (Disconnect with Will Message) (3.14.2.1 Disconnect Reason Code subsection).
+    pub fn handle_disconnect(&mut self, session_id: &session::SessionId, disconnect: &Disconnect) {
+        if let Some(session) = self.sessions.get_mut(session_id) {
+            if disconnect.reason_code != disconnect::DISCONNECT_WITH_WILL_MESSAGE {
+                session.will = None;
+            }
+        }
+    }
+
+    // publish_will routes the Will Message of the session as a PUBLISH Packet from the Client.
+    // It is authorized by the configured ACL as well, and the denied Will Message is discarded.
+    fn publish_will(&mut self, session_id: &session::SessionId, will: &Publish) -> Result<Vec<Outgoing>, errors::Error> {
+        let Some(session) = self.sessions.get(session_id) else {
+            return Ok(Vec::new());
+        };
+        if !self.authorize(session, acl::Action::Publish, will.topic_name.val()) {
+            return Ok(Vec::new());
+        }
+        let received_at = chrono::Utc::now();
+        if will.retain() {
+            self.retain(will, received_at)?;
+        }
+        let targets = self.targets(session_id, will);
+
+        Ok(self.route(will, targets, received_at))
+    }
+
+    // assign_client_id generates a unique ClientID for the Client which has sent the zero-length Client ID [MQTT-3.1.3-6].
+    // The generated ClientID is the 128 random bits in 22 alphanumeric characters,
+    // so that it is also acceptable as a Client supplied ClientID [MQTT-3.1.3-5].
+    // The random bits keep it unique against the ClientIDs of the stored sessions which are not loaded as well.
+    // As a note, it is not validated by the configured ClientIdPolicy, the Server owns the assigned ClientIDs.
+    fn assign_client_id(&self) -> Result<session::ClientId, errors::Error> {
+        const ALPHANUMERICS: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+        loop {
+            let mut bytes = [0u8; 16];
+            getrandom::getrandom(&mut bytes)
+                .map_err(|err| errors::Error::Common(format!("Failed to generate the ClientID: {}", err)))?;
+            let mut bits = u128::from_be_bytes(bytes);
+            let id: String = (0..22)
+                .map(|_| {
+                    let c = ALPHANUMERICS[(bits % 62) as usize] as char;
+                    bits /= 62;
+                    c
+                })
+                .collect();
+            let in_use = self.sessions.values().any(|s| s.client_id.as_str() == id);
+            if !in_use {
+                return Ok(session::ClientId(id));
+            }
+        }
+    }
+
     pub fn get_session(&self, session_id: &session::SessionId) -> Option<&session::Session> {
         self.sessions.get(session_id)
     }
@@ -0,0 +1,2027 @@
+use super::*;
+use crate::packets;
+use crate::packets::connack;
+use crate::session::client_id_policy::{AllowedCharacters, ClientIdPolicy};
+use crate::packets::connect::{ConnectFlags, Payload, VariableHeader};
+use crate::packets::{Bits, FixedHeader, TwoByteInteger, UTF8EncodedString, VariableByteInteger};
+
+fn connect_packet(protocol_version: u8, client_id: &str) -> Connect {
+    connect_packet_with_flags(protocol_version, client_id, 0b0000_0010)
+}
+
+fn connect_packet_with_flags(protocol_version: u8, client_id: &str, flags: u8) -> Connect {
+    Connect::new(
+        FixedHeader::new(Bits(packets::CONNECT), Bits(0), VariableByteInteger(0)).unwrap(),
+        VariableHeader::new(
+            UTF8EncodedString("MQTT".to_string()),
+            Bits(protocol_version),
+            ConnectFlags::new(Bits(flags)).unwrap(),
+            TwoByteInteger(30),
+            packets::Properties::new(),
+        )
+        .unwrap(),
+        Payload::new(UTF8EncodedString(client_id.to_string()), None, None, None, None, None).unwrap(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn handle_connect_accepts_valid_connect() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+
+    let (session, connack) = handler.handle_connect(&connect_packet(5, "client1")).unwrap();
+    assert_eq!(connack.connect_reason_code, connack::SUCCESS);
+    assert_eq!(session.client_id.as_str(), "client1");
+    assert_eq!(session.keep_alive, chrono::Duration::seconds(30));
+    assert_eq!(handler.get_session(&session.session_id), Some(&session));
+}
+
+#[test]
+fn handle_connect_rejects_unsupported_protocol_version() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+
+    let connack = handler.handle_connect(&connect_packet(6, "client1")).unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::UNSUPPORTED_PROTOCOL_VERSION);
+    assert!(connack
+        .properties
+        .get_as::<UTF8EncodedString>(packets::REASON_STRING)
+        .unwrap()
+        .is_some());
+}
+
+#[test]
+fn handle_connect_rejects_invalid_client_id() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+
+    let connack = handler.handle_connect(&connect_packet(5, "client-1")).unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::CLIENT_IDENTIFIER_NOT_VALID);
+}
+
+#[test]
+fn handle_connect_assigns_client_id_for_zero_length_client_id() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+
+    let (session, connack) = handler.handle_connect(&connect_packet(5, "")).unwrap();
+    let assigned = connack
+        .properties
+        .get_as::<UTF8EncodedString>(packets::ASSIGNED_CLIENT_IDENTIFIER)
+        .unwrap()
+        .unwrap();
+    assert_eq!(assigned.val(), session.client_id.as_str());
+    assert_eq!(assigned.val().len(), 22);
+    assert!(session::ClientId::new(assigned.val(), None, &Default::default()).is_ok());
+
+    let (other, _) = handler.handle_connect(&connect_packet(5, "")).unwrap();
+    assert_ne!(session.client_id, other.client_id);
+}
+
+#[test]
+fn handle_connect_does_not_assign_client_id_for_supplied_client_id() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+
+    let (_, connack) = handler.handle_connect(&connect_packet(5, "client1")).unwrap();
+    assert_eq!(
+        connack.properties.get_as::<UTF8EncodedString>(packets::ASSIGNED_CLIENT_IDENTIFIER).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn handle_connect_rejects_zero_length_client_id_without_clean_start() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+
+    let connack = handler
+        .handle_connect(&connect_packet_with_flags(5, "", 0b0000_0000))
+        .unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::CLIENT_IDENTIFIER_NOT_VALID);
+}
+
+#[test]
+fn handle_connect_validates_client_id_by_configured_policy() {
+    let config = config::Config {
+        client_id_policy: ClientIdPolicy::new(
+            36,
+            AllowedCharacters::pattern("[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}").unwrap(),
+        ),
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+
+    let (session, _) = handler
+        .handle_connect(&connect_packet(5, "0b5a4a5e-3c1f-4a8e-9c6b-2f1d0e9a7b3c"))
+        .unwrap();
+    assert_eq!(session.client_id.as_str(), "0b5a4a5e-3c1f-4a8e-9c6b-2f1d0e9a7b3c");
+
+    let connack = handler.handle_connect(&connect_packet(5, "client1")).unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::CLIENT_IDENTIFIER_NOT_VALID);
+}
+
+#[test]
+fn handle_connect_keeps_negotiated_protocol_version() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+
+    let (session, _) = handler.handle_connect(&connect_packet(4, "client1")).unwrap();
+    assert_eq!(session.protocol_version, packets::ProtocolVersion::V3_1_1);
+
+    let (session, _) = handler.handle_connect(&connect_packet(5, "client2")).unwrap();
+    assert_eq!(session.protocol_version, packets::ProtocolVersion::V5);
+}
+
+#[test]
+fn handle_connect_accepts_mqisdp_with_legacy_protocol() {
+    let config = config::Config {
+        legacy_protocol_enabled: true,
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+
+    let mut connect = connect_packet(3, "client1");
+    connect.variable_header.protocol_name = UTF8EncodedString("MQIsdp".to_string());
+    let (session, connack) = handler.handle_connect(&connect).unwrap();
+    assert_eq!(session.protocol_version, packets::ProtocolVersion::V3_1);
+    assert!(connack.properties.is_empty());
+}
+
+#[test]
+fn handle_connect_authenticates_by_configured_provider() {
+    let config = config::Config {
+        auth_provider: std::sync::Arc::new(auth::InMemory::new().with_user("alice", b"secret")),
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+
+    let mut connect = connect_packet_with_flags(5, "client1", 0b1100_0010);
+    connect.payload.user_name = Some(UTF8EncodedString("alice".to_string()));
+    connect.payload.password = Some(packets::BinaryData(b"secret".to_vec()));
+    assert!(handler.handle_connect(&connect).is_ok());
+
+    connect.payload.password = Some(packets::BinaryData(b"wrong".to_vec()));
+    let connack = handler.handle_connect(&connect).unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::BAD_USER_NAME_OR_PASSWORD);
+
+    let connack = handler.handle_connect(&connect_packet(5, "client2")).unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::NOT_AUTHORIZED);
+}
+
+fn subscribe_packet(filters: &[(&str, u8)]) -> Subscribe {
+    let subscriptions = filters
+        .iter()
+        .map(|(filter, options)| {
+            packets::subscribe::Subscription::new(
+                UTF8EncodedString(filter.to_string()),
+                SubscriptionOptions::new(Bits(*options)).unwrap(),
+            )
+        })
+        .collect();
+    Subscribe::new(
+        FixedHeader::new(Bits(packets::SUBSCRIBE), Bits(0b0010), VariableByteInteger(0)).unwrap(),
+        packets::PacketIdentity::new(TwoByteInteger(1)),
+        packets::Properties::new(),
+        subscriptions,
+    )
+    .unwrap()
+}
+
+fn unsubscribe_packet(filters: &[&str]) -> Unsubscribe {
+    Unsubscribe::new(
+        FixedHeader::new(Bits(packets::UNSUBSCRIBE), Bits(0b0010), VariableByteInteger(0)).unwrap(),
+        packets::PacketIdentity::new(TwoByteInteger(2)),
+        packets::Properties::new(),
+        filters.iter().map(|filter| UTF8EncodedString(filter.to_string())).collect(),
+    )
+    .unwrap()
+}
+
+fn publish_packet(topic_name: &str, qos: QoS) -> Publish {
+    let packet_identifier = if qos == QoS::AtMostOnce {
+        None
+    } else {
+        Some(packets::PacketIdentity::new(TwoByteInteger(10)))
+    };
+    Publish::new(
+        Publish::fixed_header(false, qos, false),
+        UTF8EncodedString(topic_name.to_string()),
+        packet_identifier,
+        packets::Properties::new(),
+        b"hello".to_vec(),
+    )
+    .unwrap()
+}
+
+fn connect_user(handler: &mut Handler, client_id: &str, user_name: Option<&str>) -> session::SessionId {
+    let mut connect = connect_packet(5, client_id);
+    if let Some(user_name) = user_name {
+        connect.variable_header.connect_flags = ConnectFlags::new(Bits(0b1000_0010)).unwrap();
+        connect.payload.user_name = Some(UTF8EncodedString(user_name.to_string()));
+    }
+    let (session, _) = handler.handle_connect(&connect).unwrap();
+    session.session_id
+}
+
+const ACL_FILE: &str = "
+user alice
+topic readwrite alice/#
+topic read public/#
+user bob
+topic read alice/status
+";
+
+#[test]
+fn handle_subscribe_grants_subscriptions() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", None);
+
+    let (suback, _) = handler.handle_subscribe(&session_id, &subscribe_packet(&[("a/+", 0), ("b/#", 2), ("a/#/b", 1)])).unwrap();
+    assert_eq!(suback.reason_codes, vec![suback::GRANTED_QOS_0, suback::GRANTED_QOS_2, suback::TOPIC_FILTER_INVALID]);
+    let session = handler.get_session(&session_id).unwrap();
+    assert_eq!(session.subscriptions.len(), 2);
+    assert_eq!(session.subscriptions["b/#"].options.maximum_qos(), QoS::ExactlyOnce);
+}
+
+#[test]
+fn handle_unsubscribe_removes_subscriptions() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", None);
+    handler.handle_subscribe(&session_id, &subscribe_packet(&[("a/+", 0), ("b/#", 1)])).unwrap();
+
+    let unsuback = handler.handle_unsubscribe(&session_id, &unsubscribe_packet(&["a/+", "c"])).unwrap();
+    assert_eq!(unsuback.packet_identifier.val(), 2);
+    assert_eq!(unsuback.reason_codes, vec![unsuback::SUCCESS, unsuback::NO_SUBSCRIPTION_EXISTED]);
+    let session = handler.get_session(&session_id).unwrap();
+    assert_eq!(session.subscriptions.keys().collect::<Vec<_>>(), vec!["b/#"]);
+}
+
+#[test]
+fn handle_unsubscribe_stops_routing() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 0)])).unwrap();
+    handler.handle_unsubscribe(&subscriber, &unsubscribe_packet(&["a/b"])).unwrap();
+
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtMostOnce)).unwrap();
+    assert!(outgoings.is_empty());
+}
+
+#[test]
+fn handle_subscribe_rejects_unauthorized_topic_filter() {
+    let config = config::Config {
+        acl: Some(acl::Acl::parse(ACL_FILE).unwrap()),
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", Some("bob"));
+
+    let (suback, _) = handler.handle_subscribe(&session_id, &subscribe_packet(&[("alice/status", 1), ("alice/#", 1)])).unwrap();
+    assert_eq!(suback.reason_codes, vec![suback::GRANTED_QOS_1, suback::NOT_AUTHORIZED]);
+    assert_eq!(handler.get_session(&session_id).unwrap().subscriptions.len(), 1);
+}
+
+#[test]
+fn handle_publish_routes_to_matching_subscribers() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let publisher = connect_user(&mut handler, "client1", None);
+    let subscriber1 = connect_user(&mut handler, "client2", None);
+    let subscriber2 = connect_user(&mut handler, "client3", None);
+    handler.handle_subscribe(&subscriber1, &subscribe_packet(&[("a/+", 1), ("a/b", 0)])).unwrap();
+    handler.handle_subscribe(&subscriber2, &subscribe_packet(&[("a/b", 0)])).unwrap();
+
+    let mut outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+    let puback = outgoings.remove(0);
+    assert_eq!(puback.session_id, publisher);
+    assert_eq!(
+        puback.packet,
+        packets::Packet::PubAck(PubAck::new(
+            packets::PacketIdentity::new(TwoByteInteger(10)),
+            puback::SUCCESS,
+            packets::Properties::new()
+        ))
+    );
+
+    outgoings.sort_by_key(|outgoing| outgoing.session_id == subscriber2);
+    assert_eq!(outgoings.len(), 2);
+    let packets::Packet::Publish(delivery) = &outgoings[0].packet else {
+        panic!("PUBLISH Packet is expected");
+    };
+    assert_eq!(outgoings[0].session_id, subscriber1);
+    assert_eq!(delivery.qos(), QoS::AtLeastOnce);
+    assert_eq!(delivery.packet_identifier, Some(packets::PacketIdentity::new(TwoByteInteger(1))));
+    assert_eq!(delivery.payload, b"hello".to_vec());
+    let packets::Packet::Publish(delivery) = &outgoings[1].packet else {
+        panic!("PUBLISH Packet is expected");
+    };
+    assert_eq!(delivery.qos(), QoS::AtMostOnce);
+    assert_eq!(delivery.packet_identifier, None);
+}
+
+#[test]
+fn handle_publish_without_subscribers() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let publisher = connect_user(&mut handler, "client1", None);
+
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(outgoings.len(), 1);
+    let packets::Packet::PubAck(puback) = &outgoings[0].packet else {
+        panic!("PUBACK Packet is expected");
+    };
+    assert_eq!(puback.reason_code, puback::NO_MATCHING_SUBSCRIBERS);
+
+    assert!(handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtMostOnce)).unwrap().is_empty());
+}
+
+#[test]
+fn handle_publish_disconnects_invalid_topic_name() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let publisher = connect_user(&mut handler, "client1", None);
+
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/+", QoS::AtMostOnce)).unwrap();
+    assert_eq!(disconnect_reason_code(&outgoings), Some(disconnect::TOPIC_NAME_INVALID));
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(disconnect_reason_code(&outgoings), Some(disconnect::TOPIC_NAME_INVALID));
+}
+
+#[test]
+fn handle_publish_rejects_unauthorized_topic_name() {
+    let config = config::Config {
+        acl: Some(acl::Acl::parse(ACL_FILE).unwrap()),
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+    let alice = connect_user(&mut handler, "client1", Some("alice"));
+    let bob = connect_user(&mut handler, "client2", Some("bob"));
+    handler.handle_subscribe(&bob, &subscribe_packet(&[("alice/status", 1)])).unwrap();
+
+    let outgoings = handler.handle_publish(&bob, &publish_packet("alice/status", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(outgoings.len(), 1);
+    let packets::Packet::PubAck(puback) = &outgoings[0].packet else {
+        panic!("PUBACK Packet is expected");
+    };
+    assert_eq!(puback.reason_code, puback::NOT_AUTHORIZED);
+    assert!(handler.handle_publish(&bob, &publish_packet("alice/status", QoS::AtMostOnce)).unwrap().is_empty());
+
+    let outgoings = handler.handle_publish(&alice, &publish_packet("alice/status", QoS::AtMostOnce)).unwrap();
+    assert_eq!(outgoings.len(), 1);
+    assert_eq!(outgoings[0].session_id, bob);
+}
+
+#[test]
+fn handle_connect_from_identified_peer_skips_auth_provider() {
+    let config = config::Config {
+        auth_provider: std::sync::Arc::new(auth::InMemory::new()),
+        acl: Some(acl::Acl::parse(ACL_FILE).unwrap()),
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+
+    assert!(handler.handle_connect(&connect_packet(5, "client1")).is_err());
+    let peer = auth::Peer {
+        identity: Some(auth::PeerIdentity {
+            client_id: Some("device1".to_string()),
+            user_name: Some("alice".to_string()),
+        }),
+        credentials: None,
+    };
+    let (session, connack, _) = handler
+        .handle_connect_from(&connect_packet(5, "client1"), &peer)
+        .unwrap();
+    assert_eq!(connack.connect_reason_code, connack::SUCCESS);
+    assert_eq!(session.client_id.as_str(), "device1");
+    assert_eq!(session.user_name.as_deref(), Some("alice"));
+
+    let (suback, _) = handler.handle_subscribe(&session.session_id, &subscribe_packet(&[("alice/#", 0)])).unwrap();
+    assert_eq!(suback.reason_codes, vec![suback::GRANTED_QOS_0]);
+}
+
+#[test]
+fn handle_connect_from_passes_peer_credentials_to_auth_provider() {
+    let config = config::Config {
+        auth_provider: std::sync::Arc::new(auth::Hook::new(|credentials| match credentials.peer_credentials {
+            Some(peer_credentials) if peer_credentials.uid == 1000 => Ok(()),
+            _ => Err(auth::not_authorized("Only the uid 1000 is accepted")),
+        })),
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+
+    let peer = |uid| auth::Peer {
+        identity: None,
+        credentials: Some(auth::PeerCredentials { uid, gid: 1000, pid: Some(42) }),
+    };
+    assert!(handler.handle_connect_from(&connect_packet(5, "client1"), &peer(1000)).is_ok());
+    let connack = handler.handle_connect_from(&connect_packet(5, "client2"), &peer(1001)).unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::NOT_AUTHORIZED);
+    assert!(handler.handle_connect(&connect_packet(5, "client3")).is_err());
+}
+
+#[test]
+fn handle_connect_caps_session_expiry_interval() {
+    let config = config::Config {
+        session_expiry_interval_maximum: Some(600),
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+
+    let mut connect = connect_packet(5, "client1");
+    connect
+        .variable_header
+        .properties
+        .insert(packets::SESSION_EXPIRY_INTERVAL, packets::FourByteInteger(3600).into());
+    let (session, connack) = handler.handle_connect(&connect).unwrap();
+    assert_eq!(session.session_expiry_interval, 600);
+    assert_eq!(
+        connack.properties.get_as::<packets::FourByteInteger>(packets::SESSION_EXPIRY_INTERVAL).unwrap(),
+        Some(&packets::FourByteInteger(600))
+    );
+
+    connect
+        .variable_header
+        .properties
+        .insert(packets::SESSION_EXPIRY_INTERVAL, packets::FourByteInteger(60).into());
+    connect.payload.client_id = UTF8EncodedString("client2".to_string());
+    let (session, connack) = handler.handle_connect(&connect).unwrap();
+    assert_eq!(session.session_expiry_interval, 60);
+    assert_eq!(
+        connack.properties.get_as::<packets::FourByteInteger>(packets::SESSION_EXPIRY_INTERVAL).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn handle_subscribe_grants_configured_maximum_qos() {
+    let config = config::Config {
+        maximum_qos: QoS::AtMostOnce,
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", None);
+
+    let (suback, _) = handler.handle_subscribe(&session_id, &subscribe_packet(&[("a/b", 1)])).unwrap();
+    assert_eq!(suback.reason_codes, vec![suback::GRANTED_QOS_0]);
+}
+
+#[test]
+fn handle_connect_advertises_default_capabilities() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+
+    let (_, connack) = handler.handle_connect(&connect_packet(5, "client1")).unwrap();
+    let properties = &connack.properties;
+    // The capabilities which the absent properties mean are not sent.
+    assert_eq!(properties.get_as::<Bits>(packets::MAXIMUM_QOS).unwrap(), None);
+    assert_eq!(properties.get_as::<Bits>(packets::SUBSCRIPTION_IDENTIFIER_AVAILABLE).unwrap(), None);
+    assert_eq!(properties.get_as::<Bits>(packets::SHARED_SUBSCRIPTION_AVAILABLE).unwrap(), None);
+    assert_eq!(properties.get_as::<Bits>(packets::RETAIN_AVAILABLE).unwrap(), None);
+    assert_eq!(properties.get_as::<Bits>(packets::WILDCARD_SUBSCRIPTION_AVAILABLE).unwrap(), None);
+    assert_eq!(properties.get_as::<packets::FourByteInteger>(packets::MAXIMUM_PACKET_SIZE).unwrap(), None);
+    assert_eq!(properties.get_as::<TwoByteInteger>(packets::TOPIC_ALIAS_MAXIMUM).unwrap(), None);
+    assert_eq!(properties.get_as::<TwoByteInteger>(packets::RECEIVE_MAXIMUM).unwrap(), None);
+    assert_eq!(properties.get_as::<TwoByteInteger>(packets::SERVER_KEEP_ALIVE).unwrap(), None);
+    assert!(properties.is_empty());
+}
+
+#[test]
+fn handle_connect_advertises_configured_capabilities() {
+    let config = config::Config {
+        maximum_qos: QoS::AtMostOnce,
+        retain_available: false,
+        maximum_packet_size: Some(1024),
+        receive_maximum: 10,
+        wildcard_subscription_available: false,
+        server_keep_alive: Some(120),
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+
+    let (session, connack) = handler.handle_connect(&connect_packet(5, "client1")).unwrap();
+    let properties = &connack.properties;
+    assert_eq!(properties.get_as::<Bits>(packets::MAXIMUM_QOS).unwrap(), Some(&Bits(0)));
+    assert_eq!(properties.get_as::<Bits>(packets::RETAIN_AVAILABLE).unwrap(), Some(&Bits(0)));
+    assert_eq!(
+        properties.get_as::<packets::FourByteInteger>(packets::MAXIMUM_PACKET_SIZE).unwrap(),
+        Some(&packets::FourByteInteger(1024))
+    );
+    assert_eq!(properties.get_as::<TwoByteInteger>(packets::RECEIVE_MAXIMUM).unwrap(), Some(&TwoByteInteger(10)));
+    assert_eq!(properties.get_as::<Bits>(packets::WILDCARD_SUBSCRIPTION_AVAILABLE).unwrap(), Some(&Bits(0)));
+    assert_eq!(properties.get_as::<TwoByteInteger>(packets::SERVER_KEEP_ALIVE).unwrap(), Some(&TwoByteInteger(120)));
+    // The Server Keep Alive replaces the Keep Alive of the CONNECT Packet.
+    assert_eq!(session.keep_alive, chrono::Duration::seconds(120));
+}
+
+#[test]
+fn handle_connect_rejects_will_exceeding_capabilities() {
+    let config = config::Config {
+        maximum_qos: QoS::AtLeastOnce,
+        retain_available: false,
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+
+    // The Will Flag with the Will QoS 2.
+    let connect = connect_packet_with_flags(5, "client1", 0b0001_0110);
+    let connack = handler.handle_connect(&connect).unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::QOS_NOT_SUPPORTED);
+
+    // The Will Flag with the Will Retain.
+    let connect = connect_packet_with_flags(5, "client1", 0b0010_0110);
+    let connack = handler.handle_connect(&connect).unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::RETAIN_NOT_SUPPORTED);
+}
+
+#[test]
+fn handle_subscribe_rejects_unsupported_subscriptions() {
+    let config = config::Config {
+        wildcard_subscription_available: false,
+        subscription_identifier_available: false,
+        shared_subscription_available: false,
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", None);
+
+    let subscribe = subscribe_packet(&[("a/b", 1), ("a/+", 1), ("$share/group/a/b", 1)]);
+    let (suback, _) = handler.handle_subscribe(&session_id, &subscribe).unwrap();
+    assert_eq!(
+        suback.reason_codes,
+        vec![
+            suback::GRANTED_QOS_1,
+            suback::WILDCARD_SUBSCRIPTIONS_NOT_SUPPORTED,
+            suback::SHARED_SUBSCRIPTIONS_NOT_SUPPORTED,
+        ]
+    );
+
+    let mut subscribe = subscribe_packet(&[("a/c", 1)]);
+    subscribe.properties.insert(packets::SUBSCRIPTION_IDENTIFIER, VariableByteInteger(1).into());
+    let (suback, _) = handler.handle_subscribe(&session_id, &subscribe).unwrap();
+    assert_eq!(suback.reason_codes, vec![suback::SUBSCRIPTION_IDENTIFIERS_NOT_SUPPORTED]);
+    assert_eq!(handler.get_session(&session_id).unwrap().subscriptions.len(), 1);
+}
+
+fn disconnect_reason_code(outgoings: &[Outgoing]) -> Option<disconnect::DisconnectReasonCode> {
+    match outgoings {
+        [Outgoing { packet: packets::Packet::Disconnect(disconnect), .. }] => Some(disconnect.reason_code.clone()),
+        _ => None,
+    }
+}
+
+#[test]
+fn handle_publish_disconnects_publish_exceeding_capabilities() {
+    let config = config::Config {
+        maximum_qos: QoS::AtMostOnce,
+        retain_available: false,
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", None);
+
+    let outgoings = handler.handle_publish(&session_id, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(disconnect_reason_code(&outgoings), Some(disconnect::QOS_NOT_SUPPORTED));
+
+    let mut publish = publish_packet("a/b", QoS::AtMostOnce);
+    publish.fixed_header = Publish::fixed_header(false, QoS::AtMostOnce, true);
+    let outgoings = handler.handle_publish(&session_id, &publish).unwrap();
+    assert_eq!(disconnect_reason_code(&outgoings), Some(disconnect::RETAIN_NOT_SUPPORTED));
+
+    // No Topic Alias is accepted with the default Topic Alias Maximum 0.
+    let mut publish = publish_packet("a/b", QoS::AtMostOnce);
+    publish.properties.insert(packets::TOPIC_ALIAS, TwoByteInteger(1).into());
+    let outgoings = handler.handle_publish(&session_id, &publish).unwrap();
+    assert_eq!(disconnect_reason_code(&outgoings), Some(disconnect::TOPIC_ALIAS_INVALID));
+
+    let outgoings = handler.handle_publish(&session_id, &publish_packet("a/b", QoS::AtMostOnce)).unwrap();
+    assert!(outgoings.is_empty());
+}
+
+#[test]
+fn handle_connect_keeps_client_maximum_packet_size() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+
+    let mut connect = connect_packet(5, "client1");
+    connect
+        .variable_header
+        .properties
+        .insert(packets::MAXIMUM_PACKET_SIZE, packets::FourByteInteger(1024).into());
+    let (session, _) = handler.handle_connect(&connect).unwrap();
+    assert_eq!(session.maximum_packet_size, Some(1024));
+
+    connect
+        .variable_header
+        .properties
+        .insert(packets::MAXIMUM_PACKET_SIZE, packets::FourByteInteger(0).into());
+    let connack = handler.handle_connect(&connect).unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::PROTOCOL_ERROR);
+}
+
+fn publish_packet_identifiers(outgoings: &[Outgoing]) -> Vec<u16> {
+    outgoings
+        .iter()
+        .filter_map(|outgoing| match &outgoing.packet {
+            packets::Packet::Publish(publish) => publish.packet_identifier.as_ref().map(|id| id.val()),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn handle_publish_keeps_deliveries_within_client_receive_maximum() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let mut connect = connect_packet(5, "subscriber");
+    connect
+        .variable_header
+        .properties
+        .insert(packets::RECEIVE_MAXIMUM, TwoByteInteger(2).into());
+    let (subscriber, _) = handler.handle_connect(&connect).unwrap();
+    assert_eq!(subscriber.receive_maximum, 2);
+    let subscriber = subscriber.session_id;
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 1)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+
+    let mut delivered = Vec::new();
+    for _ in 0..3 {
+        let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+        // The publisher is acknowledged with Success even when the delivery waits for the quota.
+        assert!(matches!(
+            &outgoings[0].packet,
+            packets::Packet::PubAck(puback) if puback.reason_code == puback::SUCCESS
+        ));
+        delivered.extend(publish_packet_identifiers(&outgoings));
+    }
+    assert_eq!(delivered, vec![1, 2]);
+    assert_eq!(handler.get_session(&subscriber).unwrap().outbound_pending.len(), 1);
+
+    // The QoS 0 message does not need the quota.
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtMostOnce)).unwrap();
+    assert_eq!(outgoings.len(), 1);
+
+    let puback = PubAck::new(packets::PacketIdentity::new(TwoByteInteger(1)), puback::SUCCESS, packets::Properties::new());
+    let outgoings = handler.handle_puback(&subscriber, &puback).unwrap();
+    assert_eq!(publish_packet_identifiers(&outgoings), vec![3]);
+    assert!(outgoings.iter().all(|outgoing| outgoing.session_id == subscriber));
+
+    // The duplicated PUBACK Packet releases nothing.
+    assert!(handler.handle_puback(&subscriber, &puback).unwrap().is_empty());
+}
+
+#[test]
+fn handle_connect_rejects_zero_receive_maximum() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+
+    let mut connect = connect_packet(5, "client1");
+    connect
+        .variable_header
+        .properties
+        .insert(packets::RECEIVE_MAXIMUM, TwoByteInteger(0).into());
+    let connack = handler.handle_connect(&connect).unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::PROTOCOL_ERROR);
+}
+
+fn request_response_information(client_id: &str, request_response_information: u8) -> Connect {
+    let mut connect = connect_packet(5, client_id);
+    connect
+        .variable_header
+        .properties
+        .insert(packets::REQUEST_RESPONSE_INFORMATION, Bits(request_response_information).into());
+    connect
+}
+
+#[test]
+fn handle_connect_rejects_invalid_request_response_information() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+
+    let connack = handler.handle_connect(&request_response_information("client1", 2)).unwrap_err();
+    assert_eq!(connack.connect_reason_code, connack::PROTOCOL_ERROR);
+}
+
+#[test]
+fn handle_connect_returns_requested_response_information() {
+    let config = config::Config {
+        response_information_prefix: Some("responses/".to_string()),
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+
+    let (_, connack) = handler.handle_connect(&request_response_information("client1", 1)).unwrap();
+    assert_eq!(
+        connack.properties.get_as::<UTF8EncodedString>(packets::RESPONSE_INFORMATION).unwrap(),
+        Some(&UTF8EncodedString("responses/client1".to_string()))
+    );
+    for connect in [request_response_information("client2", 0), connect_packet(5, "client3")] {
+        let (_, connack) = handler.handle_connect(&connect).unwrap();
+        assert!(connack.properties.get_as::<UTF8EncodedString>(packets::RESPONSE_INFORMATION).unwrap().is_none());
+    }
+
+    // The Server without the prefix sends no Response Information even the Client requests it.
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let (_, connack) = handler.handle_connect(&request_response_information("client1", 1)).unwrap();
+    assert!(connack.properties.get_as::<UTF8EncodedString>(packets::RESPONSE_INFORMATION).unwrap().is_none());
+}
+
+#[test]
+fn handle_publish_forwards_response_topic_and_correlation_data() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let responder = connect_user(&mut handler, "responder", None);
+    handler.handle_subscribe(&responder, &subscribe_packet(&[("requests", 0)])).unwrap();
+    let requester = connect_user(&mut handler, "requester", None);
+
+    let mut request = publish_packet("requests", QoS::AtMostOnce);
+    request.properties.insert(packets::RESPONSE_TOPIC, UTF8EncodedString("responses/requester".to_string()).into());
+    request.properties.insert(packets::CORRELATION_DATA, packets::BinaryData(vec![0x01, 0x02]).into());
+    let outgoings = handler.handle_publish(&requester, &request).unwrap();
+
+    // The Server MUST send the Response Topic and the Correlation Data unaltered [MQTT-3.3.2-15] [MQTT-3.3.2-16].
+    let publishes = delivered_publishes(&outgoings);
+    assert_eq!(publishes.len(), 1);
+    let properties = &publishes[0].1.properties;
+    assert_eq!(
+        properties.get_as::<UTF8EncodedString>(packets::RESPONSE_TOPIC).unwrap(),
+        Some(&UTF8EncodedString("responses/requester".to_string()))
+    );
+    assert_eq!(
+        properties.get_as::<packets::BinaryData>(packets::CORRELATION_DATA).unwrap(),
+        Some(&packets::BinaryData(vec![0x01, 0x02]))
+    );
+}
+
+#[test]
+fn handle_publish_disconnects_publish_exceeding_receive_maximum() {
+    let config = config::Config {
+        receive_maximum: 1,
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", None);
+    let mut session = handler.get_session(&session_id).unwrap().clone();
+    session.inbound_inflight.insert(10);
+    handler.update_session(session);
+
+    let outgoings = handler.handle_publish(&session_id, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(disconnect_reason_code(&outgoings), Some(disconnect::RECEIVE_MAXIMUM_EXCEEDED));
+
+    // The QoS 0 message is not limited by the Receive Maximum.
+    let outgoings = handler.handle_publish(&session_id, &publish_packet("a/b", QoS::AtMostOnce)).unwrap();
+    assert!(outgoings.is_empty());
+}
+
+#[test]
+fn handle_publish_does_not_forward_topic_alias() {
+    let config = config::Config {
+        topic_alias_maximum: 10,
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+    let mut connect = connect_packet(5, "subscriber");
+    connect
+        .variable_header
+        .properties
+        .insert(packets::TOPIC_ALIAS_MAXIMUM, TwoByteInteger(5).into());
+    let (subscriber, _) = handler.handle_connect(&connect).unwrap();
+    assert_eq!(subscriber.topic_alias_maximum, 5);
+    handler.handle_subscribe(&subscriber.session_id, &subscribe_packet(&[("a/b", 0)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+
+    let mut publish = publish_packet("a/b", QoS::AtMostOnce);
+    publish.properties.insert(packets::TOPIC_ALIAS, TwoByteInteger(1).into());
+    let outgoings = handler.handle_publish(&publisher, &publish).unwrap();
+    let delivery = publish_packet("a/b", QoS::AtMostOnce);
+    assert_eq!(outgoings, vec![Outgoing::new(subscriber.session_id, packets::Packet::Publish(delivery))]);
+}
+
+fn delivered_sessions(outgoings: &[Outgoing]) -> Vec<session::SessionId> {
+    outgoings
+        .iter()
+        .filter(|outgoing| matches!(outgoing.packet, packets::Packet::Publish(_)))
+        .map(|outgoing| outgoing.session_id.clone())
+        .collect()
+}
+
+#[test]
+fn handle_subscribe_validates_shared_subscriptions() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", None);
+
+    let subscribe = subscribe_packet(&[("$share/group/a/#", 1), ("$share/gr+oup/a", 1), ("$share/group", 1)]);
+    let (suback, _) = handler.handle_subscribe(&session_id, &subscribe).unwrap();
+    assert_eq!(
+        suback.reason_codes,
+        vec![suback::GRANTED_QOS_1, suback::TOPIC_FILTER_INVALID, suback::TOPIC_FILTER_INVALID]
+    );
+    assert!(handler.get_session(&session_id).unwrap().subscriptions.contains_key("$share/group/a/#"));
+}
+
+#[test]
+fn handle_publish_delivers_shared_subscription_to_one_member() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let members: Vec<session::SessionId> = ["member1", "member2", "member3"]
+        .iter()
+        .map(|client_id| {
+            let session_id = connect_user(&mut handler, client_id, None);
+            handler.handle_subscribe(&session_id, &subscribe_packet(&[("$share/group/a/+", 1)])).unwrap();
+            session_id
+        })
+        .collect();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 0)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+
+    let mut delivered = Vec::new();
+    for _ in 0..3 {
+        let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+        let sessions = delivered_sessions(&outgoings);
+        // The non-shared subscription receives every message besides the Shared Subscription.
+        assert_eq!(sessions.len(), 2);
+        assert!(sessions.contains(&subscriber));
+        delivered.extend(sessions.into_iter().filter(|session_id| *session_id != subscriber));
+    }
+    // The default strategy is round-robin.
+    assert_eq!(delivered, members);
+}
+
+#[test]
+fn disconnect_session_redelivers_shared_messages() {
+    let config = config::Config {
+        shared_subscription_strategy: session::shared_subscription::SharedSubscriptionStrategy::LeastInflight,
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+    let member1 = connect_user(&mut handler, "member1", None);
+    let member2 = connect_user(&mut handler, "member2", None);
+    for session_id in [&member1, &member2] {
+        handler.handle_subscribe(session_id, &subscribe_packet(&[("$share/group/a/b", 1)])).unwrap();
+    }
+    handler.handle_subscribe(&member1, &subscribe_packet(&[("c/d", 1)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+
+    // The least in-flight member receives each message, so they receive one message each.
+    for _ in 0..2 {
+        handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+    }
+    handler.handle_publish(&publisher, &publish_packet("c/d", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(handler.get_session(&member1).unwrap().outbound_inflight.len(), 2);
+
+    let outgoings = handler.disconnect_session(&member1).unwrap();
+    assert_eq!(delivered_sessions(&outgoings), vec![member2.clone()]);
+    let session = handler.get_session(&member1).unwrap();
+    assert_eq!(session.state, session::SessionState::Disconnected);
+    // The message of the non-shared subscription stays in the session.
+    assert_eq!(session.outbound_inflight.len(), 1);
+    assert_eq!(handler.get_session(&member2).unwrap().outbound_inflight.len(), 2);
+
+    // No member is left to receive the messages.
+    assert!(handler.disconnect_session(&member2).unwrap().is_empty());
+    assert_eq!(handler.get_session(&member2).unwrap().outbound_pending.len(), 2);
+}
+
+fn subscribe_with_identifier(filters: &[(&str, u8)], subscription_identifier: u32) -> Subscribe {
+    let mut subscribe = subscribe_packet(filters);
+    subscribe
+        .properties
+        .insert(packets::SUBSCRIPTION_IDENTIFIER, VariableByteInteger(subscription_identifier).into());
+    subscribe
+}
+
+fn subscription_identifiers(outgoing: &Outgoing) -> Vec<u32> {
+    match &outgoing.packet {
+        packets::Packet::Publish(publish) => publish
+            .properties
+            .get_all_as::<VariableByteInteger>(packets::SUBSCRIPTION_IDENTIFIER)
+            .unwrap()
+            .iter()
+            .map(|identifier| identifier.val())
+            .collect(),
+        packet => panic!("PUBLISH Packet is expected: {:?}", packet),
+    }
+}
+
+#[test]
+fn handle_publish_attaches_matching_subscription_identifiers() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_with_identifier(&[("a/+", 0)], 7)).unwrap();
+    handler.handle_subscribe(&subscriber, &subscribe_with_identifier(&[("a/#", 0)], 300)).unwrap();
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 0), ("c/d", 0)])).unwrap();
+    handler.handle_subscribe(&subscriber, &subscribe_with_identifier(&[("$share/group/a/b", 0)], 9)).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtMostOnce)).unwrap();
+    let mut identifiers: Vec<Vec<u32>> = outgoings.iter().map(subscription_identifiers).collect();
+    identifiers.sort();
+    // The non-shared subscriptions result a message with their identifiers, and the Shared Subscription results another.
+    assert_eq!(identifiers, vec![vec![7, 300], vec![9]]);
+
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("c/d", QoS::AtMostOnce)).unwrap();
+    assert_eq!(subscription_identifiers(&outgoings[0]), Vec::<u32>::new());
+}
+
+#[test]
+fn handle_subscribe_rejects_zero_subscription_identifier() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", None);
+
+    assert!(handler.handle_subscribe(&session_id, &subscribe_with_identifier(&[("a/b", 0)], 0)).is_err());
+}
+
+#[test]
+fn handle_publish_disconnects_publish_with_subscription_identifier() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", None);
+
+    let mut publish = publish_packet("a/b", QoS::AtMostOnce);
+    publish.properties.append(packets::SUBSCRIPTION_IDENTIFIER, VariableByteInteger(1).into());
+    let outgoings = handler.handle_publish(&session_id, &publish).unwrap();
+    assert_eq!(disconnect_reason_code(&outgoings), Some(disconnect::PROTOCOL_ERROR));
+}
+
+fn retained_publish_packet(topic_name: &str, payload: &[u8]) -> Publish {
+    let mut publish = publish_packet(topic_name, QoS::AtLeastOnce);
+    publish.fixed_header = Publish::fixed_header(false, QoS::AtLeastOnce, true);
+    publish.payload = payload.to_vec();
+    publish
+}
+
+fn delivered_publishes(outgoings: &[Outgoing]) -> Vec<(session::SessionId, Publish)> {
+    outgoings
+        .iter()
+        .filter_map(|outgoing| match &outgoing.packet {
+            packets::Packet::Publish(publish) => Some((outgoing.session_id.clone(), publish.clone())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn handle_publish_honours_no_local() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let bridge = connect_user(&mut handler, "bridge", None);
+    // The No Local option is the bit 2.
+    handler.handle_subscribe(&bridge, &subscribe_packet(&[("a/#", 0b0000_0100)])).unwrap();
+    let other = connect_user(&mut handler, "other", None);
+    handler.handle_subscribe(&other, &subscribe_packet(&[("a/#", 0)])).unwrap();
+
+    let outgoings = handler.handle_publish(&bridge, &publish_packet("a/b", QoS::AtMostOnce)).unwrap();
+    assert_eq!(delivered_sessions(&outgoings), vec![other.clone()]);
+
+    let outgoings = handler.handle_publish(&other, &publish_packet("a/b", QoS::AtMostOnce)).unwrap();
+    let mut sessions = delivered_sessions(&outgoings);
+    sessions.sort_by_key(|session_id| session_id.0);
+    assert_eq!(sessions, vec![bridge, other]);
+}
+
+#[test]
+fn handle_publish_honours_retain_as_published() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    // The Retain As Published option is the bit 3, and the Retain Handling 2 sends no retained message.
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 0b0010_1001)])).unwrap();
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("c/d", 0b0010_0001)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+
+    let outgoings = handler.handle_publish(&publisher, &retained_publish_packet("a/b", b"on")).unwrap();
+    let publishes = delivered_publishes(&outgoings);
+    assert!(publishes[0].1.retain());
+
+    let outgoings = handler.handle_publish(&publisher, &retained_publish_packet("c/d", b"on")).unwrap();
+    let publishes = delivered_publishes(&outgoings);
+    assert!(!publishes[0].1.retain());
+}
+
+#[test]
+fn handle_subscribe_sends_retained_messages_by_retain_handling() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+    handler.handle_publish(&publisher, &retained_publish_packet("a/b", b"on")).unwrap();
+    handler.handle_publish(&publisher, &retained_publish_packet("a/c", b"off")).unwrap();
+    // The zero-length payload removes the retained message.
+    handler.handle_publish(&publisher, &retained_publish_packet("a/c", b"")).unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+
+    // Retain Handling 0 sends the retained messages at every subscribe, with the RETAIN flag.
+    for _ in 0..2 {
+        let (_, outgoings) = handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/+", 0)])).unwrap();
+        let publishes = delivered_publishes(&outgoings);
+        assert_eq!(publishes.len(), 1);
+        assert_eq!(publishes[0].0, subscriber);
+        assert_eq!(publishes[0].1.topic_name.val(), "a/b");
+        assert_eq!(publishes[0].1.payload, b"on".to_vec());
+        assert!(publishes[0].1.retain());
+        assert_eq!(publishes[0].1.qos(), QoS::AtMostOnce);
+    }
+
+    // Retain Handling 1 sends them only for the new subscription.
+    let (_, outgoings) = handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/#", 0b0001_0001)])).unwrap();
+    assert_eq!(delivered_publishes(&outgoings).len(), 1);
+    let (_, outgoings) = handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/#", 0b0001_0001)])).unwrap();
+    assert!(outgoings.is_empty());
+
+    // Retain Handling 2 never sends them, and neither does the Shared Subscription.
+    let (_, outgoings) = handler.handle_subscribe(&subscriber, &subscribe_packet(&[("+/b", 0b0010_0000)])).unwrap();
+    assert!(outgoings.is_empty());
+    let (_, outgoings) = handler.handle_subscribe(&subscriber, &subscribe_packet(&[("$share/g/a/b", 0)])).unwrap();
+    assert!(outgoings.is_empty());
+}
+
+#[test]
+fn handle_subscribe_rejects_no_local_shared_subscription() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", None);
+
+    let subscribe = subscribe_packet(&[("$share/group/a/b", 0b0000_0100)]);
+    assert!(handler.handle_subscribe(&session_id, &subscribe).is_err());
+}
+
+// connect_persistent connects the Client with Clean Start 0 and the Session Expiry Interval,
+// and results the session and the Session Present flag of the CONNACK Packet.
+fn connect_persistent(handler: &mut Handler, client_id: &str) -> (session::SessionId, bool) {
+    let (session, connack) = handler.handle_connect(&persistent_connect_packet(client_id)).unwrap();
+    (session.session_id, connack.session_present)
+}
+
+fn persistent_connect_packet(client_id: &str) -> Connect {
+    let mut connect = connect_packet_with_flags(5, client_id, 0b0000_0000);
+    connect
+        .variable_header
+        .properties
+        .insert(packets::SESSION_EXPIRY_INTERVAL, packets::FourByteInteger(3600).into());
+    connect
+}
+
+fn qos2_publish_packet(topic_name: &str, packet_identifier: u16, dup: bool) -> Publish {
+    let mut publish = publish_packet(topic_name, QoS::ExactlyOnce);
+    publish.fixed_header = Publish::fixed_header(dup, QoS::ExactlyOnce, false);
+    publish.packet_identifier = Some(packets::PacketIdentity::new(TwoByteInteger(packet_identifier)));
+    publish
+}
+
+fn packet_identity(packet_identifier: u16) -> packets::PacketIdentity {
+    packets::PacketIdentity::new(TwoByteInteger(packet_identifier))
+}
+
+fn pubrel_packet(packet_identifier: u16) -> PubRel {
+    PubRel::new(packet_identity(packet_identifier), pubrel::SUCCESS, packets::Properties::new())
+}
+
+fn pubrec_packet(packet_identifier: u16, reason_code: pubrec::PubRecReasonCode) -> PubRec {
+    PubRec::new(packet_identity(packet_identifier), reason_code, packets::Properties::new())
+}
+
+#[test]
+fn handle_publish_delivers_qos2_message_once() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 2)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+
+    let outgoings = handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 10, false)).unwrap();
+    let pubrec = pubrec_packet(10, pubrec::SUCCESS);
+    assert_eq!(outgoings[0], Outgoing::new(publisher.clone(), packets::Packet::PubRec(pubrec.clone())));
+    assert_eq!(delivered_publishes(&outgoings).len(), 1);
+    assert_eq!(delivered_publishes(&outgoings)[0].1.qos(), QoS::ExactlyOnce);
+
+    // The PUBREC Packet is lost, and the publisher sends the PUBLISH Packet again with the DUP flag.
+    let outgoings = handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 10, true)).unwrap();
+    assert_eq!(outgoings, vec![Outgoing::new(publisher.clone(), packets::Packet::PubRec(pubrec.clone()))]);
+    // The Packet Identifier is still in use even without the DUP flag.
+    let outgoings = handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 10, false)).unwrap();
+    assert_eq!(outgoings, vec![Outgoing::new(publisher.clone(), packets::Packet::PubRec(pubrec))]);
+
+    let outgoings = handler.handle_pubrel(&publisher, &pubrel_packet(10)).unwrap();
+    let pubcomp = PubComp::new(packet_identity(10), pubcomp::SUCCESS, packets::Properties::new());
+    assert_eq!(outgoings, vec![Outgoing::new(publisher.clone(), packets::Packet::PubComp(pubcomp))]);
+    assert!(handler.get_session(&publisher).unwrap().inbound_inflight.is_empty());
+
+    // The PUBCOMP Packet is lost, and the publisher sends the PUBREL Packet again.
+    let outgoings = handler.handle_pubrel(&publisher, &pubrel_packet(10)).unwrap();
+    let pubcomp = PubComp::new(packet_identity(10), pubcomp::PACKET_IDENTIFIER_NOT_FOUND, packets::Properties::new());
+    assert_eq!(outgoings, vec![Outgoing::new(publisher.clone(), packets::Packet::PubComp(pubcomp))]);
+
+    // After the PUBCOMP Packet, the Packet Identifier is a new message.
+    let outgoings = handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 10, false)).unwrap();
+    assert_eq!(delivered_publishes(&outgoings).len(), 1);
+}
+
+#[test]
+fn handle_publish_frees_packet_identifier_of_rejected_qos2_message() {
+    let config = config::Config {
+        acl: Some(acl::Acl::parse(ACL_FILE).unwrap()),
+        ..config::Config::default()
+    };
+    let handler = Handler::with_config(config);
+    let mut handler = handler.write().unwrap();
+    let session_id = connect_user(&mut handler, "client1", Some("bob"));
+
+    let outgoings = handler.handle_publish(&session_id, &qos2_publish_packet("alice/status", 10, false)).unwrap();
+    let pubrec = pubrec_packet(10, pubrec::NOT_AUTHORIZED);
+    assert_eq!(outgoings, vec![Outgoing::new(session_id.clone(), packets::Packet::PubRec(pubrec))]);
+    assert!(handler.get_session(&session_id).unwrap().inbound_inflight.is_empty());
+}
+
+#[test]
+fn handle_publish_keeps_qos2_packet_identifiers_across_reconnect() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 2)])).unwrap();
+    let (publisher, session_present) = connect_persistent(&mut handler, "publisher");
+    assert!(!session_present);
+
+    let outgoings = handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 10, false)).unwrap();
+    assert_eq!(delivered_publishes(&outgoings).len(), 1);
+
+    // The Network Connection is closed before the PUBREC Packet arrives, and the publisher resumes the session.
+    handler.disconnect_session(&publisher).unwrap();
+    let (resumed, session_present) = connect_persistent(&mut handler, "publisher");
+    assert!(session_present);
+    assert_eq!(resumed, publisher);
+    let outgoings = handler.handle_publish(&resumed, &qos2_publish_packet("a/b", 10, true)).unwrap();
+    assert_eq!(
+        outgoings,
+        vec![Outgoing::new(resumed.clone(), packets::Packet::PubRec(pubrec_packet(10, pubrec::SUCCESS)))]
+    );
+    let outgoings = handler.handle_pubrel(&resumed, &pubrel_packet(10)).unwrap();
+    assert!(matches!(&outgoings[0].packet, packets::Packet::PubComp(pubcomp) if pubcomp.reason_code == pubcomp::SUCCESS));
+}
+
+#[test]
+fn handle_connect_discards_session_by_clean_start() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 2)])).unwrap();
+    let (publisher, _) = connect_persistent(&mut handler, "publisher");
+    handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 10, false)).unwrap();
+    handler.disconnect_session(&publisher).unwrap();
+
+    let (session, connack) = handler.handle_connect(&connect_packet(5, "publisher")).unwrap();
+    assert!(!connack.session_present);
+    assert_ne!(session.session_id, publisher);
+    assert!(handler.get_session(&publisher).is_none());
+    // The PUBLISH Packet with the Packet Identifier is a new message in the new session.
+    let outgoings = handler.handle_publish(&session.session_id, &qos2_publish_packet("a/b", 10, false)).unwrap();
+    assert_eq!(delivered_publishes(&outgoings).len(), 1);
+
+    // The session with the Session Expiry Interval 0 ends with the Network Connection.
+    handler.disconnect_session(&session.session_id).unwrap();
+    let (_, connack) = handler.handle_connect(&connect_packet_with_flags(5, "publisher", 0b0000_0000)).unwrap();
+    assert!(!connack.session_present);
+}
+
+fn taken_over(session_id: &session::SessionId) -> Outgoing {
+    let disconnect = Disconnect::with_reason(disconnect::SESSION_TAKEN_OVER, "Another Client has connected with the ClientID");
+    Outgoing::new(session_id.clone(), packets::Packet::Disconnect(disconnect))
+}
+
+#[test]
+fn handle_connect_takes_over_connected_session() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let (first, _) = connect_persistent(&mut handler, "client1");
+    handler.handle_publish(&first, &qos2_publish_packet("a/b", 10, false)).unwrap();
+
+    // The second CONNECT Packet arrives while the Network Connection of the first one is still open.
+    let (second, connack, outgoings) = handler.handle_connect_from(&persistent_connect_packet("client1"), &auth::Peer::default()).unwrap();
+    assert_eq!(outgoings, vec![taken_over(&first)]);
+    assert!(connack.session_present);
+    assert_ne!(second.session_id, first);
+    assert!(second.inbound_inflight.contains(&10));
+    assert!(handler.get_session(&first).is_none());
+
+    // The closing connection of the first one no longer reaches the session.
+    assert!(handler.disconnect_session(&first).unwrap().is_empty());
+    assert_ne!(handler.get_session(&second.session_id).unwrap().state, session::SessionState::Disconnected);
+
+    // With Clean Start 1 the session is taken over and discarded.
+    let (third, connack, outgoings) = handler.handle_connect_from(&connect_packet(5, "client1"), &auth::Peer::default()).unwrap();
+    assert_eq!(outgoings, vec![taken_over(&second.session_id)]);
+    assert!(!connack.session_present);
+    assert!(third.inbound_inflight.is_empty());
+    assert_eq!(handler.sessions.values().filter(|session| session.client_id.as_str() == "client1").count(), 1);
+}
+
+#[test]
+fn handle_connect_publishes_will_message_of_session_taken_over() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("clients/+/status", 1)])).unwrap();
+    let client = connect_with_will(&mut handler, "client1");
+
+    let (_, _, outgoings) = handler.handle_connect_from(&connect_packet(5, "client1"), &auth::Peer::default()).unwrap();
+    assert_eq!(outgoings[0], taken_over(&client));
+    assert_eq!(published(&outgoings), vec![(subscriber, "clients/client1/status".to_string())]);
+}
+
+#[test]
+fn handle_pubrec_releases_qos2_delivery() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 2)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+    let outgoings = handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 10, false)).unwrap();
+    assert_eq!(publish_packet_identifiers(&outgoings), vec![1]);
+
+    let pubrel = PubRel::new(packet_identity(1), pubrel::SUCCESS, packets::Properties::new());
+    let outgoings = handler.handle_pubrec(&subscriber, &pubrec_packet(1, pubrec::SUCCESS)).unwrap();
+    assert_eq!(outgoings, vec![Outgoing::new(subscriber.clone(), packets::Packet::PubRel(pubrel.clone()))]);
+    // The PUBREL Packet is lost, and the subscriber sends the PUBREC Packet again.
+    let outgoings = handler.handle_pubrec(&subscriber, &pubrec_packet(1, pubrec::SUCCESS)).unwrap();
+    assert_eq!(outgoings, vec![Outgoing::new(subscriber.clone(), packets::Packet::PubRel(pubrel))]);
+    assert_eq!(handler.get_session(&subscriber).unwrap().outbound_released.len(), 1);
+
+    let pubcomp = PubComp::new(packet_identity(1), pubcomp::SUCCESS, packets::Properties::new());
+    assert!(handler.handle_pubcomp(&subscriber, &pubcomp).unwrap().is_empty());
+    let session = handler.get_session(&subscriber).unwrap();
+    assert!(session.outbound_inflight.is_empty());
+    assert!(session.outbound_released.is_empty());
+
+    // The PUBREC Packet of the unknown Packet Identifier.
+    let outgoings = handler.handle_pubrec(&subscriber, &pubrec_packet(1, pubrec::SUCCESS)).unwrap();
+    let pubrel = PubRel::new(packet_identity(1), pubrel::PACKET_IDENTIFIER_NOT_FOUND, packets::Properties::new());
+    assert_eq!(outgoings, vec![Outgoing::new(subscriber, packets::Packet::PubRel(pubrel))]);
+}
+
+#[test]
+fn handle_pubrec_with_failure_completes_delivery() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let mut connect = connect_packet(5, "subscriber");
+    connect
+        .variable_header
+        .properties
+        .insert(packets::RECEIVE_MAXIMUM, TwoByteInteger(1).into());
+    let (subscriber, _) = handler.handle_connect(&connect).unwrap();
+    let subscriber = subscriber.session_id;
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 2)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+    handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 10, false)).unwrap();
+    handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 11, false)).unwrap();
+
+    // No PUBREL Packet is sent, and the freed quota sends the pending message.
+    let outgoings = handler.handle_pubrec(&subscriber, &pubrec_packet(1, pubrec::UNSPECIFIED_ERROR)).unwrap();
+    assert_eq!(publish_packet_identifiers(&outgoings), vec![2]);
+    assert_eq!(outgoings.len(), 1);
+}
+
+#[test]
+fn resume_deliveries_sends_inflight_messages_again() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let (subscriber, _) = connect_persistent(&mut handler, "subscriber");
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 2)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+    handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 10, false)).unwrap();
+    handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 11, false)).unwrap();
+    handler.handle_pubrec(&subscriber, &pubrec_packet(1, pubrec::SUCCESS)).unwrap();
+
+    // The Network Connection is closed before the PUBCOMP Packet of 1 and the PUBREC Packet of 2.
+    handler.disconnect_session(&subscriber).unwrap();
+    let (resumed, session_present) = connect_persistent(&mut handler, "subscriber");
+    assert!(session_present);
+    let outgoings = handler.resume_deliveries(&resumed).unwrap();
+    assert_eq!(outgoings.len(), 2);
+    let pubrel = PubRel::new(packet_identity(1), pubrel::SUCCESS, packets::Properties::new());
+    assert_eq!(outgoings[0], Outgoing::new(resumed.clone(), packets::Packet::PubRel(pubrel)));
+    match &outgoings[1].packet {
+        packets::Packet::Publish(publish) => {
+            assert_eq!(publish.packet_identifier, Some(packet_identity(2)));
+            assert!(publish.dup());
+            assert_eq!(publish.qos(), QoS::ExactlyOnce);
+        }
+        packet => panic!("Unexpected packet: {:?}", packet),
+    }
+    // The subscriptions are kept as well.
+    let outgoings = handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 12, false)).unwrap();
+    assert_eq!(publish_packet_identifiers(&outgoings), vec![3]);
+}
+
+#[test]
+fn handle_publish_skips_packet_identifiers_in_use() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 1)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+    let mut session = handler.get_session(&subscriber).unwrap().clone();
+    session.packet_identifiers.reserve(1);
+    handler.update_session(session);
+
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(publish_packet_identifiers(&outgoings), vec![2]);
+
+    // The acknowledged Packet Identifier is reusable, but the next one is issued first.
+    let puback = PubAck::new(packets::PacketIdentity::new(TwoByteInteger(2)), puback::SUCCESS, packets::Properties::new());
+    handler.handle_puback(&subscriber, &puback).unwrap();
+    let session = handler.get_session(&subscriber).unwrap();
+    assert!(!session.packet_identifiers.is_in_use(2));
+    assert!(session.packet_identifiers.is_in_use(1));
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(publish_packet_identifiers(&outgoings), vec![3]);
+}
+
+#[test]
+fn handle_publish_rewrites_message_expiry_interval() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 1)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+
+    let mut publish = publish_packet("a/b", QoS::AtLeastOnce);
+    publish.properties.insert(packets::MESSAGE_EXPIRY_INTERVAL, packets::FourByteInteger(60).into());
+    let outgoings = handler.handle_publish(&publisher, &publish).unwrap();
+    let publishes = delivered_publishes(&outgoings);
+    assert!(matches!(publishes[0].1.message_expiry_interval(), Some(59..=60)));
+
+    // The message without the Message Expiry Interval does not expire.
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(delivered_publishes(&outgoings)[0].1.message_expiry_interval(), None);
+}
+
+#[test]
+fn handle_puback_drops_expired_pending_messages() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let mut connect = connect_packet(5, "subscriber");
+    connect
+        .variable_header
+        .properties
+        .insert(packets::RECEIVE_MAXIMUM, TwoByteInteger(1).into());
+    let (subscriber, _) = handler.handle_connect(&connect).unwrap();
+    let subscriber = subscriber.session_id;
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 1)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+
+    handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+    let mut publish = publish_packet("a/b", QoS::AtLeastOnce);
+    publish.properties.insert(packets::MESSAGE_EXPIRY_INTERVAL, packets::FourByteInteger(60).into());
+    handler.handle_publish(&publisher, &publish).unwrap();
+    handler.handle_publish(&publisher, &publish).unwrap();
+
+    // The first pending message has waited beyond its Message Expiry Interval, and the second one has waited 30 seconds.
+    let mut session = handler.get_session(&subscriber).unwrap().clone();
+    let now = chrono::Utc::now();
+    session.outbound_pending[0].expires_at = Some(now - chrono::Duration::seconds(1));
+    session.outbound_pending[1].expires_at = Some(now + chrono::Duration::seconds(30));
+    handler.update_session(session);
+
+    let puback = PubAck::new(packets::PacketIdentity::new(TwoByteInteger(1)), puback::SUCCESS, packets::Properties::new());
+    let outgoings = handler.handle_puback(&subscriber, &puback).unwrap();
+    let publishes = delivered_publishes(&outgoings);
+    assert_eq!(publishes.len(), 1);
+    assert!(matches!(publishes[0].1.message_expiry_interval(), Some(29..=30)));
+    assert!(handler.get_session(&subscriber).unwrap().outbound_pending.is_empty());
+}
+
+#[test]
+fn handle_subscribe_drops_expired_retained_messages() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+    for topic_name in ["a/1", "a/2", "a/3"] {
+        let mut publish = retained_publish_packet(topic_name, b"on");
+        publish.properties.insert(packets::MESSAGE_EXPIRY_INTERVAL, packets::FourByteInteger(60).into());
+        handler.handle_publish(&publisher, &publish).unwrap();
+    }
+    handler.handle_publish(&publisher, &retained_publish_packet("a/4", b"on")).unwrap();
+    let now = chrono::Utc::now();
+    handler.retained.get_mut("a/1").unwrap().expires_at = Some(now - chrono::Duration::seconds(1));
+    handler.retained.get_mut("a/2").unwrap().expires_at = Some(now);
+    handler.retained.get_mut("a/3").unwrap().expires_at = Some(now + chrono::Duration::seconds(10));
+
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    let (_, outgoings) = handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/+", 1)])).unwrap();
+    let publishes = delivered_publishes(&outgoings);
+    let topic_names: Vec<&str> = publishes.iter().map(|(_, publish)| publish.topic_name.val()).collect();
+    assert_eq!(topic_names, vec!["a/3", "a/4"]);
+    assert!(matches!(publishes[0].1.message_expiry_interval(), Some(9..=10)));
+    assert_eq!(publishes[1].1.message_expiry_interval(), None);
+    assert_eq!(handler.retained.len(), 2);
+}
+
+fn queue_config(max_messages: usize, max_bytes: usize, overflow_policy: OverflowPolicy) -> config::Config {
+    config::Config {
+        queue_limits: crate::session::queue::QueueLimits {
+            max_messages,
+            max_bytes,
+            overflow_policy,
+        },
+        ..config::Config::default()
+    }
+}
+
+// queued_topic_names publishes the messages of QoS 1 to a/1, a/2, ... while the subscriber is disconnected,
+// and results the Topic Names which the subscriber receives when it resumes the session.
+fn queued_topic_names(handler: &mut Handler, count: usize) -> Vec<String> {
+    let (subscriber, _) = connect_persistent(handler, "subscriber");
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/+", 1)])).unwrap();
+    handler.disconnect_session(&subscriber).unwrap();
+    let publisher = connect_user(handler, "publisher", None);
+    for i in 1..=count {
+        handler.handle_publish(&publisher, &publish_packet(&format!("a/{}", i), QoS::AtLeastOnce)).unwrap();
+    }
+
+    let (resumed, _) = connect_persistent(handler, "subscriber");
+    delivered_publishes(&handler.resume_deliveries(&resumed).unwrap())
+        .into_iter()
+        .map(|(_, publish)| publish.topic_name.val().to_string())
+        .collect()
+}
+
+#[test]
+fn handle_publish_queues_messages_for_disconnected_session() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let (subscriber, _) = connect_persistent(&mut handler, "subscriber");
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 2)])).unwrap();
+    let transient = connect_user(&mut handler, "transient", None);
+    handler.handle_subscribe(&transient, &subscribe_packet(&[("a/b", 2)])).unwrap();
+    handler.disconnect_session(&subscriber).unwrap();
+    handler.disconnect_session(&transient).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+
+    // The QoS 0 message is not queued.
+    assert!(handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtMostOnce)).unwrap().is_empty());
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(outgoings.len(), 1);
+    let packets::Packet::PubAck(puback) = &outgoings[0].packet else {
+        panic!("PUBACK Packet is expected");
+    };
+    assert_eq!(puback.reason_code, puback::SUCCESS);
+    let outgoings = handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 11, false)).unwrap();
+    assert_eq!(outgoings, vec![Outgoing::new(publisher, packets::Packet::PubRec(pubrec_packet(11, pubrec::SUCCESS)))]);
+    assert_eq!(handler.get_session(&subscriber).unwrap().outbound_pending.len(), 2);
+
+    let (resumed, _) = connect_persistent(&mut handler, "subscriber");
+    let outgoings = handler.resume_deliveries(&resumed).unwrap();
+    assert_eq!(publish_packet_identifiers(&outgoings), vec![1, 2]);
+    let publishes = delivered_publishes(&outgoings);
+    assert_eq!(publishes[0].1.qos(), QoS::AtLeastOnce);
+    assert_eq!(publishes[1].1.qos(), QoS::ExactlyOnce);
+    assert!(publishes.iter().all(|(session_id, publish)| *session_id == resumed && !publish.dup()));
+    assert!(handler.get_session(&resumed).unwrap().outbound_pending.is_empty());
+}
+
+#[test]
+fn handle_publish_drops_oldest_queued_messages() {
+    let handler = Handler::with_config(queue_config(2, usize::MAX, OverflowPolicy::DropOldest));
+    let mut handler = handler.write().unwrap();
+    assert_eq!(queued_topic_names(&mut handler, 3), vec!["a/2", "a/3"]);
+
+    // Each message is 8 bytes, the Topic Name of 3 bytes and the payload of 5 bytes.
+    let handler = Handler::with_config(queue_config(10, 20, OverflowPolicy::DropOldest));
+    let mut handler = handler.write().unwrap();
+    assert_eq!(queued_topic_names(&mut handler, 4), vec!["a/3", "a/4"]);
+}
+
+#[test]
+fn handle_publish_drops_newest_queued_messages() {
+    let handler = Handler::with_config(queue_config(2, usize::MAX, OverflowPolicy::DropNewest));
+    let mut handler = handler.write().unwrap();
+    assert_eq!(queued_topic_names(&mut handler, 3), vec!["a/1", "a/2"]);
+
+    let handler = Handler::with_config(queue_config(10, 20, OverflowPolicy::DropNewest));
+    let mut handler = handler.write().unwrap();
+    assert_eq!(queued_topic_names(&mut handler, 4), vec!["a/1", "a/2"]);
+}
+
+#[test]
+fn handle_publish_rejects_publisher_by_full_queue() {
+    let handler = Handler::with_config(queue_config(1, usize::MAX, OverflowPolicy::RejectPublisher));
+    let mut handler = handler.write().unwrap();
+    let (subscriber, _) = connect_persistent(&mut handler, "subscriber");
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 2)])).unwrap();
+    let online = connect_user(&mut handler, "online", None);
+    handler.handle_subscribe(&online, &subscribe_packet(&[("a/b", 2)])).unwrap();
+    handler.disconnect_session(&subscriber).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+    handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+
+    // The rejected message is delivered to nobody, even to the connected subscriber.
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(outgoings.len(), 1);
+    let packets::Packet::PubAck(puback) = &outgoings[0].packet else {
+        panic!("PUBACK Packet is expected");
+    };
+    assert_eq!(puback.reason_code, puback::QUOTA_EXCEEDED);
+    let outgoings = handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 12, false)).unwrap();
+    assert_eq!(
+        outgoings,
+        vec![Outgoing::new(publisher.clone(), packets::Packet::PubRec(pubrec_packet(12, pubrec::QUOTA_EXCEEDED)))]
+    );
+    // The Packet Identifier of the rejected QoS 2 message is free to use again.
+    let outgoings = handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 12, false)).unwrap();
+    assert_eq!(outgoings.len(), 1);
+    // The QoS 0 message is not queued, so it is not rejected.
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/b", QoS::AtMostOnce)).unwrap();
+    assert_eq!(delivered_publishes(&outgoings).len(), 1);
+
+    let (resumed, _) = connect_persistent(&mut handler, "subscriber");
+    assert_eq!(publish_packet_identifiers(&handler.resume_deliveries(&resumed).unwrap()), vec![1]);
+}
+
+fn stored_config(store: &Arc<dyn store::Store>) -> config::Config {
+    config::Config {
+        store: store.clone(),
+        ..config::Config::default()
+    }
+}
+
+#[test]
+fn restore_continues_persistent_sessions_and_retained_messages() {
+    let store: Arc<dyn store::Store> = Arc::new(store::InMemory::new());
+    {
+        let handler = Handler::with_config(stored_config(&store));
+        let mut handler = handler.write().unwrap();
+        let (subscriber, _) = connect_persistent(&mut handler, "subscriber");
+        handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/+", 2)])).unwrap();
+        let transient = connect_user(&mut handler, "transient", None);
+        handler.handle_subscribe(&transient, &subscribe_packet(&[("a/+", 2)])).unwrap();
+        let publisher = connect_user(&mut handler, "publisher", None);
+        handler.handle_publish(&publisher, &qos2_publish_packet("a/1", 10, false)).unwrap();
+        handler.disconnect_session(&subscriber).unwrap();
+        handler.handle_publish(&publisher, &retained_publish_packet("a/2", b"retained")).unwrap();
+        // The Server stops before the subscriber acknowledges the message in flight.
+    }
+
+    let handler = Handler::with_config(stored_config(&store));
+    let mut handler = handler.write().unwrap();
+    handler.restore().unwrap();
+    assert_eq!(handler.sessions.len(), 1);
+    let (resumed, session_present) = connect_persistent(&mut handler, "subscriber");
+    assert!(session_present);
+    let outgoings = handler.resume_deliveries(&resumed).unwrap();
+    assert_eq!(publish_packet_identifiers(&outgoings), vec![1, 2]);
+    let publishes = delivered_publishes(&outgoings);
+    assert_eq!(publishes[0].1.topic_name.val(), "a/1");
+    assert!(publishes[0].1.dup());
+    assert_eq!(publishes[1].1.topic_name.val(), "a/2");
+    assert!(!publishes[1].1.dup());
+
+    let other = connect_user(&mut handler, "other", None);
+    let (_, outgoings) = handler.handle_subscribe(&other, &subscribe_packet(&[("a/2", 0)])).unwrap();
+    assert_eq!(delivered_publishes(&outgoings)[0].1.payload, b"retained");
+}
+
+#[test]
+fn restore_discards_expired_sessions_and_retained_messages() {
+    let store: Arc<dyn store::Store> = Arc::new(store::InMemory::new());
+    let now = chrono::Utc::now();
+    let mut session = session::Session::new(
+        session::SessionId::new(1),
+        session::ClientId("c1".to_string()),
+        chrono::Duration::seconds(30),
+        packets::ProtocolVersion::V5,
+    );
+    session.session_expiry_interval = 5;
+    session.disconnected_at = Some(now - chrono::Duration::seconds(10));
+    store.save_session(&session).unwrap();
+    let expired = store::Retained::new(retained_publish_packet("a/1", b"expired"), Some(now - chrono::Duration::seconds(1)));
+    store.save_retained(&expired).unwrap();
+
+    let handler = Handler::with_config(stored_config(&store));
+    let mut handler = handler.write().unwrap();
+    handler.restore().unwrap();
+    assert!(handler.sessions.is_empty());
+    assert!(handler.retained.is_empty());
+    assert_eq!(store.load().unwrap(), store::State::new());
+}
+
+#[test]
+fn restore_recovers_from_write_ahead_log_after_crash() {
+    let path = std::env::temp_dir().join(format!("mini_mqtt_handler_crash_{}.log", std::process::id()));
+    let snapshot_path = std::env::temp_dir().join(format!("mini_mqtt_handler_crash_{}.log.snapshot", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&snapshot_path);
+    let open = || -> Arc<dyn store::Store> { Arc::new(store::append_log::AppendLog::open(&path).unwrap().with_snapshot_interval(5)) };
+    {
+        let store = open();
+        let handler = Handler::with_config(stored_config(&store));
+        let mut handler = handler.write().unwrap();
+        let (subscriber, _) = connect_persistent(&mut handler, "subscriber");
+        handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/+", 1)])).unwrap();
+        handler.handle_subscribe(&subscriber, &subscribe_packet(&[("b", 1)])).unwrap();
+        let publisher = connect_user(&mut handler, "publisher", None);
+        handler.handle_publish(&publisher, &publish_packet("a/1", QoS::AtLeastOnce)).unwrap();
+        handler.handle_publish(&publisher, &publish_packet("b", QoS::AtLeastOnce)).unwrap();
+        let puback = PubAck::new(packets::PacketIdentity::new(TwoByteInteger(1)), puback::SUCCESS, packets::Properties::new());
+        handler.handle_puback(&subscriber, &puback).unwrap();
+        // The Server crashes without closing the Network Connection.
+    }
+    assert!(snapshot_path.exists());
+
+    let store = open();
+    let handler = Handler::with_config(stored_config(&store));
+    let mut handler = handler.write().unwrap();
+    handler.restore().unwrap();
+    let (resumed, session_present) = connect_persistent(&mut handler, "subscriber");
+    assert!(session_present);
+    let session = handler.get_session(&resumed).unwrap();
+    let mut topic_filters: Vec<&str> = session.subscriptions.keys().map(String::as_str).collect();
+    topic_filters.sort();
+    assert_eq!(topic_filters, vec!["a/+", "b"]);
+    let outgoings = handler.resume_deliveries(&resumed).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&snapshot_path).unwrap();
+    assert_eq!(publish_packet_identifiers(&outgoings), vec![2]);
+    assert_eq!(delivered_publishes(&outgoings)[0].1.topic_name.val(), "b");
+}
+
+#[test]
+fn expire_sessions_removes_expired_sessions_from_store() {
+    let store: Arc<dyn store::Store> = Arc::new(store::InMemory::new());
+    let handler = Handler::with_config(stored_config(&store));
+    let mut handler = handler.write().unwrap();
+    let (session_id, _) = connect_persistent(&mut handler, "c1");
+    handler.disconnect_session(&session_id).unwrap();
+    let disconnected_at = handler.get_session(&session_id).unwrap().disconnected_at.unwrap();
+    handler.expire_sessions(disconnected_at + chrono::Duration::seconds(1)).unwrap();
+    assert!(handler.get_session(&session_id).is_some());
+
+    let expiry = handler.get_session(&session_id).unwrap().session_expiry_interval as i64;
+    handler.expire_sessions(disconnected_at + chrono::Duration::seconds(expiry)).unwrap();
+    assert!(handler.get_session(&session_id).is_none());
+    assert!(store.load().unwrap().sessions.is_empty());
+}
+
+#[test]
+fn handle_connect_removes_discarded_session_from_store() {
+    let store: Arc<dyn store::Store> = Arc::new(store::InMemory::new());
+    let handler = Handler::with_config(stored_config(&store));
+    let mut handler = handler.write().unwrap();
+    let (session_id, _) = connect_persistent(&mut handler, "c1");
+    assert!(store.load().unwrap().sessions.contains_key("c1"));
+    handler.disconnect_session(&session_id).unwrap();
+    assert!(store.load().unwrap().sessions["c1"].disconnected_at.is_some());
+
+    // Clean Start 1 discards the session, and the new one ends with its Network Connection.
+    connect_user(&mut handler, "c1", None);
+    assert!(store.load().unwrap().sessions.is_empty());
+}
+
+// RecordingStore keeps the records which the Handler saves in order, and fails every save while failing is set.
+#[derive(Debug, Default)]
+struct RecordingStore {
+    records: std::sync::Mutex<Vec<store::record::Record>>,
+    failing: std::sync::atomic::AtomicBool,
+}
+
+impl RecordingStore {
+    fn save(&self, record: store::record::Record) -> Result<(), errors::Error> {
+        if self.failing.load(std::sync::atomic::Ordering::SeqCst) {
+            return Err(errors::Error::Common("The store is failing".to_string()));
+        }
+        self.records.lock().unwrap().push(record);
+        Ok(())
+    }
+
+    fn take(&self) -> Vec<store::record::Record> {
+        std::mem::take(&mut *self.records.lock().unwrap())
+    }
+}
+
+impl store::Store for RecordingStore {
+    fn save_session(&self, session: &session::Session) -> Result<(), errors::Error> {
+        self.save(store::record::Record::Session(session.clone()))
+    }
+
+    fn remove_session(&self, client_id: &str) -> Result<(), errors::Error> {
+        self.save(store::record::Record::SessionRemoved(client_id.to_string()))
+    }
+
+    fn save_subscription(&self, client_id: &str, topic_filter: &str, subscription: &session::Subscription) -> Result<(), errors::Error> {
+        self.save(store::record::Record::Subscribed {
+            client_id: client_id.to_string(),
+            topic_filter: topic_filter.to_string(),
+            subscription: subscription.clone(),
+        })
+    }
+
+    fn remove_subscription(&self, client_id: &str, topic_filter: &str) -> Result<(), errors::Error> {
+        self.save(store::record::Record::Unsubscribed {
+            client_id: client_id.to_string(),
+            topic_filter: topic_filter.to_string(),
+        })
+    }
+
+    fn save_queued(&self, client_id: &str, delivery: &session::Delivery) -> Result<(), errors::Error> {
+        self.save(store::record::Record::Queued {
+            client_id: client_id.to_string(),
+            delivery: delivery.clone(),
+        })
+    }
+
+    fn save_in_flight(&self, client_id: &str, packet_identifier: u16, delivery: &session::Delivery) -> Result<(), errors::Error> {
+        self.save(store::record::Record::InFlight {
+            client_id: client_id.to_string(),
+            packet_identifier,
+            delivery: delivery.clone(),
+        })
+    }
+
+    fn remove_queued(&self, client_id: &str, count: usize) -> Result<(), errors::Error> {
+        self.save(store::record::Record::Dequeued {
+            client_id: client_id.to_string(),
+            count: count as u32,
+        })
+    }
+
+    fn acknowledge(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.save(store::record::Record::Acknowledged {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn release(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.save(store::record::Record::Released {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn save_inbound(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.save(store::record::Record::InboundReceived {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn remove_inbound(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.save(store::record::Record::InboundCompleted {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn save_retained(&self, retained: &store::Retained) -> Result<(), errors::Error> {
+        self.save(store::record::Record::Retained(retained.clone()))
+    }
+
+    fn remove_retained(&self, topic_name: &str) -> Result<(), errors::Error> {
+        self.save(store::record::Record::RetainedRemoved(topic_name.to_string()))
+    }
+
+    fn load(&self) -> Result<store::State, errors::Error> {
+        let mut state = store::State::new();
+        for record in self.records.lock().unwrap().iter() {
+            state.apply(record.clone());
+        }
+        Ok(state)
+    }
+}
+
+fn record_kinds(records: &[store::record::Record]) -> Vec<&'static str> {
+    records
+        .iter()
+        .map(|record| match record {
+            store::record::Record::Session(_) => "session",
+            store::record::Record::Queued { .. } => "queued",
+            store::record::Record::InFlight { .. } => "in_flight",
+            store::record::Record::Dequeued { .. } => "dequeued",
+            store::record::Record::Acknowledged { .. } => "acknowledged",
+            store::record::Record::Released { .. } => "released",
+            store::record::Record::InboundReceived { .. } => "inbound_received",
+            store::record::Record::InboundCompleted { .. } => "inbound_completed",
+            store::record::Record::Unsubscribed { .. } => "unsubscribed",
+            _ => "other",
+        })
+        .collect()
+}
+
+#[test]
+fn handle_publish_stores_only_changed_deliveries() {
+    let recording = Arc::new(RecordingStore::default());
+    let store: Arc<dyn store::Store> = recording.clone();
+    let handler = Handler::with_config(stored_config(&store));
+    let mut handler = handler.write().unwrap();
+    let (subscriber, _) = connect_persistent(&mut handler, "subscriber");
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/+", 1)])).unwrap();
+    let transient = connect_user(&mut handler, "transient", None);
+    handler.handle_subscribe(&transient, &subscribe_packet(&[("a/+", 1)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+    recording.take();
+
+    // The QoS 0 message to the connected sessions changes nothing durable.
+    handler.handle_publish(&publisher, &publish_packet("a/1", QoS::AtMostOnce)).unwrap();
+    assert!(recording.take().is_empty());
+
+    // Only the persistent session saves the message in flight, by itself.
+    handler.handle_publish(&publisher, &publish_packet("a/1", QoS::AtLeastOnce)).unwrap();
+    let records = recording.take();
+    assert_eq!(record_kinds(&records), vec!["in_flight"]);
+    assert!(matches!(&records[0], store::record::Record::InFlight { client_id, packet_identifier: 1, .. } if client_id == "subscriber"));
+
+    handler.disconnect_session(&subscriber).unwrap();
+    recording.take();
+    handler.handle_publish(&publisher, &publish_packet("a/2", QoS::AtLeastOnce)).unwrap();
+    assert_eq!(record_kinds(&recording.take()), vec!["queued"]);
+}
+
+#[test]
+fn qos2_flows_store_only_changed_packet_identifiers() {
+    let recording = Arc::new(RecordingStore::default());
+    let store: Arc<dyn store::Store> = recording.clone();
+    let handler = Handler::with_config(stored_config(&store));
+    let mut handler = handler.write().unwrap();
+    let (subscriber, _) = connect_persistent(&mut handler, "subscriber");
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/b", 2)])).unwrap();
+    let (publisher, _) = connect_persistent(&mut handler, "publisher");
+    recording.take();
+
+    // The Packet Identifier of the publisher is stored before the message is routed.
+    handler.handle_publish(&publisher, &qos2_publish_packet("a/b", 10, false)).unwrap();
+    assert_eq!(record_kinds(&recording.take()), vec!["inbound_received", "in_flight"]);
+    handler.handle_pubrec(&subscriber, &pubrec_packet(1, pubrec::SUCCESS)).unwrap();
+    assert_eq!(record_kinds(&recording.take()), vec!["released"]);
+    handler.handle_pubrel(&publisher, &pubrel_packet(10)).unwrap();
+    assert_eq!(record_kinds(&recording.take()), vec!["inbound_completed"]);
+    let pubcomp = PubComp::new(packet_identity(1), pubcomp::SUCCESS, packets::Properties::new());
+    handler.handle_pubcomp(&subscriber, &pubcomp).unwrap();
+    assert_eq!(record_kinds(&recording.take()), vec!["acknowledged"]);
+
+    // The unknown Packet Identifiers change nothing.
+    handler.handle_pubrec(&subscriber, &pubrec_packet(1, pubrec::SUCCESS)).unwrap();
+    handler.handle_pubrel(&publisher, &pubrel_packet(10)).unwrap();
+    assert!(recording.take().is_empty());
+}
+
+#[test]
+fn resume_deliveries_stores_dequeued_messages() {
+    let recording = Arc::new(RecordingStore::default());
+    let store: Arc<dyn store::Store> = recording.clone();
+    let handler = Handler::with_config(stored_config(&store));
+    let mut handler = handler.write().unwrap();
+    let (subscriber, _) = connect_persistent(&mut handler, "subscriber");
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/+", 1)])).unwrap();
+    handler.disconnect_session(&subscriber).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+    handler.handle_publish(&publisher, &publish_packet("a/1", QoS::AtLeastOnce)).unwrap();
+    handler.handle_publish(&publisher, &publish_packet("a/2", QoS::AtLeastOnce)).unwrap();
+
+    let (resumed, _) = connect_persistent(&mut handler, "subscriber");
+    recording.take();
+    let outgoings = handler.resume_deliveries(&resumed).unwrap();
+    assert_eq!(publish_packet_identifiers(&outgoings), vec![1, 2]);
+    let records = recording.take();
+    assert_eq!(record_kinds(&records), vec!["dequeued", "in_flight", "in_flight"]);
+    assert!(matches!(&records[0], store::record::Record::Dequeued { count: 2, .. }));
+}
+
+#[test]
+fn restore_does_not_resubscribe_unsubscribed_topic_filter_after_crash() {
+    let path = std::env::temp_dir().join(format!("mini_mqtt_handler_unsubscribe_{}.log", std::process::id()));
+    let snapshot_path = std::env::temp_dir().join(format!("mini_mqtt_handler_unsubscribe_{}.log.snapshot", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&snapshot_path);
+    let open = || -> Arc<dyn store::Store> { Arc::new(store::append_log::AppendLog::open(&path).unwrap()) };
+    {
+        let store = open();
+        let handler = Handler::with_config(stored_config(&store));
+        let mut handler = handler.write().unwrap();
+        let (subscriber, _) = connect_persistent(&mut handler, "subscriber");
+        handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/+", 1), ("b", 1)])).unwrap();
+        handler.handle_unsubscribe(&subscriber, &unsubscribe_packet(&["b"])).unwrap();
+        // The Server crashes without closing the Network Connection.
+    }
+
+    let store = open();
+    let handler = Handler::with_config(stored_config(&store));
+    let mut handler = handler.write().unwrap();
+    handler.restore().unwrap();
+    let (resumed, session_present) = connect_persistent(&mut handler, "subscriber");
+    std::fs::remove_file(&path).unwrap();
+    assert!(session_present);
+    let session = handler.get_session(&resumed).unwrap();
+    assert_eq!(session.subscriptions.keys().collect::<Vec<_>>(), vec!["a/+"]);
+}
+
+#[test]
+fn handle_publish_answers_publisher_when_store_fails() {
+    let recording = Arc::new(RecordingStore::default());
+    let store: Arc<dyn store::Store> = recording.clone();
+    let handler = Handler::with_config(stored_config(&store));
+    let mut handler = handler.write().unwrap();
+    let (subscriber, _) = connect_persistent(&mut handler, "subscriber");
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("a/+", 1)])).unwrap();
+    let publisher = connect_user(&mut handler, "publisher", None);
+
+    recording.failing.store(true, std::sync::atomic::Ordering::SeqCst);
+    let outgoings = handler.handle_publish(&publisher, &publish_packet("a/1", QoS::AtLeastOnce)).unwrap();
+    assert!(matches!(&outgoings[0].packet, packets::Packet::PubAck(puback) if puback.reason_code == puback::SUCCESS));
+    assert_eq!(publish_packet_identifiers(&outgoings), vec![1]);
+}
+
+fn connect_with_will(handler: &mut Handler, client_id: &str) -> session::SessionId {
+    // Will QoS 1 and Will Flag.
+    let mut connect = connect_packet_with_flags(5, client_id, 0b0000_1110);
+    connect.payload.will_topic = Some(UTF8EncodedString(format!("clients/{}/status", client_id)));
+    connect.payload.will_payload = Some(packets::BinaryData(b"gone".to_vec()));
+    let (session, _) = handler.handle_connect(&connect).unwrap();
+    session.session_id
+}
+
+fn published(outgoings: &[Outgoing]) -> Vec<(session::SessionId, String)> {
+    outgoings
+        .iter()
+        .filter_map(|outgoing| match &outgoing.packet {
+            packets::Packet::Publish(publish) => Some((outgoing.session_id.clone(), publish.topic_name.val().to_string())),
+            _ => None,
+        })
+        .collect()
+}
+
+#[test]
+fn disconnect_session_publishes_will_message() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("clients/+/status", 1)])).unwrap();
+    let client = connect_with_will(&mut handler, "client1");
+
+    let outgoings = handler.disconnect_session(&client).unwrap();
+    assert_eq!(published(&outgoings), vec![(subscriber.clone(), "clients/client1/status".to_string())]);
+    assert!(handler.get_session(&client).unwrap().will.is_none());
+}
+
+#[test]
+fn handle_disconnect_discards_will_message() {
+    let handler = Handler::new();
+    let mut handler = handler.write().unwrap();
+    let subscriber = connect_user(&mut handler, "subscriber", None);
+    handler.handle_subscribe(&subscriber, &subscribe_packet(&[("clients/+/status", 1)])).unwrap();
+
+    let client1 = connect_with_will(&mut handler, "client1");
+    handler.handle_disconnect(&client1, &Disconnect::new(disconnect::NORMAL_DISCONNECTION, packets::Properties::new()));
+    assert!(handler.disconnect_session(&client1).unwrap().is_empty());
+
+    // The Reason Code 0x04 (Disconnect with Will Message) keeps the Will Message.
+    let client2 = connect_with_will(&mut handler, "client2");
+    handler.handle_disconnect(&client2, &Disconnect::new(disconnect::DISCONNECT_WITH_WILL_MESSAGE, packets::Properties::new()));
+    let outgoings = handler.disconnect_session(&client2).unwrap();
+    assert_eq!(published(&outgoings), vec![(subscriber, "clients/client2/status".to_string())]);
+}
+
+#[test]
+fn disconnect_session_does_not_store_replaced_session() {
+    let recording = Arc::new(RecordingStore::default());
+    let store: Arc<dyn store::Store> = recording.clone();
+    let handler = Handler::with_config(stored_config(&store));
+    let mut handler = handler.write().unwrap();
+    let (older, _) = connect_persistent(&mut handler, "client1");
+    let mut newer = handler.create_session(&session::ClientId("client1".to_string()), chrono::Duration::zero(), packets::ProtocolVersion::V5);
+    newer.session_expiry_interval = 3600;
+    handler.update_session(newer.clone());
+    recording.take();
+
+    // The older session must not overwrite the record of the ClientID which the newer session owns.
+    handler.disconnect_session(&older).unwrap();
+    assert!(recording.take().is_empty());
+
+    handler.disconnect_session(&newer.session_id).unwrap();
+    let records = recording.take();
+    assert!(matches!(&records[..], [store::record::Record::Session(session)] if session.session_id == newer.session_id));
+}
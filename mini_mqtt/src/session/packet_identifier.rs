@@ -0,0 +1,68 @@
+use std::collections::BTreeSet;
+
+use crate::packets;
+
+#[path = "packet_identifier_tests.rs"]
+#[cfg(test)]
+mod packet_identifier_tests;
+
+// PacketIdentifierAllocator issues the Packet Identifiers of a session, for the PUBLISH Packets with QoS > 0
+// and the SUBSCRIBE and UNSUBSCRIBE Packets which the session sends (2.2.1 Packet Identifier subsection).
+// The Packet Identifier MUST be non-zero and unused [MQTT-2.2.1-3], and it becomes reusable
+// after the sender has processed its acknowledgement, so each issued one is kept until release.
+// The Packet Identifiers are issued in order and wrap around after 65,535, so a released one is not reused at once.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct PacketIdentifierAllocator {
+    last: u16,
+    in_use: BTreeSet<u16>,
+}
+
+impl PacketIdentifierAllocator {
+    pub fn new() -> PacketIdentifierAllocator {
+        PacketIdentifierAllocator::default()
+    }
+
+    // allocate results the next unused Packet Identifier and marks it in use.
+    // It results None when all 65,535 Packet Identifiers are in flight, the sender must wait for an acknowledgement then.
+    pub fn allocate(&mut self) -> Option<packets::PacketIdentity> {
+        if self.is_exhausted() {
+            return None;
+        }
+        let mut candidate = self.last;
+        loop {
+            candidate = candidate.wrapping_add(1).max(1);
+            if self.in_use.insert(candidate) {
+                self.last = candidate;
+                return Some(packets::PacketIdentity::new(packets::TwoByteInteger(candidate)));
+            }
+        }
+    }
+
+    // reserve marks the Packet Identifier in use, e.g. the one of a message in flight restored from the storage.
+    // It results false when the Packet Identifier is 0 or already in use.
+    pub fn reserve(&mut self, packet_identifier: u16) -> bool {
+        packet_identifier != 0 && self.in_use.insert(packet_identifier)
+    }
+
+    // release makes the Packet Identifier reusable when its exchange is complete.
+    // It results false for the Packet Identifier which is not in use.
+    pub fn release(&mut self, packet_identifier: u16) -> bool {
+        self.in_use.remove(&packet_identifier)
+    }
+
+    pub fn is_in_use(&self, packet_identifier: u16) -> bool {
+        self.in_use.contains(&packet_identifier)
+    }
+
+    pub fn is_exhausted(&self) -> bool {
+        self.in_use.len() >= u16::MAX as usize
+    }
+
+    pub fn len(&self) -> usize {
+        self.in_use.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.in_use.is_empty()
+    }
+}
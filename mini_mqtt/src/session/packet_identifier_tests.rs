@@ -0,0 +1,58 @@
+use super::*;
+use crate::packets::ExtractValue;
+
+fn allocate(allocator: &mut PacketIdentifierAllocator) -> u16 {
+    allocator.allocate().unwrap().val()
+}
+
+#[test]
+fn allocate_issues_non_zero_identifiers_in_order() {
+    let mut allocator = PacketIdentifierAllocator::new();
+
+    assert_eq!(allocate(&mut allocator), 1);
+    assert_eq!(allocate(&mut allocator), 2);
+    assert_eq!(allocator.len(), 2);
+    assert!(allocator.is_in_use(1));
+    assert!(!allocator.is_in_use(3));
+}
+
+#[test]
+fn allocate_does_not_reuse_released_identifier_at_once() {
+    let mut allocator = PacketIdentifierAllocator::new();
+    allocate(&mut allocator);
+    allocate(&mut allocator);
+
+    assert!(allocator.release(1));
+    assert!(!allocator.release(1));
+    assert_eq!(allocate(&mut allocator), 3);
+}
+
+#[test]
+fn allocate_wraps_around_skipping_zero_and_identifiers_in_flight() {
+    let mut allocator = PacketIdentifierAllocator::new();
+    assert!(allocator.reserve(1));
+    assert!(allocator.reserve(u16::MAX - 1));
+    assert!(!allocator.reserve(0));
+    assert!(!allocator.reserve(1));
+    for packet_identifier in 2..u16::MAX - 1 {
+        assert_eq!(allocate(&mut allocator), packet_identifier);
+        allocator.release(packet_identifier);
+    }
+
+    assert_eq!(allocate(&mut allocator), u16::MAX);
+    // 0 is never issued, and 1 is still in flight.
+    assert_eq!(allocate(&mut allocator), 2);
+}
+
+#[test]
+fn allocate_reports_exhaustion() {
+    let mut allocator = PacketIdentifierAllocator::new();
+    for _ in 0..u16::MAX {
+        assert!(allocator.allocate().is_some());
+    }
+
+    assert!(allocator.is_exhausted());
+    assert!(allocator.allocate().is_none());
+    allocator.release(300);
+    assert_eq!(allocate(&mut allocator), 300);
+}
@@ -0,0 +1,54 @@
+use crate::errors;
+
+#[path = "queue_tests.rs"]
+#[cfg(test)]
+mod queue_tests;
+
+// OverflowPolicy decides which message is lost when the queue of a session is full.
+// The queue keeps the QoS 1 and QoS 2 messages of a disconnected persistent session until the Client resumes it,
+// as well as the ones waiting for the Receive Maximum of the Client (4.1 Session State section).
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum OverflowPolicy {
+    // The oldest messages are dropped to keep the new one.
+    #[default]
+    DropOldest,
+    // The new message is dropped.
+    DropNewest,
+    // The new message is delivered to nobody, and the publisher is answered with the Reason Code 0x97 (Quota exceeded).
+    RejectPublisher,
+}
+
+impl std::str::FromStr for OverflowPolicy {
+    type Err = errors::Error;
+
+    fn from_str(policy: &str) -> Result<OverflowPolicy, errors::Error> {
+        match policy {
+            "drop_oldest" => Ok(OverflowPolicy::DropOldest),
+            "drop_newest" => Ok(OverflowPolicy::DropNewest),
+            "reject_publisher" => Ok(OverflowPolicy::RejectPublisher),
+            _ => Err(errors::Error::Common(format!(
+                "Unknown queue overflow policy {}, it must be drop_oldest, drop_newest or reject_publisher",
+                policy
+            ))),
+        }
+    }
+}
+
+// QueueLimits are the limits of the queue of each session.
+// max_bytes limits the sum of the sizes of the queued messages, which are the sizes of their Topic Names and payloads.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct QueueLimits {
+    pub max_messages: usize,
+    pub max_bytes: usize,
+    pub overflow_policy: OverflowPolicy,
+}
+
+impl Default for QueueLimits {
+    fn default() -> QueueLimits {
+        QueueLimits {
+            max_messages: 1000,
+            max_bytes: 16 * 1024 * 1024,
+            overflow_policy: OverflowPolicy::default(),
+        }
+    }
+}
@@ -0,0 +1,9 @@
+use super::*;
+
+#[test]
+fn parse_overflow_policy() {
+    assert_eq!("drop_oldest".parse::<OverflowPolicy>().unwrap(), OverflowPolicy::DropOldest);
+    assert_eq!("drop_newest".parse::<OverflowPolicy>().unwrap(), OverflowPolicy::DropNewest);
+    assert_eq!("reject_publisher".parse::<OverflowPolicy>().unwrap(), OverflowPolicy::RejectPublisher);
+    assert!("drop-oldest".parse::<OverflowPolicy>().is_err());
+}
@@ -0,0 +1,93 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::errors;
+
+#[path = "shared_subscription_tests.rs"]
+#[cfg(test)]
+mod shared_subscription_tests;
+
+// Each message matching a Shared Subscription is delivered to only one of the sessions which share it
+// (4.8.2 Shared Subscriptions subsection). The specification leaves the choice of the session to the Server,
+// and SharedSubscriptionStrategy is how this implementation chooses it.
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default)]
+pub enum SharedSubscriptionStrategy {
+    // The sessions receive the messages in turn.
+    #[default]
+    RoundRobin,
+    // A session is chosen at random for each message.
+    Random,
+    // The session which has the fewest QoS 1 and QoS 2 messages waiting for the acknowledgements.
+    LeastInflight,
+    // The messages of a Topic Name are delivered to the same session while the sessions are unchanged.
+    StickyByTopicHash,
+}
+
+impl std::str::FromStr for SharedSubscriptionStrategy {
+    type Err = errors::Error;
+
+    fn from_str(strategy: &str) -> Result<SharedSubscriptionStrategy, errors::Error> {
+        match strategy {
+            "round_robin" => Ok(SharedSubscriptionStrategy::RoundRobin),
+            "random" => Ok(SharedSubscriptionStrategy::Random),
+            "least_inflight" => Ok(SharedSubscriptionStrategy::LeastInflight),
+            "sticky_by_topic_hash" => Ok(SharedSubscriptionStrategy::StickyByTopicHash),
+            _ => Err(errors::Error::Common(format!(
+                "Unknown shared subscription strategy {}, it must be round_robin, random, least_inflight or sticky_by_topic_hash",
+                strategy
+            ))),
+        }
+    }
+}
+
+// Balancer chooses the session of the Shared Subscription by the strategy.
+// It keeps the state which the strategy needs across the messages, e.g. the turn of each Shared Subscription.
+#[derive(Debug)]
+pub struct Balancer {
+    strategy: SharedSubscriptionStrategy,
+    turns: HashMap<String, usize>,
+    random: u64,
+}
+
+impl Balancer {
+    pub fn new(strategy: SharedSubscriptionStrategy) -> Balancer {
+        // The seed of the xorshift generator must not be 0.
+        let seed = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default() as u64;
+        Balancer {
+            strategy,
+            turns: HashMap::new(),
+            random: seed | 1,
+        }
+    }
+
+    // select results the index of the session which receives the message of the Topic Name.
+    // The sessions of the Shared Subscription are provided as their numbers of the messages in flight,
+    // in an order which is stable across the messages. There must be at least one session.
+    pub fn select(&mut self, shared_subscription: &str, topic_name: &str, inflight: &[usize]) -> usize {
+        let count = inflight.len();
+        match self.strategy {
+            SharedSubscriptionStrategy::RoundRobin => {
+                let turn = self.turns.entry(shared_subscription.to_string()).or_insert(0);
+                let index = *turn % count;
+                *turn = index + 1;
+                index
+            }
+            SharedSubscriptionStrategy::Random => {
+                self.random ^= self.random << 13;
+                self.random ^= self.random >> 7;
+                self.random ^= self.random << 17;
+                (self.random % count as u64) as usize
+            }
+            SharedSubscriptionStrategy::LeastInflight => {
+                // The first one wins the tie, so the idle sessions are chosen in the stable order.
+                (0..count).min_by_key(|index| inflight[*index]).unwrap_or(0)
+            }
+            SharedSubscriptionStrategy::StickyByTopicHash => {
+                // DefaultHasher::new always has the same keys, so the choice survives a restart of the Server.
+                let mut hasher = std::collections::hash_map::DefaultHasher::new();
+                topic_name.hash(&mut hasher);
+                (hasher.finish() % count as u64) as usize
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use super::*;
+
+#[test]
+fn parse_strategy() {
+    assert_eq!("round_robin".parse::<SharedSubscriptionStrategy>().unwrap(), SharedSubscriptionStrategy::RoundRobin);
+    assert_eq!(
+        "sticky_by_topic_hash".parse::<SharedSubscriptionStrategy>().unwrap(),
+        SharedSubscriptionStrategy::StickyByTopicHash
+    );
+    assert!("least-inflight".parse::<SharedSubscriptionStrategy>().is_err());
+}
+
+#[test]
+fn select_round_robin_per_shared_subscription() {
+    let mut balancer = Balancer::new(SharedSubscriptionStrategy::RoundRobin);
+
+    let selected: Vec<usize> = (0..4).map(|_| balancer.select("$share/g/a", "a", &[0, 0, 0])).collect();
+    assert_eq!(selected, vec![0, 1, 2, 0]);
+    assert_eq!(balancer.select("$share/g/b", "b", &[0, 0, 0]), 0);
+    // The turn is kept when a session leaves.
+    assert_eq!(balancer.select("$share/g/a", "a", &[0, 0]), 1);
+    assert_eq!(balancer.select("$share/g/a", "a", &[0, 0]), 0);
+}
+
+#[test]
+fn select_random_within_sessions() {
+    let mut balancer = Balancer::new(SharedSubscriptionStrategy::Random);
+
+    let mut selected = [0; 3];
+    for _ in 0..300 {
+        selected[balancer.select("$share/g/a", "a", &[0, 0, 0])] += 1;
+    }
+    assert!(selected.iter().all(|count| *count > 0), "{:?}", selected);
+}
+
+#[test]
+fn select_least_inflight() {
+    let mut balancer = Balancer::new(SharedSubscriptionStrategy::LeastInflight);
+
+    assert_eq!(balancer.select("$share/g/a", "a", &[2, 0, 1]), 1);
+    assert_eq!(balancer.select("$share/g/a", "a", &[1, 1, 1]), 0);
+}
+
+#[test]
+fn select_sticky_by_topic_hash() {
+    let mut balancer = Balancer::new(SharedSubscriptionStrategy::StickyByTopicHash);
+
+    let first = balancer.select("$share/g/#", "devices/0001", &[0, 0, 0]);
+    for _ in 0..10 {
+        assert_eq!(balancer.select("$share/g/#", "devices/0001", &[5, 0, 0]), first);
+    }
+    let selected: std::collections::HashSet<usize> = (0..100)
+        .map(|i| balancer.select("$share/g/#", &format!("devices/{}", i), &[0, 0, 0]))
+        .collect();
+    assert!(selected.len() > 1);
+}
@@ -0,0 +1,266 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Mutex;
+
+use crate::errors;
+use crate::packets::publish::Publish;
+use crate::packets::ExtractValue;
+use crate::session;
+
+pub mod append_log;
+pub mod record;
+
+#[path = "store_tests.rs"]
+#[cfg(test)]
+mod store_tests;
+
+// Retained is a retained message, and when its Message Expiry Interval passes.
+// The expired retained message is not sent to the new subscriptions (3.3.2.3.3 Message Expiry Interval subsection).
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub struct Retained {
+    pub publish: Publish,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl Retained {
+    pub fn new(publish: Publish, expires_at: Option<chrono::DateTime<chrono::Utc>>) -> Retained {
+        Retained { publish, expires_at }
+    }
+
+    pub fn is_expired(&self, now: chrono::DateTime<chrono::Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+}
+
+// State is what the Store keeps: the persistent sessions by their ClientIDs and the retained messages by their Topic Names.
+// The sessions are identified by the ClientIDs because the session ids are renewed when the Server restarts.
+#[derive(Debug, Eq, PartialEq, Clone, Default)]
+pub struct State {
+    pub sessions: BTreeMap<String, session::Session>,
+    pub retained: BTreeMap<String, Retained>,
+}
+
+impl State {
+    pub fn new() -> State {
+        State::default()
+    }
+
+    // apply changes the state by the record. The later record of a ClientID or a Topic Name replaces the earlier one.
+    pub fn apply(&mut self, record: record::Record) {
+        match record {
+            record::Record::Session(session) => {
+                self.sessions.insert(session.client_id.as_str().to_string(), session);
+            }
+            record::Record::SessionRemoved(client_id) => {
+                self.sessions.remove(&client_id);
+            }
+            record::Record::Retained(retained) => {
+                self.retained.insert(retained.publish.topic_name.val().to_string(), retained);
+            }
+            record::Record::RetainedRemoved(topic_name) => {
+                self.retained.remove(&topic_name);
+            }
+            record::Record::Subscribed {
+                client_id,
+                topic_filter,
+                subscription,
+            } => {
+                if let Some(session) = self.sessions.get_mut(&client_id) {
+                    session.subscriptions.insert(topic_filter, subscription);
+                }
+            }
+            record::Record::Unsubscribed { client_id, topic_filter } => {
+                if let Some(session) = self.sessions.get_mut(&client_id) {
+                    session.subscriptions.remove(&topic_filter);
+                }
+            }
+            record::Record::Acknowledged {
+                client_id,
+                packet_identifier,
+            } => {
+                if let Some(session) = self.sessions.get_mut(&client_id) {
+                    session.outbound_inflight.remove(&packet_identifier);
+                    session.outbound_released.remove(&packet_identifier);
+                    session.packet_identifiers.release(packet_identifier);
+                }
+            }
+            record::Record::Queued { client_id, delivery } => {
+                if let Some(session) = self.sessions.get_mut(&client_id) {
+                    session.outbound_pending.push_back(delivery);
+                }
+            }
+            record::Record::InFlight {
+                client_id,
+                packet_identifier,
+                delivery,
+            } => {
+                if let Some(session) = self.sessions.get_mut(&client_id) {
+                    session.packet_identifiers.reserve(packet_identifier);
+                    session.outbound_inflight.insert(packet_identifier, delivery);
+                }
+            }
+            record::Record::Dequeued { client_id, count } => {
+                if let Some(session) = self.sessions.get_mut(&client_id) {
+                    let count = (count as usize).min(session.outbound_pending.len());
+                    session.outbound_pending.drain(..count);
+                }
+            }
+            record::Record::Released {
+                client_id,
+                packet_identifier,
+            } => {
+                if let Some(session) = self.sessions.get_mut(&client_id) {
+                    if session.outbound_inflight.contains_key(&packet_identifier) {
+                        session.outbound_released.insert(packet_identifier);
+                    }
+                }
+            }
+            record::Record::InboundReceived {
+                client_id,
+                packet_identifier,
+            } => {
+                if let Some(session) = self.sessions.get_mut(&client_id) {
+                    session.inbound_inflight.insert(packet_identifier);
+                }
+            }
+            record::Record::InboundCompleted {
+                client_id,
+                packet_identifier,
+            } => {
+                if let Some(session) = self.sessions.get_mut(&client_id) {
+                    session.inbound_inflight.remove(&packet_identifier);
+                }
+            }
+            // The checkpoint orders the snapshots and the logs, it changes nothing.
+            record::Record::Checkpoint(_) => {}
+        }
+    }
+}
+
+// Store keeps the state of the Server which must outlive the Server process, so a restart of the Server loses
+// neither the persistent sessions, i.e. their subscriptions and their QoS 1 and QoS 2 messages in flight or queued,
+// nor the retained messages (4.1 Session State section).
+// The Server saves each change as it happens, and loads the whole state when it starts.
+// The small changes of a persistent session, i.e. a subscription, a message queued or sent, an acknowledgement,
+// or a Packet Identifier of the QoS 2 flows, are saved by themselves instead of the whole session.
+pub trait Store: Send + Sync + fmt::Debug {
+    fn save_session(&self, session: &session::Session) -> Result<(), errors::Error>;
+    fn remove_session(&self, client_id: &str) -> Result<(), errors::Error>;
+    fn save_subscription(&self, client_id: &str, topic_filter: &str, subscription: &session::Subscription) -> Result<(), errors::Error>;
+    fn remove_subscription(&self, client_id: &str, topic_filter: &str) -> Result<(), errors::Error>;
+    fn save_queued(&self, client_id: &str, delivery: &session::Delivery) -> Result<(), errors::Error>;
+    fn save_in_flight(&self, client_id: &str, packet_identifier: u16, delivery: &session::Delivery) -> Result<(), errors::Error>;
+    fn remove_queued(&self, client_id: &str, count: usize) -> Result<(), errors::Error>;
+    fn acknowledge(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error>;
+    fn release(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error>;
+    fn save_inbound(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error>;
+    fn remove_inbound(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error>;
+    fn save_retained(&self, retained: &Retained) -> Result<(), errors::Error>;
+    fn remove_retained(&self, topic_name: &str) -> Result<(), errors::Error>;
+    fn load(&self) -> Result<State, errors::Error>;
+}
+
+// InMemory keeps the state in the memory of the process. It is the default, the state is lost with the process,
+// but a Handler restored from it continues the sessions of another Handler, e.g. in the tests.
+#[derive(Debug, Default)]
+pub struct InMemory {
+    state: Mutex<State>,
+}
+
+impl InMemory {
+    pub fn new() -> InMemory {
+        InMemory::default()
+    }
+
+    fn apply(&self, record: record::Record) -> Result<(), errors::Error> {
+        self.state.lock().unwrap().apply(record);
+        Ok(())
+    }
+}
+
+impl Store for InMemory {
+    fn save_session(&self, session: &session::Session) -> Result<(), errors::Error> {
+        self.apply(record::Record::Session(session.clone()))
+    }
+
+    fn remove_session(&self, client_id: &str) -> Result<(), errors::Error> {
+        self.apply(record::Record::SessionRemoved(client_id.to_string()))
+    }
+
+    fn save_subscription(&self, client_id: &str, topic_filter: &str, subscription: &session::Subscription) -> Result<(), errors::Error> {
+        self.apply(record::Record::Subscribed {
+            client_id: client_id.to_string(),
+            topic_filter: topic_filter.to_string(),
+            subscription: subscription.clone(),
+        })
+    }
+
+    fn remove_subscription(&self, client_id: &str, topic_filter: &str) -> Result<(), errors::Error> {
+        self.apply(record::Record::Unsubscribed {
+            client_id: client_id.to_string(),
+            topic_filter: topic_filter.to_string(),
+        })
+    }
+
+    fn save_queued(&self, client_id: &str, delivery: &session::Delivery) -> Result<(), errors::Error> {
+        self.apply(record::Record::Queued {
+            client_id: client_id.to_string(),
+            delivery: delivery.clone(),
+        })
+    }
+
+    fn save_in_flight(&self, client_id: &str, packet_identifier: u16, delivery: &session::Delivery) -> Result<(), errors::Error> {
+        self.apply(record::Record::InFlight {
+            client_id: client_id.to_string(),
+            packet_identifier,
+            delivery: delivery.clone(),
+        })
+    }
+
+    fn remove_queued(&self, client_id: &str, count: usize) -> Result<(), errors::Error> {
+        self.apply(record::Record::Dequeued {
+            client_id: client_id.to_string(),
+            count: count as u32,
+        })
+    }
+
+    fn acknowledge(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.apply(record::Record::Acknowledged {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn release(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.apply(record::Record::Released {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn save_inbound(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.apply(record::Record::InboundReceived {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn remove_inbound(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.apply(record::Record::InboundCompleted {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn save_retained(&self, retained: &Retained) -> Result<(), errors::Error> {
+        self.apply(record::Record::Retained(retained.clone()))
+    }
+
+    fn remove_retained(&self, topic_name: &str) -> Result<(), errors::Error> {
+        self.apply(record::Record::RetainedRemoved(topic_name.to_string()))
+    }
+
+    fn load(&self) -> Result<State, errors::Error> {
+        Ok(self.state.lock().unwrap().clone())
+    }
+}
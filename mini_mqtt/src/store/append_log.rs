@@ -0,0 +1,289 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::errors;
+use crate::session;
+use crate::store::record::Record;
+use crate::store::{Retained, State, Store};
+
+#[path = "append_log_tests.rs"]
+#[cfg(test)]
+mod append_log_tests;
+
+// DEFAULT_SNAPSHOT_INTERVAL is the number of the records appended to the log between the snapshots.
+pub const DEFAULT_SNAPSHOT_INTERVAL: usize = 10_000;
+
+// AppendLog keeps the state in a write-ahead log: every change is appended to the file as a Record,
+// and it is on the disk before the Server goes on.
+// Every snapshot_interval records the log is compacted: the whole state is written to the snapshot file next to it,
+// i.e. the path with the suffix .snapshot, as the records of the sessions and the retained messages, and the log is emptied.
+// The snapshot and the log start with the Checkpoint of the generation of the snapshot, so that opening the log
+// replays the snapshot and then only the records of the log which follow it.
+// A crash can cut off or damage the last records, so opening the file truncates the log at the first record
+// which is incomplete or does not match its CRC-32 before the new records are appended.
+#[derive(Debug)]
+pub struct AppendLog {
+    path: PathBuf,
+    snapshot_interval: usize,
+    log: Mutex<Log>,
+}
+
+// Log is the open file of the log, the state which its records result, the number of its records,
+// and the generation of the snapshot which it follows.
+#[derive(Debug)]
+struct Log {
+    file: fs::File,
+    state: State,
+    records: usize,
+    generation: u64,
+}
+
+impl AppendLog {
+    pub fn open(path: &Path) -> Result<AppendLog, errors::Error> {
+        let mut state = State::new();
+        let mut generation = 0;
+        let snapshot_path = suffixed(path, ".snapshot");
+        if snapshot_path.exists() {
+            let (records, length) = read(&snapshot_path)?;
+            // The snapshot is renamed into place after it has been written, so it is never cut off.
+            if length as u64 != fs::metadata(&snapshot_path)?.len() {
+                return Err(errors::Error::Common(format!("{} is corrupted at {}", snapshot_path.display(), length)));
+            }
+            for record in records {
+                match record {
+                    Record::Checkpoint(checkpoint) => generation = checkpoint,
+                    record => state.apply(record),
+                }
+            }
+        }
+
+        let file = fs::OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(path)
+            .map_err(|err| errors::Error::Common(format!("Failed to open {}: {}", path.display(), err)))?;
+        let (records, length) = read(path)?;
+        let mut records = records.into_iter();
+        let length = match records.next() {
+            Some(Record::Checkpoint(checkpoint)) if checkpoint == generation => length,
+            // A crash after the snapshot has been renamed into place leaves the log which it contains.
+            Some(Record::Checkpoint(checkpoint)) if checkpoint < generation => 0,
+            Some(Record::Checkpoint(checkpoint)) => {
+                return Err(errors::Error::Common(format!(
+                    "{} follows the snapshot {}, but {} is {}",
+                    path.display(),
+                    checkpoint,
+                    snapshot_path.display(),
+                    generation
+                )));
+            }
+            Some(_) => return Err(errors::Error::Common(format!("{} does not start with a checkpoint", path.display()))),
+            None => 0,
+        };
+        let mut log = Log {
+            file,
+            state,
+            records: 0,
+            generation,
+        };
+        log.file.set_len(length as u64)?;
+        if length == 0 {
+            log.start()?;
+        } else {
+            for record in records {
+                log.state.apply(record);
+                log.records += 1;
+            }
+        }
+
+        Ok(AppendLog {
+            path: path.to_path_buf(),
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL,
+            log: Mutex::new(log),
+        })
+    }
+
+    pub fn with_snapshot_interval(self, snapshot_interval: usize) -> AppendLog {
+        AppendLog {
+            snapshot_interval: snapshot_interval.max(1),
+            ..self
+        }
+    }
+
+    fn append(&self, record: Record) -> Result<(), errors::Error> {
+        let mut buffer = Vec::new();
+        record.encode(&mut buffer)?;
+        let mut log = self.log.lock().unwrap();
+        // The record is written at once, so a crash leaves at most the last record incomplete.
+        // A write which fails partway leaves the incomplete record, which would hide the records appended after it
+        // on the next open, so the log is cut back to its previous length.
+        let length = log.file.metadata()?.len();
+        if let Err(err) = log.file.write_all(&buffer).and_then(|_| log.file.sync_data()) {
+            if let Err(err) = log.file.set_len(length) {
+                log::error!("Failed to truncate {} to {}: {}", self.path.display(), length, err);
+            }
+            return Err(err.into());
+        }
+        log.state.apply(record);
+        log.records += 1;
+        // The record is already on the disk, so the failed snapshot is not the error of the append,
+        // and it is tried again by the next append.
+        if log.records >= self.snapshot_interval {
+            if let Err(err) = self.snapshot(&mut log) {
+                log::error!("Failed to write the snapshot of {}: {}", self.path.display(), err);
+            }
+        }
+        Ok(())
+    }
+
+    // snapshot writes the whole state of the next generation to a temporary file and syncs it,
+    // renames it to the snapshot file and syncs the directory, and then empties the log and starts it with the checkpoint.
+    // A crash before the rename leaves the previous snapshot and the log which follows it,
+    // and a crash after it leaves the log of the previous generation, which the new snapshot already contains.
+    fn snapshot(&self, log: &mut Log) -> Result<(), errors::Error> {
+        let generation = log.generation + 1;
+        let mut buffer = Vec::new();
+        Record::Checkpoint(generation).encode(&mut buffer)?;
+        for session in log.state.sessions.values() {
+            Record::Session(session.clone()).encode(&mut buffer)?;
+        }
+        for retained in log.state.retained.values() {
+            Record::Retained(retained.clone()).encode(&mut buffer)?;
+        }
+        let temporary_path = suffixed(&self.path, ".snapshot.tmp");
+        let mut file = fs::File::create(&temporary_path)
+            .map_err(|err| errors::Error::Common(format!("Failed to create {}: {}", temporary_path.display(), err)))?;
+        file.write_all(&buffer)?;
+        file.sync_all()?;
+        fs::rename(&temporary_path, suffixed(&self.path, ".snapshot"))?;
+        // The rename is on the disk only when the directory is, and the log must not be emptied before it.
+        let directory = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(Path::new("."));
+        fs::File::open(directory)?.sync_all()?;
+
+        log.generation = generation;
+        log.file.set_len(0)?;
+        log.start()
+    }
+}
+
+impl Log {
+    // start writes the checkpoint of the generation to the empty log.
+    fn start(&mut self) -> Result<(), errors::Error> {
+        let mut buffer = Vec::new();
+        Record::Checkpoint(self.generation).encode(&mut buffer)?;
+        self.file.write_all(&buffer)?;
+        self.file.sync_all()?;
+        self.records = 0;
+        Ok(())
+    }
+}
+
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut path = path.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+// read results the records in the file up to the first one which is incomplete or damaged, and their length.
+fn read(path: &Path) -> Result<(Vec<Record>, usize), errors::Error> {
+    let content = fs::read(path).map_err(|err| errors::Error::Common(format!("Failed to read {}: {}", path.display(), err)))?;
+    let mut records = Vec::new();
+    let mut offset = 0;
+    while let Some((record, length)) = Record::decode(&content[offset..])
+        .map_err(|err| errors::Error::Common(format!("{} is corrupted at {}: {}", path.display(), offset, err)))?
+    {
+        records.push(record);
+        offset += length;
+    }
+    Ok((records, offset))
+}
+
+impl Store for AppendLog {
+    fn save_session(&self, session: &session::Session) -> Result<(), errors::Error> {
+        self.append(Record::Session(session.clone()))
+    }
+
+    fn remove_session(&self, client_id: &str) -> Result<(), errors::Error> {
+        self.append(Record::SessionRemoved(client_id.to_string()))
+    }
+
+    fn save_subscription(&self, client_id: &str, topic_filter: &str, subscription: &session::Subscription) -> Result<(), errors::Error> {
+        self.append(Record::Subscribed {
+            client_id: client_id.to_string(),
+            topic_filter: topic_filter.to_string(),
+            subscription: subscription.clone(),
+        })
+    }
+
+    fn remove_subscription(&self, client_id: &str, topic_filter: &str) -> Result<(), errors::Error> {
+        self.append(Record::Unsubscribed {
+            client_id: client_id.to_string(),
+            topic_filter: topic_filter.to_string(),
+        })
+    }
+
+    fn save_queued(&self, client_id: &str, delivery: &session::Delivery) -> Result<(), errors::Error> {
+        self.append(Record::Queued {
+            client_id: client_id.to_string(),
+            delivery: delivery.clone(),
+        })
+    }
+
+    fn save_in_flight(&self, client_id: &str, packet_identifier: u16, delivery: &session::Delivery) -> Result<(), errors::Error> {
+        self.append(Record::InFlight {
+            client_id: client_id.to_string(),
+            packet_identifier,
+            delivery: delivery.clone(),
+        })
+    }
+
+    fn remove_queued(&self, client_id: &str, count: usize) -> Result<(), errors::Error> {
+        self.append(Record::Dequeued {
+            client_id: client_id.to_string(),
+            count: count as u32,
+        })
+    }
+
+    fn acknowledge(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.append(Record::Acknowledged {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn release(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.append(Record::Released {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn save_inbound(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.append(Record::InboundReceived {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn remove_inbound(&self, client_id: &str, packet_identifier: u16) -> Result<(), errors::Error> {
+        self.append(Record::InboundCompleted {
+            client_id: client_id.to_string(),
+            packet_identifier,
+        })
+    }
+
+    fn save_retained(&self, retained: &Retained) -> Result<(), errors::Error> {
+        self.append(Record::Retained(retained.clone()))
+    }
+
+    fn remove_retained(&self, topic_name: &str) -> Result<(), errors::Error> {
+        self.append(Record::RetainedRemoved(topic_name.to_string()))
+    }
+
+    fn load(&self) -> Result<State, errors::Error> {
+        Ok(self.log.lock().unwrap().state.clone())
+    }
+}
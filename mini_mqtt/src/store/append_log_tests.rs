@@ -0,0 +1,189 @@
+use super::*;
+use crate::packets;
+use crate::packets::publish::Publish;
+use crate::packets::UTF8EncodedString;
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("mini_mqtt_{}_{}.log", name, std::process::id()));
+    let _ = fs::remove_file(&path);
+    path
+}
+
+fn retained(topic_name: &str) -> Retained {
+    let publish = Publish::new(
+        Publish::fixed_header(false, packets::QoS::AtMostOnce, true),
+        UTF8EncodedString(topic_name.to_string()),
+        None,
+        packets::Properties::new(),
+        b"hello".to_vec(),
+    )
+    .unwrap();
+    Retained::new(publish, None)
+}
+
+fn persistent_session(client_id: &str) -> session::Session {
+    let mut session = session::Session::new(
+        session::SessionId::new(0),
+        session::ClientId(client_id.to_string()),
+        chrono::Duration::zero(),
+        packets::ProtocolVersion::V5,
+    );
+    session.session_expiry_interval = 60;
+    session.state = session::SessionState::Disconnected;
+    session
+}
+
+#[test]
+fn load_replays_records_after_reopen() {
+    let path = temp_path("replay");
+    let log = AppendLog::open(&path).unwrap();
+    log.save_session(&persistent_session("c1")).unwrap();
+    log.save_session(&persistent_session("c2")).unwrap();
+    log.remove_session("c1").unwrap();
+    log.save_retained(&retained("a/1")).unwrap();
+    log.save_retained(&retained("a/2")).unwrap();
+    log.remove_retained("a/2").unwrap();
+    drop(log);
+
+    let state = AppendLog::open(&path).unwrap().load().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(state.sessions.keys().collect::<Vec<_>>(), vec!["c2"]);
+    assert_eq!(state.sessions["c2"], persistent_session("c2"));
+    assert_eq!(state.retained.keys().collect::<Vec<_>>(), vec!["a/1"]);
+}
+
+#[test]
+fn snapshot_compacts_log() {
+    let path = temp_path("snapshot");
+    let snapshot_path = suffixed(&path, ".snapshot");
+    let _ = fs::remove_file(&snapshot_path);
+    let log = AppendLog::open(&path).unwrap().with_snapshot_interval(4);
+    for topic_name in ["a/1", "a/2", "a/3"] {
+        log.save_retained(&retained(topic_name)).unwrap();
+    }
+    assert!(!snapshot_path.exists());
+    log.remove_retained("a/2").unwrap();
+    // The fourth record has written the snapshot and emptied the log but its checkpoint.
+    let mut checkpoint = Vec::new();
+    Record::Checkpoint(1).encode(&mut checkpoint).unwrap();
+    assert_eq!(fs::read(&path).unwrap(), checkpoint);
+    log.save_session(&persistent_session("c1")).unwrap();
+    drop(log);
+
+    let state = AppendLog::open(&path).unwrap().load().unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&snapshot_path).unwrap();
+    assert_eq!(state.sessions.keys().collect::<Vec<_>>(), vec!["c1"]);
+    assert_eq!(state.retained.keys().collect::<Vec<_>>(), vec!["a/1", "a/3"]);
+}
+
+#[test]
+fn append_keeps_record_when_snapshot_fails() {
+    let path = temp_path("failed_snapshot");
+    // The directory in place of the temporary snapshot file fails the snapshot.
+    let temporary_path = suffixed(&path, ".snapshot.tmp");
+    let _ = fs::remove_dir(&temporary_path);
+    fs::create_dir(&temporary_path).unwrap();
+    let log = AppendLog::open(&path).unwrap().with_snapshot_interval(2);
+    log.save_retained(&retained("a/1")).unwrap();
+    log.save_retained(&retained("a/2")).unwrap();
+    assert!(!suffixed(&path, ".snapshot").exists());
+    drop(log);
+
+    let state = AppendLog::open(&path).unwrap().load().unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::remove_dir(&temporary_path).unwrap();
+    assert_eq!(state.retained.keys().collect::<Vec<_>>(), vec!["a/1", "a/2"]);
+}
+
+#[test]
+fn open_skips_log_contained_in_snapshot() {
+    let path = temp_path("contained");
+    let snapshot_path = suffixed(&path, ".snapshot");
+    let log = AppendLog::open(&path).unwrap();
+    log.save_session(&persistent_session("c1")).unwrap();
+    let delivery = session::Delivery::new(retained("a/1").publish, None);
+    log.save_queued("c1", &delivery).unwrap();
+    drop(log);
+    // A crash has happened after the snapshot was renamed into place and before the log was emptied.
+    let state = AppendLog::open(&path).unwrap().load().unwrap();
+    let mut snapshot = Vec::new();
+    Record::Checkpoint(1).encode(&mut snapshot).unwrap();
+    Record::Session(state.sessions["c1"].clone()).encode(&mut snapshot).unwrap();
+    fs::write(&snapshot_path, snapshot).unwrap();
+
+    let log = AppendLog::open(&path).unwrap();
+    let reopened = log.load().unwrap();
+    drop(log);
+    // The queued message is not queued again, and the log follows the new snapshot.
+    assert_eq!(reopened, state);
+    assert_eq!(reopened.sessions["c1"].outbound_pending.len(), 1);
+    let (records, _) = read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&snapshot_path).unwrap();
+    assert_eq!(records, vec![Record::Checkpoint(1)]);
+}
+
+#[test]
+fn open_reports_log_newer_than_snapshot() {
+    let path = temp_path("newer");
+    let mut log = Vec::new();
+    Record::Checkpoint(2).encode(&mut log).unwrap();
+    fs::write(&path, log).unwrap();
+    let err = AppendLog::open(&path).unwrap_err();
+    fs::remove_file(&path).unwrap();
+    assert!(err.to_string().contains(&path.display().to_string()));
+}
+
+#[test]
+fn open_truncates_incomplete_record() {
+    let path = temp_path("truncate");
+    let log = AppendLog::open(&path).unwrap();
+    log.save_retained(&retained("a/1")).unwrap();
+    drop(log);
+    // A crash has cut off the record being written.
+    let mut file = fs::OpenOptions::new().append(true).open(&path).unwrap();
+    file.write_all(&[0x00, 0x00, 0x01, 0x00, 0x03]).unwrap();
+    drop(file);
+
+    let log = AppendLog::open(&path).unwrap();
+    log.save_retained(&retained("a/2")).unwrap();
+    let state = log.load().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(state.retained.keys().collect::<Vec<_>>(), vec!["a/1", "a/2"]);
+}
+
+#[test]
+fn open_truncates_at_damaged_record() {
+    let path = temp_path("damaged");
+    let log = AppendLog::open(&path).unwrap();
+    log.save_retained(&retained("a/1")).unwrap();
+    let length = fs::metadata(&path).unwrap().len();
+    log.save_retained(&retained("a/2")).unwrap();
+    log.save_retained(&retained("a/3")).unwrap();
+    drop(log);
+    // A byte of the second record has been damaged on the disk, and the records after it are not trusted either.
+    let mut content = fs::read(&path).unwrap();
+    content[length as usize + 12] ^= 0xFF;
+    fs::write(&path, content).unwrap();
+
+    let log = AppendLog::open(&path).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().len(), length);
+    log.save_retained(&retained("a/4")).unwrap();
+    let state = log.load().unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(state.retained.keys().collect::<Vec<_>>(), vec!["a/1", "a/4"]);
+}
+
+#[test]
+fn open_reports_corrupted_file() {
+    let path = temp_path("corrupted");
+    // The record matches its CRC-32, but its kind is unknown.
+    let mut content = vec![0x00, 0x00, 0x00, 0x01];
+    content.extend_from_slice(&crate::store::record::crc32(&[0xFF]).to_be_bytes());
+    content.push(0xFF);
+    fs::write(&path, content).unwrap();
+    let err = AppendLog::open(&path).unwrap_err();
+    fs::remove_file(&path).unwrap();
+    assert!(err.to_string().contains(&path.display().to_string()));
+}
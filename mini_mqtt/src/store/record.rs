@@ -0,0 +1,446 @@
+use std::io::Write;
+
+use nom::combinator::{map, map_opt, map_res};
+use nom::multi::{length_count, length_data};
+use nom::number::complete::{be_i64, be_u16, be_u32, be_u64, be_u8};
+use nom::{Finish, IResult};
+
+use crate::codec::{decoder, encoder};
+use crate::errors;
+use crate::packets;
+use crate::packets::publish::Publish;
+use crate::packets::ExtractValue;
+use crate::session;
+use crate::store::Retained;
+
+#[path = "record_tests.rs"]
+#[cfg(test)]
+mod record_tests;
+
+const SESSION: u8 = 1;
+const SESSION_REMOVED: u8 = 2;
+const RETAINED: u8 = 3;
+const RETAINED_REMOVED: u8 = 4;
+const SUBSCRIBED: u8 = 5;
+const UNSUBSCRIBED: u8 = 6;
+const ACKNOWLEDGED: u8 = 7;
+const QUEUED: u8 = 8;
+const IN_FLIGHT: u8 = 9;
+const DEQUEUED: u8 = 10;
+const RELEASED: u8 = 11;
+const INBOUND_RECEIVED: u8 = 12;
+const INBOUND_COMPLETED: u8 = 13;
+const CHECKPOINT: u8 = 14;
+
+// Record is a change of the state which a Store keeps.
+// A record is encoded as the Four Byte Integer of the length of its body, the Four Byte Integer of the CRC-32 of the body,
+// and the body, which starts with the byte of its kind.
+// The integers are big-endian, and the strings and the binary data are prefixed by their lengths as Four Byte Integers.
+// The PUBLISH Packets are encoded as the MQTT v5.0 packets without their Packet Identifiers,
+// because the sessions assign the Packet Identifiers when they send the messages.
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum Record {
+    Session(session::Session), // The persistent session of the ClientID is created or changed.
+    SessionRemoved(String),    // The session of the ClientID has ended.
+    Retained(Retained),        // The retained message of the Topic Name is set.
+    RetainedRemoved(String),   // The retained message of the Topic Name is cleared.
+    // The persistent session of the ClientID has subscribed to the Topic Filter, or replaced the subscription.
+    Subscribed {
+        client_id: String,
+        topic_filter: String,
+        subscription: session::Subscription,
+    },
+    // The persistent session of the ClientID has unsubscribed from the Topic Filter.
+    Unsubscribed { client_id: String, topic_filter: String },
+    // The Client of the persistent session has acknowledged the message in flight of the Packet Identifier.
+    Acknowledged { client_id: String, packet_identifier: u16 },
+    // The message is appended to the queue of the persistent session of the ClientID.
+    Queued { client_id: String, delivery: session::Delivery },
+    // The message is sent to the Client of the persistent session with the Packet Identifier, and waits for its acknowledgement.
+    InFlight {
+        client_id: String,
+        packet_identifier: u16,
+        delivery: session::Delivery,
+    },
+    // The oldest messages of the count are taken from the queue of the persistent session, to be sent or as expired.
+    Dequeued { client_id: String, count: u32 },
+    // The Client of the persistent session has received the QoS 2 message in flight by the PUBREC Packet.
+    Released { client_id: String, packet_identifier: u16 },
+    // The persistent session has received the QoS 2 message of the Packet Identifier from the Client.
+    InboundReceived { client_id: String, packet_identifier: u16 },
+    // The Client of the persistent session has released the QoS 2 message of the Packet Identifier by the PUBREL Packet.
+    InboundCompleted { client_id: String, packet_identifier: u16 },
+    // The generation of the snapshot, which a snapshot and the log after it start with.
+    Checkpoint(u64),
+}
+
+impl Record {
+    pub fn encode(&self, writer: &mut dyn Write) -> Result<(), errors::Error> {
+        let mut body = Vec::new();
+        match self {
+            Record::Session(session) => {
+                body.push(SESSION);
+                encode_session(&mut body, session)?;
+            }
+            Record::SessionRemoved(client_id) => {
+                body.push(SESSION_REMOVED);
+                encode_string(&mut body, client_id);
+            }
+            Record::Retained(retained) => {
+                body.push(RETAINED);
+                encode_publish(&mut body, &retained.publish)?;
+                encode_time(&mut body, retained.expires_at);
+            }
+            Record::RetainedRemoved(topic_name) => {
+                body.push(RETAINED_REMOVED);
+                encode_string(&mut body, topic_name);
+            }
+            Record::Subscribed {
+                client_id,
+                topic_filter,
+                subscription,
+            } => {
+                body.push(SUBSCRIBED);
+                encode_string(&mut body, client_id);
+                encode_subscription(&mut body, topic_filter, subscription);
+            }
+            Record::Unsubscribed { client_id, topic_filter } => {
+                body.push(UNSUBSCRIBED);
+                encode_string(&mut body, client_id);
+                encode_string(&mut body, topic_filter);
+            }
+            Record::Acknowledged {
+                client_id,
+                packet_identifier,
+            } => {
+                body.push(ACKNOWLEDGED);
+                encode_string(&mut body, client_id);
+                body.extend_from_slice(&packet_identifier.to_be_bytes());
+            }
+            Record::Queued { client_id, delivery } => {
+                body.push(QUEUED);
+                encode_string(&mut body, client_id);
+                encode_delivery(&mut body, delivery)?;
+            }
+            Record::InFlight {
+                client_id,
+                packet_identifier,
+                delivery,
+            } => {
+                body.push(IN_FLIGHT);
+                encode_string(&mut body, client_id);
+                body.extend_from_slice(&packet_identifier.to_be_bytes());
+                encode_delivery(&mut body, delivery)?;
+            }
+            Record::Dequeued { client_id, count } => {
+                body.push(DEQUEUED);
+                encode_string(&mut body, client_id);
+                body.extend_from_slice(&count.to_be_bytes());
+            }
+            Record::Released {
+                client_id,
+                packet_identifier,
+            } => {
+                body.push(RELEASED);
+                encode_string(&mut body, client_id);
+                body.extend_from_slice(&packet_identifier.to_be_bytes());
+            }
+            Record::InboundReceived {
+                client_id,
+                packet_identifier,
+            } => {
+                body.push(INBOUND_RECEIVED);
+                encode_string(&mut body, client_id);
+                body.extend_from_slice(&packet_identifier.to_be_bytes());
+            }
+            Record::InboundCompleted {
+                client_id,
+                packet_identifier,
+            } => {
+                body.push(INBOUND_COMPLETED);
+                encode_string(&mut body, client_id);
+                body.extend_from_slice(&packet_identifier.to_be_bytes());
+            }
+            Record::Checkpoint(generation) => {
+                body.push(CHECKPOINT);
+                body.extend_from_slice(&generation.to_be_bytes());
+            }
+        }
+        writer.write_all(&(body.len() as u32).to_be_bytes())?;
+        writer.write_all(&crc32(&body).to_be_bytes())?;
+        writer.write_all(&body)?;
+        Ok(())
+    }
+
+    // decode results the record at the head of the input and its encoded size.
+    // It results None when the input does not start with a whole intact record, i.e. the record is cut off by a crash
+    // at the end of a file, or its body does not match its CRC-32.
+    pub fn decode(input: &[u8]) -> Result<Option<(Record, usize)>, errors::Error> {
+        let Some(header) = input.get(..8) else {
+            return Ok(None);
+        };
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let crc = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let Some(body) = input.get(8..8 + length) else {
+            return Ok(None);
+        };
+        if crc32(body) != crc {
+            return Ok(None);
+        }
+        let (rest, record) = record_parser(body).finish()?;
+        if !rest.is_empty() {
+            return Err(errors::Error::ParserError(format!("The record has {} bytes after its body", rest.len())));
+        }
+        Ok(Some((record, 8 + length)))
+    }
+}
+
+// crc32 results the CRC-32 of the bytes, the one of IEEE 802.3 with the reflected polynomial 0xEDB88320.
+pub(super) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn encode_session(writer: &mut Vec<u8>, session: &session::Session) -> Result<(), errors::Error> {
+    encode_string(writer, session.client_id.as_str());
+    writer.push(session.protocol_version.level());
+    encode_option(writer, session.user_name.as_ref(), |writer, user_name| encode_string(writer, user_name));
+    writer.extend_from_slice(&session.session_expiry_interval.to_be_bytes());
+    encode_time(writer, session.disconnected_at);
+
+    let mut subscriptions: Vec<_> = session.subscriptions.iter().collect();
+    subscriptions.sort_by_key(|(filter, _)| filter.as_str());
+    writer.extend_from_slice(&(subscriptions.len() as u32).to_be_bytes());
+    for (filter, subscription) in subscriptions {
+        encode_subscription(writer, filter, subscription);
+    }
+
+    writer.extend_from_slice(&(session.outbound_inflight.len() as u32).to_be_bytes());
+    for (packet_identifier, delivery) in &session.outbound_inflight {
+        writer.extend_from_slice(&packet_identifier.to_be_bytes());
+        encode_delivery(writer, delivery)?;
+    }
+    writer.extend_from_slice(&(session.outbound_pending.len() as u32).to_be_bytes());
+    for delivery in &session.outbound_pending {
+        encode_delivery(writer, delivery)?;
+    }
+    for packet_identifiers in [&session.outbound_released, &session.inbound_inflight] {
+        writer.extend_from_slice(&(packet_identifiers.len() as u32).to_be_bytes());
+        for packet_identifier in packet_identifiers {
+            writer.extend_from_slice(&packet_identifier.to_be_bytes());
+        }
+    }
+    Ok(())
+}
+
+fn encode_subscription(writer: &mut Vec<u8>, topic_filter: &str, subscription: &session::Subscription) {
+    encode_string(writer, topic_filter);
+    writer.push(subscription.options.0.val());
+    encode_option(writer, subscription.identifier.as_ref(), |writer, identifier| {
+        writer.extend_from_slice(&identifier.to_be_bytes())
+    });
+}
+
+fn encode_delivery(writer: &mut Vec<u8>, delivery: &session::Delivery) -> Result<(), errors::Error> {
+    encode_publish(writer, &delivery.publish)?;
+    encode_option(writer, delivery.shared_subscription.as_ref(), |writer, shared_subscription| {
+        encode_string(writer, shared_subscription)
+    });
+    encode_time(writer, delivery.expires_at);
+    Ok(())
+}
+
+// encode_publish encodes the PUBLISH Packet as MQTT v5.0. The decoder requires the Packet Identifier of QoS 1 and QoS 2,
+// so it is replaced with 1, and decode_publish drops it.
+fn encode_publish(writer: &mut Vec<u8>, publish: &Publish) -> Result<(), errors::Error> {
+    let mut publish = publish.clone();
+    publish.packet_identifier = (publish.qos() != packets::QoS::AtMostOnce)
+        .then(|| packets::PacketIdentity::new(packets::TwoByteInteger(1)));
+    let mut packet = Vec::new();
+    encoder::encode(&mut packet, &packets::Packet::Publish(publish))?;
+    encode_bytes(writer, &packet);
+    Ok(())
+}
+
+fn encode_string(writer: &mut Vec<u8>, string: &str) {
+    encode_bytes(writer, string.as_bytes());
+}
+
+fn encode_bytes(writer: &mut Vec<u8>, bytes: &[u8]) {
+    writer.extend_from_slice(&(bytes.len() as u32).to_be_bytes());
+    writer.extend_from_slice(bytes);
+}
+
+// The times are encoded as the milliseconds since the UNIX epoch.
+fn encode_time(writer: &mut Vec<u8>, time: Option<chrono::DateTime<chrono::Utc>>) {
+    encode_option(writer, time.as_ref(), |writer, time| {
+        writer.extend_from_slice(&time.timestamp_millis().to_be_bytes())
+    });
+}
+
+fn encode_option<T>(writer: &mut Vec<u8>, value: Option<&T>, encode: impl FnOnce(&mut Vec<u8>, &T)) {
+    match value {
+        Some(value) => {
+            writer.push(1);
+            encode(writer, value);
+        }
+        None => writer.push(0),
+    }
+}
+
+fn record_parser(input: &[u8]) -> IResult<&[u8], Record> {
+    let (input, kind) = be_u8(input)?;
+    match kind {
+        SESSION => map(session_parser, Record::Session)(input),
+        SESSION_REMOVED => map(string_parser, Record::SessionRemoved)(input),
+        RETAINED => {
+            let (input, publish) = publish_parser(input)?;
+            let (input, expires_at) = time_parser(input)?;
+            Ok((input, Record::Retained(Retained::new(publish, expires_at))))
+        }
+        RETAINED_REMOVED => map(string_parser, Record::RetainedRemoved)(input),
+        SUBSCRIBED => {
+            let (input, client_id) = string_parser(input)?;
+            let (input, (topic_filter, subscription)) = subscription_parser(input)?;
+            Ok((input, Record::Subscribed { client_id, topic_filter, subscription }))
+        }
+        UNSUBSCRIBED => {
+            let (input, client_id) = string_parser(input)?;
+            let (input, topic_filter) = string_parser(input)?;
+            Ok((input, Record::Unsubscribed { client_id, topic_filter }))
+        }
+        ACKNOWLEDGED => {
+            let (input, client_id) = string_parser(input)?;
+            let (input, packet_identifier) = be_u16(input)?;
+            Ok((input, Record::Acknowledged { client_id, packet_identifier }))
+        }
+        QUEUED => {
+            let (input, client_id) = string_parser(input)?;
+            let (input, delivery) = delivery_parser(input)?;
+            Ok((input, Record::Queued { client_id, delivery }))
+        }
+        IN_FLIGHT => {
+            let (input, client_id) = string_parser(input)?;
+            let (input, packet_identifier) = be_u16(input)?;
+            let (input, delivery) = delivery_parser(input)?;
+            Ok((input, Record::InFlight { client_id, packet_identifier, delivery }))
+        }
+        DEQUEUED => {
+            let (input, client_id) = string_parser(input)?;
+            let (input, count) = be_u32(input)?;
+            Ok((input, Record::Dequeued { client_id, count }))
+        }
+        RELEASED => {
+            let (input, client_id) = string_parser(input)?;
+            let (input, packet_identifier) = be_u16(input)?;
+            Ok((input, Record::Released { client_id, packet_identifier }))
+        }
+        INBOUND_RECEIVED => {
+            let (input, client_id) = string_parser(input)?;
+            let (input, packet_identifier) = be_u16(input)?;
+            Ok((input, Record::InboundReceived { client_id, packet_identifier }))
+        }
+        INBOUND_COMPLETED => {
+            let (input, client_id) = string_parser(input)?;
+            let (input, packet_identifier) = be_u16(input)?;
+            Ok((input, Record::InboundCompleted { client_id, packet_identifier }))
+        }
+        CHECKPOINT => map(be_u64, Record::Checkpoint)(input),
+        _ => Err(nom::Err::Failure(nom::error::Error::new(input, nom::error::ErrorKind::Switch))),
+    }
+}
+
+// session_parser results the session as disconnected. The session id is assigned when the Server restores it,
+// and the Network Connection specific values are renewed when the Client resumes it.
+fn session_parser(input: &[u8]) -> IResult<&[u8], session::Session> {
+    let (input, client_id) = string_parser(input)?;
+    let (input, protocol_version) = map_opt(be_u8, packets::ProtocolVersion::from_level)(input)?;
+    let (input, user_name) = option_parser(string_parser)(input)?;
+    let (input, session_expiry_interval) = be_u32(input)?;
+    let (input, disconnected_at) = time_parser(input)?;
+    let (input, subscriptions) = length_count(be_u32, subscription_parser)(input)?;
+    let (input, outbound_inflight) = length_count(be_u32, |input| {
+        let (input, packet_identifier) = be_u16(input)?;
+        let (input, delivery) = delivery_parser(input)?;
+        Ok((input, (packet_identifier, delivery)))
+    })(input)?;
+    let (input, outbound_pending) = length_count(be_u32, delivery_parser)(input)?;
+    let (input, outbound_released) = length_count(be_u32, be_u16)(input)?;
+    let (input, inbound_inflight) = length_count(be_u32, be_u16)(input)?;
+
+    let mut session = session::Session::new(
+        session::SessionId::new(0),
+        session::ClientId(client_id),
+        chrono::Duration::zero(),
+        protocol_version,
+    );
+    session.user_name = user_name;
+    session.session_expiry_interval = session_expiry_interval;
+    session.disconnected_at = disconnected_at;
+    session.subscriptions = subscriptions.into_iter().collect();
+    for packet_identifier in outbound_inflight.iter().map(|(packet_identifier, _)| *packet_identifier) {
+        session.packet_identifiers.reserve(packet_identifier);
+    }
+    session.outbound_inflight = outbound_inflight.into_iter().collect();
+    session.outbound_pending = outbound_pending.into();
+    session.outbound_released = outbound_released.into_iter().collect();
+    session.inbound_inflight = inbound_inflight.into_iter().collect();
+    session.state = session::SessionState::Disconnected;
+    Ok((input, session))
+}
+
+fn subscription_parser(input: &[u8]) -> IResult<&[u8], (String, session::Subscription)> {
+    let (input, topic_filter) = string_parser(input)?;
+    let (input, options) = map_res(be_u8, |options| packets::subscribe::SubscriptionOptions::new(packets::Bits(options)))(input)?;
+    let (input, identifier) = option_parser(be_u32)(input)?;
+    Ok((input, (topic_filter, session::Subscription::new(options, identifier))))
+}
+
+fn delivery_parser(input: &[u8]) -> IResult<&[u8], session::Delivery> {
+    let (input, publish) = publish_parser(input)?;
+    let (input, shared_subscription) = option_parser(string_parser)(input)?;
+    let (input, expires_at) = time_parser(input)?;
+    Ok((input, session::Delivery::new(publish, shared_subscription).with_expires_at(expires_at)))
+}
+
+fn publish_parser(input: &[u8]) -> IResult<&[u8], Publish> {
+    map_res(length_data(be_u32), decode_publish)(input)
+}
+
+fn decode_publish(mut packet: &[u8]) -> Result<Publish, errors::Error> {
+    match decoder::decode(&mut packet)? {
+        packets::Packet::Publish(mut publish) => {
+            publish.packet_identifier = None;
+            // The Remaining Length is calculated by the encoder, as the other PUBLISH Packets which the Server creates.
+            publish.fixed_header = Publish::fixed_header(publish.dup(), publish.qos(), publish.retain());
+            Ok(publish)
+        }
+        packet => Err(errors::Error::ParserError(format!("The record has {:?} instead of a PUBLISH Packet", packet))),
+    }
+}
+
+fn string_parser(input: &[u8]) -> IResult<&[u8], String> {
+    map_res(length_data(be_u32), |bytes: &[u8]| String::from_utf8(bytes.to_vec()))(input)
+}
+
+fn time_parser(input: &[u8]) -> IResult<&[u8], Option<chrono::DateTime<chrono::Utc>>> {
+    option_parser(map_opt(be_i64, chrono::DateTime::from_timestamp_millis))(input)
+}
+
+fn option_parser<'a, O>(
+    mut parser: impl FnMut(&'a [u8]) -> IResult<&'a [u8], O>,
+) -> impl FnMut(&'a [u8]) -> IResult<&'a [u8], Option<O>> {
+    move |input| {
+        let (input, present) = be_u8(input)?;
+        if present == 0 {
+            return Ok((input, None));
+        }
+        map(&mut parser, Some)(input)
+    }
+}
@@ -0,0 +1,167 @@
+use super::*;
+use crate::packets::{Bits, TwoByteInteger, UTF8EncodedString};
+
+fn publish(topic_name: &str, qos: packets::QoS, retain: bool) -> Publish {
+    let mut properties = packets::Properties::new();
+    properties.insert(packets::CONTENT_TYPE, UTF8EncodedString("text/plain".to_string()).into());
+    let packet_identifier = (qos != packets::QoS::AtMostOnce).then(|| packets::PacketIdentity::new(TwoByteInteger(7)));
+    let mut publish = Publish::new(
+        Publish::fixed_header(false, qos, retain),
+        UTF8EncodedString(topic_name.to_string()),
+        packet_identifier,
+        properties,
+        b"hello".to_vec(),
+    )
+    .unwrap();
+    // The messages which the sessions keep have no Packet Identifiers.
+    publish.packet_identifier = None;
+    publish
+}
+
+fn time(millis: i64) -> Option<chrono::DateTime<chrono::Utc>> {
+    chrono::DateTime::from_timestamp_millis(millis)
+}
+
+fn round_trip(record: &Record) -> Record {
+    let mut encoded = Vec::new();
+    record.encode(&mut encoded).unwrap();
+    let (decoded, length) = Record::decode(&encoded).unwrap().unwrap();
+    assert_eq!(length, encoded.len());
+    decoded
+}
+
+#[test]
+fn encode_and_decode_session() {
+    let mut session = session::Session::new(
+        session::SessionId::new(0),
+        session::ClientId("client1".to_string()),
+        chrono::Duration::zero(),
+        packets::ProtocolVersion::V3_1_1,
+    );
+    session.user_name = Some("alice".to_string());
+    session.session_expiry_interval = 3600;
+    session.disconnected_at = time(1_700_000_000_123);
+    let options = packets::subscribe::SubscriptionOptions::new(Bits(0b0000_1110)).unwrap();
+    session.subscriptions.insert("a/+".to_string(), session::Subscription::new(options.clone(), Some(5)));
+    session.subscriptions.insert("$share/g/b".to_string(), session::Subscription::new(options, None));
+    session.outbound_inflight.insert(
+        3,
+        session::Delivery::new(publish("a/1", packets::QoS::ExactlyOnce, false), None).with_expires_at(time(1_700_000_060_000)),
+    );
+    session.outbound_inflight.insert(4, session::Delivery::new(publish("b", packets::QoS::AtLeastOnce, true), Some("$share/g/b".to_string())));
+    session.packet_identifiers.reserve(3);
+    session.packet_identifiers.reserve(4);
+    session.outbound_pending.push_back(session::Delivery::new(publish("a/2", packets::QoS::AtLeastOnce, false), None));
+    session.outbound_released.insert(3);
+    session.inbound_inflight.insert(10);
+    session.state = session::SessionState::Disconnected;
+
+    assert_eq!(round_trip(&Record::Session(session.clone())), Record::Session(session));
+}
+
+#[test]
+fn encode_and_decode_retained() {
+    let retained = Retained::new(publish("a/b", packets::QoS::AtLeastOnce, true), time(1_700_000_000_000));
+    assert_eq!(round_trip(&Record::Retained(retained.clone())), Record::Retained(retained));
+    let retained = Retained::new(publish("a/c", packets::QoS::AtMostOnce, true), None);
+    assert_eq!(round_trip(&Record::Retained(retained.clone())), Record::Retained(retained));
+
+    for record in [Record::SessionRemoved("client1".to_string()), Record::RetainedRemoved("a/b".to_string())] {
+        assert_eq!(round_trip(&record), record);
+    }
+}
+
+#[test]
+fn encode_and_decode_session_changes() {
+    let options = packets::subscribe::SubscriptionOptions::new(Bits(0b0010_0101)).unwrap();
+    let records = [
+        Record::Subscribed {
+            client_id: "client1".to_string(),
+            topic_filter: "a/#".to_string(),
+            subscription: session::Subscription::new(options.clone(), Some(268_435_455)),
+        },
+        Record::Subscribed {
+            client_id: "client1".to_string(),
+            topic_filter: "$share/g/b".to_string(),
+            subscription: session::Subscription::new(options, None),
+        },
+        Record::Unsubscribed {
+            client_id: "client1".to_string(),
+            topic_filter: "a/#".to_string(),
+        },
+        Record::Acknowledged {
+            client_id: "client1".to_string(),
+            packet_identifier: 65535,
+        },
+        Record::Queued {
+            client_id: "client1".to_string(),
+            delivery: session::Delivery::new(publish("a/1", packets::QoS::AtLeastOnce, false), None).with_expires_at(time(1_700_000_000_000)),
+        },
+        Record::InFlight {
+            client_id: "client1".to_string(),
+            packet_identifier: 3,
+            delivery: session::Delivery::new(publish("a/2", packets::QoS::ExactlyOnce, false), Some("$share/g/a/+".to_string())),
+        },
+        Record::Dequeued {
+            client_id: "client1".to_string(),
+            count: 2,
+        },
+        Record::Released {
+            client_id: "client1".to_string(),
+            packet_identifier: 3,
+        },
+        Record::InboundReceived {
+            client_id: "client1".to_string(),
+            packet_identifier: 7,
+        },
+        Record::InboundCompleted {
+            client_id: "client1".to_string(),
+            packet_identifier: 7,
+        },
+        Record::Checkpoint(u64::MAX),
+    ];
+    for record in records {
+        assert_eq!(round_trip(&record), record);
+    }
+}
+
+#[test]
+fn decode_incomplete_record() {
+    let mut encoded = Vec::new();
+    Record::RetainedRemoved("a/b".to_string()).encode(&mut encoded).unwrap();
+
+    for length in 0..encoded.len() {
+        assert!(Record::decode(&encoded[..length]).unwrap().is_none());
+    }
+}
+
+#[test]
+fn decode_damaged_record() {
+    let mut encoded = Vec::new();
+    Record::RetainedRemoved("a/b".to_string()).encode(&mut encoded).unwrap();
+
+    for index in 4..encoded.len() {
+        let mut damaged = encoded.clone();
+        damaged[index] ^= 0x01;
+        assert!(Record::decode(&damaged).unwrap().is_none());
+    }
+}
+
+#[test]
+fn crc32_of_check_value() {
+    assert_eq!(crc32(b""), 0);
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
+
+#[test]
+fn decode_unknown_record() {
+    let framed = |body: &[u8]| {
+        let mut encoded = (body.len() as u32).to_be_bytes().to_vec();
+        encoded.extend_from_slice(&crc32(body).to_be_bytes());
+        encoded.extend_from_slice(body);
+        encoded
+    };
+    assert!(Record::decode(&framed(&[0xFF])).is_err());
+    // The body must be consumed to the end.
+    assert!(Record::decode(&framed(&[RETAINED_REMOVED, 0x00, 0x00, 0x00, 0x01, b'a', 0x00, 0x00, 0x00, 0x00])).is_err());
+}
@@ -0,0 +1,137 @@
+use super::*;
+use crate::packets;
+use crate::packets::UTF8EncodedString;
+
+fn retained(topic_name: &str, payload: &[u8]) -> Retained {
+    let publish = Publish::new(
+        Publish::fixed_header(false, packets::QoS::AtMostOnce, true),
+        UTF8EncodedString(topic_name.to_string()),
+        None,
+        packets::Properties::new(),
+        payload.to_vec(),
+    )
+    .unwrap();
+    Retained::new(publish, None)
+}
+
+#[test]
+fn in_memory_keeps_latest_state() {
+    let store = InMemory::new();
+    let mut session = session::Session::new(
+        session::SessionId::new(1),
+        session::ClientId("c1".to_string()),
+        chrono::Duration::seconds(30),
+        packets::ProtocolVersion::V5,
+    );
+    store.save_session(&session).unwrap();
+    session.session_expiry_interval = 60;
+    store.save_session(&session).unwrap();
+    store.save_retained(&retained("a/1", b"first")).unwrap();
+    store.save_retained(&retained("a/1", b"second")).unwrap();
+    store.save_retained(&retained("a/2", b"third")).unwrap();
+    store.remove_retained("a/2").unwrap();
+
+    let state = store.load().unwrap();
+    assert_eq!(state.sessions.len(), 1);
+    assert_eq!(state.sessions["c1"].session_expiry_interval, 60);
+    assert_eq!(state.retained.len(), 1);
+    assert_eq!(state.retained["a/1"].publish.payload, b"second");
+
+    store.remove_session("c1").unwrap();
+    assert!(store.load().unwrap().sessions.is_empty());
+}
+
+#[test]
+fn state_applies_session_changes() {
+    let mut session = session::Session::new(
+        session::SessionId::new(1),
+        session::ClientId("c1".to_string()),
+        chrono::Duration::seconds(30),
+        packets::ProtocolVersion::V5,
+    );
+    session.packet_identifiers.reserve(1);
+    session.outbound_inflight.insert(1, session::Delivery::new(retained("a/1", b"first").publish, None));
+    let options = packets::subscribe::SubscriptionOptions::new(packets::Bits(0b0000_0001)).unwrap();
+    let subscription = session::Subscription::new(options, None);
+
+    let mut state = State::new();
+    state.apply(record::Record::Session(session));
+    for topic_filter in ["a/+", "b"] {
+        state.apply(record::Record::Subscribed {
+            client_id: "c1".to_string(),
+            topic_filter: topic_filter.to_string(),
+            subscription: subscription.clone(),
+        });
+    }
+    state.apply(record::Record::Unsubscribed {
+        client_id: "c1".to_string(),
+        topic_filter: "b".to_string(),
+    });
+    state.apply(record::Record::Acknowledged {
+        client_id: "c1".to_string(),
+        packet_identifier: 1,
+    });
+    let delivery = session::Delivery::new(retained("a/2", b"second").publish, None);
+    state.apply(record::Record::InFlight {
+        client_id: "c1".to_string(),
+        packet_identifier: 2,
+        delivery: delivery.clone(),
+    });
+    state.apply(record::Record::Queued {
+        client_id: "c1".to_string(),
+        delivery: delivery.clone(),
+    });
+    let queued = session::Delivery::new(retained("a/3", b"third").publish, None);
+    state.apply(record::Record::Queued {
+        client_id: "c1".to_string(),
+        delivery: queued.clone(),
+    });
+    state.apply(record::Record::Dequeued {
+        client_id: "c1".to_string(),
+        count: 1,
+    });
+    state.apply(record::Record::Released {
+        client_id: "c1".to_string(),
+        packet_identifier: 2,
+    });
+    // Only a message in flight is released.
+    state.apply(record::Record::Released {
+        client_id: "c1".to_string(),
+        packet_identifier: 3,
+    });
+    for packet_identifier in [4, 5] {
+        state.apply(record::Record::InboundReceived {
+            client_id: "c1".to_string(),
+            packet_identifier,
+        });
+    }
+    state.apply(record::Record::InboundCompleted {
+        client_id: "c1".to_string(),
+        packet_identifier: 4,
+    });
+    // The changes of an unknown session are ignored.
+    state.apply(record::Record::Subscribed {
+        client_id: "c2".to_string(),
+        topic_filter: "a/+".to_string(),
+        subscription,
+    });
+
+    assert_eq!(state.sessions.keys().collect::<Vec<_>>(), vec!["c1"]);
+    let session = &state.sessions["c1"];
+    assert_eq!(session.subscriptions.keys().collect::<Vec<_>>(), vec!["a/+"]);
+    assert_eq!(session.outbound_inflight.keys().collect::<Vec<_>>(), vec![&2]);
+    assert!(!session.packet_identifiers.is_in_use(1));
+    assert!(session.packet_identifiers.is_in_use(2));
+    assert_eq!(session.outbound_pending, vec![queued]);
+    assert_eq!(session.outbound_released.iter().collect::<Vec<_>>(), vec![&2]);
+    assert_eq!(session.inbound_inflight.iter().collect::<Vec<_>>(), vec![&5]);
+}
+
+#[test]
+fn retained_is_expired() {
+    let now = chrono::Utc::now();
+    assert!(!retained("a", b"x").is_expired(now));
+    let expiring = Retained::new(retained("a", b"x").publish, Some(now));
+    assert!(expiring.is_expired(now));
+    assert!(!expiring.is_expired(now - chrono::Duration::seconds(1)));
+}
@@ -0,0 +1,125 @@
+use crate::errors;
+
+pub mod alias;
+
+#[path = "topic_tests.rs"]
+#[cfg(test)]
+mod topic_tests;
+
+// The implementations of this module follow the 4.7 Topic Names and Topic Filters section in the MQTT 5.0 specs.
+pub const LEVEL_SEPARATOR: char = '/';
+pub const MULTI_LEVEL_WILDCARD: &str = "#";
+pub const SINGLE_LEVEL_WILDCARD: &str = "+";
+// The Topic Filters of the Shared Subscriptions start with "$share/" (4.8.2 Shared Subscriptions subsection).
+pub const SHARED_SUBSCRIPTION_PREFIX: &str = "$share/";
+
+// validate_topic_name validates the Topic Name of the PUBLISH Packet.
+// The Topic Name MUST NOT contain wildcard characters [MQTT-3.3.2-2], and it MUST be at least one character long [MQTT-4.7.3-1].
+pub fn validate_topic_name(name: &str) -> Result<(), errors::Error> {
+    if name.is_empty() {
+        return Err(errors::Error::ProtocolError("Topic Name is empty".to_string()));
+    }
+    if name.contains(['+', '#']) {
+        return Err(errors::Error::ProtocolError(
+            format!("Topic Name {} contains the wildcard characters", name)
+        ));
+    }
+
+    Ok(())
+}
+
+// validate_topic_filter validates the Topic Filter of the SUBSCRIBE Packet.
+// The multi-level wildcard MUST be the last character and it occupies an entire level [MQTT-4.7.1-1],
+// and the single-level wildcard MUST occupy an entire level of the filter [MQTT-4.7.1-2].
+pub fn validate_topic_filter(filter: &str) -> Result<(), errors::Error> {
+    if filter.is_empty() {
+        return Err(errors::Error::ProtocolError("Topic Filter is empty".to_string()));
+    }
+
+    let levels: Vec<&str> = filter.split(LEVEL_SEPARATOR).collect();
+    for (i, level) in levels.iter().enumerate() {
+        let is_last = i == levels.len() - 1;
+        if level.contains('#') && (*level != MULTI_LEVEL_WILDCARD || !is_last) {
+            return Err(errors::Error::MalformedPacket(
+                format!("Topic Filter {} has the invalid multi-level wildcard", filter)
+            ));
+        }
+        if level.contains('+') && *level != SINGLE_LEVEL_WILDCARD {
+            return Err(errors::Error::MalformedPacket(
+                format!("Topic Filter {} has the invalid single-level wildcard", filter)
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn has_wildcard(filter: &str) -> bool {
+    filter.contains(['+', '#'])
+}
+
+// parse_shared_subscription results the ShareName and the Topic Filter of the Shared Subscription "$share/{ShareName}/{filter}",
+// and None for the other Topic Filters (4.8.2 Shared Subscriptions subsection).
+// The ShareName MUST be at least one character long and MUST NOT contain "/", "+" or "#" [MQTT-4.8.2-1] [MQTT-4.8.2-2],
+// and the Topic Filter follows the rules of the non-shared ones.
+pub fn parse_shared_subscription(filter: &str) -> Result<Option<(&str, &str)>, errors::Error> {
+    let Some(shared) = filter.strip_prefix(SHARED_SUBSCRIPTION_PREFIX) else {
+        return Ok(None);
+    };
+    let Some((share_name, topic_filter)) = shared.split_once(LEVEL_SEPARATOR) else {
+        return Err(errors::Error::ProtocolError(
+            format!("Shared Subscription {} has no Topic Filter", filter)
+        ));
+    };
+    if share_name.is_empty() || share_name.contains(['+', '#']) {
+        return Err(errors::Error::ProtocolError(
+            format!("ShareName {} of the Shared Subscription is invalid", share_name)
+        ));
+    }
+    validate_topic_filter(topic_filter)?;
+
+    Ok(Some((share_name, topic_filter)))
+}
+
+// matches results whether the Topic Name matches the Topic Filter.
+// The Server MUST NOT match Topic Filters starting with a wildcard character with Topic Names beginning with a $ character [MQTT-4.7.2-1].
+pub fn matches(filter: &str, name: &str) -> bool {
+    if name.starts_with('$') && (filter.starts_with('+') || filter.starts_with('#')) {
+        return false;
+    }
+
+    let mut filter_levels = filter.split(LEVEL_SEPARATOR);
+    let mut name_levels = name.split(LEVEL_SEPARATOR);
+    loop {
+        match (filter_levels.next(), name_levels.next()) {
+            // "sport/#" also matches the parent level "sport".
+            (Some(MULTI_LEVEL_WILDCARD), _) => return true,
+            (Some(SINGLE_LEVEL_WILDCARD), Some(_)) => continue,
+            (Some(f), Some(n)) if f == n => continue,
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+// covers results whether every Topic Name matching the other filter also matches the filter.
+// e.g. "a/#" covers "a/+/c", but "a/+" does not cover "a/#".
+pub fn covers(filter: &str, other: &str) -> bool {
+    if other.starts_with('$') && (filter.starts_with('+') || filter.starts_with('#')) {
+        return false;
+    }
+
+    let mut filter_levels = filter.split(LEVEL_SEPARATOR);
+    let mut other_levels = other.split(LEVEL_SEPARATOR);
+    loop {
+        match (filter_levels.next(), other_levels.next()) {
+            (Some(MULTI_LEVEL_WILDCARD), _) => return true,
+            (Some(_), Some(MULTI_LEVEL_WILDCARD)) => return false,
+            (Some(SINGLE_LEVEL_WILDCARD), Some(_)) => continue,
+            (Some(_), Some(SINGLE_LEVEL_WILDCARD)) => return false,
+            (Some(f), Some(o)) if f == o => continue,
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::collections::HashMap;
+
+use crate::packets;
+use crate::packets::disconnect::{self, Disconnect};
+use crate::packets::publish::Publish;
+use crate::packets::{ExtractValue, UTF8EncodedString};
+
+#[path = "alias_tests.rs"]
+#[cfg(test)]
+mod alias_tests;
+
+// The implementations of this module follow the 3.3.2.3.4 Topic Alias subsection in the MQTT 5.0 specs.
+// The Topic Alias mappings exist only within a Network Connection, and each direction has its own mappings
+// whose Topic Alias Maximum is sent by the receiver in the CONNECT or CONNACK Packet.
+
+// topic_alias results the Topic Alias property of the PUBLISH Packet.
+fn topic_alias(publish: &Publish) -> Option<u16> {
+    publish
+        .properties
+        .get_as::<packets::TwoByteInteger>(packets::TOPIC_ALIAS)
+        .ok()
+        .flatten()
+        .map(|topic_alias| topic_alias.val())
+}
+
+// InboundTopicAliases are the Topic Alias mappings which the Client sets in the PUBLISH Packets to the Server.
+#[derive(Debug, Default)]
+pub struct InboundTopicAliases {
+    maximum: u16,
+    topics: HashMap<u16, String>,
+}
+
+impl InboundTopicAliases {
+    // new results the empty mappings which accept the Topic Aliases up to the Topic Alias Maximum of the Server.
+    pub fn new(maximum: u16) -> InboundTopicAliases {
+        InboundTopicAliases {
+            maximum,
+            topics: HashMap::new(),
+        }
+    }
+
+    // resolve replaces the Topic Alias of the PUBLISH Packet by the Topic Name, and the Topic Alias property is removed.
+    // The PUBLISH Packet with both of them sets the mapping, and the one with the zero length Topic Name uses the mapping.
+    // - The Topic Alias of 0 or greater than the Topic Alias Maximum is the Reason Code 0x94 (Topic Alias invalid) [MQTT-3.3.2-8] [MQTT-3.3.2-9].
+    // - The zero length Topic Name without the mapping of the Topic Alias is 0x82 (Protocol Error).
+    pub fn resolve(&mut self, publish: &mut Publish) -> Result<(), Disconnect> {
+        let Some(topic_alias) = topic_alias(publish) else {
+            return Ok(());
+        };
+        if topic_alias == 0 || topic_alias > self.maximum {
+            return Err(Disconnect::with_reason(
+                disconnect::TOPIC_ALIAS_INVALID,
+                &format!("Topic Alias {} exceeds the Topic Alias Maximum {}", topic_alias, self.maximum),
+            ));
+        }
+
+        let topic_name = publish.topic_name.val();
+        if topic_name.is_empty() {
+            let Some(topic_name) = self.topics.get(&topic_alias) else {
+                return Err(Disconnect::with_reason(
+                    disconnect::PROTOCOL_ERROR,
+                    &format!("Topic Alias {} has no Topic Name", topic_alias),
+                ));
+            };
+            publish.topic_name = UTF8EncodedString(topic_name.clone());
+        } else {
+            self.topics.insert(topic_alias, topic_name.to_string());
+        }
+        publish.properties.remove(&packets::TOPIC_ALIAS);
+
+        Ok(())
+    }
+}
+
+// OutboundTopicAliases are the Topic Alias mappings which the Server sets in the PUBLISH Packets to the Client.
+// The Topic Names are mapped as they are sent while the Topic Aliases remain, and then the least recently used mapping
+// is replaced, so the frequently used Topic Names keep their Topic Aliases.
+#[derive(Debug, Default)]
+pub struct OutboundTopicAliases {
+    maximum: u16,
+    aliases: HashMap<String, (u16, u64)>, // The Topic Alias and the time it was used last.
+    free: Vec<u16>,                       // The revoked Topic Aliases.
+    last_alias: u16,                      // The greatest Topic Alias ever mapped.
+    clock: u64,
+}
+
+impl OutboundTopicAliases {
+    // new results the empty mappings which use the Topic Aliases up to the Topic Alias Maximum of the Client.
+    // The Server MUST NOT send a Topic Alias greater than it, and 0 means no Topic Alias is sent [MQTT-3.3.2-10] [MQTT-3.3.2-11].
+    pub fn new(maximum: u16) -> OutboundTopicAliases {
+        OutboundTopicAliases {
+            maximum,
+            aliases: HashMap::new(),
+            free: Vec::new(),
+            last_alias: 0,
+            clock: 0,
+        }
+    }
+
+    // apply results the PUBLISH Packet with the Topic Alias of its Topic Name.
+    // The Topic Name is sent with the Topic Alias to set the new mapping, and it is omitted when the Client knows the mapping.
+    pub fn apply(&mut self, publish: &Publish) -> Publish {
+        let topic_name = publish.topic_name.val();
+        if self.maximum == 0 || topic_name.is_empty() {
+            return publish.clone();
+        }
+        self.clock += 1;
+
+        let mut aliased = publish.clone();
+        if let Some((topic_alias, used)) = self.aliases.get_mut(topic_name) {
+            *used = self.clock;
+            aliased.topic_name = UTF8EncodedString(String::new());
+            aliased
+                .properties
+                .insert(packets::TOPIC_ALIAS, packets::TwoByteInteger(*topic_alias).into());
+            return aliased;
+        }
+
+        let topic_alias = if let Some(topic_alias) = self.free.pop() {
+            Some(topic_alias)
+        } else if self.last_alias < self.maximum {
+            self.last_alias += 1;
+            Some(self.last_alias)
+        } else {
+            let least_recently_used = self
+                .aliases
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(topic_name, (topic_alias, _))| (topic_name.clone(), *topic_alias));
+            least_recently_used.map(|(topic_name, topic_alias)| {
+                self.aliases.remove(&topic_name);
+                topic_alias
+            })
+        };
+        if let Some(topic_alias) = topic_alias {
+            self.aliases.insert(topic_name.to_string(), (topic_alias, self.clock));
+            aliased
+                .properties
+                .insert(packets::TOPIC_ALIAS, packets::TwoByteInteger(topic_alias).into());
+        }
+        aliased
+    }
+
+    // revoke forgets the mapping which the PUBLISH Packet resulted by apply has set,
+    // when the packet is not sent to the Client, e.g. it is discarded for the Maximum Packet Size of the Client.
+    pub fn revoke(&mut self, aliased: &Publish) {
+        let topic_name = aliased.topic_name.val();
+        if topic_name.is_empty() {
+            return;
+        }
+        if let (Some(topic_alias), Some((mapped, _))) = (topic_alias(aliased), self.aliases.get(topic_name)) {
+            if *mapped == topic_alias {
+                self.aliases.remove(topic_name);
+                self.free.push(topic_alias);
+            }
+        }
+    }
+}
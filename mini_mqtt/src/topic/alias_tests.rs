@@ -0,0 +1,103 @@
+use super::*;
+use crate::packets::{QoS, TwoByteInteger};
+
+fn publish_packet(topic_name: &str, topic_alias: Option<u16>) -> Publish {
+    let mut properties = packets::Properties::new();
+    if let Some(topic_alias) = topic_alias {
+        properties.insert(packets::TOPIC_ALIAS, TwoByteInteger(topic_alias).into());
+    }
+    Publish::new(
+        Publish::fixed_header(false, QoS::AtMostOnce, false),
+        UTF8EncodedString(topic_name.to_string()),
+        None,
+        properties,
+        b"hello".to_vec(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn inbound_resolve_sets_and_uses_mapping() {
+    let mut aliases = InboundTopicAliases::new(10);
+
+    let mut publish = publish_packet("devices/0001/telemetry", Some(1));
+    aliases.resolve(&mut publish).unwrap();
+    assert_eq!(publish, publish_packet("devices/0001/telemetry", None));
+
+    let mut publish = publish_packet("", Some(1));
+    aliases.resolve(&mut publish).unwrap();
+    assert_eq!(publish, publish_packet("devices/0001/telemetry", None));
+
+    // The mapping is replaced by the new Topic Name.
+    let mut publish = publish_packet("devices/0002/telemetry", Some(1));
+    aliases.resolve(&mut publish).unwrap();
+    let mut publish = publish_packet("", Some(1));
+    aliases.resolve(&mut publish).unwrap();
+    assert_eq!(publish.topic_name.val(), "devices/0002/telemetry");
+
+    let mut publish = publish_packet("a/b", None);
+    aliases.resolve(&mut publish).unwrap();
+    assert_eq!(publish, publish_packet("a/b", None));
+}
+
+#[test]
+fn inbound_resolve_rejects_invalid_topic_alias() {
+    let mut aliases = InboundTopicAliases::new(10);
+
+    let err = aliases.resolve(&mut publish_packet("a/b", Some(0))).unwrap_err();
+    assert_eq!(err.reason_code, disconnect::TOPIC_ALIAS_INVALID);
+    let err = aliases.resolve(&mut publish_packet("a/b", Some(11))).unwrap_err();
+    assert_eq!(err.reason_code, disconnect::TOPIC_ALIAS_INVALID);
+    let err = aliases.resolve(&mut publish_packet("", Some(2))).unwrap_err();
+    assert_eq!(err.reason_code, disconnect::PROTOCOL_ERROR);
+
+    let err = InboundTopicAliases::new(0).resolve(&mut publish_packet("a/b", Some(1))).unwrap_err();
+    assert_eq!(err.reason_code, disconnect::TOPIC_ALIAS_INVALID);
+}
+
+#[test]
+fn outbound_apply_omits_topic_name_of_mapped_topic() {
+    let mut aliases = OutboundTopicAliases::new(10);
+
+    let publish = publish_packet("devices/0001/telemetry", None);
+    assert_eq!(aliases.apply(&publish), publish_packet("devices/0001/telemetry", Some(1)));
+    assert_eq!(aliases.apply(&publish), publish_packet("", Some(1)));
+    assert_eq!(aliases.apply(&publish_packet("a/b", None)), publish_packet("a/b", Some(2)));
+    assert_eq!(aliases.apply(&publish), publish_packet("", Some(1)));
+}
+
+#[test]
+fn outbound_apply_without_topic_alias_maximum() {
+    let mut aliases = OutboundTopicAliases::new(0);
+
+    let publish = publish_packet("a/b", None);
+    assert_eq!(aliases.apply(&publish), publish);
+    assert_eq!(aliases.apply(&publish), publish);
+}
+
+#[test]
+fn outbound_apply_replaces_least_recently_used_mapping() {
+    let mut aliases = OutboundTopicAliases::new(2);
+
+    aliases.apply(&publish_packet("a", None));
+    aliases.apply(&publish_packet("b", None));
+    aliases.apply(&publish_packet("a", None));
+    // "b" is the least recently used, so its Topic Alias is mapped to "c".
+    assert_eq!(aliases.apply(&publish_packet("c", None)), publish_packet("c", Some(2)));
+    assert_eq!(aliases.apply(&publish_packet("a", None)), publish_packet("", Some(1)));
+    assert_eq!(aliases.apply(&publish_packet("b", None)), publish_packet("b", Some(2)));
+}
+
+#[test]
+fn outbound_revoke_forgets_unsent_mapping() {
+    let mut aliases = OutboundTopicAliases::new(10);
+
+    let aliased = aliases.apply(&publish_packet("a/b", None));
+    aliases.revoke(&aliased);
+    assert_eq!(aliases.apply(&publish_packet("a/b", None)), publish_packet("a/b", Some(1)));
+
+    // The PUBLISH Packet using the known mapping does not forget it.
+    let aliased = aliases.apply(&publish_packet("a/b", None));
+    aliases.revoke(&aliased);
+    assert_eq!(aliases.apply(&publish_packet("a/b", None)), publish_packet("", Some(1)));
+}
@@ -0,0 +1,98 @@
+use super::*;
+
+#[test]
+fn validate_topic_name_valid() {
+    assert!(validate_topic_name("sport/tennis/player1").is_ok());
+    assert!(validate_topic_name("/").is_ok());
+}
+
+#[test]
+fn validate_topic_name_invalid() {
+    assert!(validate_topic_name("").is_err());
+    assert!(validate_topic_name("sport/+").is_err());
+    assert!(validate_topic_name("sport/#").is_err());
+}
+
+#[test]
+fn validate_topic_filter_valid() {
+    assert!(validate_topic_filter("sport/tennis/#").is_ok());
+    assert!(validate_topic_filter("#").is_ok());
+    assert!(validate_topic_filter("+/tennis/+").is_ok());
+    assert!(validate_topic_filter("+").is_ok());
+}
+
+#[test]
+fn validate_topic_filter_invalid() {
+    assert!(validate_topic_filter("").is_err());
+    assert!(validate_topic_filter("sport/tennis#").is_err());
+    assert!(validate_topic_filter("sport/#/ranking").is_err());
+    assert!(validate_topic_filter("sport+").is_err());
+}
+
+#[test]
+fn matches_multi_level_wildcard() {
+    assert!(matches("sport/tennis/player1/#", "sport/tennis/player1"));
+    assert!(matches("sport/tennis/player1/#", "sport/tennis/player1/ranking"));
+    assert!(matches("sport/tennis/player1/#", "sport/tennis/player1/score/wimbledon"));
+    assert!(matches("#", "sport"));
+    assert!(!matches("sport/tennis/#", "sport/football"));
+}
+
+#[test]
+fn matches_single_level_wildcard() {
+    assert!(matches("sport/tennis/+", "sport/tennis/player1"));
+    assert!(!matches("sport/tennis/+", "sport/tennis/player1/ranking"));
+    assert!(matches("sport/+", "sport/"));
+    assert!(!matches("sport/+", "sport"));
+    assert!(matches("+/+", "/finance"));
+    assert!(matches("/+", "/finance"));
+    assert!(!matches("+", "/finance"));
+}
+
+#[test]
+fn matches_exact() {
+    assert!(matches("sport/tennis", "sport/tennis"));
+    assert!(!matches("sport/tennis", "sport/tennis/player1"));
+    assert!(!matches("sport/tennis", "sport"));
+}
+
+#[test]
+fn matches_dollar_topics() {
+    assert!(!matches("#", "$SYS/monitor/Clients"));
+    assert!(!matches("+/monitor/Clients", "$SYS/monitor/Clients"));
+    assert!(matches("$SYS/#", "$SYS/monitor/Clients"));
+    assert!(matches("$SYS/monitor/+", "$SYS/monitor/Clients"));
+}
+
+#[test]
+fn covers_filters() {
+    assert!(covers("a/#", "a/+/c"));
+    assert!(covers("a/#", "a/#"));
+    assert!(covers("a/#", "a"));
+    assert!(covers("a/+", "a/b"));
+    assert!(covers("a/+", "a/+"));
+    assert!(!covers("a/+", "a/#"));
+    assert!(!covers("a/b", "a/+"));
+    assert!(!covers("a/+", "a/b/c"));
+    assert!(!covers("a", "a/#"));
+    assert!(!covers("#", "$SYS/#"));
+    assert!(covers("$SYS/#", "$SYS/monitor"));
+}
+
+#[test]
+fn parse_shared_subscription_valid() {
+    assert_eq!(parse_shared_subscription("$share/consumers/sport/#").unwrap(), Some(("consumers", "sport/#")));
+    assert_eq!(parse_shared_subscription("$share/g/+").unwrap(), Some(("g", "+")));
+    assert_eq!(parse_shared_subscription("sport/#").unwrap(), None);
+    assert_eq!(parse_shared_subscription("$SYS/#").unwrap(), None);
+}
+
+#[test]
+fn parse_shared_subscription_invalid() {
+    assert!(parse_shared_subscription("$share/consumers").is_err());
+    assert!(parse_shared_subscription("$share//sport").is_err());
+    assert!(parse_shared_subscription("$share/+/sport").is_err());
+    assert!(parse_shared_subscription("$share/g#/sport").is_err());
+    assert!(parse_shared_subscription("$share/g/").is_err());
+    assert!(parse_shared_subscription("$share/g/sport/#/x").is_err());
+}
@@ -0,0 +1,75 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc, Mutex, RwLock};
+
+use mini_mqtt::config;
+use mini_mqtt::packets;
+use mini_mqtt::session;
+use mini_mqtt::session::handler::{Handler, Outgoing};
+
+// Broker is shared by the connections of every listener.
+// The Handler holds the sessions, and the Broker routes the packets which the Handler results
+// to the connection of each session.
+// max_connections limits the connected Clients, None means no limit.
+pub struct Broker {
+    handler: Arc<RwLock<Handler>>,
+    connections: Mutex<HashMap<session::SessionId, mpsc::Sender<packets::Packet>>>,
+    max_connections: Option<usize>,
+    active_connections: AtomicUsize,
+}
+
+// ConnectionSlot is a connection counted by the Broker. The slot is released when it is dropped.
+pub struct ConnectionSlot {
+    broker: Arc<Broker>,
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.broker.active_connections.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Broker {
+    pub fn new(config: config::Config, max_connections: Option<usize>) -> Arc<Broker> {
+        Arc::new(Broker {
+            handler: Handler::with_config(config),
+            connections: Mutex::new(HashMap::new()),
+            max_connections,
+            active_connections: AtomicUsize::new(0),
+        })
+    }
+
+    // acquire_connection results a slot for the accepted socket, or None when max_connections Clients are connected.
+    pub fn acquire_connection(self: &Arc<Self>) -> Option<ConnectionSlot> {
+        let active = self.active_connections.fetch_add(1, Ordering::SeqCst);
+        let slot = ConnectionSlot { broker: self.clone() };
+        match self.max_connections {
+            Some(max_connections) if active >= max_connections => None,
+            _ => Some(slot),
+        }
+    }
+
+    pub fn handler(&self) -> &Arc<RwLock<Handler>> {
+        &self.handler
+    }
+
+    // register the connection of the session. The packets sent to the session are written by the connection.
+    pub fn register(&self, session_id: session::SessionId, sender: mpsc::Sender<packets::Packet>) {
+        self.connections.lock().unwrap().insert(session_id, sender);
+    }
+
+    pub fn unregister(&self, session_id: &session::SessionId) {
+        self.connections.lock().unwrap().remove(session_id);
+    }
+
+    // deliver sends the packets to the connections of the sessions.
+    // The packets to the session whose connection has been closed are discarded.
+    pub fn deliver(&self, outgoings: Vec<Outgoing>) {
+        let connections = self.connections.lock().unwrap();
+        for outgoing in outgoings {
+            if let Some(sender) = connections.get(&outgoing.session_id) {
+                let _ = sender.send(outgoing.packet);
+            }
+        }
+    }
+}
@@ -0,0 +1,279 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+
+use mini_mqtt::errors;
+
+use crate::settings::{self, Settings};
+
+#[path = "cli_tests.rs"]
+#[cfg(test)]
+mod cli_tests;
+
+// Cli is the command line of the broker. The flags override the settings of the configuration file.
+#[derive(Debug, Parser)]
+#[command(name = "mini_mqtt_broker", version, about = "MQTT broker")]
+pub struct Cli {
+    /// The TOML configuration file
+    #[arg(long, value_name = "PATH")]
+    pub config: Option<PathBuf>,
+
+    /// The address of the mqtt:// listener [default: 0.0.0.0:1883]
+    #[arg(long, value_name = "ADDRESS")]
+    pub bind: Option<String>,
+    /// Disable the mqtt:// listener
+    #[arg(long)]
+    pub no_tcp: bool,
+
+    /// The address of the mqtts:// listener [default: 0.0.0.0:8883]
+    #[arg(long, value_name = "ADDRESS")]
+    pub tls_bind: Option<String>,
+    /// The PEM certificate chain of the Server, which enables the mqtts:// listener
+    #[arg(long, value_name = "PATH", requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+    /// The PEM private key of the Server
+    #[arg(long, value_name = "PATH", requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+    /// The PEM CA certificates which verify the client certificates
+    #[arg(long, value_name = "PATH")]
+    pub tls_client_ca: Option<PathBuf>,
+    /// Reject the Clients without a client certificate
+    #[arg(long)]
+    pub tls_require_client_cert: bool,
+    /// Map the certificate field (cn or san) to the Client's (client-id or username)
+    #[arg(long, value_name = "FIELD:TARGET")]
+    pub tls_identity: Option<String>,
+
+    /// The address of the MQTT over WebSocket listener
+    #[arg(long, value_name = "ADDRESS")]
+    pub ws_bind: Option<String>,
+
+    /// The path of the Unix domain socket listener
+    #[arg(long, value_name = "PATH")]
+    pub unix_socket: Option<PathBuf>,
+    /// The octal file permissions of the Unix domain socket [default: 660]
+    #[arg(long, value_name = "MODE")]
+    pub unix_socket_mode: Option<String>,
+
+    /// The maximum number of connected Clients
+    #[arg(long, value_name = "COUNT")]
+    pub max_connections: Option<usize>,
+    /// The largest packet in bytes which the Server accepts
+    #[arg(long, value_name = "BYTES")]
+    pub max_packet_size: Option<u32>,
+    /// The highest QoS which the Server accepts [default: 2]
+    #[arg(long, value_name = "QOS")]
+    pub maximum_qos: Option<u8>,
+    /// Whether the Server accepts the messages with the RETAIN flag [default: true]
+    #[arg(long, value_name = "BOOL")]
+    pub retain_available: Option<bool>,
+    /// The maximum Session Expiry Interval in seconds
+    #[arg(long, value_name = "SECONDS")]
+    pub session_expiry_max: Option<u32>,
+    /// The number of QoS 1 and QoS 2 publications which the Server processes concurrently for a Client [default: 65535]
+    #[arg(long, value_name = "COUNT")]
+    pub receive_maximum: Option<u16>,
+    /// The highest Topic Alias which the Server accepts from a Client, 0 disables them [default: 0]
+    #[arg(long, value_name = "COUNT")]
+    pub topic_alias_maximum: Option<u16>,
+    /// How a Shared Subscription chooses the Client of each message:
+    /// round_robin, random, least_inflight or sticky_by_topic_hash [default: round_robin]
+    #[arg(long, value_name = "STRATEGY")]
+    pub shared_subscription_strategy: Option<String>,
+    /// The maximum number of messages queued for each session [default: 1000]
+    #[arg(long, value_name = "COUNT")]
+    pub max_queued_messages: Option<usize>,
+    /// The maximum bytes of the Topic Names and payloads queued for each session [default: 16777216]
+    #[arg(long, value_name = "BYTES")]
+    pub max_queued_bytes: Option<usize>,
+    /// Which message is lost when the queue of a session is full:
+    /// drop_oldest, drop_newest or reject_publisher [default: drop_oldest]
+    #[arg(long, value_name = "POLICY")]
+    pub queue_overflow_policy: Option<String>,
+    /// The Keep Alive in seconds which the Clients must use instead of their own
+    #[arg(long, value_name = "SECONDS")]
+    pub server_keep_alive: Option<u16>,
+    /// The prefix of the Response Information returned to the Clients requesting it, followed by their ClientIDs
+    #[arg(long, value_name = "PREFIX")]
+    pub response_information_prefix: Option<String>,
+
+    /// The longest ClientID in bytes which the Server accepts [default: 23]
+    #[arg(long, value_name = "BYTES")]
+    pub client_id_max_length: Option<usize>,
+    /// The characters which the ClientIDs can contain besides the alphanumeric ones, e.g. "-_:"
+    #[arg(long, value_name = "CHARACTERS")]
+    pub client_id_allowed_characters: Option<String>,
+    /// The regular expression which the whole ClientID must match instead of the allowed characters
+    #[arg(long, value_name = "REGEX")]
+    pub client_id_pattern: Option<String>,
+    /// The prefix which the ClientIDs of the User Name must start with, can be repeated
+    #[arg(long, value_name = "USERNAME=PREFIX", value_parser = parse_tenant_prefix)]
+    pub client_id_tenant_prefix: Vec<(String, String)>,
+    /// Accept the Clients of MQTT v3.1, whose protocol name is "MQIsdp"
+    #[arg(long)]
+    pub legacy_protocol: bool,
+
+    /// The password file, which enables the password_file auth backend
+    #[arg(long, value_name = "PATH")]
+    pub password_file: Option<PathBuf>,
+    /// The ACL file
+    #[arg(long, value_name = "PATH")]
+    pub acl_file: Option<PathBuf>,
+
+    /// The file which keeps the persistent sessions and the retained messages across restarts,
+    /// which enables the append_log store backend
+    #[arg(long, value_name = "PATH")]
+    pub store_path: Option<PathBuf>,
+    /// The number of the records appended to the store file between its snapshots [default: 10000]
+    #[arg(long, value_name = "COUNT")]
+    pub store_snapshot_interval: Option<usize>,
+
+    /// The log level: off, error, warn, info, debug or trace [default: info]
+    #[arg(long, value_name = "LEVEL")]
+    pub log_level: Option<String>,
+}
+
+impl Cli {
+    // settings loads the configuration file, or the defaults without it, and applies the flags.
+    pub fn settings(&self) -> Result<Settings, errors::Error> {
+        let mut settings = match &self.config {
+            Some(path) => Settings::load(path)?,
+            None => Settings::default(),
+        };
+        self.apply(&mut settings);
+        Ok(settings)
+    }
+
+    pub fn apply(&self, settings: &mut Settings) {
+        let listeners = &mut settings.listeners;
+        if let Some(bind) = &self.bind {
+            listeners.tcp.bind = bind.clone();
+        }
+        if self.no_tcp {
+            listeners.tcp.enabled = false;
+        }
+
+        if let (Some(certificate), Some(private_key)) = (&self.tls_cert, &self.tls_key) {
+            let bind = listeners.tls.as_ref().map(|tls| tls.bind.clone());
+            listeners.tls = Some(settings::TlsListenerSettings {
+                bind: bind.unwrap_or_else(settings::default_tls_bind),
+                certificate: certificate.clone(),
+                private_key: private_key.clone(),
+                client_ca: None,
+                require_client_certificate: false,
+                identity: None,
+            });
+        }
+        if let Some(tls) = &mut listeners.tls {
+            if let Some(bind) = &self.tls_bind {
+                tls.bind = bind.clone();
+            }
+            if let Some(client_ca) = &self.tls_client_ca {
+                tls.client_ca = Some(client_ca.clone());
+            }
+            if self.tls_require_client_cert {
+                tls.require_client_certificate = true;
+            }
+            if let Some(identity) = &self.tls_identity {
+                tls.identity = Some(identity.clone());
+            }
+        }
+
+        if let Some(bind) = &self.ws_bind {
+            listeners.websocket = Some(settings::WebSocketListenerSettings { bind: bind.clone() });
+        }
+
+        if let Some(path) = &self.unix_socket {
+            let mode = listeners.unix.as_ref().map(|unix| unix.mode.clone());
+            listeners.unix = Some(settings::UnixListenerSettings {
+                path: path.clone(),
+                mode: mode.unwrap_or_else(settings::default_unix_mode),
+            });
+        }
+        if let (Some(unix), Some(mode)) = (&mut listeners.unix, &self.unix_socket_mode) {
+            unix.mode = mode.clone();
+        }
+
+        let limits = &mut settings.limits;
+        if self.max_connections.is_some() {
+            limits.max_connections = self.max_connections;
+        }
+        if self.max_packet_size.is_some() {
+            limits.max_packet_size = self.max_packet_size;
+        }
+        if let Some(maximum_qos) = self.maximum_qos {
+            limits.maximum_qos = maximum_qos;
+        }
+        if let Some(retain_available) = self.retain_available {
+            limits.retain_available = retain_available;
+        }
+        if self.session_expiry_max.is_some() {
+            limits.session_expiry_interval_max = self.session_expiry_max;
+        }
+        if let Some(receive_maximum) = self.receive_maximum {
+            limits.receive_maximum = receive_maximum;
+        }
+        if let Some(topic_alias_maximum) = self.topic_alias_maximum {
+            limits.topic_alias_maximum = topic_alias_maximum;
+        }
+        if let Some(strategy) = &self.shared_subscription_strategy {
+            limits.shared_subscription_strategy = strategy.clone();
+        }
+        if let Some(max_queued_messages) = self.max_queued_messages {
+            limits.max_queued_messages = max_queued_messages;
+        }
+        if let Some(max_queued_bytes) = self.max_queued_bytes {
+            limits.max_queued_bytes = max_queued_bytes;
+        }
+        if let Some(policy) = &self.queue_overflow_policy {
+            limits.queue_overflow_policy = policy.clone();
+        }
+        if self.server_keep_alive.is_some() {
+            limits.server_keep_alive = self.server_keep_alive;
+        }
+        if let Some(prefix) = &self.response_information_prefix {
+            limits.response_information_prefix = Some(prefix.clone());
+        }
+
+        let client_id = &mut settings.client_id;
+        if let Some(max_length) = self.client_id_max_length {
+            client_id.max_length = max_length;
+        }
+        if let Some(characters) = &self.client_id_allowed_characters {
+            client_id.allowed_characters = Some(characters.clone());
+        }
+        if let Some(pattern) = &self.client_id_pattern {
+            client_id.pattern = Some(pattern.clone());
+        }
+        client_id.tenant_prefixes.extend(self.client_id_tenant_prefix.iter().cloned());
+        if self.legacy_protocol {
+            settings.legacy_protocol = true;
+        }
+
+        if let Some(password_file) = &self.password_file {
+            settings.auth.backend = settings::AuthBackend::PasswordFile;
+            settings.auth.password_file = Some(password_file.clone());
+        }
+        if let Some(acl_file) = &self.acl_file {
+            settings.auth.acl_file = Some(acl_file.clone());
+        }
+        if let Some(store_path) = &self.store_path {
+            settings.store.backend = settings::StoreBackend::AppendLog;
+            settings.store.path = Some(store_path.clone());
+        }
+        if let Some(snapshot_interval) = self.store_snapshot_interval {
+            settings.store.snapshot_interval = snapshot_interval;
+        }
+
+        if let Some(log_level) = &self.log_level {
+            settings.log_level = log_level.clone();
+        }
+    }
+}
+
+fn parse_tenant_prefix(value: &str) -> Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((user_name, prefix)) if !user_name.is_empty() => Ok((user_name.to_string(), prefix.to_string())),
+        _ => Err(format!("{} is not USERNAME=PREFIX", value)),
+    }
+}
@@ -0,0 +1,116 @@
+use super::*;
+
+fn parse(args: &[&str]) -> Cli {
+    Cli::try_parse_from(std::iter::once("mini_mqtt_broker").chain(args.iter().copied())).unwrap()
+}
+
+#[test]
+fn apply_without_flags() {
+    let mut settings = Settings::default();
+    parse(&[]).apply(&mut settings);
+    assert_eq!(settings, Settings::default());
+}
+
+#[test]
+fn apply_overrides_settings() {
+    let mut settings = Settings::parse("[limits]\nmax_connections = 10\nmaximum_qos = 0\n").unwrap();
+    parse(&[
+        "--bind",
+        "127.0.0.1:1884",
+        "--tls-cert",
+        "server.pem",
+        "--tls-key",
+        "server.key",
+        "--tls-identity",
+        "san:client-id",
+        "--ws-bind",
+        "127.0.0.1:8080",
+        "--unix-socket",
+        "/tmp/mini_mqtt.sock",
+        "--unix-socket-mode",
+        "600",
+        "--max-connections",
+        "20",
+        "--server-keep-alive",
+        "30",
+        "--response-information-prefix",
+        "responses/",
+        "--receive-maximum",
+        "5",
+        "--topic-alias-maximum",
+        "12",
+        "--shared-subscription-strategy",
+        "random",
+        "--max-queued-messages",
+        "50",
+        "--max-queued-bytes",
+        "4096",
+        "--queue-overflow-policy",
+        "drop_newest",
+        "--retain-available",
+        "false",
+        "--client-id-max-length",
+        "64",
+        "--client-id-allowed-characters=-_",
+        "--client-id-tenant-prefix",
+        "alice=alice-",
+        "--client-id-tenant-prefix",
+        "bob=bob-",
+        "--legacy-protocol",
+        "--password-file",
+        "passwords",
+        "--store-path",
+        "state.log",
+        "--store-snapshot-interval",
+        "500",
+        "--log-level",
+        "warn",
+    ])
+    .apply(&mut settings);
+
+    assert_eq!(settings.listeners.tcp.bind, "127.0.0.1:1884");
+    let tls = settings.listeners.tls.as_ref().unwrap();
+    assert_eq!(tls.bind, "0.0.0.0:8883");
+    assert_eq!(tls.identity.as_deref(), Some("san:client-id"));
+    assert_eq!(settings.listeners.websocket.as_ref().unwrap().bind, "127.0.0.1:8080");
+    assert_eq!(settings.listeners.unix.as_ref().unwrap().mode, "600");
+    assert_eq!(settings.limits.max_connections, Some(20));
+    assert_eq!(settings.limits.server_keep_alive, Some(30));
+    assert_eq!(settings.limits.response_information_prefix.as_deref(), Some("responses/"));
+    assert_eq!(settings.limits.receive_maximum, 5);
+    assert_eq!(settings.limits.topic_alias_maximum, 12);
+    assert_eq!(settings.limits.shared_subscription_strategy, "random");
+    assert_eq!(settings.limits.max_queued_messages, 50);
+    assert_eq!(settings.limits.max_queued_bytes, 4096);
+    assert_eq!(settings.limits.queue_overflow_policy, "drop_newest");
+    // The settings which the flags do not override are kept.
+    assert_eq!(settings.limits.maximum_qos, 0);
+    assert!(!settings.limits.retain_available);
+    assert_eq!(settings.client_id.max_length, 64);
+    assert_eq!(settings.client_id.allowed_characters.as_deref(), Some("-_"));
+    assert_eq!(settings.client_id.tenant_prefixes.len(), 2);
+    assert_eq!(settings.client_id.tenant_prefixes["bob"], "bob-");
+    assert!(settings.legacy_protocol);
+    assert_eq!(settings.auth.backend, settings::AuthBackend::PasswordFile);
+    assert_eq!(settings.store.backend, settings::StoreBackend::AppendLog);
+    assert_eq!(settings.store.path, Some(PathBuf::from("state.log")));
+    assert_eq!(settings.store.snapshot_interval, 500);
+    assert_eq!(settings.log_level(), Some(log::LevelFilter::Warn));
+    assert!(settings.validate().is_ok());
+}
+
+#[test]
+fn tls_cert_requires_tls_key() {
+    assert!(Cli::try_parse_from(["mini_mqtt_broker", "--tls-cert", "server.pem"]).is_err());
+}
+
+#[test]
+fn client_id_tenant_prefix_requires_user_name() {
+    assert!(Cli::try_parse_from(["mini_mqtt_broker", "--client-id-tenant-prefix", "alice-"]).is_err());
+    assert!(Cli::try_parse_from(["mini_mqtt_broker", "--client-id-tenant-prefix", "=alice-"]).is_err());
+}
+
+#[test]
+fn unknown_flag() {
+    assert!(Cli::try_parse_from(["mini_mqtt_broker", "--max-conections", "1"]).is_err());
+}
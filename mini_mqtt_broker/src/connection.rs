@@ -0,0 +1,303 @@
+use std::io::{self, Read, Write};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+use mini_mqtt::auth;
+use mini_mqtt::codec::{decoder, encoder};
+use mini_mqtt::errors;
+use mini_mqtt::packets;
+use mini_mqtt::packets::disconnect::{self, Disconnect};
+use mini_mqtt::packets::ExtractValue;
+use mini_mqtt::session;
+use mini_mqtt::topic::alias::{InboundTopicAliases, OutboundTopicAliases};
+
+use crate::broker::Broker;
+
+#[path = "connection_tests.rs"]
+#[cfg(test)]
+mod connection_tests;
+
+// Received is the result of a read from the stream.
+enum Received {
+    Packet(Box<packets::Packet>),
+    Idle,            // The read has timed out without a whole packet.
+    Closed,          // The Client has closed the connection.
+    TooLarge(usize), // The packet of the size exceeds the Maximum Packet Size of the Server.
+}
+
+// Connection runs the MQTT protocol on a stream of a listener.
+// The stream must time out the blocking reads, e.g. by TcpStream::set_read_timeout,
+// so that the packets which the other sessions route to the Client are written between the reads.
+// The Maximum Packet Sizes limit the packets in both directions, the one of the Server limits the received packets,
+// and the one which the Client has sent in the CONNECT Packet limits the sent packets.
+// Likewise the Topic Alias mappings of each direction belong to the connection, limited by the Topic Alias Maximums.
+// The Keep Alive of the session is watched by the time of the last received packet, 0 disables it.
+pub struct Connection<S: Read + Write> {
+    stream: S,
+    buffer: Vec<u8>,
+    protocol_version: packets::ProtocolVersion,
+    established: bool, // The CONNACK Packet accepting the Client has been sent.
+    server_maximum_packet_size: Option<u32>,
+    client_maximum_packet_size: Option<u32>,
+    inbound_topic_aliases: InboundTopicAliases,
+    outbound_topic_aliases: OutboundTopicAliases,
+    keep_alive: Duration,
+    last_received_at: Instant,
+}
+
+impl<S: Read + Write> Connection<S> {
+    pub fn new(stream: S) -> Connection<S> {
+        Connection {
+            stream,
+            buffer: Vec::new(),
+            protocol_version: packets::ProtocolVersion::V5,
+            established: false,
+            server_maximum_packet_size: None,
+            client_maximum_packet_size: None,
+            inbound_topic_aliases: InboundTopicAliases::default(),
+            outbound_topic_aliases: OutboundTopicAliases::default(),
+            keep_alive: Duration::ZERO,
+            last_received_at: Instant::now(),
+        }
+    }
+
+    // serve handles the packets until the Client closes the connection or violates the protocol.
+    // The peer is what the transport knows about the Client, e.g. the identity of the TLS client certificate.
+    pub fn serve(mut self, broker: &Broker, peer: &auth::Peer) -> Result<(), errors::Error> {
+        let (server_maximum_packet_size, topic_alias_maximum) = {
+            let handler = broker.handler().read().unwrap();
+            (handler.config().maximum_packet_size, handler.config().topic_alias_maximum)
+        };
+        self.server_maximum_packet_size = server_maximum_packet_size;
+        // After a Network Connection is established by a Client to a Server,
+        // the first packet sent from the Client to the Server MUST be a CONNECT packet [MQTT-3.1.0-1].
+        let connect = match self.wait_packet()? {
+            Some(packets::Packet::Connect(connect)) => connect,
+            Some(packet) => {
+                return Err(errors::Error::ProtocolError(format!("The first packet is not CONNECT: {:?}", packet)));
+            }
+            None => return Ok(()),
+        };
+        let result = broker.handler().write().unwrap().handle_connect_from(&connect, peer);
+        let session = match result {
+            Ok((session, connack, outgoings)) => {
+                // The session which this Client takes over is disconnected, and its Will Message is published.
+                broker.deliver(outgoings);
+                self.protocol_version = session.protocol_version;
+                self.client_maximum_packet_size = session.maximum_packet_size;
+                self.inbound_topic_aliases = InboundTopicAliases::new(topic_alias_maximum);
+                self.outbound_topic_aliases = OutboundTopicAliases::new(session.topic_alias_maximum);
+                self.keep_alive = session.keep_alive.to_std().unwrap_or_default();
+                self.send(&packets::Packet::ConnAck(connack))?;
+                self.established = true;
+                session
+            }
+            Err(connack) => {
+                self.protocol_version = connect.protocol_version().unwrap_or(packets::ProtocolVersion::V5);
+                return self.send(&packets::Packet::ConnAck(connack)).map(|_| ());
+            }
+        };
+
+        let (sender, receiver) = mpsc::channel();
+        broker.register(session.session_id.clone(), sender);
+        // The resumed session sends the messages in flight again after the CONNACK Packet [MQTT-4.4.0-1],
+        // and then the messages queued while it was disconnected.
+        let resumed = broker.handler().write().unwrap().resume_deliveries(&session.session_id);
+        let result = resumed.and_then(|outgoings| {
+            broker.deliver(outgoings);
+            self.run(broker, &session.session_id, &receiver)
+        });
+        broker.unregister(&session.session_id);
+        // The unacknowledged messages of the Shared Subscriptions are delivered to the other Clients.
+        let disconnected = broker.handler().write().unwrap().disconnect_session(&session.session_id);
+
+        result.and(disconnected.map(|outgoings| broker.deliver(outgoings)))
+    }
+
+    fn run(
+        &mut self,
+        broker: &Broker,
+        session_id: &session::SessionId,
+        receiver: &mpsc::Receiver<packets::Packet>,
+    ) -> Result<(), errors::Error> {
+        loop {
+            while let Ok(packet) = receiver.try_recv() {
+                if let packets::Packet::Disconnect(disconnect) = packet {
+                    return self.disconnect(disconnect);
+                }
+                let packets::Packet::Publish(publish) = packet else {
+                    self.send(&packet)?;
+                    continue;
+                };
+                let aliased = self.outbound_topic_aliases.apply(&publish);
+                if self.send(&packets::Packet::Publish(aliased.clone()))? {
+                    continue;
+                }
+                // The discarded PUBLISH Packet has not set the Topic Alias mapping to the Client,
+                // and it is treated as delivered, so it does not hold the quota of the Receive Maximum.
+                self.outbound_topic_aliases.revoke(&aliased);
+                if let Some(packet_identifier) = &publish.packet_identifier {
+                    let outgoings =
+                        broker.handler().write().unwrap().complete_delivery(session_id, packet_identifier.val())?;
+                    broker.deliver(outgoings);
+                }
+            }
+
+            let packet = match self.receive()? {
+                Received::Packet(packet) => *packet,
+                Received::Idle if self.keep_alive_expired() => {
+                    return self.disconnect(Disconnect::with_reason(
+                        disconnect::KEEP_ALIVE_TIMEOUT,
+                        &format!("No packet has been received within 1.5 times the Keep Alive of {:?}", self.keep_alive),
+                    ));
+                }
+                Received::Idle => continue,
+                Received::Closed => return Ok(()),
+                Received::TooLarge(size) => {
+                    return self.disconnect(Disconnect::with_reason(
+                        disconnect::PACKET_TOO_LARGE,
+                        &format!("The packet of {} bytes exceeds the Maximum Packet Size", size),
+                    ));
+                }
+            };
+            match packet {
+                packets::Packet::Subscribe(subscribe) => {
+                    let (suback, outgoings) = broker.handler().write().unwrap().handle_subscribe(session_id, &subscribe)?;
+                    self.send(&packets::Packet::SubAck(suback))?;
+                    // The retained messages follow the SUBACK Packet.
+                    broker.deliver(outgoings);
+                }
+                packets::Packet::Unsubscribe(unsubscribe) => {
+                    let unsuback = broker.handler().write().unwrap().handle_unsubscribe(session_id, &unsubscribe)?;
+                    self.send(&packets::Packet::UnsubAck(unsuback))?;
+                }
+                // The Server MUST send a PINGRESP packet in response to a PINGREQ packet [MQTT-3.12.4-1].
+                packets::Packet::PingReq => {
+                    self.send(&packets::Packet::PingResp)?;
+                }
+                packets::Packet::Publish(mut publish) => {
+                    if let Err(disconnect) = self.inbound_topic_aliases.resolve(&mut publish) {
+                        return self.disconnect(disconnect);
+                    }
+                    let outgoings = broker.handler().write().unwrap().handle_publish(session_id, &publish)?;
+                    broker.deliver(outgoings);
+                }
+                // The acknowledgement frees the quota of the Receive Maximum for the pending messages.
+                packets::Packet::PubAck(puback) => {
+                    let outgoings = broker.handler().write().unwrap().handle_puback(session_id, &puback)?;
+                    broker.deliver(outgoings);
+                }
+                packets::Packet::PubRec(pubrec) => {
+                    let outgoings = broker.handler().write().unwrap().handle_pubrec(session_id, &pubrec)?;
+                    broker.deliver(outgoings);
+                }
+                packets::Packet::PubRel(pubrel) => {
+                    let outgoings = broker.handler().write().unwrap().handle_pubrel(session_id, &pubrel)?;
+                    broker.deliver(outgoings);
+                }
+                packets::Packet::PubComp(pubcomp) => {
+                    let outgoings = broker.handler().write().unwrap().handle_pubcomp(session_id, &pubcomp)?;
+                    broker.deliver(outgoings);
+                }
+                // After sending a DISCONNECT packet the sender MUST close the Network Connection [MQTT-3.14.4-1].
+                packets::Packet::Disconnect(disconnect) => {
+                    broker.handler().write().unwrap().handle_disconnect(session_id, &disconnect);
+                    return Ok(());
+                }
+                packet => {
+                    return Err(errors::Error::ProtocolError(format!("Unexpected packet: {:?}", packet)));
+                }
+            }
+        }
+    }
+
+    fn wait_packet(&mut self) -> Result<Option<packets::Packet>, errors::Error> {
+        loop {
+            match self.receive()? {
+                Received::Packet(packet) => return Ok(Some(*packet)),
+                Received::Idle => continue,
+                Received::Closed => return Ok(None),
+                Received::TooLarge(size) => {
+                    return Err(errors::Error::ProtocolError(format!(
+                        "The packet of {} bytes exceeds the Maximum Packet Size",
+                        size
+                    )));
+                }
+            }
+        }
+    }
+
+    // receive reads the next packet. The size of the packet is checked as soon as its Fixed Header is received,
+    // so the packet exceeding the Maximum Packet Size is never buffered as a whole.
+    fn receive(&mut self) -> Result<Received, errors::Error> {
+        let mut chunk = [0u8; 4096];
+        loop {
+            if let Some(size) = decoder::frame_length(&self.buffer)? {
+                if self.server_maximum_packet_size.is_some_and(|maximum| size > maximum as usize) {
+                    return Ok(Received::TooLarge(size));
+                }
+            }
+            if let Some(frame) = decoder::take_frame(&mut self.buffer)? {
+                let packet = decoder::decode_as(&mut frame.as_slice(), &self.protocol_version)?;
+                self.last_received_at = Instant::now();
+                return Ok(Received::Packet(Box::new(packet)));
+            }
+
+            match self.stream.read(&mut chunk) {
+                Ok(0) => return Ok(Received::Closed),
+                Ok(n) => self.buffer.extend_from_slice(&chunk[..n]),
+                Err(err) if matches!(err.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => {
+                    return Ok(Received::Idle);
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+    }
+
+    // keep_alive_expired results whether the Client has sent no packet within one and a half times the Keep Alive,
+    // then the Server MUST close the Network Connection as if the network had failed [MQTT-3.1.2-22].
+    fn keep_alive_expired(&self) -> bool {
+        !self.keep_alive.is_zero() && self.last_received_at.elapsed() > self.keep_alive * 3 / 2
+    }
+
+    // send writes the packet within the Maximum Packet Size of the Client.
+    // The packet which is too large even without the optional properties is discarded [MQTT-3.1.2-25],
+    // and false is resulted then.
+    fn send(&mut self, packet: &packets::Packet) -> Result<bool, errors::Error> {
+        let Some(buffer) = encoder::encode_within(packet, &self.protocol_version, self.client_maximum_packet_size)? else {
+            log::debug!("The packet exceeding the Maximum Packet Size of the Client is discarded: {:?}", packet);
+            return Ok(false);
+        };
+        self.stream.write_all(&buffer)?;
+        self.stream.flush()?;
+
+        Ok(true)
+    }
+
+    // disconnect sends the DISCONNECT Packet and closes the Network Connection [MQTT-3.14.4-1].
+    // The Server MUST NOT send the DISCONNECT Packet before the CONNACK Packet accepting the Client [MQTT-3.14.0-1],
+    // and the older versions have no DISCONNECT Packet from the Server, so the connection is just closed then.
+    // The connection which the Server closes for its own reason, e.g. the Keep Alive expiry or the session taken over,
+    // results Ok, and the one closed for the error of the Client results the error.
+    fn disconnect(&mut self, disconnect: Disconnect) -> Result<(), errors::Error> {
+        let reason_code = disconnect.reason_code.clone();
+        if self.established && self.protocol_version.has_properties() {
+            self.send(&packets::Packet::Disconnect(disconnect))?;
+        }
+
+        match reason_code {
+            disconnect::NORMAL_DISCONNECTION
+            | disconnect::SERVER_SHUTTING_DOWN
+            | disconnect::KEEP_ALIVE_TIMEOUT
+            | disconnect::SESSION_TAKEN_OVER
+            | disconnect::ADMINISTRATIVE_ACTION => {
+                log::info!("The connection is closed with the Reason Code 0x{:02X}", reason_code.0);
+                Ok(())
+            }
+            _ => Err(errors::Error::ProtocolError(format!(
+                "The connection is closed with the Reason Code 0x{:02X}",
+                reason_code.0
+            ))),
+        }
+    }
+}
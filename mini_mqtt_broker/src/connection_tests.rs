@@ -0,0 +1,482 @@
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::thread;
+use std::time::Duration;
+
+use mini_mqtt::client;
+use mini_mqtt::config;
+use mini_mqtt::packets::suback;
+use mini_mqtt::packets::QoS;
+
+use super::*;
+use crate::listener;
+use crate::test_helpers::{self, connect_client, packet, publish_packet, read_packet, string};
+
+fn start(config: config::Config) -> SocketAddr {
+    test_helpers::start(config, listener::serve_tcp)
+}
+
+// will_connect_packet results the MQTT v5.0 CONNECT Packet with Clean Start, the Keep Alive and the QoS 0 Will Message.
+fn will_connect_packet(client_id: &str, keep_alive: u16, will_topic: &str) -> Vec<u8> {
+    let mut body = string("MQTT");
+    body.extend([0x05, 0x06]);
+    body.extend(keep_alive.to_be_bytes());
+    body.push(0x00);
+    body.extend(string(client_id));
+    body.push(0x00);
+    body.extend(string(will_topic));
+    body.extend(string("gone"));
+    packet(0x10, &body)
+}
+
+fn subscribe_packet(topic_filter: &str, options: u8) -> Vec<u8> {
+    let mut body = vec![0x00, 0x01, 0x00];
+    body.extend(string(topic_filter));
+    body.push(options);
+    packet(0x82, &body)
+}
+
+fn unsubscribe_packet(topic_filter: &str) -> Vec<u8> {
+    let mut body = vec![0x00, 0x02, 0x00];
+    body.extend(string(topic_filter));
+    packet(0xA2, &body)
+}
+
+#[test]
+fn serve_disconnects_packet_exceeding_maximum_packet_size() {
+    let config = config::Config {
+        maximum_packet_size: Some(64),
+        ..config::Config::default()
+    };
+    let address = start(config);
+    let mut stream = connect_client(address, "c1", &[]);
+
+    // Only the head of the PUBLISH Packet is sent, the Fixed Header is enough to reject it.
+    let publish = publish_packet("a/b", &[0u8; 100]);
+    stream.write_all(&publish[..8]).unwrap();
+    let disconnect = read_packet(&mut stream);
+    assert_eq!(disconnect[0], 0xE0);
+    assert_eq!(disconnect[2], 0x95); // Packet too large
+    let mut buffer = [0u8; 1];
+    assert!(!matches!(stream.read(&mut buffer), Ok(n) if n > 0));
+}
+
+#[test]
+fn serve_answers_pingreq_with_pingresp() {
+    let address = start(config::Config::default());
+    let mut stream = connect_client(address, "c1", &[]);
+
+    stream.write_all(&[0xC0, 0x00]).unwrap();
+    assert_eq!(read_packet(&mut stream), vec![0xD0, 0x00]);
+}
+
+#[test]
+fn serve_unsubscribes_topic_filter() {
+    let address = start(config::Config::default());
+    let mut subscriber = connect_client(address, "c1", &[]);
+    subscriber.write_all(&subscribe_packet("a/b", 0x00)).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x00]);
+    subscriber.write_all(&unsubscribe_packet("a/b")).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0xB0, 0x04, 0x00, 0x02, 0x00, 0x00]);
+    subscriber.write_all(&unsubscribe_packet("a/b")).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0xB0, 0x04, 0x00, 0x02, 0x00, 0x11]);
+
+    // The message published after the UNSUBACK Packet is not delivered, so the PINGRESP Packet comes first.
+    let mut publisher = connect_client(address, "c2", &[]);
+    publisher.write_all(&publish_packet("a/b", b"hello")).unwrap();
+    publisher.write_all(&[0xC0, 0x00]).unwrap();
+    assert_eq!(read_packet(&mut publisher), vec![0xD0, 0x00]);
+    subscriber.write_all(&[0xC0, 0x00]).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0xD0, 0x00]);
+}
+
+#[test]
+fn serve_exchanges_messages_with_client() {
+    let address = start(config::Config::default());
+    let timeout = Duration::from_secs(5);
+    let connect = |client_id: &str| {
+        let stream = TcpStream::connect(address).unwrap();
+        client::Client::connect(stream, client_id, packets::Properties::new(), timeout).unwrap().0
+    };
+    let mut subscriber = connect("c1");
+    let suback = subscriber.subscribe("a/+", QoS::AtLeastOnce, timeout).unwrap();
+    assert_eq!(suback.reason_codes, vec![suback::GRANTED_QOS_1]);
+    let mut publisher = connect("c2");
+    for payload in [b"1", b"2"] {
+        publisher.publish("a/b", QoS::AtLeastOnce, packets::Properties::new(), payload, timeout).unwrap();
+    }
+
+    for payload in [b"1", b"2"] {
+        let publish = subscriber.receive(timeout).unwrap().unwrap();
+        assert_eq!(publish.topic_name.val(), "a/b");
+        assert_eq!(publish.payload, payload);
+    }
+    publisher.disconnect().unwrap();
+    subscriber.disconnect().unwrap();
+}
+
+#[test]
+fn serve_routes_request_and_response_between_clients() {
+    let config = config::Config {
+        response_information_prefix: Some("responses/".to_string()),
+        ..config::Config::default()
+    };
+    let address = start(config);
+    let timeout = Duration::from_secs(5);
+    let stream = TcpStream::connect(address).unwrap();
+    let (mut responder, _) = client::Client::connect(stream, "service", packets::Properties::new(), timeout).unwrap();
+    responder.subscribe("service/echo", QoS::AtLeastOnce, timeout).unwrap();
+    let responder = thread::spawn(move || {
+        let request = responder.receive(timeout).unwrap().unwrap();
+        let mut payload = b"echo: ".to_vec();
+        payload.extend(&request.payload);
+        responder.respond(&request, &payload, timeout).unwrap();
+        responder.disconnect().unwrap();
+    });
+
+    let stream = TcpStream::connect(address).unwrap();
+    let mut properties = packets::Properties::new();
+    properties.insert(packets::REQUEST_RESPONSE_INFORMATION, packets::Bits(1).into());
+    let (mut requester, _) = client::Client::connect(stream, "c1", properties, timeout).unwrap();
+    assert_eq!(requester.response_topic(), "responses/c1");
+    let response = requester.request("service/echo", b"hello", timeout).unwrap();
+    assert_eq!(response.topic_name.val(), "responses/c1");
+    assert_eq!(response.payload, b"echo: hello");
+    responder.join().unwrap();
+    requester.disconnect().unwrap();
+}
+
+#[test]
+fn serve_closes_connection_after_keep_alive_and_publishes_will() {
+    let address = start(config::Config::default());
+    let mut subscriber = connect_client(address, "c1", &[]);
+    subscriber.write_all(&subscribe_packet("clients/+/status", 0x00)).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x00]);
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(&will_connect_packet("c2", 1, "clients/c2/status")).unwrap();
+    assert_eq!(read_packet(&mut stream)[0], 0x20);
+
+    // The Client sends nothing for 1.5 seconds, one and a half times the Keep Alive.
+    let disconnect = read_packet(&mut stream);
+    assert_eq!(disconnect[0], 0xE0);
+    assert_eq!(disconnect[2], 0x8D); // Keep Alive timeout
+    let mut buffer = [0u8; 1];
+    assert!(!matches!(stream.read(&mut buffer), Ok(n) if n > 0));
+
+    let will = read_packet(&mut subscriber);
+    assert_eq!(will[0], 0x30);
+    assert!(will.ends_with(b"gone"));
+}
+
+#[test]
+fn serve_discards_will_on_normal_disconnect() {
+    let address = start(config::Config::default());
+    let mut subscriber = connect_client(address, "c1", &[]);
+    subscriber.write_all(&subscribe_packet("clients/+/status", 0x00)).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x00]);
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(&will_connect_packet("c2", 60, "clients/c2/status")).unwrap();
+    assert_eq!(read_packet(&mut stream)[0], 0x20);
+    stream.write_all(&[0xE0, 0x00]).unwrap();
+    let mut buffer = [0u8; 1];
+    assert!(!matches!(stream.read(&mut buffer), Ok(n) if n > 0));
+
+    // Without the Will Message, the PINGRESP Packet is the next packet to the subscriber.
+    subscriber.write_all(&[0xC0, 0x00]).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0xD0, 0x00]);
+}
+
+#[test]
+fn serve_discards_packet_exceeding_client_maximum_packet_size() {
+    let address = start(config::Config::default());
+    // The Maximum Packet Size 20.
+    let mut subscriber = connect_client(address, "c1", &[0x27, 0x00, 0x00, 0x00, 0x14]);
+    subscriber.write_all(&subscribe_packet("a/b", 0x00)).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x00]);
+
+    let mut publisher = connect_client(address, "c2", &[]);
+    let large = publish_packet("a/b", &[0u8; 30]);
+    let small = publish_packet("a/b", b"small");
+    publisher.write_all(&[large, small.clone()].concat()).unwrap();
+
+    assert_eq!(read_packet(&mut subscriber), small);
+}
+
+#[test]
+fn serve_holds_deliveries_beyond_client_receive_maximum() {
+    let address = start(config::Config::default());
+    // The Receive Maximum 1.
+    let mut subscriber = connect_client(address, "c1", &[0x21, 0x00, 0x01]);
+    subscriber.write_all(&subscribe_packet("a/b", 0x01)).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x01]);
+
+    // Two QoS 1 PUBLISH Packets with the Packet Identifiers 1 and 2.
+    let mut publisher = connect_client(address, "c2", &[]);
+    let publish = |packet_identifier: u8| {
+        let mut body = string("a/b");
+        body.extend([0x00, packet_identifier, 0x00, b'x']);
+        packet(0x32, &body)
+    };
+    publisher.write_all(&[publish(1), publish(2)].concat()).unwrap();
+    assert_eq!(read_packet(&mut publisher), vec![0x40, 0x02, 0x00, 0x01]);
+    assert_eq!(read_packet(&mut publisher), vec![0x40, 0x02, 0x00, 0x02]);
+
+    assert_eq!(read_packet(&mut subscriber), publish(1));
+    subscriber.set_read_timeout(Some(Duration::from_millis(300))).unwrap();
+    let mut buffer = [0u8; 1];
+    assert!(subscriber.read(&mut buffer).is_err());
+
+    subscriber.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    subscriber.write_all(&[0x40, 0x02, 0x00, 0x01]).unwrap();
+    assert_eq!(read_packet(&mut subscriber), publish(2));
+}
+
+#[test]
+fn serve_resolves_and_assigns_topic_aliases() {
+    let config = config::Config {
+        topic_alias_maximum: 10,
+        ..config::Config::default()
+    };
+    let address = start(config);
+    // The Topic Alias Maximum 5.
+    let mut subscriber = connect_client(address, "c1", &[0x22, 0x00, 0x05]);
+    subscriber.write_all(&subscribe_packet("devices/0001/telemetry", 0x00)).unwrap();
+    read_packet(&mut subscriber);
+
+    // The QoS 0 PUBLISH Packet with the Topic Alias property.
+    let aliased = |topic_name: &str, topic_alias: u8| {
+        let mut body = string(topic_name);
+        body.extend([0x03, 0x23, 0x00, topic_alias, b'x']);
+        packet(0x30, &body)
+    };
+    let mut publisher = connect_client(address, "c2", &[]);
+    publisher
+        .write_all(&[aliased("devices/0001/telemetry", 1), aliased("", 1)].concat())
+        .unwrap();
+
+    assert_eq!(read_packet(&mut subscriber), aliased("devices/0001/telemetry", 1));
+    assert_eq!(read_packet(&mut subscriber), aliased("", 1));
+
+    // The Topic Alias without the mapping is a Protocol Error.
+    publisher.write_all(&aliased("", 2)).unwrap();
+    let disconnect = read_packet(&mut publisher);
+    assert_eq!(disconnect[0], 0xE0);
+    assert_eq!(disconnect[2], 0x82);
+}
+
+#[test]
+fn serve_redelivers_shared_messages_of_closed_connection() {
+    let address = start(config::Config::default());
+    let mut member1 = connect_client(address, "c1", &[]);
+    member1.write_all(&subscribe_packet("$share/group/a/b", 0x01)).unwrap();
+    read_packet(&mut member1);
+    let mut member2 = connect_client(address, "c2", &[]);
+    member2.write_all(&subscribe_packet("$share/group/a/b", 0x01)).unwrap();
+    read_packet(&mut member2);
+
+    let mut publisher = connect_client(address, "c3", &[]);
+    let mut body = string("a/b");
+    body.extend([0x00, 0x01, 0x00, b'x']);
+    publisher.write_all(&packet(0x32, &body)).unwrap();
+    assert_eq!(read_packet(&mut publisher), vec![0x40, 0x02, 0x00, 0x01]);
+
+    // The first member receives the message by the round-robin, and closes the connection without the PUBACK Packet.
+    assert_eq!(read_packet(&mut member1)[0], 0x32);
+    drop(member1);
+
+    let publish = read_packet(&mut member2);
+    assert_eq!(publish[0], 0x32);
+    assert_eq!(publish[publish.len() - 1], b'x');
+}
+
+#[test]
+fn serve_delivers_subscription_identifier() {
+    let address = start(config::Config::default());
+    let mut subscriber = connect_client(address, "c1", &[]);
+    // The SUBSCRIBE Packet of the Topic Filter "a/#" with the Subscription Identifier 128.
+    let mut body = vec![0x00, 0x01, 0x03, 0x0B, 0x80, 0x01];
+    body.extend(string("a/#"));
+    body.push(0x00);
+    subscriber.write_all(&packet(0x82, &body)).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x00]);
+
+    let mut publisher = connect_client(address, "c2", &[]);
+    publisher.write_all(&publish_packet("a/b", b"x")).unwrap();
+
+    let mut body = string("a/b");
+    body.extend([0x03, 0x0B, 0x80, 0x01, b'x']);
+    assert_eq!(read_packet(&mut subscriber), packet(0x30, &body));
+}
+
+#[test]
+fn serve_sends_retained_message_after_suback() {
+    let address = start(config::Config::default());
+    let mut publisher = connect_client(address, "c1", &[]);
+    // The QoS 0 PUBLISH Packet with the RETAIN flag.
+    let mut retained = publish_packet("a/b", b"on");
+    retained[0] |= 0x01;
+    publisher.write_all(&retained).unwrap();
+
+    // The retained message is stored before the broker handles the next packet of the publisher.
+    thread::sleep(Duration::from_millis(200));
+    let mut subscriber = connect_client(address, "c2", &[]);
+    subscriber.write_all(&subscribe_packet("a/+", 0x00)).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x00]);
+    assert_eq!(read_packet(&mut subscriber), retained);
+}
+
+// qos2_publish_packet results the QoS 2 PUBLISH Packet of the Packet Identifier, with the DUP flag for a retransmission.
+fn qos2_publish_packet(topic_name: &str, packet_identifier: u16, dup: bool) -> Vec<u8> {
+    let mut body = string(topic_name);
+    body.extend(packet_identifier.to_be_bytes());
+    body.push(0x00);
+    body.extend(b"on");
+    packet(if dup { 0x3C } else { 0x34 }, &body)
+}
+
+// connect_persistent connects the Client with Clean Start 0 and the Session Expiry Interval of an hour,
+// and results the stream and the Session Present flag of the CONNACK Packet.
+fn connect_persistent(address: SocketAddr, client_id: &str) -> (TcpStream, bool) {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut body = string("MQTT");
+    body.extend([0x05, 0x00, 0x00, 0x3C, 0x05, 0x11, 0x00, 0x00, 0x0E, 0x10]);
+    body.extend(string(client_id));
+    stream.write_all(&packet(0x10, &body)).unwrap();
+    let connack = read_packet(&mut stream);
+    assert_eq!(connack[0], 0x20);
+    assert_eq!(connack[3], 0x00);
+    (stream, connack[2] == 0x01)
+}
+
+#[test]
+fn serve_delivers_qos2_message_exactly_once() {
+    let address = start(config::Config::default());
+    let (mut subscriber, session_present) = connect_persistent(address, "c1");
+    assert!(!session_present);
+    subscriber.write_all(&subscribe_packet("a/b", 0x02)).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x02]);
+    let mut publisher = connect_client(address, "c2", &[]);
+
+    publisher.write_all(&qos2_publish_packet("a/b", 10, false)).unwrap();
+    assert_eq!(read_packet(&mut publisher), vec![0x50, 0x02, 0x00, 0x0A]);
+    // The publisher has not received the PUBREC Packet in time and sends the PUBLISH Packet again.
+    publisher.write_all(&qos2_publish_packet("a/b", 10, true)).unwrap();
+    assert_eq!(read_packet(&mut publisher), vec![0x50, 0x02, 0x00, 0x0A]);
+    publisher.write_all(&packet(0x62, &[0x00, 0x0A])).unwrap();
+    assert_eq!(read_packet(&mut publisher), vec![0x70, 0x02, 0x00, 0x0A]);
+
+    assert_eq!(read_packet(&mut subscriber), qos2_publish_packet("a/b", 1, false));
+    // The message is delivered only once, the next packet is the one published after it.
+    publisher.write_all(&publish_packet("a/b", b"next")).unwrap();
+    assert_eq!(read_packet(&mut subscriber), publish_packet("a/b", b"next"));
+
+    // The subscriber loses the connection before the PUBREC Packet, and the resumed session sends the message again.
+    drop(subscriber);
+    thread::sleep(Duration::from_millis(200));
+    let (mut subscriber, session_present) = connect_persistent(address, "c1");
+    assert!(session_present);
+    assert_eq!(read_packet(&mut subscriber), qos2_publish_packet("a/b", 1, true));
+    subscriber.write_all(&packet(0x50, &[0x00, 0x01])).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0x62, 0x02, 0x00, 0x01]);
+
+    // The PUBREL Packet is sent again instead of the PUBLISH Packet after the PUBREC Packet.
+    drop(subscriber);
+    thread::sleep(Duration::from_millis(200));
+    let (mut subscriber, session_present) = connect_persistent(address, "c1");
+    assert!(session_present);
+    assert_eq!(read_packet(&mut subscriber), vec![0x62, 0x02, 0x00, 0x01]);
+    subscriber.write_all(&packet(0x70, &[0x00, 0x01])).unwrap();
+    publisher.write_all(&publish_packet("a/b", b"done")).unwrap();
+    assert_eq!(read_packet(&mut subscriber), publish_packet("a/b", b"done"));
+}
+
+#[test]
+fn serve_delivers_queued_messages_on_reconnect() {
+    let address = start(config::Config::default());
+    let (mut subscriber, _) = connect_persistent(address, "c1");
+    subscriber.write_all(&subscribe_packet("a/b", 0x02)).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x02]);
+    drop(subscriber);
+    thread::sleep(Duration::from_millis(200));
+
+    let mut publisher = connect_client(address, "c2", &[]);
+    publisher.write_all(&qos2_publish_packet("a/b", 10, false)).unwrap();
+    assert_eq!(read_packet(&mut publisher), vec![0x50, 0x02, 0x00, 0x0A]);
+    publisher.write_all(&packet(0x62, &[0x00, 0x0A])).unwrap();
+    assert_eq!(read_packet(&mut publisher), vec![0x70, 0x02, 0x00, 0x0A]);
+    // The QoS 0 message is not queued.
+    publisher.write_all(&publish_packet("a/b", b"lost")).unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    let (mut subscriber, session_present) = connect_persistent(address, "c1");
+    assert!(session_present);
+    assert_eq!(read_packet(&mut subscriber), qos2_publish_packet("a/b", 1, false));
+    publisher.write_all(&publish_packet("a/b", b"next")).unwrap();
+    assert_eq!(read_packet(&mut subscriber), publish_packet("a/b", b"next"));
+}
+
+#[test]
+fn serve_restores_sessions_and_retained_messages_after_restart() {
+    let path = std::env::temp_dir().join(format!("mini_mqtt_connection_restart_{}.log", std::process::id()));
+    let snapshot_path = std::env::temp_dir().join(format!("mini_mqtt_connection_restart_{}.log.snapshot", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let _ = std::fs::remove_file(&snapshot_path);
+    // The small interval makes the broker restart from a snapshot and the records after it.
+    let stored_config = || config::Config {
+        store: std::sync::Arc::new(mini_mqtt::store::append_log::AppendLog::open(&path).unwrap().with_snapshot_interval(3)),
+        ..config::Config::default()
+    };
+    let address = start(stored_config());
+    let (mut subscriber, _) = connect_persistent(address, "c1");
+    subscriber.write_all(&subscribe_packet("a/b", 0x02)).unwrap();
+    assert_eq!(read_packet(&mut subscriber), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x02]);
+    drop(subscriber);
+    thread::sleep(Duration::from_millis(200));
+    let mut publisher = connect_client(address, "c2", &[]);
+    publisher.write_all(&qos2_publish_packet("a/b", 10, false)).unwrap();
+    assert_eq!(read_packet(&mut publisher), vec![0x50, 0x02, 0x00, 0x0A]);
+    let mut retained = publish_packet("r", b"kept");
+    retained[0] |= 0x01;
+    publisher.write_all(&retained).unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    // Another broker continues from the file, as if the first one had crashed.
+    let address = start(stored_config());
+    let (mut subscriber, session_present) = connect_persistent(address, "c1");
+    assert!(session_present);
+    assert_eq!(read_packet(&mut subscriber), qos2_publish_packet("a/b", 1, false));
+    let mut other = connect_client(address, "c3", &[]);
+    other.write_all(&subscribe_packet("r", 0x00)).unwrap();
+    assert_eq!(read_packet(&mut other), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x00]);
+    assert_eq!(read_packet(&mut other), retained);
+    std::fs::remove_file(&path).unwrap();
+    std::fs::remove_file(&snapshot_path).unwrap();
+}
+
+#[test]
+fn serve_tcp_closes_socket_beyond_max_connections() {
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = tcp_listener.local_addr().unwrap();
+    let broker = Broker::new(config::Config::default(), Some(1));
+    thread::spawn(move || listener::serve_tcp(tcp_listener, broker));
+    let _stream = connect_client(address, "c1", &[]);
+
+    // The socket is closed by the listener before the CONNECT Packet is read.
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    let mut buffer = [0u8; 1];
+    assert!(!matches!(stream.read(&mut buffer), Ok(n) if n > 0));
+}
+
+#[test]
+fn disconnect_results_error_only_for_error_of_client() {
+    let mut connection = Connection::new(io::Cursor::new(Vec::new()));
+    assert!(connection.disconnect(Disconnect::with_reason(disconnect::KEEP_ALIVE_TIMEOUT, "")).is_ok());
+    assert!(connection.disconnect(Disconnect::with_reason(disconnect::SESSION_TAKEN_OVER, "")).is_ok());
+    assert!(connection.disconnect(Disconnect::with_reason(disconnect::PACKET_TOO_LARGE, "")).is_err());
+    assert!(connection.disconnect(Disconnect::with_reason(disconnect::TOPIC_ALIAS_INVALID, "")).is_err());
+}
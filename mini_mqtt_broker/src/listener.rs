@@ -0,0 +1,54 @@
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use mini_mqtt::auth;
+use mini_mqtt::errors;
+
+use crate::broker::Broker;
+use crate::connection::Connection;
+
+// The default ports registered with IANA for MQTT over TCP and over TLS.
+pub const DEFAULT_PORT: u16 = 1883;
+pub const DEFAULT_TLS_PORT: u16 = 8883;
+
+// DEFAULT_UNIX_SOCKET_MODE permits the owner and the group of the broker to connect to the Unix domain socket.
+pub const DEFAULT_UNIX_SOCKET_MODE: u32 = 0o660;
+
+// READ_TIMEOUT is the interval which a connection waits for the Client before it writes the routed packets.
+pub const READ_TIMEOUT: Duration = Duration::from_millis(50);
+
+// serve_tcp accepts the mqtt:// connections and serves each of them in its own thread.
+pub fn serve_tcp(listener: TcpListener, broker: Arc<Broker>) -> Result<(), errors::Error> {
+    for stream in listener.incoming() {
+        // The failure to accept one socket does not stop the listener.
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("Failed to accept a connection: {}", err);
+                continue;
+            }
+        };
+        // The socket is closed when it is dropped without a slot.
+        let Some(slot) = broker.acquire_connection() else {
+            log::warn!("Connection is refused, the maximum number of connections is reached");
+            continue;
+        };
+        let broker = broker.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(err) = serve_tcp_stream(stream, &broker) {
+                log::info!("Connection is closed: {}", err);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn serve_tcp_stream(stream: TcpStream, broker: &Broker) -> Result<(), errors::Error> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    stream.set_nodelay(true)?;
+    Connection::new(stream).serve(broker, &auth::Peer::default())
+}
@@ -1,3 +1,100 @@
+use std::net::TcpListener;
+use std::process;
+use std::thread;
+
+use clap::Parser;
+
+use mini_mqtt::errors;
+
+mod broker;
+mod cli;
+mod connection;
+mod listener;
+mod settings;
+#[cfg(test)]
+mod test_helpers;
+mod tls;
+#[cfg(unix)]
+mod unix;
+mod websocket;
+
+fn run(settings: settings::Settings) -> Result<(), errors::Error> {
+    let broker = broker::Broker::new(settings.config()?, settings.limits.max_connections);
+    // The persistent sessions and the retained messages of the previous run are restored before accepting the connections.
+    broker.handler().write().unwrap().restore()?;
+    let listeners_settings = &settings.listeners;
+
+    let mut listeners = Vec::new();
+    if let (Some(tls_settings), Some(tls)) = (settings.tls_settings()?, &listeners_settings.tls) {
+        // The configuration is loaded before accepting the connections to report the errors at the start.
+        tls_settings.server_config()?;
+        let tls_listener = TcpListener::bind(&tls.bind)?;
+        log::info!("mqtts:// listener is bound to {}", tls.bind);
+        let broker = broker.clone();
+        listeners.push(thread::spawn(move || tls::serve_tls(tls_listener, broker, &tls_settings)));
+    }
+    if let Some(websocket) = &listeners_settings.websocket {
+        let ws_listener = TcpListener::bind(&websocket.bind)?;
+        log::info!("MQTT over WebSocket listener is bound to {}", websocket.bind);
+        let broker = broker.clone();
+        listeners.push(thread::spawn(move || websocket::serve_websocket(ws_listener, broker)));
+    }
+    if let Some(unix) = &listeners_settings.unix {
+        // The mode has been validated.
+        let mode = settings::unix_mode(&unix.mode).unwrap_or(listener::DEFAULT_UNIX_SOCKET_MODE);
+        listeners.push(serve_unix(&unix.path, mode, broker.clone())?);
+        log::info!("Unix domain socket listener is bound to {}", unix.path.display());
+    }
+    if listeners_settings.tcp.enabled {
+        let tcp_listener = TcpListener::bind(&listeners_settings.tcp.bind)?;
+        log::info!("mqtt:// listener is bound to {}", listeners_settings.tcp.bind);
+        listener::serve_tcp(tcp_listener, broker)?;
+    }
+
+    for listener in listeners {
+        listener.join().map_err(|_| errors::Error::Common("The listener has panicked".to_string()))??;
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn serve_unix(
+    path: &std::path::Path,
+    mode: u32,
+    broker: std::sync::Arc<broker::Broker>,
+) -> Result<thread::JoinHandle<Result<(), errors::Error>>, errors::Error> {
+    let unix_listener = unix::bind(path, mode)?;
+    Ok(thread::spawn(move || unix::serve_unix(unix_listener, broker)))
+}
+
+#[cfg(not(unix))]
+fn serve_unix(
+    _: &std::path::Path,
+    _: u32,
+    _: std::sync::Arc<broker::Broker>,
+) -> Result<thread::JoinHandle<Result<(), errors::Error>>, errors::Error> {
+    Err(errors::Error::Common("Unix domain sockets are not supported on the platform".to_string()))
+}
+
 fn main() {
-    println!("Hello, world!");
+    let settings = match cli::Cli::parse().settings().and_then(|settings| {
+        settings.validate()?;
+        Ok(settings)
+    }) {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(2);
+        }
+    };
+
+    env_logger::Builder::new()
+        .filter_level(settings.log_level().unwrap_or(log::LevelFilter::Info))
+        .init();
+
+    if let Err(err) = run(settings) {
+        log::error!("{}", err);
+        process::exit(1);
+    }
 }
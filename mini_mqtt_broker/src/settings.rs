@@ -0,0 +1,487 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use mini_mqtt::acl::Acl;
+use mini_mqtt::auth::password_file::PasswordFile;
+use mini_mqtt::config;
+use mini_mqtt::errors;
+use mini_mqtt::packets::QoS;
+use mini_mqtt::session::client_id_policy::{self, AllowedCharacters, ClientIdPolicy};
+use mini_mqtt::session::queue::{OverflowPolicy, QueueLimits};
+use mini_mqtt::session::shared_subscription::SharedSubscriptionStrategy;
+use mini_mqtt::store::append_log::{self, AppendLog};
+
+use crate::listener;
+use crate::tls;
+
+#[path = "settings_tests.rs"]
+#[cfg(test)]
+mod settings_tests;
+
+// The largest packet is the Fixed Header of 5 bytes and the Remaining Length of 268,435,455 bytes
+// (2.1.4 Remaining Length subsection).
+pub const MAXIMUM_PACKET_SIZE_LIMIT: u32 = 268_435_460;
+
+// Settings is the configuration file of the broker in TOML. Every table and every key is optional.
+//
+//   log_level = "info"
+//   legacy_protocol = false
+//
+//   [listeners.tcp]
+//   bind = "0.0.0.0:1883"
+//
+//   [listeners.tls]
+//   bind = "0.0.0.0:8883"
+//   certificate = "server.pem"
+//   private_key = "server.key"
+//   client_ca = "ca.pem"
+//   require_client_certificate = true
+//   identity = "cn:username"
+//
+//   [listeners.websocket]
+//   bind = "0.0.0.0:8080"
+//
+//   [listeners.unix]
+//   path = "/run/mini_mqtt.sock"
+//   mode = "660"
+//
+//   [limits]
+//   max_connections = 1000
+//   max_packet_size = 1048576
+//   maximum_qos = 2
+//   retain_available = true
+//   session_expiry_interval_max = 86400
+//   receive_maximum = 100
+//   topic_alias_maximum = 10
+//   wildcard_subscription_available = true
+//   subscription_identifier_available = true
+//   shared_subscription_available = true
+//   shared_subscription_strategy = "round_robin"
+//   max_queued_messages = 1000
+//   max_queued_bytes = 16777216
+//   queue_overflow_policy = "drop_oldest"
+//   server_keep_alive = 60
+//   response_information_prefix = "responses/"
+//
+//   [client_id]
+//   max_length = 23
+//   allowed_characters = "-_:"
+//
+//   [client_id.tenant_prefixes]
+//   alice = "alice-"
+//
+//   [auth]
+//   backend = "password_file"
+//   password_file = "passwords"
+//   acl_file = "acl"
+//
+//   [store]
+//   backend = "append_log"
+//   path = "/var/lib/mini_mqtt/state.log"
+//   snapshot_interval = 10000
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub log_level: String,
+    // legacy_protocol accepts the Clients of MQTT v3.1, whose protocol name is "MQIsdp".
+    pub legacy_protocol: bool,
+    pub listeners: Listeners,
+    pub limits: Limits,
+    pub client_id: ClientIdSettings,
+    pub auth: AuthSettings,
+    pub store: StoreSettings,
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            log_level: "info".to_string(),
+            legacy_protocol: false,
+            listeners: Listeners::default(),
+            limits: Limits::default(),
+            client_id: ClientIdSettings::default(),
+            auth: AuthSettings::default(),
+            store: StoreSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Listeners {
+    pub tcp: TcpListenerSettings,
+    pub tls: Option<TlsListenerSettings>,
+    pub websocket: Option<WebSocketListenerSettings>,
+    pub unix: Option<UnixListenerSettings>,
+}
+
+// TcpListenerSettings is the mqtt:// listener, which is enabled by default.
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TcpListenerSettings {
+    pub enabled: bool,
+    pub bind: String,
+}
+
+impl Default for TcpListenerSettings {
+    fn default() -> TcpListenerSettings {
+        TcpListenerSettings {
+            enabled: true,
+            bind: format!("0.0.0.0:{}", listener::DEFAULT_PORT),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TlsListenerSettings {
+    #[serde(default = "default_tls_bind")]
+    pub bind: String,
+    pub certificate: PathBuf,
+    pub private_key: PathBuf,
+    pub client_ca: Option<PathBuf>,
+    #[serde(default)]
+    pub require_client_certificate: bool,
+    pub identity: Option<String>,
+}
+
+pub(crate) fn default_tls_bind() -> String {
+    format!("0.0.0.0:{}", listener::DEFAULT_TLS_PORT)
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct WebSocketListenerSettings {
+    pub bind: String,
+}
+
+// UnixListenerSettings is the Unix domain socket listener. The mode is the octal file permissions of the socket.
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UnixListenerSettings {
+    pub path: PathBuf,
+    #[serde(default = "default_unix_mode")]
+    pub mode: String,
+}
+
+pub(crate) fn default_unix_mode() -> String {
+    format!("{:o}", listener::DEFAULT_UNIX_SOCKET_MODE)
+}
+
+// Limits are the capabilities of the Server. None means no limit.
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    pub max_connections: Option<usize>,
+    pub max_packet_size: Option<u32>,
+    pub maximum_qos: u8,
+    pub retain_available: bool,
+    pub session_expiry_interval_max: Option<u32>,
+    pub receive_maximum: u16,
+    pub topic_alias_maximum: u16,
+    pub wildcard_subscription_available: bool,
+    pub subscription_identifier_available: bool,
+    pub shared_subscription_available: bool,
+    // round_robin, random, least_inflight or sticky_by_topic_hash.
+    pub shared_subscription_strategy: String,
+    // The limits of the messages queued for each session, e.g. while a persistent session is disconnected.
+    pub max_queued_messages: usize,
+    pub max_queued_bytes: usize,
+    // drop_oldest, drop_newest or reject_publisher.
+    pub queue_overflow_policy: String,
+    pub server_keep_alive: Option<u16>,
+    // The Response Information returned to the Clients requesting it is this prefix followed by their ClientIDs.
+    pub response_information_prefix: Option<String>,
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_connections: None,
+            max_packet_size: None,
+            maximum_qos: 2,
+            retain_available: true,
+            session_expiry_interval_max: None,
+            receive_maximum: u16::MAX,
+            topic_alias_maximum: 0,
+            wildcard_subscription_available: true,
+            subscription_identifier_available: true,
+            shared_subscription_available: true,
+            shared_subscription_strategy: "round_robin".to_string(),
+            max_queued_messages: 1000,
+            max_queued_bytes: 16 * 1024 * 1024,
+            queue_overflow_policy: "drop_oldest".to_string(),
+            server_keep_alive: None,
+            response_information_prefix: None,
+        }
+    }
+}
+
+// ClientIdSettings are the ClientIDs which the Server accepts. The characters are the alphanumeric ones by default,
+// allowed_characters adds the provided characters to them, and pattern is a regular expression which the whole ClientID
+// must match instead. The tenant_prefixes map the User Names to the prefixes which the ClientIDs of them must start with.
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ClientIdSettings {
+    pub max_length: usize,
+    pub allowed_characters: Option<String>,
+    pub pattern: Option<String>,
+    pub tenant_prefixes: HashMap<String, String>,
+}
+
+impl Default for ClientIdSettings {
+    fn default() -> ClientIdSettings {
+        ClientIdSettings {
+            max_length: client_id_policy::DEFAULT_MAX_LENGTH,
+            allowed_characters: None,
+            pattern: None,
+            tenant_prefixes: HashMap::new(),
+        }
+    }
+}
+
+impl ClientIdSettings {
+    fn policy(&self) -> Result<ClientIdPolicy, errors::Error> {
+        let allowed_characters = match (&self.pattern, &self.allowed_characters) {
+            (Some(pattern), _) => AllowedCharacters::pattern(pattern)?,
+            (None, Some(characters)) => AllowedCharacters::AlphanumericAnd(characters.clone()),
+            (None, None) => AllowedCharacters::Alphanumeric,
+        };
+        let mut policy = ClientIdPolicy::new(self.max_length, allowed_characters);
+        policy.tenant_prefixes = self.tenant_prefixes.clone();
+        Ok(policy)
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthBackend {
+    #[default]
+    AllowAll,     // Every Client is accepted.
+    PasswordFile, // The Clients are authenticated by the User Names and the hashed passwords of the file.
+}
+
+// AuthSettings are the authentication backend and the ACL file. Without the ACL file, every action is permitted.
+#[derive(Debug, Eq, PartialEq, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AuthSettings {
+    pub backend: AuthBackend,
+    pub password_file: Option<PathBuf>,
+    pub acl_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StoreBackend {
+    #[default]
+    Memory,    // The persistent sessions and the retained messages are lost when the broker stops.
+    AppendLog, // Every change is appended to the file and its snapshot, which are replayed when the broker starts.
+}
+
+// StoreSettings are where the persistent sessions and the retained messages are kept.
+#[derive(Debug, Eq, PartialEq, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StoreSettings {
+    pub backend: StoreBackend,
+    pub path: Option<PathBuf>,
+    // The number of the records appended to the log of the append_log backend between its snapshots.
+    pub snapshot_interval: usize,
+}
+
+impl Default for StoreSettings {
+    fn default() -> StoreSettings {
+        StoreSettings {
+            backend: StoreBackend::default(),
+            path: None,
+            snapshot_interval: append_log::DEFAULT_SNAPSHOT_INTERVAL,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> Result<Settings, errors::Error> {
+        let content = fs::read_to_string(path)
+            .map_err(|err| errors::Error::Common(format!("Failed to read {}: {}", path.display(), err)))?;
+        Settings::from_toml(&content).map_err(|err| errors::Error::Common(format!("{}: {}", path.display(), err)))
+    }
+
+    #[cfg(test)]
+    pub fn parse(content: &str) -> Result<Settings, errors::Error> {
+        Settings::from_toml(content).map_err(|err| errors::Error::Common(err.to_string()))
+    }
+
+    fn from_toml(content: &str) -> Result<Settings, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    // validate reports every invalid setting at once, so that the configuration can be fixed in one go.
+    pub fn validate(&self) -> Result<(), errors::Error> {
+        let mut problems = Vec::new();
+
+        if self.log_level().is_none() {
+            problems.push(format!(
+                "log_level must be one of off, error, warn, info, debug or trace. It is {}",
+                self.log_level
+            ));
+        }
+
+        let listeners = &self.listeners;
+        if !listeners.tcp.enabled && listeners.tls.is_none() && listeners.websocket.is_none() && listeners.unix.is_none() {
+            problems.push("listeners must enable at least one listener".to_string());
+        }
+        if let Some(identity) = listeners.tls.as_ref().and_then(|tls| tls.identity.as_ref()) {
+            if let Err(err) = tls::IdentityMapping::parse(identity) {
+                problems.push(format!("listeners.tls.identity: {}", err));
+            }
+        }
+        if let Some(tls) = &listeners.tls {
+            if tls.require_client_certificate && tls.client_ca.is_none() {
+                problems.push("listeners.tls.require_client_certificate requires listeners.tls.client_ca".to_string());
+            }
+        }
+        if let Some(unix) = &listeners.unix {
+            if unix_mode(&unix.mode).is_none() {
+                problems.push(format!(
+                    "listeners.unix.mode must be octal file permissions such as 660. It is {}",
+                    unix.mode
+                ));
+            }
+        }
+
+        let limits = &self.limits;
+        if limits.max_connections == Some(0) {
+            problems.push("limits.max_connections must be greater than 0".to_string());
+        }
+        // It is a Protocol Error to include the Maximum Packet Size value 0 (3.2.2.3.6 Maximum Packet Size subsection).
+        if let Some(max_packet_size) = limits.max_packet_size {
+            if !(1..=MAXIMUM_PACKET_SIZE_LIMIT).contains(&max_packet_size) {
+                problems.push(format!(
+                    "limits.max_packet_size must be between 1 and {}. It is {}",
+                    MAXIMUM_PACKET_SIZE_LIMIT, max_packet_size
+                ));
+            }
+        }
+        if limits.maximum_qos > 2 {
+            problems.push(format!(
+                "limits.maximum_qos must be 0, 1 or 2. It is {}",
+                limits.maximum_qos
+            ));
+        }
+        if let Err(err) = limits.shared_subscription_strategy.parse::<SharedSubscriptionStrategy>() {
+            problems.push(format!("limits.shared_subscription_strategy is invalid. {}", err));
+        }
+        if let Err(err) = limits.queue_overflow_policy.parse::<OverflowPolicy>() {
+            problems.push(format!("limits.queue_overflow_policy is invalid. {}", err));
+        }
+        // It is a Protocol Error to include the Receive Maximum value 0 (3.2.2.3.3 Receive Maximum subsection).
+        if limits.receive_maximum == 0 {
+            problems.push("limits.receive_maximum must be greater than 0".to_string());
+        }
+        // The Clients build the Response Topics from the Response Information, which cannot contain the wildcards.
+        if limits.response_information_prefix.as_ref().is_some_and(|prefix| prefix.contains(['+', '#'])) {
+            problems.push("limits.response_information_prefix must not contain the wildcard characters".to_string());
+        }
+
+        let client_id = &self.client_id;
+        // The Server MUST allow the ClientIDs between 1 and 23 bytes [MQTT-3.1.3-5],
+        // and a UTF-8 Encoded String is at most 65,535 bytes (1.5.4 UTF-8 Encoded String subsection).
+        if !(client_id_policy::DEFAULT_MAX_LENGTH..=u16::MAX as usize).contains(&client_id.max_length) {
+            problems.push(format!(
+                "client_id.max_length must be between {} and {}. It is {}",
+                client_id_policy::DEFAULT_MAX_LENGTH, u16::MAX, client_id.max_length
+            ));
+        }
+        if client_id.pattern.is_some() && client_id.allowed_characters.is_some() {
+            problems.push("client_id.pattern and client_id.allowed_characters cannot be used together".to_string());
+        }
+        if let Some(pattern) = &client_id.pattern {
+            if let Err(err) = AllowedCharacters::pattern(pattern) {
+                problems.push(format!("client_id.pattern is invalid. {}", err));
+            }
+        }
+
+        if self.auth.backend == AuthBackend::PasswordFile && self.auth.password_file.is_none() {
+            problems.push("auth.backend password_file requires auth.password_file".to_string());
+        }
+        if self.store.backend == StoreBackend::AppendLog && self.store.path.is_none() {
+            problems.push("store.backend append_log requires store.path".to_string());
+        }
+        if self.store.snapshot_interval == 0 {
+            problems.push("store.snapshot_interval must be greater than 0".to_string());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(errors::Error::Common(format!("Invalid configuration:\n  {}", problems.join("\n  "))))
+        }
+    }
+
+    pub fn log_level(&self) -> Option<log::LevelFilter> {
+        self.log_level.parse().ok()
+    }
+
+    pub fn tls_settings(&self) -> Result<Option<tls::TlsSettings>, errors::Error> {
+        let Some(tls) = &self.listeners.tls else {
+            return Ok(None);
+        };
+        let mut settings = tls::TlsSettings::new(&tls.certificate, &tls.private_key);
+        if let Some(client_ca) = &tls.client_ca {
+            settings = settings.with_client_ca(client_ca, tls.require_client_certificate);
+        }
+        if let Some(identity) = &tls.identity {
+            settings = settings.with_identity_mapping(tls::IdentityMapping::parse(identity)?);
+        }
+
+        Ok(Some(settings))
+    }
+
+    // config results the Server behaviours of the library, the password file and the ACL file are loaded.
+    pub fn config(&self) -> Result<config::Config, errors::Error> {
+        let mut config = config::Config {
+            maximum_qos: match self.limits.maximum_qos {
+                0 => QoS::AtMostOnce,
+                1 => QoS::AtLeastOnce,
+                _ => QoS::ExactlyOnce,
+            },
+            retain_available: self.limits.retain_available,
+            maximum_packet_size: self.limits.max_packet_size,
+            session_expiry_interval_maximum: self.limits.session_expiry_interval_max,
+            receive_maximum: self.limits.receive_maximum,
+            topic_alias_maximum: self.limits.topic_alias_maximum,
+            wildcard_subscription_available: self.limits.wildcard_subscription_available,
+            subscription_identifier_available: self.limits.subscription_identifier_available,
+            shared_subscription_available: self.limits.shared_subscription_available,
+            shared_subscription_strategy: self.limits.shared_subscription_strategy.parse()?,
+            queue_limits: QueueLimits {
+                max_messages: self.limits.max_queued_messages,
+                max_bytes: self.limits.max_queued_bytes,
+                overflow_policy: self.limits.queue_overflow_policy.parse()?,
+            },
+            server_keep_alive: self.limits.server_keep_alive,
+            response_information_prefix: self.limits.response_information_prefix.clone(),
+            client_id_policy: self.client_id.policy()?,
+            legacy_protocol_enabled: self.legacy_protocol,
+            ..config::Config::default()
+        };
+        if let (AuthBackend::PasswordFile, Some(password_file)) = (self.auth.backend, &self.auth.password_file) {
+            config.auth_provider = Arc::new(PasswordFile::load(password_file)?);
+        }
+        if let Some(acl_file) = &self.auth.acl_file {
+            config.acl = Some(Acl::load(acl_file)?);
+        }
+        if let (StoreBackend::AppendLog, Some(path)) = (self.store.backend, &self.store.path) {
+            config.store = Arc::new(AppendLog::open(path)?.with_snapshot_interval(self.store.snapshot_interval));
+        }
+
+        Ok(config)
+    }
+}
+
+pub fn unix_mode(mode: &str) -> Option<u32> {
+    u32::from_str_radix(mode, 8).ok().filter(|mode| *mode <= 0o777)
+}
@@ -0,0 +1,284 @@
+use super::*;
+
+#[test]
+fn parse_empty() {
+    let settings = Settings::parse("").unwrap();
+    assert_eq!(settings, Settings::default());
+    assert!(settings.validate().is_ok());
+    assert_eq!(settings.log_level(), Some(log::LevelFilter::Info));
+    assert_eq!(settings.listeners.tcp.bind, "0.0.0.0:1883");
+}
+
+#[test]
+fn parse_full() {
+    let settings = Settings::parse(
+        r#"
+log_level = "debug"
+legacy_protocol = true
+
+[listeners.tcp]
+bind = "127.0.0.1:1884"
+
+[listeners.tls]
+certificate = "server.pem"
+private_key = "server.key"
+client_ca = "ca.pem"
+require_client_certificate = true
+identity = "cn:username"
+
+[listeners.websocket]
+bind = "127.0.0.1:8080"
+
+[listeners.unix]
+path = "/run/mini_mqtt.sock"
+mode = "600"
+
+[limits]
+max_connections = 100
+max_packet_size = 65536
+maximum_qos = 0
+retain_available = false
+session_expiry_interval_max = 3600
+receive_maximum = 10
+topic_alias_maximum = 16
+wildcard_subscription_available = false
+subscription_identifier_available = false
+shared_subscription_strategy = "least_inflight"
+max_queued_messages = 100
+max_queued_bytes = 65536
+queue_overflow_policy = "reject_publisher"
+server_keep_alive = 60
+response_information_prefix = "responses/"
+
+[client_id]
+max_length = 64
+allowed_characters = "-_"
+
+[client_id.tenant_prefixes]
+alice = "alice-"
+
+[auth]
+acl_file = "acl"
+"#,
+    )
+    .unwrap();
+    assert!(settings.validate().is_ok());
+
+    assert_eq!(settings.log_level(), Some(log::LevelFilter::Debug));
+    assert_eq!(settings.listeners.tcp.bind, "127.0.0.1:1884");
+    let tls = settings.listeners.tls.as_ref().unwrap();
+    assert_eq!(tls.bind, "0.0.0.0:8883");
+    assert!(tls.require_client_certificate);
+    assert_eq!(
+        settings.listeners.websocket,
+        Some(WebSocketListenerSettings {
+            bind: "127.0.0.1:8080".to_string()
+        })
+    );
+    assert_eq!(unix_mode(&settings.listeners.unix.as_ref().unwrap().mode), Some(0o600));
+    assert_eq!(
+        settings.limits,
+        Limits {
+            max_connections: Some(100),
+            max_packet_size: Some(65536),
+            maximum_qos: 0,
+            retain_available: false,
+            session_expiry_interval_max: Some(3600),
+            receive_maximum: 10,
+            topic_alias_maximum: 16,
+            wildcard_subscription_available: false,
+            subscription_identifier_available: false,
+            shared_subscription_available: true,
+            shared_subscription_strategy: "least_inflight".to_string(),
+            max_queued_messages: 100,
+            max_queued_bytes: 65536,
+            queue_overflow_policy: "reject_publisher".to_string(),
+            server_keep_alive: Some(60),
+            response_information_prefix: Some("responses/".to_string()),
+        }
+    );
+    assert!(settings.legacy_protocol);
+    assert_eq!(
+        settings.client_id,
+        ClientIdSettings {
+            max_length: 64,
+            allowed_characters: Some("-_".to_string()),
+            pattern: None,
+            tenant_prefixes: HashMap::from([("alice".to_string(), "alice-".to_string())]),
+        }
+    );
+    assert_eq!(settings.auth.backend, AuthBackend::AllowAll);
+
+    let tls_settings = settings.tls_settings().unwrap().unwrap();
+    assert_eq!(
+        tls_settings.identity_mapping,
+        Some(tls::IdentityMapping::new(
+            tls::CertificateField::CommonName,
+            tls::IdentityTarget::UserName
+        ))
+    );
+}
+
+#[test]
+fn parse_unknown_field() {
+    let err = Settings::parse("[limits]\nmax_conections = 10\n").unwrap_err();
+    assert!(err.to_string().contains("max_conections"));
+}
+
+#[test]
+fn parse_invalid_type() {
+    let err = Settings::parse("[limits]\nmax_connections = \"ten\"\n").unwrap_err();
+    assert!(err.to_string().contains("max_connections"));
+}
+
+#[test]
+fn load_reports_path() {
+    let path = std::env::temp_dir().join(format!("mini_mqtt_settings_{}.toml", std::process::id()));
+    fs::write(&path, "log_level = \n").unwrap();
+    let err = Settings::load(&path).unwrap_err();
+    fs::remove_file(&path).unwrap();
+    assert!(err.to_string().contains(&path.display().to_string()));
+    assert!(err.to_string().contains("log_level"));
+
+    assert!(Settings::load(&path).is_err());
+}
+
+#[test]
+fn validate_reports_every_problem() {
+    let settings = Settings::parse(
+        r#"
+log_level = "verbose"
+
+[listeners.unix]
+path = "/tmp/mini_mqtt.sock"
+mode = "rw"
+
+[limits]
+max_connections = 0
+max_packet_size = 0
+maximum_qos = 3
+receive_maximum = 0
+shared_subscription_strategy = "fastest"
+queue_overflow_policy = "drop_all"
+response_information_prefix = "responses/+/"
+
+[client_id]
+max_length = 8
+pattern = "sensor-("
+
+[auth]
+backend = "password_file"
+
+[store]
+backend = "append_log"
+snapshot_interval = 0
+"#,
+    )
+    .unwrap();
+    let err = settings.validate().unwrap_err().to_string();
+    for field in [
+        "log_level",
+        "listeners.unix.mode",
+        "limits.max_connections",
+        "limits.max_packet_size",
+        "limits.maximum_qos",
+        "limits.receive_maximum",
+        "limits.shared_subscription_strategy",
+        "limits.queue_overflow_policy",
+        "limits.response_information_prefix",
+        "client_id.max_length",
+        "client_id.pattern",
+        "auth.password_file",
+        "store.path",
+        "store.snapshot_interval",
+    ] {
+        assert!(err.contains(field), "{} is not reported in {}", field, err);
+    }
+}
+
+#[test]
+fn validate_requires_a_listener() {
+    let settings = Settings::parse("[listeners.tcp]\nenabled = false\n").unwrap();
+    assert!(settings.validate().is_err());
+}
+
+#[test]
+fn config_of_limits() {
+    let settings = Settings::parse(
+        "[limits]\nmaximum_qos = 0\nretain_available = false\nmax_packet_size = 1024\nsession_expiry_interval_max = 60\nserver_keep_alive = 30\nreceive_maximum = 20\ntopic_alias_maximum = 8\nshared_subscription_strategy = \"sticky_by_topic_hash\"\nmax_queued_messages = 10\nmax_queued_bytes = 2048\nqueue_overflow_policy = \"drop_newest\"\nresponse_information_prefix = \"responses/\"\n",
+    )
+    .unwrap();
+    let config = settings.config().unwrap();
+    assert_eq!(config.maximum_qos, QoS::AtMostOnce);
+    assert!(!config.retain_available);
+    assert_eq!(config.maximum_packet_size, Some(1024));
+    assert_eq!(config.session_expiry_interval_maximum, Some(60));
+    assert_eq!(config.server_keep_alive, Some(30));
+    assert_eq!(config.response_information_prefix.as_deref(), Some("responses/"));
+    assert_eq!(config.receive_maximum, 20);
+    assert_eq!(config.topic_alias_maximum, 8);
+    assert!(config.wildcard_subscription_available);
+    assert!(config.subscription_identifier_available);
+    assert!(config.shared_subscription_available);
+    assert_eq!(config.shared_subscription_strategy, SharedSubscriptionStrategy::StickyByTopicHash);
+    assert_eq!(
+        config.queue_limits,
+        QueueLimits {
+            max_messages: 10,
+            max_bytes: 2048,
+            overflow_policy: OverflowPolicy::DropNewest,
+        }
+    );
+    assert!(config.acl.is_none());
+}
+
+#[test]
+fn config_of_store() {
+    let path = std::env::temp_dir().join(format!("mini_mqtt_settings_store_{}.log", std::process::id()));
+    let settings = Settings::parse(&format!("[store]\nbackend = \"append_log\"\npath = \"{}\"\n", path.display())).unwrap();
+    assert!(settings.validate().is_ok());
+    let config = settings.config().unwrap();
+    assert!(config.store.load().unwrap().sessions.is_empty());
+    fs::remove_file(&path).unwrap();
+
+    let settings = Settings::parse("[store]\nbackend = \"append_log\"\npath = \"/nonexistent/state.log\"\n").unwrap();
+    assert!(settings.config().is_err());
+}
+
+#[test]
+fn config_of_client_id() {
+    let config = Settings::default().config().unwrap();
+    assert!(!config.legacy_protocol_enabled);
+    assert!(config.client_id_policy.validate("sensor01", None).is_ok());
+    assert!(config.client_id_policy.validate("sensor-01", None).is_err());
+
+    let settings = Settings::parse(
+        "legacy_protocol = true\n[client_id]\nmax_length = 32\nallowed_characters = \"-\"\n[client_id.tenant_prefixes]\nalice = \"alice-\"\n",
+    )
+    .unwrap();
+    assert!(settings.validate().is_ok());
+    let config = settings.config().unwrap();
+    assert!(config.legacy_protocol_enabled);
+    assert_eq!(config.client_id_policy.max_length, 32);
+    assert!(config.client_id_policy.validate("sensor-01", None).is_ok());
+    assert!(config.client_id_policy.validate("sensor-01", Some("alice")).is_err());
+    assert!(config.client_id_policy.validate("alice-01", Some("alice")).is_ok());
+
+    let settings = Settings::parse("[client_id]\npattern = \"sensor/[0-9]+\"\n").unwrap();
+    let config = settings.config().unwrap();
+    assert!(config.client_id_policy.validate("sensor/01", None).is_ok());
+    assert!(config.client_id_policy.validate("sensor01", None).is_err());
+}
+
+#[test]
+fn validate_rejects_pattern_with_allowed_characters() {
+    let settings = Settings::parse("[client_id]\nallowed_characters = \"-\"\npattern = \"[a-z]+\"\n").unwrap();
+    let err = settings.validate().unwrap_err().to_string();
+    assert!(err.contains("client_id.pattern and client_id.allowed_characters"), "{}", err);
+}
+
+#[test]
+fn config_reports_missing_acl_file() {
+    let settings = Settings::parse("[auth]\nacl_file = \"/nonexistent/mini_mqtt.acl\"\n").unwrap();
+    assert!(settings.config().is_err());
+}
@@ -0,0 +1,97 @@
+// The fixtures shared by the tests of the listeners and the connection.
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use mini_mqtt::config;
+use mini_mqtt::errors;
+
+use crate::broker::Broker;
+
+// start runs the broker of the config on a TCP port of the loopback address by the serve function of a listener,
+// and results the address to connect.
+pub fn start<F>(config: config::Config, serve: F) -> SocketAddr
+where
+    F: FnOnce(TcpListener, Arc<Broker>) -> Result<(), errors::Error> + Send + 'static,
+{
+    let tcp_listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = tcp_listener.local_addr().unwrap();
+    let broker = Broker::new(config, None);
+    broker.handler().write().unwrap().restore().unwrap();
+    thread::spawn(move || serve(tcp_listener, broker));
+    address
+}
+
+// packet results the bytes of the packet whose Fixed Header has the first byte and the Remaining Length of the body.
+pub fn packet(first_byte: u8, body: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![first_byte];
+    let mut length = body.len();
+    loop {
+        let mut byte = (length % 0x80) as u8;
+        length /= 0x80;
+        if length > 0 {
+            byte |= 0x80;
+        }
+        bytes.push(byte);
+        if length == 0 {
+            break;
+        }
+    }
+    bytes.extend(body);
+    bytes
+}
+
+pub fn string(value: &str) -> Vec<u8> {
+    [(value.len() as u16).to_be_bytes().to_vec(), value.as_bytes().to_vec()].concat()
+}
+
+// connect_packet results the MQTT v5.0 CONNECT Packet with Clean Start and the encoded CONNECT Properties.
+pub fn connect_packet(client_id: &str, properties: &[u8]) -> Vec<u8> {
+    let mut body = string("MQTT");
+    body.extend([0x05, 0x02, 0x00, 0x3C, properties.len() as u8]);
+    body.extend(properties);
+    body.extend(string(client_id));
+    packet(0x10, &body)
+}
+
+// publish_packet results the QoS 0 PUBLISH Packet without the Properties.
+pub fn publish_packet(topic_name: &str, payload: &[u8]) -> Vec<u8> {
+    let mut body = string(topic_name);
+    body.push(0x00);
+    body.extend(payload);
+    packet(0x30, &body)
+}
+
+pub fn read_packet(stream: &mut impl Read) -> Vec<u8> {
+    let mut bytes = vec![0u8; 1];
+    stream.read_exact(&mut bytes).unwrap();
+    let mut length = 0usize;
+    let mut multiplier = 1usize;
+    loop {
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte).unwrap();
+        bytes.push(byte[0]);
+        length += (byte[0] & 0x7F) as usize * multiplier;
+        multiplier *= 128;
+        if byte[0] & 0x80 == 0 {
+            break;
+        }
+    }
+    let mut body = vec![0u8; length];
+    stream.read_exact(&mut body).unwrap();
+    bytes.extend(body);
+    bytes
+}
+
+// connect_client connects the Client over TCP and results the stream after the CONNACK Packet accepting it.
+pub fn connect_client(address: SocketAddr, client_id: &str, properties: &[u8]) -> TcpStream {
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(&connect_packet(client_id, properties)).unwrap();
+    let connack = read_packet(&mut stream);
+    assert_eq!(connack[0], 0x20);
+    assert_eq!(connack[3], 0x00);
+    stream
+}
@@ -0,0 +1,259 @@
+use std::fs;
+use std::io::BufReader;
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig, ServerConnection, StreamOwned};
+use x509_parser::extensions::GeneralName;
+
+use mini_mqtt::auth;
+use mini_mqtt::errors;
+
+use crate::broker::Broker;
+use crate::connection::Connection;
+use crate::listener;
+
+#[path = "tls_tests.rs"]
+#[cfg(test)]
+mod tls_tests;
+
+// HANDSHAKE_TIMEOUT limits the time which a Client can take to complete the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// CertificateField is the field of the client certificate which identifies the Client.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum CertificateField {
+    CommonName,     // The first Common Name of the Subject.
+    SubjectAltName, // The first DNS name or e-mail address of the Subject Alternative Name extension.
+}
+
+// IdentityTarget is the value of the CONNECT Packet which the certificate identity replaces.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub enum IdentityTarget {
+    ClientId,
+    UserName,
+}
+
+// IdentityMapping maps a field of the verified client certificate to the ClientID or the User Name,
+// which the auth layer and the ACL use afterwards.
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+pub struct IdentityMapping {
+    pub field: CertificateField,
+    pub target: IdentityTarget,
+}
+
+impl IdentityMapping {
+    pub fn new(field: CertificateField, target: IdentityTarget) -> IdentityMapping {
+        IdentityMapping { field, target }
+    }
+
+    // parse the mapping of the form "<field>:<target>", the field is "cn" or "san",
+    // and the target is "client-id" or "username", e.g. "cn:username".
+    pub fn parse(value: &str) -> Result<IdentityMapping, errors::Error> {
+        let invalid = || {
+            errors::Error::Common(format!(
+                "Invalid identity mapping {}. It must be <cn|san>:<client-id|username>",
+                value
+            ))
+        };
+        let (field, target) = value.split_once(':').ok_or_else(invalid)?;
+        let field = match field {
+            "cn" => CertificateField::CommonName,
+            "san" => CertificateField::SubjectAltName,
+            _ => return Err(invalid()),
+        };
+        let target = match target {
+            "client-id" => IdentityTarget::ClientId,
+            "username" => IdentityTarget::UserName,
+            _ => return Err(invalid()),
+        };
+
+        Ok(IdentityMapping::new(field, target))
+    }
+
+    // identity results the PeerIdentity of the DER encoded certificate.
+    // It is an error that the certificate does not have the field.
+    pub fn identity(&self, certificate: &[u8]) -> Result<auth::PeerIdentity, errors::Error> {
+        let (_, certificate) = x509_parser::parse_x509_certificate(certificate)
+            .map_err(|err| errors::Error::Common(format!("Invalid client certificate: {}", err)))?;
+        let value = match self.field {
+            CertificateField::CommonName => certificate
+                .subject()
+                .iter_common_name()
+                .next()
+                .and_then(|common_name| common_name.as_str().ok())
+                .map(|common_name| common_name.to_string()),
+            CertificateField::SubjectAltName => certificate
+                .subject_alternative_name()
+                .ok()
+                .flatten()
+                .and_then(|extension| {
+                    extension.value.general_names.iter().find_map(|name| match name {
+                        GeneralName::DNSName(name) | GeneralName::RFC822Name(name) => Some(name.to_string()),
+                        _ => None,
+                    })
+                }),
+        };
+        let value = value.ok_or_else(|| {
+            errors::Error::Common(format!("The client certificate does not have the {:?}", self.field))
+        })?;
+
+        Ok(match self.target {
+            IdentityTarget::ClientId => auth::PeerIdentity {
+                client_id: Some(value),
+                user_name: None,
+            },
+            IdentityTarget::UserName => auth::PeerIdentity {
+                client_id: None,
+                user_name: Some(value),
+            },
+        })
+    }
+}
+
+// TlsSettings configures the mqtts:// listener.
+// The certificate and the private key are PEM files of the Server.
+// client_ca enables mutual TLS, the client certificates are verified by the CA certificates of the PEM file.
+// When require_client_certificate is false, the Clients without the certificate are authenticated by the auth layer.
+#[derive(Debug, Clone)]
+pub struct TlsSettings {
+    pub certificate: PathBuf,
+    pub private_key: PathBuf,
+    pub client_ca: Option<PathBuf>,
+    pub require_client_certificate: bool,
+    pub identity_mapping: Option<IdentityMapping>,
+}
+
+impl TlsSettings {
+    pub fn new(certificate: &Path, private_key: &Path) -> TlsSettings {
+        TlsSettings {
+            certificate: certificate.to_path_buf(),
+            private_key: private_key.to_path_buf(),
+            client_ca: None,
+            require_client_certificate: false,
+            identity_mapping: None,
+        }
+    }
+
+    pub fn with_client_ca(mut self, client_ca: &Path, required: bool) -> TlsSettings {
+        self.client_ca = Some(client_ca.to_path_buf());
+        self.require_client_certificate = required;
+        self
+    }
+
+    pub fn with_identity_mapping(mut self, identity_mapping: IdentityMapping) -> TlsSettings {
+        self.identity_mapping = Some(identity_mapping);
+        self
+    }
+
+    pub fn server_config(&self) -> Result<Arc<ServerConfig>, errors::Error> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(tls_error)?;
+        let builder = match &self.client_ca {
+            Some(client_ca) => {
+                let mut roots = RootCertStore::empty();
+                for certificate in load_certificates(client_ca)? {
+                    roots.add(certificate).map_err(tls_error)?;
+                }
+                let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(roots), provider);
+                let verifier = if self.require_client_certificate {
+                    verifier.build()
+                } else {
+                    verifier.allow_unauthenticated().build()
+                };
+                builder.with_client_cert_verifier(verifier.map_err(tls_error)?)
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let config = builder
+            .with_single_cert(load_certificates(&self.certificate)?, load_private_key(&self.private_key)?)
+            .map_err(tls_error)?;
+
+        Ok(Arc::new(config))
+    }
+}
+
+fn tls_error(err: impl std::fmt::Display) -> errors::Error {
+    errors::Error::Common(format!("TLS error: {}", err))
+}
+
+fn load_certificates(path: &Path) -> Result<Vec<CertificateDer<'static>>, errors::Error> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    let certificates = rustls_pemfile::certs(&mut reader).collect::<Result<Vec<_>, _>>()?;
+    if certificates.is_empty() {
+        return Err(errors::Error::Common(format!("{} has no certificate", path.display())));
+    }
+
+    Ok(certificates)
+}
+
+fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>, errors::Error> {
+    let mut reader = BufReader::new(fs::File::open(path)?);
+    rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| errors::Error::Common(format!("{} has no private key", path.display())))
+}
+
+// serve_tls accepts the mqtts:// connections and serves each of them in its own thread.
+pub fn serve_tls(listener: TcpListener, broker: Arc<Broker>, settings: &TlsSettings) -> Result<(), errors::Error> {
+    let config = settings.server_config()?;
+    for stream in listener.incoming() {
+        // The failure to accept one socket does not stop the listener.
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("Failed to accept a connection: {}", err);
+                continue;
+            }
+        };
+        // The socket is closed when it is dropped without a slot.
+        let Some(slot) = broker.acquire_connection() else {
+            log::warn!("Connection is refused, the maximum number of connections is reached");
+            continue;
+        };
+        let broker = broker.clone();
+        let config = config.clone();
+        let identity_mapping = settings.identity_mapping;
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(err) = serve_tls_stream(stream, &broker, config, identity_mapping) {
+                log::info!("TLS connection is closed: {}", err);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn serve_tls_stream(
+    mut stream: TcpStream,
+    broker: &Broker,
+    config: Arc<ServerConfig>,
+    identity_mapping: Option<IdentityMapping>,
+) -> Result<(), errors::Error> {
+    let mut connection = ServerConnection::new(config).map_err(tls_error)?;
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    while connection.is_handshaking() {
+        connection.complete_io(&mut stream)?;
+    }
+
+    // The identity is taken only from the certificate which the verifier has accepted.
+    let identity = match (identity_mapping, connection.peer_certificates()) {
+        (Some(identity_mapping), Some([certificate, ..])) => Some(identity_mapping.identity(certificate)?),
+        _ => None,
+    };
+    let peer = auth::Peer {
+        identity,
+        credentials: None,
+    };
+
+    stream.set_read_timeout(Some(listener::READ_TIMEOUT))?;
+    stream.set_nodelay(true)?;
+    Connection::new(StreamOwned::new(connection, stream)).serve(broker, &peer)
+}
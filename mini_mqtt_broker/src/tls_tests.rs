@@ -0,0 +1,173 @@
+use std::io::{Read, Write};
+use std::net::TcpStream;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use rcgen::{BasicConstraints, CertificateParams, DnType, IsCa, KeyPair};
+use rustls::pki_types::{PrivateKeyDer, PrivatePkcs8KeyDer, ServerName};
+use rustls::{ClientConfig, ClientConnection};
+
+use mini_mqtt::acl;
+use mini_mqtt::auth::InMemory;
+use mini_mqtt::config;
+
+use super::*;
+use crate::test_helpers::{self, connect_packet, read_packet};
+
+// Certificates are the self-signed CA and the certificates which the CA has issued.
+struct Certificates {
+    ca: rcgen::Certificate,
+    client: (rcgen::Certificate, KeyPair),
+    directory: PathBuf,
+}
+
+fn issue(ca: &rcgen::Certificate, ca_key: &KeyPair, names: Vec<String>, common_name: &str) -> (rcgen::Certificate, KeyPair) {
+    let key = KeyPair::generate().unwrap();
+    let mut params = CertificateParams::new(names).unwrap();
+    params.distinguished_name.push(DnType::CommonName, common_name);
+    (params.signed_by(&key, ca, ca_key).unwrap(), key)
+}
+
+fn certificates(name: &str) -> Certificates {
+    let ca_key = KeyPair::generate().unwrap();
+    let mut params = CertificateParams::new(Vec::<String>::new()).unwrap();
+    params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+    params.distinguished_name.push(DnType::CommonName, "mini_mqtt test CA");
+    let ca = params.self_signed(&ca_key).unwrap();
+    let server = issue(&ca, &ca_key, vec!["localhost".to_string()], "localhost");
+    let client = issue(&ca, &ca_key, vec!["device1.example.com".to_string()], "device1");
+
+    let directory = std::env::temp_dir().join(format!("mini_mqtt_tls_{}_{}", name, std::process::id()));
+    fs::create_dir_all(&directory).unwrap();
+    fs::write(directory.join("ca.pem"), ca.pem()).unwrap();
+    fs::write(directory.join("server.pem"), server.0.pem()).unwrap();
+    fs::write(directory.join("server.key"), server.1.serialize_pem()).unwrap();
+
+    Certificates { ca, client, directory }
+}
+
+impl Certificates {
+    fn settings(&self) -> TlsSettings {
+        TlsSettings::new(&self.directory.join("server.pem"), &self.directory.join("server.key"))
+    }
+
+    fn client_config(&self, with_client_certificate: bool) -> Arc<ClientConfig> {
+        let mut roots = RootCertStore::empty();
+        roots.add(self.ca.der().clone()).unwrap();
+        let builder = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots);
+        let config = if with_client_certificate {
+            let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(self.client.1.serialize_der()));
+            builder.with_client_auth_cert(vec![self.client.0.der().clone()], key).unwrap()
+        } else {
+            builder.with_no_client_auth()
+        };
+        Arc::new(config)
+    }
+}
+
+impl Drop for Certificates {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.directory);
+    }
+}
+
+fn start(settings: TlsSettings, config: config::Config) -> std::net::SocketAddr {
+    test_helpers::start(config, move |listener, broker| serve_tls(listener, broker, &settings))
+}
+
+fn connect_client(address: std::net::SocketAddr, config: Arc<ClientConfig>) -> StreamOwned<ClientConnection, TcpStream> {
+    let connection = ClientConnection::new(config, ServerName::try_from("localhost").unwrap()).unwrap();
+    let stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    StreamOwned::new(connection, stream)
+}
+
+#[test]
+fn serve_tls_without_client_certificate() {
+    let certificates = certificates("server_only");
+    let address = start(certificates.settings(), config::Config::default());
+
+    let mut stream = connect_client(address, certificates.client_config(false));
+    stream.write_all(&connect_packet("c1", &[])).unwrap();
+    assert_eq!(read_packet(&mut stream)[..4], [0x20, 0x03, 0x00, 0x00]);
+}
+
+#[test]
+fn serve_tls_rejects_client_without_required_certificate() {
+    let certificates = certificates("required");
+    let settings = certificates.settings().with_client_ca(&certificates.directory.join("ca.pem"), true);
+    let address = start(settings, config::Config::default());
+
+    let mut stream = connect_client(address, certificates.client_config(false));
+    let _ = stream.write_all(&connect_packet("c1", &[]));
+    let mut buffer = [0u8; 1];
+    assert!(!matches!(stream.read(&mut buffer), Ok(n) if n > 0));
+}
+
+#[test]
+fn serve_tls_maps_client_certificate_to_user_name() {
+    let certificates = certificates("identity");
+    let settings = certificates
+        .settings()
+        .with_client_ca(&certificates.directory.join("ca.pem"), false)
+        .with_identity_mapping(IdentityMapping::new(CertificateField::CommonName, IdentityTarget::UserName));
+    // No password is registered, so only the certificate can authenticate the Client.
+    let config = config::Config {
+        auth_provider: Arc::new(InMemory::new()),
+        acl: Some(acl::Acl::parse("user device1\ntopic read devices/device1/#").unwrap()),
+        ..config::Config::default()
+    };
+    let address = start(settings, config);
+
+    let mut stream = connect_client(address, certificates.client_config(false));
+    stream.write_all(&connect_packet("c1", &[])).unwrap();
+    assert_eq!(read_packet(&mut stream)[3], 0x87); // Not authorized
+
+    let mut stream = connect_client(address, certificates.client_config(true));
+    stream.write_all(&connect_packet("c1", &[])).unwrap();
+    assert_eq!(read_packet(&mut stream)[..4], [0x20, 0x03, 0x00, 0x00]);
+
+    // SUBSCRIBE Packet of the Topic Filters "devices/device1/#" and "devices/device2/#".
+    let mut body = vec![0x00, 0x01, 0x00];
+    for filter in [b"devices/device1/#", b"devices/device2/#"] {
+        body.extend([0x00, filter.len() as u8]);
+        body.extend(filter);
+        body.push(0x01);
+    }
+    stream.write_all(&[vec![0x82, body.len() as u8], body].concat()).unwrap();
+    assert_eq!(read_packet(&mut stream), vec![0x90, 0x05, 0x00, 0x01, 0x00, 0x01, 0x87]);
+}
+
+#[test]
+fn identity_mapping_of_certificate_fields() {
+    let certificates = certificates("fields");
+    let certificate = certificates.client.0.der();
+
+    let identity = IdentityMapping::new(CertificateField::CommonName, IdentityTarget::ClientId)
+        .identity(certificate)
+        .unwrap();
+    assert_eq!(identity.client_id.as_deref(), Some("device1"));
+    assert_eq!(identity.user_name, None);
+
+    let identity = IdentityMapping::new(CertificateField::SubjectAltName, IdentityTarget::UserName)
+        .identity(certificate)
+        .unwrap();
+    assert_eq!(identity.user_name.as_deref(), Some("device1.example.com"));
+}
+
+#[test]
+fn parse_identity_mapping() {
+    assert_eq!(
+        IdentityMapping::parse("cn:username").unwrap(),
+        IdentityMapping::new(CertificateField::CommonName, IdentityTarget::UserName)
+    );
+    assert_eq!(
+        IdentityMapping::parse("san:client-id").unwrap(),
+        IdentityMapping::new(CertificateField::SubjectAltName, IdentityTarget::ClientId)
+    );
+    assert!(IdentityMapping::parse("cn").is_err());
+    assert!(IdentityMapping::parse("uid:username").is_err());
+}
@@ -0,0 +1,115 @@
+use std::fs;
+use std::io;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::os::unix::io::AsRawFd;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::Arc;
+use std::thread;
+
+use mini_mqtt::auth;
+use mini_mqtt::errors;
+
+use crate::broker::Broker;
+use crate::connection::Connection;
+use crate::listener;
+
+#[path = "unix_tests.rs"]
+#[cfg(test)]
+mod unix_tests;
+
+// bind creates the Unix domain socket at the path. The file permissions of the socket are the access control,
+// only the users who can write to the socket are able to connect.
+// The socket which a previous run has left is replaced, but any other file at the path is an error.
+pub fn bind(path: &Path, mode: u32) -> Result<UnixListener, errors::Error> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => fs::remove_file(path)?,
+        Ok(_) => {
+            return Err(errors::Error::Common(format!("{} exists and is not a socket", path.display())));
+        }
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err.into()),
+    }
+
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+    Ok(listener)
+}
+
+// peer_credentials results the user, the group and the process of the peer which the kernel reports.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn peer_credentials(stream: &UnixStream) -> io::Result<auth::PeerCredentials> {
+    let mut credentials = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    // SAFETY: credentials and length are valid for writes, and length is the size of credentials.
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(auth::PeerCredentials {
+        uid: credentials.uid,
+        gid: credentials.gid,
+        pid: Some(credentials.pid),
+    })
+}
+
+// peer_credentials results the user and the group of the peer, the platform does not report the process.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn peer_credentials(stream: &UnixStream) -> io::Result<auth::PeerCredentials> {
+    let mut uid = 0;
+    let mut gid = 0;
+    // SAFETY: uid and gid are valid for writes.
+    let result = unsafe { libc::getpeereid(stream.as_raw_fd(), &mut uid, &mut gid) };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(auth::PeerCredentials { uid, gid, pid: None })
+}
+
+// serve_unix accepts the connections of the Unix domain socket and serves each of them in its own thread.
+// The peer credentials are passed to the auth layer.
+pub fn serve_unix(listener: UnixListener, broker: Arc<Broker>) -> Result<(), errors::Error> {
+    for stream in listener.incoming() {
+        // The failure to accept one socket does not stop the listener.
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("Failed to accept a connection: {}", err);
+                continue;
+            }
+        };
+        // The socket is closed when it is dropped without a slot.
+        let Some(slot) = broker.acquire_connection() else {
+            log::warn!("Connection is refused, the maximum number of connections is reached");
+            continue;
+        };
+        let broker = broker.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(err) = serve_unix_stream(stream, &broker) {
+                log::info!("Unix domain socket connection is closed: {}", err);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn serve_unix_stream(stream: UnixStream, broker: &Broker) -> Result<(), errors::Error> {
+    let peer = auth::Peer {
+        identity: None,
+        credentials: Some(peer_credentials(&stream)?),
+    };
+    stream.set_read_timeout(Some(listener::READ_TIMEOUT))?;
+    Connection::new(stream).serve(broker, &peer)
+}
@@ -0,0 +1,70 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use mini_mqtt::config;
+
+use super::*;
+use crate::test_helpers::{connect_packet, read_packet};
+
+fn socket_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("mini_mqtt_{}_{}.sock", name, std::process::id()))
+}
+
+fn connect(path: &Path) -> Vec<u8> {
+    let mut stream = UnixStream::connect(path).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    stream.write_all(&connect_packet("c1", &[])).unwrap();
+    read_packet(&mut stream)
+}
+
+#[test]
+fn bind_sets_permissions() {
+    let path = socket_path("permissions");
+    let listener = bind(&path, 0o600).unwrap();
+    assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+
+    // The socket which is left by a previous run is replaced.
+    drop(listener);
+    assert!(bind(&path, listener::DEFAULT_UNIX_SOCKET_MODE).is_ok());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn bind_does_not_replace_regular_file() {
+    let path = socket_path("regular_file");
+    fs::write(&path, b"").unwrap();
+    assert!(bind(&path, listener::DEFAULT_UNIX_SOCKET_MODE).is_err());
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn serve_unix_passes_peer_credentials_to_auth_provider() {
+    // SAFETY: getuid has no preconditions.
+    let uid = unsafe { libc::getuid() };
+    let config = config::Config {
+        auth_provider: Arc::new(auth::Hook::new(move |credentials| match credentials.peer_credentials {
+            Some(peer_credentials) if peer_credentials.uid == uid && credentials.client_id == "c1" => Ok(()),
+            _ => Err(auth::not_authorized("The peer is not the owner")),
+        })),
+        ..config::Config::default()
+    };
+    let path = socket_path("credentials");
+    let listener = bind(&path, listener::DEFAULT_UNIX_SOCKET_MODE).unwrap();
+    thread::spawn(move || serve_unix(listener, Broker::new(config, None)));
+
+    assert_eq!(connect(&path)[..4], [0x20, 0x03, 0x00, 0x00]);
+    assert_eq!(
+        peer_credentials(&UnixStream::connect(&path).unwrap()).unwrap().uid,
+        uid
+    );
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[test]
+fn peer_credentials_reports_process() {
+    let (client, _server) = UnixStream::pair().unwrap();
+    let credentials = peer_credentials(&client).unwrap();
+    assert_eq!(credentials.pid, Some(std::process::id() as i32));
+}
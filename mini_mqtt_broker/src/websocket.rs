@@ -0,0 +1,155 @@
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tungstenite::http::{HeaderValue, StatusCode};
+use tungstenite::{Message, WebSocket};
+
+use mini_mqtt::auth;
+use mini_mqtt::errors;
+
+use crate::broker::Broker;
+use crate::connection::Connection;
+use crate::listener;
+
+#[path = "websocket_tests.rs"]
+#[cfg(test)]
+mod websocket_tests;
+
+// The Client MUST include "mqtt" in the list of WebSocket Sub Protocols it offers,
+// and the WebSocket Subprotocol name selected and returned by the Server MUST be "mqtt" [MQTT-6.0.0-3] [MQTT-6.0.0-4].
+pub const SUBPROTOCOL: &str = "mqtt";
+
+// HANDSHAKE_TIMEOUT limits the time which a Client can take to complete the WebSocket opening handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+// WebSocketStream reads and writes the MQTT Control Packets as the payloads of the binary data frames.
+// A Control Packet can be split across several frames, and a frame can contain several Control Packets,
+// so the payloads are handed to the codec as a byte stream [MQTT-6.0.0-2].
+pub struct WebSocketStream<S: Read + Write> {
+    websocket: WebSocket<S>,
+    received: Vec<u8>,
+    sending: Vec<u8>,
+}
+
+impl<S: Read + Write> WebSocketStream<S> {
+    pub fn new(websocket: WebSocket<S>) -> WebSocketStream<S> {
+        WebSocketStream {
+            websocket,
+            received: Vec::new(),
+            sending: Vec::new(),
+        }
+    }
+}
+
+fn io_error(err: tungstenite::Error) -> io::Error {
+    match err {
+        tungstenite::Error::Io(err) => err,
+        err => io::Error::other(err),
+    }
+}
+
+impl<S: Read + Write> Read for WebSocketStream<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.received.is_empty() {
+            match self.websocket.read() {
+                Ok(Message::Binary(payload)) => self.received.extend_from_slice(&payload),
+                // MQTT Control Packets MUST be sent in WebSocket binary data frames.
+                // If any other type of data frame is received the recipient MUST close the Network Connection [MQTT-6.0.0-1].
+                Ok(Message::Text(_)) => {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, "WebSocket text frame is received"));
+                }
+                Ok(Message::Close(_)) => return Ok(0),
+                // The Ping frames are answered by the WebSocket implementation.
+                Ok(_) => {}
+                Err(tungstenite::Error::ConnectionClosed) | Err(tungstenite::Error::AlreadyClosed) => return Ok(0),
+                Err(err) => return Err(io_error(err)),
+            }
+        }
+
+        let n = buf.len().min(self.received.len());
+        buf[..n].copy_from_slice(&self.received[..n]);
+        self.received.drain(..n);
+        Ok(n)
+    }
+}
+
+impl<S: Read + Write> Write for WebSocketStream<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sending.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    // flush sends the written bytes in a binary data frame.
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.sending.is_empty() {
+            let payload = std::mem::take(&mut self.sending);
+            self.websocket.send(Message::binary(payload)).map_err(io_error)?;
+        }
+        Ok(())
+    }
+}
+
+// select_subprotocol accepts the opening handshake only when the Client offers the "mqtt" subprotocol.
+// The signature is the one of the tungstenite handshake Callback.
+#[allow(clippy::result_large_err)]
+fn select_subprotocol(request: &Request, mut response: Response) -> Result<Response, ErrorResponse> {
+    let offered = request
+        .headers()
+        .get_all("Sec-WebSocket-Protocol")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .any(|protocol| protocol.trim() == SUBPROTOCOL);
+    if !offered {
+        let mut response = ErrorResponse::new(Some("The mqtt WebSocket subprotocol is required".to_string()));
+        *response.status_mut() = StatusCode::BAD_REQUEST;
+        return Err(response);
+    }
+
+    response
+        .headers_mut()
+        .insert("Sec-WebSocket-Protocol", HeaderValue::from_static(SUBPROTOCOL));
+    Ok(response)
+}
+
+// serve_websocket accepts the MQTT over WebSocket connections and serves each of them in its own thread.
+pub fn serve_websocket(listener: TcpListener, broker: Arc<Broker>) -> Result<(), errors::Error> {
+    for stream in listener.incoming() {
+        // The failure to accept one socket does not stop the listener.
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::warn!("Failed to accept a connection: {}", err);
+                continue;
+            }
+        };
+        // The socket is closed when it is dropped without a slot.
+        let Some(slot) = broker.acquire_connection() else {
+            log::warn!("Connection is refused, the maximum number of connections is reached");
+            continue;
+        };
+        let broker = broker.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(err) = serve_websocket_stream(stream, &broker) {
+                log::info!("WebSocket connection is closed: {}", err);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+fn serve_websocket_stream(stream: TcpStream, broker: &Broker) -> Result<(), errors::Error> {
+    stream.set_read_timeout(Some(HANDSHAKE_TIMEOUT))?;
+    let websocket = tungstenite::accept_hdr(stream, select_subprotocol)
+        .map_err(|err| errors::Error::Common(format!("WebSocket handshake failed: {}", err)))?;
+
+    websocket.get_ref().set_read_timeout(Some(listener::READ_TIMEOUT))?;
+    websocket.get_ref().set_nodelay(true)?;
+    Connection::new(WebSocketStream::new(websocket)).serve(broker, &auth::Peer::default())
+}
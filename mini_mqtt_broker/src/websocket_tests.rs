@@ -0,0 +1,91 @@
+use std::net::{SocketAddr, TcpStream};
+
+use tungstenite::client::IntoClientRequest;
+
+use mini_mqtt::config;
+
+use super::*;
+use crate::test_helpers::{self, connect_packet, publish_packet};
+
+fn start() -> SocketAddr {
+    test_helpers::start(config::Config::default(), serve_websocket)
+}
+
+// connect_client results the status of the rejected handshake as the error.
+fn connect_client(address: SocketAddr, subprotocol: Option<&str>) -> Result<WebSocket<TcpStream>, StatusCode> {
+    let mut request = format!("ws://{}/mqtt", address).into_client_request().unwrap();
+    if let Some(subprotocol) = subprotocol {
+        request
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", HeaderValue::from_str(subprotocol).unwrap());
+    }
+    let stream = TcpStream::connect(address).unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+    tungstenite::client(request, stream).map(|(websocket, _)| websocket).map_err(|err| match err {
+        tungstenite::HandshakeError::Failure(tungstenite::Error::Http(response)) => response.status(),
+        err => panic!("The handshake has failed: {}", err),
+    })
+}
+
+fn read_binary(websocket: &mut WebSocket<TcpStream>) -> Vec<u8> {
+    match websocket.read().unwrap() {
+        Message::Binary(payload) => payload.to_vec(),
+        message => panic!("Binary message is expected: {:?}", message),
+    }
+}
+
+// MQTT v5.0 SUBSCRIBE Packet of the Topic Filter "a/b" with QoS 1.
+const SUBSCRIBE: [u8; 11] = [0x82, 0x09, 0x00, 0x01, 0x00, 0x00, 0x03, b'a', b'/', b'b', 0x01];
+
+#[test]
+fn serve_websocket_selects_mqtt_subprotocol() {
+    let address = start();
+
+    let websocket = connect_client(address, Some("mqttv3.1, mqtt")).unwrap();
+    drop(websocket);
+    assert_eq!(connect_client(address, None).err(), Some(StatusCode::BAD_REQUEST));
+}
+
+#[test]
+fn serve_websocket_reassembles_packets_across_frames() {
+    let address = start();
+    let mut websocket = connect_client(address, Some(SUBPROTOCOL)).unwrap();
+
+    // The CONNECT Packet is split across two frames, and the second frame also carries the SUBSCRIBE Packet.
+    let connect = connect_packet("c1", &[]);
+    websocket.send(Message::binary(connect[..5].to_vec())).unwrap();
+    websocket
+        .send(Message::binary([&connect[5..], &SUBSCRIBE[..]].concat()))
+        .unwrap();
+
+    assert_eq!(read_binary(&mut websocket)[..4], [0x20, 0x03, 0x00, 0x00]);
+    assert_eq!(read_binary(&mut websocket), vec![0x90, 0x04, 0x00, 0x01, 0x00, 0x01]);
+}
+
+#[test]
+fn serve_websocket_delivers_publish_to_subscriber() {
+    let address = start();
+    let mut subscriber = connect_client(address, Some(SUBPROTOCOL)).unwrap();
+    subscriber.send(Message::binary([connect_packet("c1", &[]), SUBSCRIBE.to_vec()].concat())).unwrap();
+    read_binary(&mut subscriber);
+    read_binary(&mut subscriber);
+
+    let mut publisher = connect_client(address, Some(SUBPROTOCOL)).unwrap();
+    publisher.send(Message::binary(connect_packet("c2", &[]))).unwrap();
+    read_binary(&mut publisher);
+    // Two QoS 0 PUBLISH Packets of the Topic Name "a/b" in a frame.
+    let publish = publish_packet("a/b", b"x");
+    publisher.send(Message::binary([publish.clone(), publish.clone()].concat())).unwrap();
+
+    assert_eq!(read_binary(&mut subscriber), publish);
+    assert_eq!(read_binary(&mut subscriber), publish);
+}
+
+#[test]
+fn serve_websocket_closes_connection_on_text_frame() {
+    let address = start();
+    let mut websocket = connect_client(address, Some(SUBPROTOCOL)).unwrap();
+    websocket.send(Message::text("hello")).unwrap();
+
+    assert!(!matches!(websocket.read(), Ok(Message::Binary(_))));
+}